@@ -162,6 +162,8 @@ class CropResizeParameters {
           enabled: true,
           preset: CropResizePreset.resize4k,
           resizeEnabled: true,
+          targetWidth: 3840,
+          targetHeight: 2160,
           useIntegerUpscale: true,
           upscaleMethod: UpscaleMethod.nnedi3Rpow2,
           upscaleFactor: 2,
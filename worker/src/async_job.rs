@@ -0,0 +1,169 @@
+//! Async entry point for running a job in-process (feature `async`).
+//!
+//! Complements the subprocess model for apps that embed the worker. The job
+//! runs on its own thread and the returned future completes when it ends, so
+//! it can be awaited from any executor without pulling in a runtime.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::thread;
+
+use anyhow::Result;
+
+use crate::job_runner::run_worker;
+use crate::models::VideoJob;
+use crate::progress_reporter::{ProgressReporter, ProgressSink};
+
+/// Cancels a running job when `cancel` is called on any clone.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    /// Create a token that is not cancelled.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Request cancellation; the job stops its processes and fails with
+    /// `WorkerError::Cancelled`.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    /// Whether cancellation has been requested.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+}
+
+/// Run `job` through the vspipe | ffmpeg pipeline, sending worker messages
+/// (ending with `complete`) to `sink`. Resolves to the output path.
+pub fn run_job_async(
+    job: VideoJob,
+    sink: Arc<dyn ProgressSink>,
+    token: CancellationToken,
+) -> impl Future<Output = Result<String>> {
+    let reporter = ProgressReporter::with_sink(sink);
+    spawn_job(move || {
+        let result = run_job(&job.resolved(), &reporter, &token);
+        reporter.send_complete(result.is_ok(), result.as_deref().ok());
+        result
+    })
+}
+
+/// Run `job` as the CLI does, stopping when `token` is cancelled.
+fn run_job(job: &VideoJob, reporter: &ProgressReporter, token: &CancellationToken) -> Result<String> {
+    let paused = Arc::new(AtomicBool::new(false));
+    run_worker(job, reporter, token.cancelled.clone(), paused, false)
+}
+
+/// Run `work` on a new thread, returning a future for its result.
+fn spawn_job<T, F>(work: F) -> JobFuture<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+{
+    let state = Arc::new(Mutex::new(JobState { result: None, waker: None }));
+    let thread_state = state.clone();
+    thread::spawn(move || {
+        let result = work();
+        let mut state = thread_state.lock().unwrap();
+        state.result = Some(result);
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+    });
+    JobFuture { state }
+}
+
+struct JobState<T> {
+    result: Option<T>,
+    waker: Option<Waker>,
+}
+
+/// Completes with the result of a job running on its own thread.
+struct JobFuture<T> {
+    state: Arc<Mutex<JobState<T>>>,
+}
+
+impl<T> Future for JobFuture<T> {
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        let mut state = self.state.lock().unwrap();
+        match state.result.take() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{WorkerError, WorkerMessage};
+    use std::sync::mpsc;
+    use std::task::Wake;
+
+    /// Minimal executor: park the thread until the future's waker fires.
+    fn block_on<F: Future>(future: F) -> F::Output {
+        struct Unpark(thread::Thread);
+        impl Wake for Unpark {
+            fn wake(self: Arc<Self>) {
+                self.0.unpark();
+            }
+        }
+
+        let waker = Waker::from(Arc::new(Unpark(thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        let mut future = std::pin::pin!(future);
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return output;
+            }
+            thread::park();
+        }
+    }
+
+    struct ChannelSink(Mutex<mpsc::Sender<WorkerMessage>>);
+
+    impl ProgressSink for ChannelSink {
+        fn send(&self, message: &WorkerMessage) {
+            let _ = self.0.lock().unwrap().send(message.clone());
+        }
+    }
+
+    #[test]
+    fn test_cancelled_job_completes_with_cancelled_error() {
+        let (sender, messages) = mpsc::channel();
+        let job: VideoJob = serde_json::from_value(serde_json::json!({
+            "id": "00000000-0000-0000-0000-000000000001",
+            "inputPath": "input.avi",
+            "outputPath": "output.mkv",
+            "qtgmcParameters": {},
+            "encodingSettings": {}
+        }))
+        .unwrap();
+        let token = CancellationToken::new();
+        token.cancel();
+
+        let error = block_on(run_job_async(job, Arc::new(ChannelSink(Mutex::new(sender))), token))
+            .expect_err("Cancelled job should fail");
+        assert_eq!(WorkerError::classify(&error), Some(WorkerError::Cancelled));
+
+        // The sink sees the same completion message as the CLI's stdout
+        let messages: Vec<WorkerMessage> = messages.try_iter().collect();
+        assert!(matches!(
+            messages.last(),
+            Some(WorkerMessage::Complete { success: false, output_path: None })
+        ));
+    }
+}
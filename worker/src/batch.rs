@@ -0,0 +1,165 @@
+//! Batch mode: run a list of jobs sequentially.
+//!
+//! Every message for a job carries its `jobIndex` in the batch, and a
+//! `batchSummary` message is sent once all jobs have run.
+
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use anyhow::{Context, Result};
+
+use crate::models::{BatchSummary, DependencyError, LogLevel, VideoJob};
+use crate::progress_reporter::ProgressReporter;
+use crate::qtgmc_presets::QTGMCPresetLibrary;
+
+/// Load a batch manifest (a JSON array of jobs), apply custom QTGMC presets
+/// and resolve each job.
+///
+/// Jobs that only use legacy `qtgmcParameters` get a warning tagged with
+/// their index.
+pub fn load_batch(path: &Path, reporter: &ProgressReporter) -> Result<Vec<VideoJob>> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read batch file: {:?}", path))?;
+    let jobs: Vec<VideoJob> = serde_json::from_str(&content)
+        .with_context(|| "Failed to parse batch manifest")?;
+    for (index, job) in jobs.iter().enumerate() {
+        if let Some(warning) = job.legacy_warning() {
+            reporter.for_job(index).send_log(LogLevel::Warning, warning);
+        }
+    }
+    let presets = QTGMCPresetLibrary::load_user_presets()?;
+    jobs.iter()
+        .enumerate()
+        .map(|(index, job)| {
+            presets.apply_to_job(job)
+                .map(|job| job.resolved())
+                .with_context(|| format!("Job {}", index))
+        })
+        .collect()
+}
+
+/// Run `jobs` in order with `run_job`, which returns the output path on success.
+///
+/// Stops at the first failure unless `continue_on_error` is set, and after any
+/// job once `cancelled` is raised. Sends and returns the batch summary.
+pub fn run_batch<F>(
+    jobs: &[VideoJob],
+    reporter: &ProgressReporter,
+    continue_on_error: bool,
+    cancelled: &AtomicBool,
+    mut run_job: F,
+) -> BatchSummary
+where
+    F: FnMut(&VideoJob, &ProgressReporter) -> Result<String>,
+{
+    let mut summary = BatchSummary { total: jobs.len(), ..BatchSummary::default() };
+
+    for (index, job) in jobs.iter().enumerate() {
+        let job_reporter = reporter.for_job(index);
+        match run_job(job, &job_reporter) {
+            Ok(output_path) => {
+                summary.succeeded += 1;
+                job_reporter.send_complete(true, Some(&output_path));
+            }
+            Err(e) => {
+                summary.failed += 1;
+                if let Some(dep) = e.downcast_ref::<DependencyError>() {
+                    job_reporter.send_dependency_missing(dep);
+                } else {
+                    job_reporter.send_error(&format!("{:#}", e));
+                }
+                job_reporter.send_complete(false, None);
+            }
+        }
+
+        let stop = if cancelled.load(Ordering::SeqCst) {
+            reporter.send_log(LogLevel::Info, "Batch cancelled by user");
+            true
+        } else if summary.failed > 0 && !continue_on_error {
+            reporter.send_log(LogLevel::Warning, "Stopping batch after failed job");
+            true
+        } else {
+            false
+        };
+        if stop {
+            summary.skipped = jobs.len() - index - 1;
+            break;
+        }
+    }
+
+    reporter.send_batch_summary(&summary);
+    summary
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{EncodingSettings, QTGMCParameters, WorkerMessage};
+    use anyhow::bail;
+
+    fn create_test_jobs(count: usize) -> Vec<VideoJob> {
+        (0..count)
+            .map(|i| {
+                serde_json::from_value(serde_json::json!({
+                    "id": uuid::Uuid::new_v4(),
+                    "inputPath": format!("input{}.mp4", i),
+                    "outputPath": format!("output{}.mp4", i),
+                    "qtgmcParameters": QTGMCParameters::default(),
+                    "encodingSettings": EncodingSettings::default(),
+                }))
+                .unwrap()
+            })
+            .collect()
+    }
+
+    /// (job index, success) of every completion message
+    fn completions(reporter: &ProgressReporter) -> Vec<(Option<usize>, bool)> {
+        reporter.captured_with_job_index().into_iter()
+            .filter_map(|(index, message)| match message {
+                WorkerMessage::Complete { success, .. } => Some((index, success)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_two_job_batch_runs_both() {
+        let jobs = create_test_jobs(2);
+        let reporter = ProgressReporter::capturing();
+        let mut ran = Vec::new();
+
+        let summary = run_batch(&jobs, &reporter, false, &AtomicBool::new(false), |job, _| {
+            ran.push(job.input_path.clone());
+            Ok(job.output_path.clone())
+        });
+
+        assert_eq!(ran, ["input0.mp4", "input1.mp4"]);
+        assert_eq!(completions(&reporter), [(Some(0), true), (Some(1), true)]);
+        assert_eq!(summary, BatchSummary { total: 2, succeeded: 2, failed: 0, skipped: 0 });
+        assert!(matches!(
+            reporter.captured().last(),
+            Some(WorkerMessage::BatchSummary { total: 2, succeeded: 2, .. })
+        ));
+    }
+
+    #[test]
+    fn test_batch_failure_stops_unless_continue_on_error() {
+        let jobs = create_test_jobs(3);
+        let run = |job: &VideoJob, _: &ProgressReporter| {
+            if job.input_path == "input0.mp4" {
+                bail!("boom");
+            }
+            Ok(job.output_path.clone())
+        };
+
+        let reporter = ProgressReporter::capturing();
+        let summary = run_batch(&jobs, &reporter, false, &AtomicBool::new(false), run);
+        assert_eq!(summary, BatchSummary { total: 3, succeeded: 0, failed: 1, skipped: 2 });
+        assert_eq!(completions(&reporter), [(Some(0), false)]);
+
+        let reporter = ProgressReporter::capturing();
+        let summary = run_batch(&jobs, &reporter, true, &AtomicBool::new(false), run);
+        assert_eq!(summary, BatchSummary { total: 3, succeeded: 2, failed: 1, skipped: 0 });
+        assert_eq!(completions(&reporter), [(Some(0), false), (Some(1), true), (Some(2), true)]);
+    }
+}
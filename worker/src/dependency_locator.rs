@@ -3,7 +3,10 @@
 use std::env;
 use std::path::{Path, PathBuf};
 
-use anyhow::{bail, Context, Result};
+use anyhow::{Context, Result};
+
+use crate::models::DependencyError;
+use crate::platform;
 
 /// Platform-specific dependency locator.
 pub struct DependencyLocator {
@@ -29,6 +32,12 @@ impl DependencyLocator {
         Ok(Self { base_path, platform })
     }
 
+    /// Locator for a deps directory laid out like the bundled one.
+    #[cfg(test)]
+    pub(crate) fn at(base_path: PathBuf) -> Self {
+        Self { base_path, platform: Self::detect_platform() }
+    }
+
     /// Find the deps directory by searching various locations.
     fn find_deps_directory(exe_path: &Path) -> Result<PathBuf> {
         // Development only: search upward from executable for project deps.
@@ -119,70 +128,65 @@ impl DependencyLocator {
 
     /// Get the path to vspipe executable.
     pub fn vspipe_path(&self) -> Result<PathBuf> {
+        Self::locate_tool("vspipe", &self.vspipe_candidates(), |name| which::which(name).ok())
+    }
+
+    /// Bundled locations checked for vspipe, in search order.
+    fn vspipe_candidates(&self) -> Vec<PathBuf> {
         let vs_dir = self.platform_dir().join("vapoursynth");
 
         #[cfg(target_os = "windows")]
         {
-            // Windows: VapourSynth portable uses VSPipe.exe
-            let path = vs_dir.join("VSPipe.exe");
-            if path.exists() {
-                return Ok(path);
-            }
-            // Fallback to lowercase
-            let alt = vs_dir.join("vspipe.exe");
-            if alt.exists() {
-                return Ok(alt);
-            }
+            // Windows: VapourSynth portable uses VSPipe.exe, fall back to lowercase
+            vec![vs_dir.join("VSPipe.exe"), vs_dir.join("vspipe.exe")]
         }
 
         #[cfg(not(target_os = "windows"))]
         {
-            let path = vs_dir.join("vspipe");
-            if path.exists() {
-                return Ok(path);
-            }
-        }
-
-        // Try system PATH as last resort
-        if let Ok(system_path) = which::which("vspipe") {
-            return Ok(system_path);
+            vec![vs_dir.join("vspipe")]
         }
-
-        bail!("vspipe not found in {:?}", vs_dir);
     }
 
     /// Get the path to ffmpeg executable.
     pub fn ffmpeg_path(&self) -> Result<PathBuf> {
-        let exe_name = if cfg!(windows) { "ffmpeg.exe" } else { "ffmpeg" };
-        let path = self.platform_dir().join("ffmpeg").join(exe_name);
-
-        if !path.exists() {
-            // Try system PATH as last resort
-            if let Ok(system_path) = which::which("ffmpeg") {
-                return Ok(system_path);
-            }
-
-            bail!("ffmpeg not found at {:?}", path);
-        }
-
-        Ok(path)
+        Self::locate_tool("ffmpeg", &[self.ffmpeg_dir_tool("ffmpeg")], |name| which::which(name).ok())
     }
 
     /// Get the path to ffprobe executable.
     pub fn ffprobe_path(&self) -> Result<PathBuf> {
-        let exe_name = if cfg!(windows) { "ffprobe.exe" } else { "ffprobe" };
-        let path = self.platform_dir().join("ffmpeg").join(exe_name);
+        Self::locate_tool("ffprobe", &[self.ffmpeg_dir_tool("ffprobe")], |name| which::which(name).ok())
+    }
 
-        if !path.exists() {
-            // Try system PATH as last resort
-            if let Ok(system_path) = which::which("ffprobe") {
-                return Ok(system_path);
-            }
+    /// Bundled path of a tool in the ffmpeg directory.
+    fn ffmpeg_dir_tool(&self, name: &str) -> PathBuf {
+        let exe_name = if cfg!(windows) { format!("{}.exe", name) } else { name.to_string() };
+        self.platform_dir().join("ffmpeg").join(exe_name)
+    }
 
-            bail!("ffprobe not found at {:?}", path);
+    /// Return the first existing candidate, then try the system PATH as a last resort.
+    /// Fails with a [`DependencyError`] listing every location searched.
+    fn locate_tool(
+        which: &str,
+        candidates: &[PathBuf],
+        system_lookup: impl Fn(&str) -> Option<PathBuf>,
+    ) -> Result<PathBuf> {
+        if let Some(path) = candidates.iter().find(|p| p.exists()) {
+            return Ok(PathBuf::from(platform::long_path(&path.to_string_lossy())));
+        }
+
+        if let Some(system_path) = system_lookup(which) {
+            return Ok(system_path);
         }
 
-        Ok(path)
+        let mut searched_paths: Vec<String> = candidates.iter()
+            .map(|p| p.display().to_string())
+            .collect();
+        searched_paths.push("system PATH".to_string());
+
+        Err(DependencyError {
+            which: which.to_string(),
+            searched_paths,
+        }.into())
     }
 
     /// Get the Python home directory, or None if Python is not bundled.
@@ -363,4 +367,43 @@ mod tests {
         };
         assert_eq!(locator.platform_suffix(), "windows-x64");
     }
+
+    fn missing_locator() -> DependencyLocator {
+        DependencyLocator {
+            base_path: PathBuf::from("/nonexistent/vapourbox/deps"),
+            platform: Platform::MacOSArm64,
+        }
+    }
+
+    #[test]
+    fn test_missing_vspipe_reports_dependency_error() {
+        let locator = missing_locator();
+        let err = DependencyLocator::locate_tool("vspipe", &locator.vspipe_candidates(), |_| None)
+            .unwrap_err();
+
+        let dep = err.downcast_ref::<DependencyError>().expect("should be a DependencyError");
+        assert_eq!(dep.which, "vspipe");
+        assert!(dep.searched_paths[0].contains("vapoursynth"));
+        assert_eq!(dep.searched_paths.last().unwrap(), "system PATH");
+    }
+
+    #[test]
+    fn test_missing_ffmpeg_reports_dependency_error() {
+        let locator = missing_locator();
+        let err = DependencyLocator::locate_tool("ffmpeg", &[locator.ffmpeg_dir_tool("ffmpeg")], |_| None)
+            .unwrap_err();
+
+        let dep = err.downcast_ref::<DependencyError>().expect("should be a DependencyError");
+        assert_eq!(dep.which, "ffmpeg");
+        assert!(dep.searched_paths[0].ends_with("ffmpeg") || dep.searched_paths[0].ends_with("ffmpeg.exe"));
+    }
+
+    #[test]
+    fn test_system_path_used_as_fallback() {
+        let locator = missing_locator();
+        let path = DependencyLocator::locate_tool("ffmpeg", &[locator.ffmpeg_dir_tool("ffmpeg")], |_| {
+            Some(PathBuf::from("/usr/bin/ffmpeg"))
+        }).unwrap();
+        assert_eq!(path, PathBuf::from("/usr/bin/ffmpeg"));
+    }
 }
@@ -130,22 +130,9 @@ impl FilterRegistry {
         let mut missing = HashMap::new();
 
         for filter in self.filters.values() {
-            if let Some(deps) = &filter.dependencies {
-                let mut filter_missing = Vec::new();
-
-                // Check VS plugins
-                if let Some(vs_plugins) = &deps.vs_plugins {
-                    for plugin in vs_plugins {
-                        let plugin_path = plugin_dir.join(plugin);
-                        if !plugin_path.exists() {
-                            filter_missing.push(plugin.clone());
-                        }
-                    }
-                }
-
-                if !filter_missing.is_empty() {
-                    missing.insert(filter.id.clone(), filter_missing);
-                }
+            let filter_missing = filter.missing_vs_plugins(plugin_dir);
+            if !filter_missing.is_empty() {
+                missing.insert(filter.id.clone(), filter_missing);
             }
         }
 
@@ -1,5 +1,6 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::path::Path;
 
 /// Type of parameter value.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -312,6 +313,18 @@ impl FilterSchema {
 
         errors
     }
+
+    /// Required VapourSynth plugins not present in `plugin_dir`.
+    pub fn missing_vs_plugins(&self, plugin_dir: &Path) -> Vec<String> {
+        self.dependencies
+            .as_ref()
+            .and_then(|deps| deps.vs_plugins.as_ref())
+            .into_iter()
+            .flatten()
+            .filter(|plugin| !plugin_dir.join(plugin).exists())
+            .cloned()
+            .collect()
+    }
 }
 
 /// Dynamic parameter container for schema-based filters.
@@ -0,0 +1,211 @@
+//! Commands run after a job, e.g. to send a notification or upload the output.
+//!
+//! A job file could come from anywhere, so its commands only run when the
+//! worker is started with `--allow-hooks`.
+
+use std::io::Read;
+use std::process::{Command, ExitStatus, Stdio};
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Context, Result};
+
+use crate::models::{LogLevel, VideoJob};
+use crate::progress_reporter::ProgressReporter;
+
+/// Placeholder replaced by the (quoted) output path.
+pub const OUTPUT_PLACEHOLDER: &str = "{output}";
+
+/// How long an on-complete command may run before it is killed, so a hung
+/// hook can't keep the job from reporting completion.
+pub const HOOK_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// How often a running hook is checked for exit.
+const HOOK_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// `template` with every `{output}` replaced by `output_path`, quoted for the
+/// platform shell.
+pub fn expand_command(template: &str, output_path: &str) -> String {
+    template.replace(OUTPUT_PLACEHOLDER, &shell_quote(output_path))
+}
+
+/// Run the job's `on_complete_command` if `result` (the output path) is a
+/// success and hooks are allowed, logging its exit status.
+///
+/// Returns the exit status when the command ran. A failing command is
+/// logged as a warning; it doesn't fail the job.
+pub fn run_on_complete(
+    job: &VideoJob,
+    result: &Result<String>,
+    allow_hooks: bool,
+    reporter: &ProgressReporter,
+) -> Option<ExitStatus> {
+    let command = job.on_complete_command.as_deref().filter(|c| !c.trim().is_empty())?;
+    let output_path = result.as_ref().ok()?;
+    if !allow_hooks {
+        reporter.send_log(
+            LogLevel::Warning,
+            "Skipping the job's on-complete command; start the worker with --allow-hooks to run it",
+        );
+        return None;
+    }
+
+    let command = expand_command(command, output_path);
+    reporter.send_log(LogLevel::Info, &format!("Running on-complete command: {}", command));
+    match run_shell(&command, HOOK_TIMEOUT) {
+        Ok((status, output)) => {
+            for line in output.lines().filter(|line| !line.trim().is_empty()) {
+                reporter.send_log(LogLevel::Debug, &format!("on-complete: {}", line));
+            }
+            let level = if status.success() { LogLevel::Info } else { LogLevel::Warning };
+            reporter.send_log(level, &format!("On-complete command exited with {}", status));
+            Some(status)
+        }
+        Err(e) => {
+            reporter.send_log(LogLevel::Warning, &format!("{:#}", e));
+            None
+        }
+    }
+}
+
+/// Run `command` through the platform shell, returning its status and
+/// combined output. Stdout is captured so it can't interleave with the
+/// worker's JSON messages. The command is killed after `timeout`.
+fn run_shell(command: &str, timeout: Duration) -> Result<(ExitStatus, String)> {
+    #[cfg(windows)]
+    let mut shell = {
+        use std::os::windows::process::CommandExt;
+        // cmd parses its command line itself; Rust's argument quoting would
+        // escape the quotes around the output path
+        let mut shell = Command::new("cmd");
+        shell.arg("/C").raw_arg(command);
+        shell
+    };
+    #[cfg(not(windows))]
+    let mut shell = {
+        let mut shell = Command::new("sh");
+        shell.arg("-c").arg(command);
+        shell
+    };
+    let mut child = shell
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("Failed to run on-complete command")?;
+
+    let read_all = |mut pipe: Box<dyn Read + Send>| {
+        std::thread::spawn(move || {
+            let mut bytes = Vec::new();
+            let _ = pipe.read_to_end(&mut bytes);
+            String::from_utf8_lossy(&bytes).into_owned()
+        })
+    };
+    let stdout = child.stdout.take().map(|pipe| read_all(Box::new(pipe)));
+    let stderr = child.stderr.take().map(|pipe| read_all(Box::new(pipe)));
+
+    let started = Instant::now();
+    let status = loop {
+        if let Some(status) = child.try_wait().context("Failed to wait for on-complete command")? {
+            break status;
+        }
+        if started.elapsed() >= timeout {
+            let _ = child.kill();
+            let _ = child.wait();
+            // The readers are left behind: a grandchild may still hold the pipes
+            bail!("On-complete command timed out after {}s and was killed", timeout.as_secs());
+        }
+        std::thread::sleep(HOOK_POLL_INTERVAL);
+    };
+
+    let mut text = String::new();
+    for reader in [stdout, stderr].into_iter().flatten() {
+        text += &reader.join().unwrap_or_default();
+    }
+    Ok((status, text))
+}
+
+#[cfg(not(windows))]
+fn shell_quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', r"'\''"))
+}
+
+#[cfg(windows)]
+fn shell_quote(text: &str) -> String {
+    // Quotes aren't valid in Windows paths, so wrapping is enough; cmd still
+    // expands %VAR% inside quotes, so each % is escaped outside them
+    format!("\"{}\"", text.replace('%', "\"^%\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::WorkerMessage;
+
+    fn job(command: &str) -> VideoJob {
+        let json = serde_json::json!({
+            "id": "00000000-0000-0000-0000-000000000001",
+            "inputPath": "input.avi",
+            "outputPath": "output.mkv",
+            "qtgmcParameters": {},
+            "encodingSettings": {},
+            "onCompleteCommand": command
+        });
+        serde_json::from_value(json).unwrap()
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_expand_command_quotes_output() {
+        assert_eq!(
+            expand_command("notify-send Done {output}", "/videos/tape 1.mkv"),
+            "notify-send Done '/videos/tape 1.mkv'"
+        );
+        assert_eq!(expand_command("upload {output} && rm {output}", "it's.mkv"), r"upload 'it'\''s.mkv' && rm 'it'\''s.mkv'");
+        assert_eq!(expand_command("true", "out.mkv"), "true");
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_on_complete_runs_only_on_success() {
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("out put.mkv").to_string_lossy().to_string();
+        let marker = format!("{}.done", output);
+        let upload = job("touch {output}.done && echo uploaded");
+        let reporter = ProgressReporter::capturing();
+
+        let failed: Result<String> = Err(anyhow::anyhow!("encode failed"));
+        assert!(run_on_complete(&upload, &failed, true, &reporter).is_none());
+        assert!(run_on_complete(&upload, &Ok(output.clone()), false, &reporter).is_none());
+        assert!(!std::path::Path::new(&marker).exists(), "Hook must not run");
+
+        let status = run_on_complete(&upload, &Ok(output.clone()), true, &reporter).unwrap();
+        assert!(status.success());
+        assert!(std::path::Path::new(&marker).exists(), "Hook should see the output path");
+        assert!(reporter.captured().iter().any(|m| matches!(
+            m,
+            WorkerMessage::Log { message, .. } if message == "on-complete: uploaded"
+        )));
+
+        let status = run_on_complete(&job("exit 3"), &Ok(output), true, &reporter).unwrap();
+        assert_eq!(status.code(), Some(3));
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_run_shell_kills_hung_command() {
+        let started = Instant::now();
+        let error = run_shell("sleep 10", Duration::from_millis(200)).unwrap_err();
+        assert!(error.to_string().contains("timed out"), "{}", error);
+        assert!(started.elapsed() < Duration::from_secs(5), "The hook should be killed at the timeout");
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_expand_command_quotes_output() {
+        assert_eq!(
+            expand_command("copy {output} D:\\backup", "C:\\videos\\tape 1.mkv"),
+            "copy \"C:\\videos\\tape 1.mkv\" D:\\backup"
+        );
+        assert_eq!(expand_command("upload {output}", "100%PATH%.mkv"), "upload \"100\"^%\"PATH\"^%\".mkv\"");
+    }
+}
@@ -0,0 +1,216 @@
+//! Per-source directories for BestSource index files.
+//!
+//! BestSource writes the index of a source under its `cachepath`. Two runs
+//! indexing the same source at once (a batch and a separate job, say) would
+//! write the same index file and can corrupt it, so each source gets a shared
+//! directory keyed by a hash of its contents, guarded by a lock file. A run
+//! that finds the lock held indexes into a private directory instead, which is
+//! removed when the run ends.
+
+use std::fs::{self, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use uuid::Uuid;
+
+use crate::platform::{self, TempPath};
+
+/// Lock file name inside a shared index directory.
+const LOCK_FILE: &str = ".lock";
+
+/// A lock older than this is left over from a crashed run and is taken over.
+const STALE_LOCK_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Bytes hashed from each end of the source for its cache key.
+const KEY_SAMPLE_BYTES: u64 = 1024 * 1024;
+
+/// Index directory for one source, held for the length of a run.
+#[derive(Debug)]
+pub struct IndexCache {
+    dir: PathBuf,
+    /// Lock file to remove on drop (shared directory only)
+    lock: Option<PathBuf>,
+    /// Private directory, removed on drop
+    _private: Option<TempPath>,
+}
+
+impl IndexCache {
+    /// Index directory for `input` under the platform cache directory
+    /// (or the temp directory when the cache is not writable).
+    pub fn for_source(input: &Path) -> Result<Self> {
+        let temp = std::env::temp_dir().join("vapourbox_index");
+        let root = platform::writable_dir_from(
+            &platform::cache_dir().map_or_else(|| temp.clone(), |dir| dir.join("index")),
+            Some(&temp),
+        )?;
+        Self::acquire(input, &root)
+    }
+
+    /// Lock the shared index directory for `input` under `root`, or create a
+    /// private one if another run holds it.
+    pub fn acquire(input: &Path, root: &Path) -> Result<Self> {
+        let key = source_key(input)?;
+        let shared = root.join(&key);
+        fs::create_dir_all(&shared)
+            .with_context(|| format!("Failed to create index directory: {}", shared.display()))?;
+
+        let lock = shared.join(LOCK_FILE);
+        if try_lock(&lock) || (is_stale(&lock) && fs::remove_file(&lock).is_ok() && try_lock(&lock)) {
+            return Ok(Self { dir: shared, lock: Some(lock), _private: None });
+        }
+
+        let private = root.join(format!("{}-{}", key, Uuid::new_v4()));
+        fs::create_dir_all(&private)
+            .with_context(|| format!("Failed to create index directory: {}", private.display()))?;
+        Ok(Self {
+            dir: private.clone(),
+            lock: None,
+            _private: Some(TempPath::new(private, false)),
+        })
+    }
+
+    /// Directory to pass to BestSource as `cachepath`.
+    pub fn path(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Whether this is the source's shared (persistent) directory rather than
+    /// a private one for this run.
+    pub fn is_shared(&self) -> bool {
+        self.lock.is_some()
+    }
+}
+
+impl Drop for IndexCache {
+    fn drop(&mut self) {
+        if let Some(lock) = &self.lock {
+            let _ = fs::remove_file(lock);
+        }
+    }
+}
+
+/// Delete the index files in `dir` so the next run reindexes, keeping the
+/// directory and its lock.
+pub fn clear(dir: &Path) -> Result<()> {
+    let entries = fs::read_dir(dir)
+        .with_context(|| format!("Failed to read index directory: {}", dir.display()))?;
+    for entry in entries.flatten().filter(|entry| entry.file_name() != LOCK_FILE) {
+        let path = entry.path();
+        let removed = if path.is_dir() { fs::remove_dir_all(&path) } else { fs::remove_file(&path) };
+        removed.with_context(|| format!("Failed to remove stale index: {}", path.display()))?;
+    }
+    Ok(())
+}
+
+/// Create the lock file, failing if it already exists.
+fn try_lock(lock: &Path) -> bool {
+    match OpenOptions::new().write(true).create_new(true).open(lock) {
+        Ok(mut file) => {
+            let _ = write!(file, "{}", std::process::id());
+            true
+        }
+        Err(_) => false,
+    }
+}
+
+fn is_stale(lock: &Path) -> bool {
+    fs::metadata(lock)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|modified| modified.elapsed().ok())
+        .is_some_and(|age| age > STALE_LOCK_AGE)
+}
+
+/// Cache key for a source: a hash of its size and the first and last
+/// megabyte, so a changed file gets a fresh index while a moved one keeps it.
+pub fn source_key(input: &Path) -> Result<String> {
+    let mut file = fs::File::open(input)
+        .with_context(|| format!("Failed to open source for indexing: {}", input.display()))?;
+    let len = file.metadata()?.len();
+
+    let mut hash = Fnv1a::default();
+    hash.write(&len.to_le_bytes());
+
+    let mut sample = Vec::new();
+    (&mut file).take(KEY_SAMPLE_BYTES).read_to_end(&mut sample)?;
+    hash.write(&sample);
+    if len > KEY_SAMPLE_BYTES {
+        sample.clear();
+        file.seek(SeekFrom::Start(len.saturating_sub(KEY_SAMPLE_BYTES).max(KEY_SAMPLE_BYTES)))?;
+        file.take(KEY_SAMPLE_BYTES).read_to_end(&mut sample)?;
+        hash.write(&sample);
+    }
+    Ok(format!("{:016x}", hash.finish()))
+}
+
+/// 64-bit FNV-1a, stable across builds (unlike `DefaultHasher`) so keys
+/// persist between worker versions.
+pub(crate) struct Fnv1a(u64);
+
+impl Default for Fnv1a {
+    fn default() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Fnv1a {
+    pub(crate) fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0 ^ byte as u64).wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+
+    pub(crate) fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_concurrent_runs_use_distinct_index_dirs() {
+        let root = tempfile::tempdir().unwrap();
+        let source = root.path().join("tape.avi");
+        fs::write(&source, b"interlaced video").unwrap();
+        let cache_root = root.path().join("index");
+
+        let first = IndexCache::acquire(&source, &cache_root).unwrap();
+        let second = IndexCache::acquire(&source, &cache_root).unwrap();
+        assert!(first.is_shared());
+        assert!(!second.is_shared(), "A held lock should give a private directory");
+        assert_ne!(first.path(), second.path());
+        assert!(second.path().is_dir());
+
+        let private = second.path().to_path_buf();
+        let shared = first.path().to_path_buf();
+        drop(second);
+        assert!(!private.exists(), "Private directory is removed after the run");
+        drop(first);
+
+        let third = IndexCache::acquire(&source, &cache_root).unwrap();
+        assert!(third.is_shared(), "Lock is released when the run ends");
+        assert_eq!(third.path(), shared);
+
+        fs::write(third.path().join("tape.avi.bsindex"), b"old index").unwrap();
+        clear(third.path()).unwrap();
+        assert!(!third.path().join("tape.avi.bsindex").exists(), "Index files are cleared");
+        assert!(third.path().join(LOCK_FILE).exists(), "The lock is kept");
+    }
+
+    #[test]
+    fn test_source_key_follows_content() {
+        let root = tempfile::tempdir().unwrap();
+        let a = root.path().join("a.avi");
+        let b = root.path().join("b.avi");
+        fs::write(&a, b"same").unwrap();
+        fs::write(&b, b"same").unwrap();
+        assert_eq!(source_key(&a).unwrap(), source_key(&b).unwrap());
+
+        fs::write(&b, b"different").unwrap();
+        assert_ne!(source_key(&a).unwrap(), source_key(&b).unwrap());
+    }
+}
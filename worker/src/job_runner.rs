@@ -0,0 +1,293 @@
+//! Running a single job: checking and probing the source, generating the
+//! script and encoding it. Shared by the CLI, batch mode and the async entry
+//! point.
+
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use anyhow::Result;
+
+use crate::index_cache::IndexCache;
+use crate::models::{FieldOrder, LogLevel, VideoJob, WorkerError};
+use crate::pipeline_executor::PipelineExecutor;
+use crate::platform;
+use crate::progress_reporter::{ProgressReporter, HEARTBEAT_INTERVAL};
+use crate::script_generator::ScriptGenerator;
+
+/// Detect the field order with ffprobe when neither the app nor the job
+/// supplied one, and re-resolve the job so QTGMC's TFF follows it.
+fn with_detected_field_order(job: &VideoJob, executor: &PipelineExecutor, reporter: &ProgressReporter) -> VideoJob {
+    if job.detected_field_order.is_some() || job.qtgmc_parameters.tff.is_some() {
+        return job.clone();
+    }
+
+    match executor.detect_field_order(&job.input_path) {
+        Ok(order) => {
+            reporter.send_log(
+                LogLevel::Info,
+                &format!("Detected field order: {}", order.display_name()),
+            );
+            let mut job = job.clone();
+            job.detected_field_order = Some(order);
+            job.resolved()
+        }
+        Err(e) => {
+            reporter.send_log(
+                LogLevel::Warning,
+                &format!("Field order detection failed: {:#}", e),
+            );
+            job.clone()
+        }
+    }
+}
+
+/// Warn when deinterlacing is enabled on input ffprobe reports as progressive.
+fn warn_if_progressive_input(job: &VideoJob, executor: &PipelineExecutor, reporter: &ProgressReporter) {
+    if !job.warn_unnecessary_deinterlace || !job.effective_pipeline().deinterlace.enabled {
+        return;
+    }
+    let order = match job.detected_field_order {
+        Some(order) => order,
+        None => executor.detect_field_order(&job.input_path).unwrap_or(FieldOrder::Unknown),
+    };
+    if let Some(warning) = job.unnecessary_deinterlace_warning(order) {
+        reporter.send_log(LogLevel::Warning, warning);
+    }
+}
+
+/// Probe the source for frame count and frame rate when the job doesn't
+/// supply them, and for its frame size when the crop/resize pass needs
+/// checking against it. Crop/resize settings the source can't take fail the job.
+fn with_probed_clip_info(job: &VideoJob, executor: &PipelineExecutor, reporter: &ProgressReporter) -> Result<VideoJob> {
+    let needs_size = job.effective_pipeline().crop_resize.enabled;
+    if job.total_frames.is_some() && job.input_frame_rate.is_some() && !needs_size {
+        return Ok(job.clone());
+    }
+
+    match executor.probe(&job.input_path) {
+        Ok(info) => {
+            reporter.send_log(
+                LogLevel::Debug,
+                &format!("Probed clip: {}x{}, {} frames, {}/{} fps, {}",
+                    info.width, info.height, info.frames, info.fps_num, info.fps_den, info.format),
+            );
+            job.validate_for_clip(&info).map_err(|e| WorkerError::InvalidConfig.wrap(e))?;
+            Ok(job.with_clip_info(&info))
+        }
+        Err(e) => {
+            reporter.send_log(
+                LogLevel::Warning,
+                &format!("Clip probe failed: {:#}", e),
+            );
+            Ok(job.clone())
+        }
+    }
+}
+
+/// Probe the source keyframes for a keyframes-only proxy when the job doesn't
+/// list them. Without them every frame is processed.
+fn with_keyframes(job: &VideoJob, executor: &PipelineExecutor, reporter: &ProgressReporter) -> VideoJob {
+    if !job.keyframes_only || job.keyframes.is_some() {
+        return job.clone();
+    }
+    let mut job = job.clone();
+    match executor.probe_keyframes(&job.input_path) {
+        Ok(keyframes) => {
+            reporter.send_log(
+                LogLevel::Info,
+                &format!("Keyframes-only proxy: {} keyframes", keyframes.len()),
+            );
+            job.keyframes = Some(keyframes);
+        }
+        Err(e) => {
+            reporter.send_log(
+                LogLevel::Warning,
+                &format!("Keyframe probe failed, processing every frame: {:#}", e),
+            );
+            job.keyframes_only = false;
+        }
+    }
+    job
+}
+
+/// Render the output path from the job's output template, probing the frame
+/// size only when the template uses it.
+fn with_output_path(job: &VideoJob, executor: &PipelineExecutor) -> Result<VideoJob> {
+    let uses_size = job.output_template.as_deref()
+        .is_some_and(|template| template.contains("{width}") || template.contains("{height}"));
+    let source = if uses_size {
+        executor.probe(&job.input_path).ok().map(|info| (info.width, info.height))
+    } else {
+        None
+    };
+    job.with_rendered_output_path(source).map_err(|e| WorkerError::InvalidConfig.wrap(e))
+}
+
+/// Fail early when the output volume can't hold the estimated output. Skipped
+/// (with a warning) when the size can't be estimated or free space queried.
+fn check_disk_space(job: &VideoJob, executor: &PipelineExecutor, reporter: &ProgressReporter) -> Result<()> {
+    let output_dir = Path::new(&job.output_path).parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    let estimate = executor.probe(&job.input_path)
+        .ok()
+        .and_then(|info| job.estimated_output_bytes((info.width, info.height)));
+    let (Some(required), Ok(available)) = (estimate, platform::available_space(output_dir)) else {
+        reporter.send_log(LogLevel::Warning, "Could not estimate the output size; skipping the disk space check");
+        return Ok(());
+    };
+    reporter.send_log(
+        LogLevel::Debug,
+        &format!("Estimated output size: {} bytes, {} bytes free", required, available),
+    );
+    platform::check_free_space(required, available)
+}
+
+/// Run `job` through the vspipe | ffmpeg pipeline: check and probe the source,
+/// generate the script and encode it, stopping when `cancelled` is raised and
+/// pausing while `paused` is. Returns the output path.
+pub fn run_worker(
+    job: &VideoJob,
+    reporter: &ProgressReporter,
+    cancelled: Arc<AtomicBool>,
+    paused: Arc<AtomicBool>,
+    keep_temp: bool,
+) -> Result<String> {
+    // A job cancelled before it starts doesn't touch the source
+    if cancelled.load(Ordering::SeqCst) {
+        return Err(WorkerError::Cancelled.into());
+    }
+    let _heartbeat = reporter.start_heartbeat(HEARTBEAT_INTERVAL);
+
+    // Held until the encode finishes so no other run writes the same index,
+    // including the probes below that load the source with BestSource
+    let index_cache = match IndexCache::for_source(Path::new(&job.input_path)) {
+        Ok(cache) => {
+            if !cache.is_shared() {
+                reporter.send_log(
+                    LogLevel::Info,
+                    "Source index is in use by another job; indexing into a private directory",
+                );
+            }
+            Some(cache)
+        }
+        Err(e) => {
+            reporter.send_log(LogLevel::Warning, &format!("Index cache unavailable: {:#}", e));
+            None
+        }
+    };
+    let index_cache_dir = index_cache.as_ref().map(IndexCache::path);
+    let mut executor = PipelineExecutor::new(reporter.clone())?
+        .with_keep_temp(keep_temp)
+        .with_pause_flag(paused)
+        .with_index_cache_dir(index_cache_dir);
+
+    reporter.send_log(LogLevel::Info, "Checking that the source decodes...");
+    executor.check_decodable(&job.input_path)?;
+    executor.check_output_chroma(&job.encoding_settings)?;
+
+    let job = &with_detected_field_order(job, &executor, reporter);
+    let job = &with_probed_clip_info(job, &executor, reporter)?;
+    let job = &with_keyframes(job, &executor, reporter);
+    let job = &with_output_path(job, &executor)?;
+    warn_if_progressive_input(job, &executor, reporter);
+
+    reporter.send_log(
+        LogLevel::Info,
+        &format!("Processing: {}", job.input_path),
+    );
+    reporter.send_log(
+        LogLevel::Debug,
+        &format!("QTGMC params: opencl={}, tff={:?}, preset={}",
+            job.qtgmc_parameters.opencl,
+            job.qtgmc_parameters.tff,
+            job.qtgmc_parameters.preset.as_str()),
+    );
+
+    if job.effective_pipeline().deinterlace.enabled {
+        for warning in job.qtgmc_parameters.warnings() {
+            reporter.send_log(LogLevel::Warning, &warning);
+        }
+    }
+    for advisory in job.lint() {
+        reporter.send_log(LogLevel::Warning, &advisory.to_string());
+    }
+
+    platform::ensure_output_dir(Path::new(&job.output_path), job.create_output_dirs)?;
+    if job.check_disk_space {
+        check_disk_space(job, &executor, reporter)?;
+    }
+
+    // Generate VapourSynth script
+    reporter.send_log(LogLevel::Info, "Generating VapourSynth script...");
+    let script_generator = ScriptGenerator::new()?
+        .with_keep_temp(keep_temp)
+        .with_index_cache_dir(index_cache_dir);
+    let script_path = script_generator
+        .generate(job)
+        .map_err(|e| WorkerError::ScriptGeneration.wrap(e))?;
+
+    reporter.send_log(
+        LogLevel::Debug,
+        &format!("Script written to: {:?}", script_path.path()),
+    );
+
+    // Execute pipeline
+    reporter.send_log(LogLevel::Info, "Starting encoding pipeline...");
+
+    // The scripts are removed when they drop, unless --keep-temp
+    let fallback_script = executor
+        .execute(&script_path, job, || cancelled.load(Ordering::SeqCst))
+        .map_err(|e| WorkerError::Pipeline.wrap(e))?;
+
+    if job.measure_quality && !cancelled.load(Ordering::SeqCst) {
+        reporter.send_log(LogLevel::Info, "Measuring output quality...");
+        let encoded_script = fallback_script.as_deref().unwrap_or(&script_path);
+        match executor.measure_quality(encoded_script, job) {
+            Ok(report) => reporter.send_quality_report(&report),
+            Err(e) => reporter.send_log(
+                LogLevel::Warning,
+                &format!("Quality measurement failed: {:#}", e),
+            ),
+        }
+    }
+
+    if cancelled.load(Ordering::SeqCst) {
+        return Err(discard_cancelled_output(&job.output_path, reporter));
+    }
+
+    reporter.send_log(LogLevel::Info, "Encoding complete!");
+    Ok(job.output_path.clone())
+}
+
+/// Remove the partial output of a cancelled job, returning the cancellation
+/// error so the worker exits with the cancelled code.
+fn discard_cancelled_output(output_path: &str, reporter: &ProgressReporter) -> anyhow::Error {
+    if let Err(e) = std::fs::remove_file(output_path) {
+        reporter.send_log(
+            LogLevel::Warning,
+            &format!("Failed to remove partial output: {}", e),
+        );
+    }
+    WorkerError::Cancelled.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cancelled_job_exits_with_cancelled_code() {
+        let output = std::env::temp_dir().join(format!("vapourbox_cancelled_{}.mp4", std::process::id()));
+        std::fs::write(&output, b"partial").unwrap();
+        let reporter = ProgressReporter::capturing();
+
+        let error = discard_cancelled_output(output.to_str().unwrap(), &reporter);
+
+        assert_eq!(WorkerError::classify(&error), Some(WorkerError::Cancelled));
+        assert_eq!(WorkerError::exit_code_for(&error), 130);
+        assert!(!output.exists(), "Partial output should be removed");
+        assert!(reporter.captured().is_empty(), "No warning when the removal succeeds");
+    }
+}
@@ -3,11 +3,19 @@
 //! Provides video restoration functionality using VapourSynth.
 
 pub mod models;
+#[cfg(feature = "async")]
+pub mod async_job;
+pub mod batch;
 pub mod dependency_locator;
 pub mod filter_registry;
 pub mod filter_schema;
+pub mod hooks;
+pub mod index_cache;
+pub mod job_runner;
 pub mod pipeline_executor;
+pub mod preview_cache;
 pub mod progress_reporter;
+pub mod qtgmc_presets;
 pub mod schema_script_generator;
 pub mod script_generator;
 pub mod platform;
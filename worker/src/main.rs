@@ -5,25 +5,63 @@
 //! Progress is reported via JSON messages on stdout.
 //!
 //! Preview mode: Use --preview --frame N to generate a single processed frame
-//! as PNG output to stdout (binary).
+//! as PNG output to stdout (binary), or to a file with --preview-out <path>.
+//! Add --compare <config> to stack the output of a second job's pipeline next
+//! to it for A/B comparison. Add --preview-progress to emit JSON phase messages
+//! on stderr while the preview is generated.
+//!
+//! Use --print-effective-config to print the fully-resolved job JSON
+//! (after preset expansion, TFF defaulting and legacy migration).
+//!
+//! Batch mode: Use --batch <file> with a JSON array of jobs to run them in
+//! order. Messages carry a `jobIndex`, and a `batchSummary` message follows
+//! the last job. Add --continue-on-error to keep going after a failed job.
+//!
+//! Generated scripts and preview temp files are deleted when no longer needed;
+//! pass --keep-temp to leave them on disk for debugging.
+//!
+//! A job's `onCompleteCommand` runs after it succeeds (with `{output}`
+//! replaced by the output path) only when --allow-hooks is passed.
+//!
+//! On Unix, sending SIGUSR1 pauses the running encode (a `paused` phase
+//! message) and sending it again resumes it.
+//!
+//! Exit codes:
+//! - 0: success
+//! - 1: other failure (including a batch with failed jobs)
+//! - 2: a required dependency (vspipe, ffmpeg, ...) is missing
+//! - 3: the job configuration could not be read or is invalid
+//! - 4: the VapourSynth script could not be generated
+//! - 5: the vspipe | ffmpeg pipeline failed
+//! - 130: cancelled
 
 use anyhow::{Context, Result};
 use clap::Parser;
-use std::path::PathBuf;
+use std::io::Write;
+use std::path::{Path, PathBuf};
 use std::process::ExitCode;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 
 mod models;
+mod batch;
 mod dependency_locator;
+mod hooks;
+mod index_cache;
+mod job_runner;
 mod pipeline_executor;
+mod preview_cache;
 mod progress_reporter;
+mod qtgmc_presets;
 mod script_generator;
 mod platform;
 
-use models::VideoJob;
+use models::{FrameRate, VideoJob, WorkerError};
+use job_runner::run_worker;
 use pipeline_executor::PipelineExecutor;
+use preview_cache::PreviewCache;
 use progress_reporter::ProgressReporter;
+use qtgmc_presets::QTGMCPresetLibrary;
 use script_generator::ScriptGenerator;
 
 /// Command-line arguments
@@ -33,21 +71,105 @@ use script_generator::ScriptGenerator;
 #[command(version)]
 struct Args {
     /// Path to the job configuration JSON file
-    #[arg(long)]
-    config: PathBuf,
+    #[arg(long, required_unless_present_any = ["batch", "check_templates", "list_plugins"])]
+    config: Option<PathBuf>,
+
+    /// Run every job in this JSON array of job configurations, in order
+    #[arg(long, conflicts_with_all = ["config", "preview", "print_effective_config", "validate"])]
+    batch: Option<PathBuf>,
+
+    /// In batch mode, run the remaining jobs after one fails
+    #[arg(long, requires = "batch")]
+    continue_on_error: bool,
 
     /// Preview mode: generate a single processed frame as PNG to stdout
     #[arg(long)]
     preview: bool,
 
-    /// Frame number to extract in preview mode (required with --preview)
+    /// Frame number to extract in preview mode (--preview needs this or --timecode)
     #[arg(long)]
     frame: Option<i32>,
+
+    /// Time to preview as HH:MM:SS.mmm, converted to a frame with the job's frame rate
+    #[arg(long, value_name = "HH:MM:SS.mmm", conflicts_with = "frame")]
+    timecode: Option<String>,
+
+    /// Second job configuration to compare against in preview mode (A/B side by side)
+    #[arg(long)]
+    compare: Option<PathBuf>,
+
+    /// Write the preview PNG to this file instead of stdout
+    #[arg(long)]
+    preview_out: Option<PathBuf>,
+
+    /// Emit JSON phase/log messages on stderr during preview generation
+    #[arg(long)]
+    preview_progress: bool,
+
+    /// Source frames extracted around the previewed frame in preview mode (odd,
+    /// up to 101); more gives filters with a large temporal radius context
+    #[arg(long, value_name = "N", default_value_t = pipeline_executor::PREVIEW_WINDOW_FRAMES,
+          value_parser = parse_preview_frames)]
+    preview_frames: u64,
+
+    /// Always render the preview instead of reusing a cached frame
+    #[arg(long)]
+    no_preview_cache: bool,
+
+    /// Print the effective (resolved) job configuration as JSON and exit
+    #[arg(long)]
+    print_effective_config: bool,
+
+    /// Validate the job configuration, print advisories and exit
+    #[arg(long)]
+    validate: bool,
+
+    /// Keep generated scripts and preview temp files (for debugging)
+    #[arg(long)]
+    keep_temp: bool,
+
+    /// Run the job's onCompleteCommand after it succeeds (off by default, as
+    /// the job file could come from anyone)
+    #[arg(long)]
+    allow_hooks: bool,
+
+    /// Write the job's VapourSynth script to this path for vspreview/vsedit, then exit
+    #[arg(long, value_name = "PATH", requires = "config", conflicts_with_all = ["batch", "preview", "print_effective_config"])]
+    emit_script: Option<PathBuf>,
+
+    /// Check the script templates for unbalanced blocks and unknown placeholders, then exit
+    #[arg(long, exclusive = true)]
+    check_templates: bool,
+
+    /// Report which VapourSynth plugins load (those the job needs with --config,
+    /// otherwise every known plugin) as JSON, then exit
+    #[arg(long, conflicts_with_all = ["batch", "preview", "print_effective_config", "validate"])]
+    list_plugins: bool,
 }
 
 fn main() -> ExitCode {
     let args = Args::parse();
 
+    if args.check_templates {
+        return run_check_templates();
+    }
+
+    if args.list_plugins {
+        return run_list_plugins(&args);
+    }
+
+    if args.print_effective_config {
+        return run_print_effective_config(&args);
+    }
+
+    if args.validate {
+        return run_validate(&args);
+    }
+
+    if let Some(path) = &args.emit_script {
+        return run_emit_script(&args, path);
+    }
+
     // Preview mode outputs raw PNG to stdout - no JSON messages
     if args.preview {
         return run_preview_mode(&args);
@@ -55,19 +177,37 @@ fn main() -> ExitCode {
 
     let reporter = ProgressReporter::new();
 
-    // Set up cancellation flag
+    // Set up cancellation and pause flags
     let cancelled = Arc::new(AtomicBool::new(false));
     let cancelled_clone = cancelled.clone();
+    let paused = Arc::new(AtomicBool::new(false));
+    let paused_clone = paused.clone();
 
-    // Handle SIGTERM/SIGINT for graceful cancellation
+    // SIGUSR1 toggles pause; set up before any other thread starts
+    if let Err(e) = platform::toggle_on_sigusr1(paused.clone()) {
+        reporter.send_log(models::LogLevel::Debug, &format!("{:#}", e));
+    }
+
+    // Handle SIGTERM/SIGINT for graceful cancellation (resuming a paused encode
+    // so it can see the cancellation)
     if let Err(e) = ctrlc::set_handler(move || {
         cancelled_clone.store(true, Ordering::SeqCst);
+        paused_clone.store(false, Ordering::SeqCst);
     }) {
         reporter.send_error(&format!("Failed to set signal handler: {}", e));
         return ExitCode::from(1);
     }
 
-    match run_worker(&args, &reporter, cancelled) {
+    if let Some(batch_path) = &args.batch {
+        return run_batch_mode(batch_path, &args, &reporter, cancelled, paused);
+    }
+
+    let result = load_config(&args, &reporter).and_then(|job| {
+        let result = run_worker(&job, &reporter, cancelled, paused, args.keep_temp);
+        hooks::run_on_complete(&job, &result, args.allow_hooks, &reporter);
+        result
+    });
+    match result {
         Ok(output_path) => {
             reporter.send_complete(true, Some(&output_path));
             // Small delay to ensure stdout is flushed and received by parent process
@@ -75,63 +215,285 @@ fn main() -> ExitCode {
             ExitCode::SUCCESS
         }
         Err(e) => {
-            // Check if this was a cancellation
-            if e.to_string().contains("cancelled") {
+            if WorkerError::classify(&e) == Some(WorkerError::Cancelled) {
                 reporter.send_log(models::LogLevel::Info, "Job cancelled by user");
-                reporter.send_complete(false, None);
-                ExitCode::from(130) // Standard exit code for SIGINT
+            } else if let Some(dep) = e.downcast_ref::<models::DependencyError>() {
+                // Typed report so the app can offer to download dependencies
+                reporter.send_dependency_missing(dep);
             } else {
                 reporter.send_error(&format!("{:#}", e));
-                reporter.send_complete(false, None);
+            }
+            reporter.send_complete(false, None);
+            ExitCode::from(WorkerError::exit_code_for(&e))
+        }
+    }
+}
+
+/// Parse and validate --preview-frames.
+fn parse_preview_frames(value: &str) -> Result<u64, String> {
+    let frames = value.parse().map_err(|e: std::num::ParseIntError| e.to_string())?;
+    pipeline_executor::validate_preview_frames(frames).map_err(|e| e.to_string())
+}
+
+/// Path given with --config (required outside batch mode).
+fn config_path(args: &Args) -> Result<&Path> {
+    args.config.as_deref().context("--config is required")
+}
+
+/// Read a job configuration file and apply its custom QTGMC presets,
+/// without resolving it.
+fn read_job(path: &Path) -> Result<VideoJob> {
+    let config_content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read config file: {:?}", path))?;
+    let job: VideoJob = serde_json::from_str(&config_content)
+        .with_context(|| "Failed to parse job configuration")?;
+    QTGMCPresetLibrary::load_user_presets()?.apply_to_job(&job)
+}
+
+/// Load a job configuration file and resolve it into the effective job.
+fn load_job(path: &Path) -> Result<VideoJob> {
+    read_job(path).map(|job| job.resolved())
+}
+
+/// Print every template problem to stdout; fails if there are any.
+fn run_check_templates() -> ExitCode {
+    let problems = match ScriptGenerator::new() {
+        Ok(generator) => generator.validate_template(),
+        Err(e) => {
+            eprintln!("Error: {:#}", e);
+            return ExitCode::from(1);
+        }
+    };
+    for problem in &problems {
+        println!("{}", problem);
+    }
+    if problems.is_empty() {
+        println!("Templates OK");
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::from(1)
+    }
+}
+
+/// Print the load status of the job's (or every known) plugin as JSON to
+/// stdout; fails if any is missing.
+fn run_list_plugins(args: &Args) -> ExitCode {
+    let pipeline = match args.config.as_deref().map(load_job).transpose() {
+        Ok(job) => job.map(|job| job.effective_pipeline()),
+        Err(e) => {
+            eprintln!("Error: {:#}", e);
+            return ExitCode::from(WorkerError::InvalidConfig.exit_code());
+        }
+    };
+    let namespaces = pipeline.as_ref()
+        .map_or_else(|| script_generator::KNOWN_PLUGINS.to_vec(), |p| p.required_plugins());
+
+    let report = PipelineExecutor::new(ProgressReporter::disabled())
+        .and_then(|executor| executor.list_plugins(&namespaces));
+    match report {
+        Ok(report) => {
+            println!("{}", serde_json::to_string_pretty(&report).unwrap_or_default());
+            if report.iter().all(|plugin| plugin.loaded) {
+                ExitCode::SUCCESS
+            } else {
                 ExitCode::from(1)
             }
         }
+        Err(e) => {
+            eprintln!("Error: {:#}", e);
+            ExitCode::from(1)
+        }
     }
 }
 
-/// Run in preview mode - generate single frame PNG to stdout
+/// Print the effective job configuration as JSON to stdout
+fn run_print_effective_config(args: &Args) -> ExitCode {
+    let job = match config_path(args).and_then(load_job) {
+        Ok(j) => j,
+        Err(e) => {
+            eprintln!("Error: {:#}", e);
+            return ExitCode::from(WorkerError::InvalidConfig.exit_code());
+        }
+    };
+
+    match serde_json::to_string_pretty(&job) {
+        Ok(json) => {
+            println!("{}", json);
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("Error serializing config: {}", e);
+            ExitCode::from(1)
+        }
+    }
+}
+
+/// Validate the job configuration and print its advisories to stdout
+fn run_validate(args: &Args) -> ExitCode {
+    let job = match config_path(args).and_then(load_job) {
+        Ok(j) => j,
+        Err(e) => {
+            eprintln!("Error: {:#}", e);
+            return ExitCode::from(WorkerError::InvalidConfig.exit_code());
+        }
+    };
+    if let Err(e) = script_generator::validate_job(&job) {
+        eprintln!("Error: {:#}", e);
+        return ExitCode::from(WorkerError::exit_code_for(&e));
+    }
+
+    // Decode the start of the source so a corrupt file fails here, not mid-job
+    let decodable = PipelineExecutor::new(ProgressReporter::disabled())
+        .and_then(|executor| executor.check_decodable(&job.input_path));
+    if let Err(e) = decodable {
+        if WorkerError::classify(&e) != Some(WorkerError::DependencyMissing) {
+            eprintln!("Error: {:#}", e);
+            return ExitCode::from(1);
+        }
+        println!("Note: skipped the source decode check ({:#})", e);
+    }
+
+    println!("Configuration is valid");
+    for advisory in job.lint() {
+        println!("Advisory: {}", advisory);
+    }
+    ExitCode::SUCCESS
+}
+
+/// Write the job's standalone VapourSynth script to `path`
+fn run_emit_script(args: &Args, path: &Path) -> ExitCode {
+    let job = match config_path(args).and_then(load_job) {
+        Ok(j) => j,
+        Err(e) => {
+            eprintln!("Error: {:#}", e);
+            return ExitCode::from(WorkerError::InvalidConfig.exit_code());
+        }
+    };
+
+    let result = ScriptGenerator::new()
+        .and_then(|generator| generator.generate_standalone(&job))
+        .and_then(|script| {
+            std::fs::write(path, script).with_context(|| format!("Failed to write script to {:?}", path))
+        });
+    match result {
+        Ok(()) => {
+            eprintln!("Script written to {:?}", path);
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("Error: {:#}", e);
+            ExitCode::from(WorkerError::exit_code_for(&e))
+        }
+    }
+}
+
+/// Run in preview mode - generate single frame PNG to stdout or --preview-out
 fn run_preview_mode(args: &Args) -> ExitCode {
-    let frame = match args.frame {
-        Some(f) => f,
-        None => {
-            eprintln!("Error: --frame is required with --preview");
+    let timecode = match args.timecode.as_deref().map(models::parse_timecode).transpose() {
+        Ok(t) => t,
+        Err(e) => {
+            eprintln!("Error: {:#}", e);
             return ExitCode::from(1);
         }
     };
+    if args.frame.is_none() && timecode.is_none() {
+        eprintln!("Error: --frame or --timecode is required with --preview");
+        return ExitCode::from(1);
+    }
 
     // Load job configuration
-    let config_content = match std::fs::read_to_string(&args.config) {
-        Ok(c) => c,
+    let job = match config_path(args).and_then(load_job) {
+        Ok(j) => j,
         Err(e) => {
-            eprintln!("Error reading config: {}", e);
-            return ExitCode::from(1);
+            eprintln!("Error loading config: {:#}", e);
+            return ExitCode::from(WorkerError::InvalidConfig.exit_code());
         }
     };
 
-    let job: VideoJob = match serde_json::from_str(&config_content) {
+    // Load the comparison job for A/B preview
+    let compare_job = match args.compare.as_deref().map(load_job).transpose() {
         Ok(j) => j,
         Err(e) => {
-            eprintln!("Error parsing config: {}", e);
-            return ExitCode::from(1);
+            eprintln!("Error loading compare config: {:#}", e);
+            return ExitCode::from(WorkerError::InvalidConfig.exit_code());
         }
     };
 
-    // Calculate time from frame number
-    let frame_rate = job.input_frame_rate.unwrap_or(29.97);
-    let time_seconds = frame as f64 / frame_rate;
+    // Calculate time from frame number (snapping a timecode to the frame it shows)
+    let frame_rate = job.input_frame_rate.unwrap_or(FrameRate::NTSC).as_f64();
+    let frame = match (args.frame, timecode) {
+        (Some(frame), _) => frame,
+        (None, Some(seconds)) => models::seconds_to_frame(seconds, frame_rate),
+        (None, None) => unreachable!("checked above"),
+    };
+    let time_seconds = models::frame_to_seconds(frame, frame_rate);
+
+    eprintln!(
+        "Preview: frame {} at {} (fps: {:.2})",
+        frame,
+        models::format_timecode(time_seconds),
+        frame_rate
+    );
+
+    let mut out = match pipeline_executor::preview_output(args.preview_out.as_deref()) {
+        Ok(o) => o,
+        Err(e) => {
+            eprintln!("Error: {:#}", e);
+            return ExitCode::from(1);
+        }
+    };
 
-    eprintln!("Preview: frame {} at {:.3}s (fps: {:.2})", frame, time_seconds, frame_rate);
+    // Reuse the last render when nothing that affects the preview has changed
+    let cached = (!args.no_preview_cache && !args.keep_temp)
+        .then(|| {
+            let key = PreviewCache::key(&job, compare_job.as_ref(), frame, args.preview_frames).ok()?;
+            Some((PreviewCache::open().ok()?, key))
+        })
+        .flatten();
+    if let Some(png) = cached.as_ref().and_then(|(cache, key)| cache.get(key)) {
+        eprintln!("Preview: using cached frame");
+        return match out.write_all(&png).and_then(|()| out.flush()) {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(e) => {
+                eprintln!("Error writing preview: {}", e);
+                ExitCode::from(1)
+            }
+        };
+    }
 
     // Execute preview (extracts frames with ffmpeg, processes with VapourSynth)
-    let executor = match PipelineExecutor::new(ProgressReporter::new()) {
-        Ok(e) => e,
+    // stdout carries the PNG, so structured messages go to stderr when requested
+    let reporter = if args.preview_progress {
+        ProgressReporter::stderr()
+    } else {
+        ProgressReporter::disabled()
+    };
+
+    let executor = match PipelineExecutor::new(reporter) {
+        Ok(e) => e.with_keep_temp(args.keep_temp).with_preview_frames(args.preview_frames),
         Err(e) => {
             eprintln!("Error creating executor: {}", e);
             return ExitCode::from(1);
         }
     };
 
-    match executor.generate_preview(&job, time_seconds) {
+    // Render into memory so the frame can be cached as well as written out
+    let mut png = Vec::new();
+    let result = match compare_job {
+        Some(ref job_b) => executor.generate_ab_preview(&job, job_b, time_seconds, &mut png),
+        None => executor.generate_preview(&job, time_seconds, &mut png),
+    }
+    .and_then(|()| {
+        if let Some((cache, key)) = &cached {
+            if let Err(e) = cache.put(key, &png) {
+                eprintln!("Warning: {:#}", e);
+            }
+        }
+        out.write_all(&png)?;
+        Ok(out.flush()?)
+    });
+
+    match result {
         Ok(()) => ExitCode::SUCCESS,
         Err(e) => {
             eprintln!("Error generating preview: {}", e);
@@ -140,70 +502,49 @@ fn run_preview_mode(args: &Args) -> ExitCode {
     }
 }
 
-fn run_worker(
+/// Run every job in a batch manifest and report a summary.
+fn run_batch_mode(
+    batch_path: &Path,
     args: &Args,
     reporter: &ProgressReporter,
     cancelled: Arc<AtomicBool>,
-) -> Result<String> {
-    // Load job configuration
-    reporter.send_log(models::LogLevel::Info, "Loading job configuration...");
-    let config_content = std::fs::read_to_string(&args.config)
-        .with_context(|| format!("Failed to read config file: {:?}", args.config))?;
-    let job: VideoJob = serde_json::from_str(&config_content)
-        .with_context(|| "Failed to parse job configuration")?;
-
-    reporter.send_log(
-        models::LogLevel::Info,
-        &format!("Processing: {}", job.input_path),
-    );
-    reporter.send_log(
-        models::LogLevel::Debug,
-        &format!("QTGMC params: opencl={}, tff={:?}, preset={}",
-            job.qtgmc_parameters.opencl,
-            job.qtgmc_parameters.tff,
-            job.qtgmc_parameters.preset.as_str()),
-    );
-
-    // Generate VapourSynth script
-    reporter.send_log(models::LogLevel::Info, "Generating VapourSynth script...");
-    let script_generator = ScriptGenerator::new()?;
-    let script_path = script_generator
-        .generate(&job)
-        .with_context(|| "Failed to generate VapourSynth script")?;
-
-    reporter.send_log(
-        models::LogLevel::Debug,
-        &format!("Script written to: {:?}", script_path),
-    );
-
-    // Execute pipeline
-    reporter.send_log(models::LogLevel::Info, "Starting encoding pipeline...");
-    let mut executor = PipelineExecutor::new(reporter.clone())?;
-
-    let result = executor.execute(&script_path, &job, || cancelled.load(Ordering::SeqCst));
-
-    // Keep temp script for debugging
-    // if let Err(e) = std::fs::remove_file(&script_path) {
-    //     reporter.send_log(
-    //         models::LogLevel::Warning,
-    //         &format!("Failed to remove temp script: {}", e),
-    //     );
-    // }
+    paused: Arc<AtomicBool>,
+) -> ExitCode {
+    reporter.send_log(models::LogLevel::Info, "Loading batch manifest...");
+    let jobs = match batch::load_batch(batch_path, reporter) {
+        Ok(jobs) => jobs,
+        Err(e) => {
+            reporter.send_error(&format!("{:#}", e));
+            reporter.send_complete(false, None);
+            return ExitCode::from(1);
+        }
+    };
 
-    // Handle cancellation or errors
-    result?;
+    let summary = batch::run_batch(&jobs, reporter, args.continue_on_error, &cancelled, |job, job_reporter| {
+        let result = run_worker(job, job_reporter, cancelled.clone(), paused.clone(), args.keep_temp);
+        hooks::run_on_complete(job, &result, args.allow_hooks, job_reporter);
+        result
+    });
+    // Small delay to ensure stdout is flushed and received by parent process
+    std::thread::sleep(std::time::Duration::from_millis(100));
 
-    // If cancelled, remove partial output
     if cancelled.load(Ordering::SeqCst) {
-        if let Err(e) = std::fs::remove_file(&job.output_path) {
-            reporter.send_log(
-                models::LogLevel::Warning,
-                &format!("Failed to remove partial output: {}", e),
-            );
-        }
-        anyhow::bail!("Job cancelled");
+        ExitCode::from(WorkerError::Cancelled.exit_code())
+    } else if summary.failed > 0 {
+        ExitCode::from(1)
+    } else {
+        ExitCode::SUCCESS
     }
+}
 
-    reporter.send_log(models::LogLevel::Info, "Encoding complete!");
-    Ok(job.output_path.clone())
+/// Load the --config job, reporting progress.
+fn load_config(args: &Args, reporter: &ProgressReporter) -> Result<VideoJob> {
+    reporter.send_log(models::LogLevel::Info, "Loading job configuration...");
+    let job = config_path(args)
+        .and_then(read_job)
+        .map_err(|e| WorkerError::InvalidConfig.wrap(e))?;
+    if let Some(warning) = job.legacy_warning() {
+        reporter.send_log(models::LogLevel::Warning, warning);
+    }
+    Ok(job.resolved())
 }
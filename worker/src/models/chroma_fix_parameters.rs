@@ -23,6 +23,10 @@ pub struct ChromaFixParameters {
     #[serde(default)]
     pub enabled: bool,
 
+    /// Temporarily skip this pass while keeping it enabled and configured.
+    #[serde(default)]
+    pub bypass: bool,
+
     /// Preset level for simple mode.
     #[serde(default)]
     pub preset: ChromaFixPreset,
@@ -99,6 +103,7 @@ impl Default for ChromaFixParameters {
     fn default() -> Self {
         Self {
             enabled: false,
+            bypass: false,
             preset: ChromaFixPreset::default(),
             apply_chroma_bleeding_fix: false,
             chroma_bleed_cx: default_chroma_bleed_offset(),
@@ -117,6 +122,42 @@ impl Default for ChromaFixParameters {
     }
 }
 
+impl ChromaFixParameters {
+    /// Create parameters from a preset (mirrors the app's preset values).
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub fn from_preset(preset: ChromaFixPreset) -> Self {
+        let mut params = Self { enabled: preset != ChromaFixPreset::Off, preset, ..Default::default() };
+        params.apply_preset();
+        params
+    }
+
+    /// Overlay the values `preset` defines; other fields are kept.
+    pub fn apply_preset(&mut self) {
+        match self.preset {
+            ChromaFixPreset::VhsCleanup => {
+                self.apply_chroma_bleeding_fix = true;
+                self.chroma_bleed_c_blur = 0.8;
+                self.chroma_bleed_strength = 0.8;
+                self.apply_vinverse = true;
+                self.vinverse_sstr = 2.7;
+            }
+            ChromaFixPreset::BroadcastFix => {
+                self.apply_de_crawl = true;
+                self.de_crawl_y_thresh = 12;
+                self.de_crawl_c_thresh = 12;
+            }
+            ChromaFixPreset::AnalogRepair => {
+                self.apply_chroma_bleeding_fix = true;
+                self.chroma_bleed_c_blur = 1.0;
+                self.chroma_bleed_strength = 1.0;
+                self.apply_de_crawl = true;
+                self.apply_vinverse = true;
+            }
+            ChromaFixPreset::Off | ChromaFixPreset::Custom => {}
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -131,6 +172,15 @@ mod tests {
         assert!(!params.apply_vinverse);
     }
 
+    #[test]
+    fn test_apply_preset_keeps_other_fields() {
+        let mut params = ChromaFixParameters { preset: ChromaFixPreset::BroadcastFix, apply_vinverse: true, ..Default::default() };
+        params.apply_preset();
+        let preset = ChromaFixParameters::from_preset(ChromaFixPreset::BroadcastFix);
+        assert_eq!((params.apply_de_crawl, params.de_crawl_y_thresh), (preset.apply_de_crawl, preset.de_crawl_y_thresh));
+        assert!(params.apply_vinverse, "Fields the preset leaves alone are kept");
+    }
+
     #[test]
     fn test_serialization() {
         let params = ChromaFixParameters::default();
@@ -0,0 +1,110 @@
+//! Clip metadata reported by `vspipe --info`.
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+
+use super::FrameRate;
+
+/// Basic properties of a video clip.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClipInfo {
+    /// Frame width in pixels (0 for variable-size clips)
+    pub width: i32,
+
+    /// Frame height in pixels (0 for variable-size clips)
+    pub height: i32,
+
+    /// Number of frames
+    pub frames: i32,
+
+    /// Frame rate numerator (0 for variable frame rate)
+    pub fps_num: i64,
+
+    /// Frame rate denominator (0 for variable frame rate)
+    pub fps_den: i64,
+
+    /// VapourSynth format name (e.g. "YUV420P8")
+    pub format: String,
+}
+
+impl ClipInfo {
+    /// Parse the output of `vspipe --info`.
+    ///
+    /// Expected lines (other lines are ignored):
+    /// ```text
+    /// Width: 720
+    /// Height: 480
+    /// Frames: 1000
+    /// FPS: 30000/1001 (29.970 fps)
+    /// Format Name: YUV420P8
+    /// ```
+    pub fn from_vspipe_info(output: &str) -> Result<ClipInfo> {
+        let field = |name: &str| {
+            output.lines()
+                .find_map(|line| line.strip_prefix(name)?.strip_prefix(':'))
+                .map(str::trim)
+        };
+        let int = |name: &str| -> Result<i64> {
+            let value = field(name).with_context(|| format!("vspipe --info output has no {}", name))?;
+            value.parse().with_context(|| format!("Invalid {} in vspipe --info output: {}", name, value))
+        };
+
+        let (fps_num, fps_den) = match field("FPS").and_then(|v| v.split_whitespace().next()) {
+            Some(rate) => match rate.split_once('/') {
+                Some((num, den)) => (num.parse()?, den.parse()?),
+                None => (rate.parse()?, 1),
+            },
+            None => bail!("vspipe --info output has no FPS"),
+        };
+
+        Ok(ClipInfo {
+            width: int("Width")? as i32,
+            height: int("Height")? as i32,
+            frames: int("Frames")? as i32,
+            fps_num,
+            fps_den,
+            format: field("Format Name").unwrap_or_default().to_string(),
+        })
+    }
+
+    /// Frame rate in frames per second, or `None` for variable frame rate.
+    pub fn frame_rate(&self) -> Option<FrameRate> {
+        FrameRate::new(self.fps_num, self.fps_den)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_vspipe_info() {
+        let output = "Width: 720\n\
+                      Height: 480\n\
+                      Frames: 1000\n\
+                      FPS: 30000/1001 (29.970 fps)\n\
+                      Format Name: YUV420P8\n\
+                      Color Family: YUV\n\
+                      Alpha: No\n\
+                      Sample Type: Integer\n\
+                      Bits: 8\n";
+        let info = ClipInfo::from_vspipe_info(output).unwrap();
+        assert_eq!(info, ClipInfo {
+            width: 720,
+            height: 480,
+            frames: 1000,
+            fps_num: 30000,
+            fps_den: 1001,
+            format: "YUV420P8".to_string(),
+        });
+        assert_eq!(info.frame_rate(), FrameRate::new(30000, 1001));
+
+        // Variable frame rate clips report 0/0
+        let vfr = ClipInfo::from_vspipe_info("Width: 640\nHeight: 360\nFrames: 10\nFPS: 0/0 (variable)\n").unwrap();
+        assert_eq!(vfr.frame_rate(), None);
+        assert_eq!(vfr.format, "");
+
+        assert!(ClipInfo::from_vspipe_info("Script evaluation failed").is_err());
+    }
+}
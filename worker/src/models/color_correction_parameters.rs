@@ -23,6 +23,10 @@ pub struct ColorCorrectionParameters {
     #[serde(default)]
     pub enabled: bool,
 
+    /// Temporarily skip this pass while keeping it enabled and configured.
+    #[serde(default)]
+    pub bypass: bool,
+
     /// Preset level for simple mode.
     #[serde(default)]
     pub preset: ColorCorrectionPreset,
@@ -45,10 +49,36 @@ pub struct ColorCorrectionParameters {
     #[serde(default = "default_one_f64")]
     pub saturation: f64,
 
-    /// Coring - clamp output to TV range (16-235).
+    /// Coring - clamp output to the legal broadcast range (luma 16-235,
+    /// chroma 16-240, scaled to the bit depth).
     #[serde(default)]
     pub coring: bool,
 
+    /// Start of the hue range to adjust, in degrees (0 to 360, the U/V angle).
+    /// With the other range limits, restricts hue and saturation changes
+    /// to matching colors (e.g. only oversaturated reds); the script masks
+    /// Tweak's output since adjust.Tweak itself has no range options.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tweak_start_hue: Option<f64>,
+
+    /// End of the hue range to adjust, in degrees (0 to 360).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tweak_end_hue: Option<f64>,
+
+    /// Only adjust pixels with at most this saturation (0 to 150).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tweak_max_sat: Option<f64>,
+
+    /// Only adjust pixels with at least this saturation (0 to 150).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tweak_min_sat: Option<f64>,
+
+    /// Maximum chroma magnitude (U/V distance from neutral, 8-bit units).
+    /// More saturated pixels have their chroma scaled back toward neutral,
+    /// keeping their hue.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chroma_limit: Option<f64>,
+
     // --- SmoothLevels Parameters ---
 
     /// Whether to apply levels adjustment.
@@ -76,6 +106,11 @@ pub struct ColorCorrectionParameters {
     pub gamma: f64,
 }
 
+/// Chroma magnitude (8-bit units) the broadcast-safe preset limits pixels to;
+/// more saturated colors tend to fall outside the legal RGB gamut once
+/// converted.
+pub const BROADCAST_SAFE_CHROMA_LIMIT: f64 = 100.0;
+
 fn default_one_f64() -> f64 { 1.0 }
 fn default_255() -> i32 { 255 }
 
@@ -83,12 +118,18 @@ impl Default for ColorCorrectionParameters {
     fn default() -> Self {
         Self {
             enabled: false,
+            bypass: false,
             preset: ColorCorrectionPreset::default(),
             brightness: 0.0,
             contrast: 1.0,
             hue: 0.0,
             saturation: 1.0,
             coring: false,
+            tweak_start_hue: None,
+            tweak_end_hue: None,
+            tweak_max_sat: None,
+            tweak_min_sat: None,
+            chroma_limit: None,
             apply_levels: false,
             input_low: 0,
             input_high: 255,
@@ -99,6 +140,50 @@ impl Default for ColorCorrectionParameters {
     }
 }
 
+impl ColorCorrectionParameters {
+    /// Whether hue/saturation changes are limited to a hue or saturation range.
+    pub fn has_range_limits(&self) -> bool {
+        self.tweak_start_hue.is_some()
+            || self.tweak_end_hue.is_some()
+            || self.tweak_max_sat.is_some()
+            || self.tweak_min_sat.is_some()
+    }
+
+    /// Create parameters from a preset (mirrors the app's preset values).
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub fn from_preset(preset: ColorCorrectionPreset) -> Self {
+        let mut params = Self { enabled: preset != ColorCorrectionPreset::Off, preset, ..Default::default() };
+        params.apply_preset();
+        params
+    }
+
+    /// Overlay the values `preset` defines; other fields are kept.
+    pub fn apply_preset(&mut self) {
+        match self.preset {
+            // Legal range only: limit highly saturated colors, then clamp
+            ColorCorrectionPreset::BroadcastSafe => {
+                self.coring = true;
+                self.chroma_limit = Some(BROADCAST_SAFE_CHROMA_LIMIT);
+                self.apply_levels = true;
+                self.input_low = 16;
+                self.input_high = 235;
+                self.output_low = 16;
+                self.output_high = 235;
+            }
+            ColorCorrectionPreset::EnhanceColors => {
+                self.contrast = 1.1;
+                self.saturation = 1.15;
+                self.apply_levels = true;
+                self.input_low = 8;
+                self.input_high = 247;
+                self.gamma = 0.95;
+            }
+            ColorCorrectionPreset::Desaturate => self.saturation = 0.0,
+            ColorCorrectionPreset::Off | ColorCorrectionPreset::Custom => {}
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -112,6 +197,20 @@ mod tests {
         assert_eq!(params.saturation, 1.0);
     }
 
+    #[test]
+    fn test_broadcast_safe_preset() {
+        let params = ColorCorrectionParameters::from_preset(ColorCorrectionPreset::BroadcastSafe);
+        assert!(params.enabled);
+        assert!(params.coring, "Broadcast safe clamps to legal range");
+        assert!(params.apply_levels);
+        assert_eq!((params.input_low, params.input_high), (16, 235));
+        assert_eq!((params.output_low, params.output_high), (16, 235));
+        assert_eq!(params.chroma_limit, Some(BROADCAST_SAFE_CHROMA_LIMIT), "Highly saturated colors are limited");
+        assert!(!params.has_range_limits(), "No Tweak range options are needed");
+        assert_eq!(params.saturation, 1.0);
+        assert_eq!((params.brightness, params.contrast), (0.0, 1.0));
+    }
+
     #[test]
     fn test_serialization() {
         let params = ColorCorrectionParameters::default();
@@ -1,5 +1,6 @@
 //! Crop and resize parameters for video restoration.
 
+use anyhow::{bail, Result};
 use serde::{Deserialize, Serialize};
 
 /// Resize kernel/algorithm options.
@@ -39,6 +40,17 @@ pub enum UpscaleMethod {
     Spline36,
 }
 
+/// Where in the pipeline the crop is applied.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum CropStage {
+    /// Crop before deinterlacing (reduces processing area).
+    #[default]
+    PreDeinterlace,
+    /// Crop after all filtering, before resize (e.g. removes QTGMC edge artifacts).
+    PostProcess,
+}
+
 /// Crop/resize preset options.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
 #[serde(rename_all = "camelCase")]
@@ -52,6 +64,22 @@ pub enum CropResizePreset {
     Custom,
 }
 
+/// Pixels cropped from each edge by `RemoveOverscan`. Covers the blanking and
+/// head-switching noise of both NTSC (720x480) and PAL (720x576) captures.
+pub const OVERSCAN_CROP: i32 = 8;
+
+impl CropResizePreset {
+    /// Output frame size of the resize presets, as (width, height).
+    pub fn target_dimensions(&self) -> Option<(i32, i32)> {
+        match self {
+            CropResizePreset::Resize720p => Some((1280, 720)),
+            CropResizePreset::Resize1080p => Some((1920, 1080)),
+            CropResizePreset::Resize4k => Some((3840, 2160)),
+            _ => None,
+        }
+    }
+}
+
 /// Parameters for the crop and resize pass.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -60,6 +88,10 @@ pub struct CropResizeParameters {
     #[serde(default)]
     pub enabled: bool,
 
+    /// Temporarily skip this pass while keeping it enabled and configured.
+    #[serde(default)]
+    pub bypass: bool,
+
     /// Preset for simple mode.
     #[serde(default)]
     pub preset: CropResizePreset,
@@ -86,6 +118,10 @@ pub struct CropResizeParameters {
     #[serde(default)]
     pub crop_bottom: i32,
 
+    /// Pipeline position of the crop.
+    #[serde(default)]
+    pub crop_stage: CropStage,
+
     // --- Resize Parameters ---
 
     /// Whether to apply resize.
@@ -108,6 +144,20 @@ pub struct CropResizeParameters {
     #[serde(default = "default_true")]
     pub maintain_aspect: bool,
 
+    /// Display aspect ratio of the source frame, as (num, den), e.g. (16, 9)
+    /// for anamorphic widescreen SD. When set, the resize produces square
+    /// pixels: the width follows from the target (or source) height and the
+    /// source's pixel aspect, and `target_width`/`maintain_aspect` are ignored.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target_display_aspect: Option<(i32, i32)>,
+
+    /// Resize in linear light instead of the source transfer (gamma-encoded) space.
+    /// Avoids darkened edges and ringing on high-contrast detail, at the cost of
+    /// two extra 32-bit float conversions per frame. Relies on the source's
+    /// `_Transfer`/`_Matrix` frame props; untagged sources are assumed BT.709.
+    #[serde(default)]
+    pub linearize: bool,
+
     // --- Upscale Parameters (for integer scaling) ---
 
     /// Whether to use integer upscaling (2x, 4x) instead of arbitrary resize.
@@ -121,6 +171,14 @@ pub struct CropResizeParameters {
     /// Upscale factor (2 = 2x, 4 = 4x).
     #[serde(default = "default_upscale_factor")]
     pub upscale_factor: i32,
+
+    /// Run the NNEDI3 upscale on the GPU with NNEDI3CL instead of znedi3.
+    #[serde(default)]
+    pub opencl: bool,
+
+    /// OpenCL device index for the upscale (only used when `opencl` is set).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub opencl_device: Option<i32>,
 }
 
 fn default_true() -> bool { true }
@@ -130,20 +188,26 @@ impl Default for CropResizeParameters {
     fn default() -> Self {
         Self {
             enabled: false,
+            bypass: false,
             preset: CropResizePreset::default(),
             crop_enabled: false,
             crop_left: 0,
             crop_right: 0,
             crop_top: 0,
             crop_bottom: 0,
+            crop_stage: CropStage::default(),
             resize_enabled: false,
             target_width: None,
             target_height: None,
             kernel: ResizeKernel::default(),
             maintain_aspect: true,
+            target_display_aspect: None,
+            linearize: false,
             use_integer_upscale: false,
             upscale_method: UpscaleMethod::default(),
             upscale_factor: default_upscale_factor(),
+            opencl: false,
+            opencl_device: None,
         }
     }
 }
@@ -158,6 +222,143 @@ impl CropResizeParameters {
     pub fn total_vertical_crop(&self) -> i32 {
         self.crop_top + self.crop_bottom
     }
+
+    /// Whether the standard resize computes a missing dimension from the
+    /// source aspect. True with `maintain_aspect`, and also when only one of
+    /// width/height is set, since the resizers need both. A size of 0 or
+    /// below counts as unset, as in [`Self::output_dimensions`].
+    pub fn keeps_aspect(&self) -> bool {
+        self.maintain_aspect
+            || self.target_width.filter(|&w| w > 0).is_some() != self.target_height.filter(|&h| h > 0).is_some()
+    }
+
+    /// Frame size after this pass for a `source` of (width, height), following
+    /// the script: crop, integer upscale, then standard resize (fitting within
+    /// the target and rounding down to even sizes when keeping the aspect).
+    pub fn output_dimensions(&self, source: (i32, i32)) -> (i32, i32) {
+        if !self.enabled {
+            return source;
+        }
+        let (mut width, mut height) = source;
+        if self.crop_enabled {
+            width -= self.total_horizontal_crop();
+            height -= self.total_vertical_crop();
+        }
+        if self.use_integer_upscale {
+            width *= self.upscale_factor;
+            height *= self.upscale_factor;
+        }
+        if !self.resize_enabled || width <= 0 || height <= 0 {
+            return (width, height);
+        }
+
+        if let Some(dar) = self.target_display_aspect {
+            let target_height = self.target_height.filter(|&h| h > 0).unwrap_or(height);
+            return (display_aspect_width(dar, source, (width, height), target_height), target_height);
+        }
+
+        let even = |v: f64| { let v = v as i32; v - v % 2 };
+        let aspect = width as f64 / height as f64;
+        match (self.target_width.filter(|&w| w > 0), self.target_height.filter(|&h| h > 0)) {
+            (Some(w), Some(h)) if self.maintain_aspect => {
+                let scale = (w as f64 / width as f64).min(h as f64 / height as f64);
+                (even(width as f64 * scale), even(height as f64 * scale))
+            }
+            (Some(w), Some(h)) => (w, h),
+            (Some(w), None) => (w, even(w as f64 / aspect)),
+            (None, Some(h)) => (even(h as f64 * aspect), h),
+            (w, h) => (w.unwrap_or(width), h.unwrap_or(height)),
+        }
+    }
+
+    /// Reject settings that would fail or resize twice without a clear target.
+    ///
+    /// Integer upscale and standard resize may be combined (as the 4K preset
+    /// does): the upscale runs first and the standard resize then sets the
+    /// final size, so it needs a target width or height. When the `source`
+    /// size (width, height) is known, the crop must leave part of the frame.
+    pub fn validate(&self, source: Option<(i32, i32)>) -> Result<()> {
+        if !self.enabled {
+            return Ok(());
+        }
+
+        if self.use_integer_upscale && self.resize_enabled
+            && self.target_width.is_none() && self.target_height.is_none()
+        {
+            bail!(
+                "Crop/Resize: integer upscale combined with standard resize needs a target width or height; \
+                 disable resizeEnabled to upscale by {}x only",
+                self.upscale_factor
+            );
+        }
+
+        if let Some((num, den)) = self.target_display_aspect {
+            if num <= 0 || den <= 0 {
+                bail!("Crop/Resize: display aspect must be two positive numbers, got {}:{}", num, den);
+            }
+        }
+
+        if self.crop_enabled {
+            if [self.crop_left, self.crop_right, self.crop_top, self.crop_bottom].iter().any(|&c| c < 0) {
+                bail!("Crop/Resize: crop values cannot be negative");
+            }
+            if let Some((width, height)) = source.filter(|&(w, h)| w > 0 && h > 0) {
+                let (crop_w, crop_h) = (self.total_horizontal_crop(), self.total_vertical_crop());
+                if crop_w >= width || crop_h >= height {
+                    bail!(
+                        "Crop/Resize: cropping {} x {} pixels leaves nothing of the {}x{} source",
+                        crop_w, crop_h, width, height
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Create parameters from a preset (mirrors the app's preset values).
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub fn from_preset(preset: CropResizePreset) -> Self {
+        let mut params = Self { enabled: preset != CropResizePreset::Off, preset, ..Default::default() };
+        params.resolve_preset();
+        params
+    }
+
+    /// Fill in what a named preset implies but the parameters leave unset:
+    /// resize presets get their standard frame size (and `resize_enabled`),
+    /// `RemoveOverscan` gets 8px crops when no crop is set. Explicit values are kept.
+    ///
+    /// This is the only preset mapping: [`Self::from_preset`] and preset
+    /// expansion both go through it.
+    pub fn resolve_preset(&mut self) {
+        if let Some((width, height)) = self.preset.target_dimensions() {
+            self.resize_enabled = true;
+            self.target_width.get_or_insert(width);
+            self.target_height.get_or_insert(height);
+        }
+
+        if self.preset == CropResizePreset::RemoveOverscan {
+            self.crop_enabled = true;
+            if self.total_horizontal_crop() == 0 && self.total_vertical_crop() == 0 {
+                self.crop_left = OVERSCAN_CROP;
+                self.crop_right = OVERSCAN_CROP;
+                self.crop_top = OVERSCAN_CROP;
+                self.crop_bottom = OVERSCAN_CROP;
+            }
+        }
+    }
+}
+
+/// Square-pixel width for a `frame` (width, height) resized to `height`, when
+/// the uncropped `source` frame displays at `dar` (num, den).
+///
+/// Source pixels are `dar * source_height / source_width` wide, and crop and
+/// integer upscale keep that shape. Rounded down to an even width.
+pub fn display_aspect_width(dar: (i32, i32), source: (i32, i32), frame: (i32, i32), height: i32) -> i32 {
+    let (dar_num, dar_den) = dar;
+    let numerator = frame.0 as f64 * dar_num as f64 * source.1 as f64 * height as f64;
+    let denominator = dar_den as f64 * source.0 as f64 * frame.1 as f64;
+    let width = (numerator / denominator).round() as i32;
+    width - width % 2
 }
 
 #[cfg(test)]
@@ -171,14 +372,181 @@ mod tests {
         assert_eq!(params.preset, CropResizePreset::Off);
         assert!(!params.crop_enabled);
         assert!(!params.resize_enabled);
+        assert_eq!(params.crop_stage, CropStage::PreDeinterlace);
         assert_eq!(params.upscale_factor, 2);
     }
 
+    #[test]
+    fn test_resolve_preset() {
+        let resolve = |preset| {
+            let mut params = CropResizeParameters { enabled: true, preset, ..Default::default() };
+            params.resolve_preset();
+            params
+        };
+
+        for (preset, width, height) in [
+            (CropResizePreset::Resize720p, 1280, 720),
+            (CropResizePreset::Resize1080p, 1920, 1080),
+            (CropResizePreset::Resize4k, 3840, 2160),
+        ] {
+            let params = resolve(preset);
+            assert!(params.resize_enabled, "{:?} should enable resize", preset);
+            assert_eq!((params.target_width, params.target_height), (Some(width), Some(height)));
+            assert!(!params.crop_enabled);
+        }
+
+        let overscan = resolve(CropResizePreset::RemoveOverscan);
+        assert!(overscan.crop_enabled && !overscan.resize_enabled);
+        assert_eq!(
+            (overscan.crop_left, overscan.crop_right, overscan.crop_top, overscan.crop_bottom),
+            (8, 8, 8, 8)
+        );
+
+        for preset in [CropResizePreset::Off, CropResizePreset::Custom] {
+            let params = resolve(preset);
+            assert!(!params.resize_enabled && !params.crop_enabled);
+        }
+
+        // Explicit values win over the preset's
+        let mut custom_height = CropResizeParameters {
+            preset: CropResizePreset::Resize1080p,
+            target_height: Some(1088),
+            ..Default::default()
+        };
+        custom_height.resolve_preset();
+        assert_eq!((custom_height.target_width, custom_height.target_height), (Some(1920), Some(1088)));
+
+        // from_preset maps each preset the same way
+        for preset in [
+            CropResizePreset::RemoveOverscan,
+            CropResizePreset::Resize720p,
+            CropResizePreset::Resize1080p,
+            CropResizePreset::Resize4k,
+        ] {
+            let (resolved, built) = (resolve(preset), CropResizeParameters::from_preset(preset));
+            assert_eq!(
+                serde_json::to_value(&resolved).unwrap(),
+                serde_json::to_value(&built).unwrap(),
+                "{:?} maps differently",
+                preset
+            );
+        }
+        assert!(!resolve(CropResizePreset::Resize4k).use_integer_upscale, "4K is a plain resize to 3840x2160");
+    }
+
+    #[test]
+    fn test_display_aspect_resize() {
+        let anamorphic = |dar| CropResizeParameters {
+            enabled: true,
+            resize_enabled: true,
+            target_display_aspect: Some(dar),
+            ..Default::default()
+        };
+
+        // PAL 720x576 at 16:9 and 4:3 to square pixels at the source height
+        assert_eq!(anamorphic((16, 9)).output_dimensions((720, 576)), (1024, 576));
+        assert_eq!(anamorphic((4, 3)).output_dimensions((720, 576)), (768, 576));
+
+        // To 1080 lines
+        let hd = CropResizeParameters { target_height: Some(1080), ..anamorphic((16, 9)) };
+        assert_eq!(hd.output_dimensions((720, 576)), (1920, 1080));
+
+        // Cropping keeps the source pixel shape: 704 of 720 columns
+        let cropped = CropResizeParameters {
+            crop_enabled: true,
+            crop_left: 8,
+            crop_right: 8,
+            ..anamorphic((16, 9))
+        };
+        assert_eq!(cropped.output_dimensions((720, 576)), (1000, 576));
+        assert_eq!(display_aspect_width((16, 9), (720, 576), (704, 576), 576), 1000);
+    }
+
+    #[test]
+    fn test_single_dimension_keeps_aspect() {
+        let width_only = CropResizeParameters {
+            enabled: true,
+            resize_enabled: true,
+            target_width: Some(960),
+            maintain_aspect: false,
+            ..Default::default()
+        };
+        assert!(width_only.keeps_aspect());
+        assert_eq!(width_only.output_dimensions((720, 480)), (960, 640));
+
+        let height_only = CropResizeParameters { target_width: None, target_height: Some(720), ..width_only.clone() };
+        assert_eq!(height_only.output_dimensions((720, 480)), (1080, 720));
+
+        // -1 (the script's "unset") leaves only the other dimension set
+        let height_unset = CropResizeParameters { target_height: Some(-1), ..width_only.clone() };
+        assert!(height_unset.keeps_aspect());
+        assert_eq!(height_unset.output_dimensions((720, 480)), (960, 640));
+
+        // Both set: stretched to exactly the target
+        let both = CropResizeParameters { target_height: Some(720), ..width_only };
+        assert!(!both.keeps_aspect());
+        assert_eq!(both.output_dimensions((720, 480)), (960, 720));
+    }
+
+    #[test]
+    fn test_validate_upscale_with_resize() {
+        let mut params = CropResizeParameters {
+            enabled: true,
+            resize_enabled: true,
+            use_integer_upscale: true,
+            ..Default::default()
+        };
+        let err = params.validate(None).unwrap_err().to_string();
+        assert!(err.contains("target width or height"), "{}", err);
+
+        // Upscale then resize to a target
+        params.target_height = Some(2160);
+        assert!(params.validate(None).is_ok());
+        assert!(CropResizeParameters::from_preset(CropResizePreset::Resize4k).validate(None).is_ok());
+
+        // Disabled passes are not checked
+        params.target_height = None;
+        params.enabled = false;
+        assert!(params.validate(None).is_ok());
+    }
+
+    #[test]
+    fn test_validate_display_aspect() {
+        let mut params = CropResizeParameters {
+            enabled: true,
+            resize_enabled: true,
+            target_display_aspect: Some((16, 9)),
+            ..Default::default()
+        };
+        assert!(params.validate(None).is_ok());
+
+        for aspect in [(16, 0), (0, 9), (-16, 9), (16, -9)] {
+            params.target_display_aspect = Some(aspect);
+            let err = params.validate(None).unwrap_err().to_string();
+            assert!(err.contains("display aspect"), "{:?}: {}", aspect, err);
+        }
+    }
+
+    #[test]
+    fn test_validate_crop_against_source() {
+        let mut params = CropResizeParameters::from_preset(CropResizePreset::RemoveOverscan);
+        assert!(params.validate(Some((720, 480))).is_ok());
+
+        params.crop_left = 400;
+        params.crop_right = 400;
+        assert!(params.validate(None).is_ok(), "Unknown source size can't be checked");
+        assert!(params.validate(Some((720, 480))).unwrap_err().to_string().contains("720x480"));
+
+        params.crop_left = -2;
+        assert!(params.validate(None).is_err());
+    }
+
     #[test]
     fn test_serialization() {
         let params = CropResizeParameters::default();
         let json = serde_json::to_string(&params).unwrap();
         assert!(json.contains("\"enabled\":false"));
         assert!(json.contains("\"maintainAspect\":true"));
+        assert!(json.contains("\"cropStage\":\"preDeinterlace\""));
     }
 }
@@ -9,6 +9,10 @@ pub struct DebandParameters {
     #[serde(default)]
     pub enabled: bool,
 
+    /// Temporarily skip this pass while keeping it enabled and configured.
+    #[serde(default)]
+    pub bypass: bool,
+
     /// Banding detection range (8-128, default 15).
     /// Higher values detect wider bands.
     #[serde(default = "default_range")]
@@ -40,6 +44,8 @@ pub struct DebandParameters {
     pub dynamic_grain: bool,
 
     /// Output bit depth (8, 10, 16).
+    /// Depths above what the target encoder accepts are dithered down at the
+    /// end of the script (see `VideoCodec::max_bit_depth`).
     #[serde(default = "default_output_depth")]
     pub output_depth: i32,
 }
@@ -57,6 +63,7 @@ impl Default for DebandParameters {
     fn default() -> Self {
         Self {
             enabled: false,
+            bypass: false,
             range: default_range(),
             y: default_y(),
             cb: default_cb(),
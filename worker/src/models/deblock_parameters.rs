@@ -28,6 +28,10 @@ pub struct DeblockParameters {
     #[serde(default)]
     pub enabled: bool,
 
+    /// Temporarily skip this pass while keeping it enabled and configured.
+    #[serde(default)]
+    pub bypass: bool,
+
     /// Deblocking method to use.
     #[serde(default)]
     pub method: DeblockMethod,
@@ -59,6 +63,11 @@ pub struct DeblockParameters {
     /// Overlap amount (0-half of blockSize).
     #[serde(default = "default_overlap")]
     pub overlap: i32,
+
+    /// Use the simple Deblock filter (at [`DeblockParameters::fallback_quant`])
+    /// with a warning when the installed havsfunc has no Deblock_QED.
+    #[serde(default)]
+    pub deblock_fallback: bool,
 }
 
 fn default_quant1() -> i32 { 24 }
@@ -71,6 +80,7 @@ impl Default for DeblockParameters {
     fn default() -> Self {
         Self {
             enabled: false,
+            bypass: false,
             method: DeblockMethod::default(),
             quant1: default_quant1(),
             quant2: default_quant2(),
@@ -78,6 +88,35 @@ impl Default for DeblockParameters {
             a_offset2: default_a_offset(),
             block_size: default_block_size(),
             overlap: default_overlap(),
+            deblock_fallback: false,
         }
     }
 }
+
+impl DeblockParameters {
+    /// Deblock `quant` standing in for Deblock_QED: the mean of its edge
+    /// (`quant1`) and interior (`quant2`) strengths, so QED's defaults (24/26)
+    /// map to Deblock's own default of 25.
+    pub fn fallback_quant(&self) -> i32 {
+        ((self.quant1 + self.quant2) as f64 / 2.0).round().clamp(0.0, 60.0) as i32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fallback_quant_mapping() {
+        assert_eq!(DeblockParameters::default().fallback_quant(), 25);
+
+        let strong = DeblockParameters { quant1: 40, quant2: 45, ..DeblockParameters::default() };
+        assert_eq!(strong.fallback_quant(), 43);
+
+        let edges_only = DeblockParameters { quant1: 30, quant2: 0, ..DeblockParameters::default() };
+        assert_eq!(edges_only.fallback_quant(), 15);
+
+        let out_of_range = DeblockParameters { quant1: 80, quant2: 70, ..DeblockParameters::default() };
+        assert_eq!(out_of_range.fallback_quant(), 60);
+    }
+}
@@ -0,0 +1,43 @@
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+
+/// Parameters for dropping duplicated frames (capture glitches).
+///
+/// A source frame whose luma barely differs from the previous frame is a
+/// repeat and is deleted, along with the frames deinterlacing made from it.
+/// Only actual duplicates are dropped, so the output length depends on the
+/// content; the frame rate is kept.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DedupParameters {
+    /// Whether duplicate frames are dropped.
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Mean absolute luma difference from the previous frame, in percent of
+    /// the value range, at or below which a frame counts as a duplicate
+    /// (default 0.1).
+    #[serde(default = "default_dup_threshold")]
+    pub dup_threshold: f64,
+}
+
+fn default_dup_threshold() -> f64 { 0.1 }
+
+impl Default for DedupParameters {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            dup_threshold: default_dup_threshold(),
+        }
+    }
+}
+
+impl DedupParameters {
+    /// Reject thresholds outside the 0-100% difference range.
+    pub fn validate(&self) -> Result<()> {
+        if !(0.0..=100.0).contains(&self.dup_threshold) {
+            bail!("Dedup: duplicate threshold must be between 0 and 100 percent, got {}", self.dup_threshold);
+        }
+        Ok(())
+    }
+}
@@ -31,6 +31,10 @@ pub struct DehaloParameters {
     #[serde(default)]
     pub enabled: bool,
 
+    /// Temporarily skip this pass while keeping it enabled and configured.
+    #[serde(default)]
+    pub bypass: bool,
+
     /// Dehalo method to use.
     #[serde(default)]
     pub method: DehaloMethod,
@@ -72,6 +76,12 @@ pub struct DehaloParameters {
     /// Processing depth for YAHR.
     #[serde(default = "default_yahr_depth")]
     pub yahr_depth: i32,
+
+    // --- Linear light ---
+
+    /// Run the dehalo filter in linear light (see `CropResizeParameters::linearize`).
+    #[serde(default)]
+    pub linearize: bool,
 }
 
 fn default_rx() -> f64 { 2.0 }
@@ -87,6 +97,7 @@ impl Default for DehaloParameters {
     fn default() -> Self {
         Self {
             enabled: false,
+            bypass: false,
             method: DehaloMethod::default(),
             rx: default_rx(),
             ry: default_ry(),
@@ -96,6 +107,7 @@ impl Default for DehaloParameters {
             high_threshold: default_high_threshold(),
             yahr_blur: default_yahr_blur(),
             yahr_depth: default_yahr_depth(),
+            linearize: false,
         }
     }
 }
@@ -0,0 +1,24 @@
+//! Typed error for missing external dependencies.
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// A required external tool (vspipe, ffmpeg, ...) could not be found.
+#[derive(Debug, Clone, PartialEq, Eq, Error, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[error("{which} not found. Searched: {}", searched_paths.join(", "))]
+pub struct DependencyError {
+    /// Name of the missing tool
+    pub which: String,
+
+    /// Locations that were checked, in search order
+    pub searched_paths: Vec<String>,
+}
+
+/// Machine-readable error codes attached to error messages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ErrorCode {
+    /// A required dependency is not installed; the app should offer to download it
+    DependencyMissing,
+}
@@ -0,0 +1,155 @@
+//! Exact frame rates, kept as rationals so NTSC rates don't drift.
+
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+
+/// A frame rate as `num / den` frames per second, in lowest terms.
+///
+/// Deserializes from a number (`29.97`) or a `"num/den"` string
+/// (`"30000/1001"`) and serializes as the string. Decimal NTSC rates are
+/// snapped to their exact `/1001` rationals.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "FrameRateRepr", into = "String")]
+pub struct FrameRate {
+    num: i64,
+    den: i64,
+}
+
+/// NTSC frame rates are `n * 1000/1001` for these nominal rates.
+const NTSC_NOMINAL_RATES: [i64; 5] = [24, 30, 48, 60, 120];
+
+impl FrameRate {
+    /// NTSC video (30000/1001), assumed when the source rate is unknown.
+    pub const NTSC: FrameRate = FrameRate { num: 30000, den: 1001 };
+
+    /// `num / den` in lowest terms, or None unless both are positive.
+    pub fn new(num: i64, den: i64) -> Option<Self> {
+        if num <= 0 || den <= 0 {
+            return None;
+        }
+        let divisor = gcd(num, den);
+        Some(Self { num: num / divisor, den: den / divisor })
+    }
+
+    /// Rational for a decimal rate: NTSC rates (e.g. 29.97, 23.976) map to
+    /// their `/1001` form, anything else is kept to the millisecond.
+    pub fn from_f64(fps: f64) -> Option<Self> {
+        if !fps.is_finite() || fps <= 0.0 {
+            return None;
+        }
+        if let Some(nominal) = NTSC_NOMINAL_RATES.iter()
+            .find(|&&nominal| (fps - nominal as f64 * 1000.0 / 1001.0).abs() < 0.005)
+        {
+            return Self::new(nominal * 1000, 1001);
+        }
+        Self::new((fps * 1000.0).round() as i64, 1000)
+    }
+
+    /// Numerator (frames).
+    pub fn num(&self) -> i64 {
+        self.num
+    }
+
+    /// Denominator (seconds).
+    pub fn den(&self) -> i64 {
+        self.den
+    }
+
+    /// Frames per second as a float, for display and time math.
+    pub fn as_f64(&self) -> f64 {
+        self.num as f64 / self.den as f64
+    }
+
+    /// The rate multiplied by `factor` (e.g. 2 for double-rate deinterlacing).
+    pub fn scaled(&self, factor: i64) -> Self {
+        Self::new(self.num * factor, self.den).unwrap_or(*self)
+    }
+}
+
+impl fmt::Display for FrameRate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.num, self.den)
+    }
+}
+
+impl FromStr for FrameRate {
+    type Err = anyhow::Error;
+
+    /// Parse `"num/den"` or a decimal rate such as `"29.97"`.
+    fn from_str(text: &str) -> Result<Self> {
+        let text = text.trim();
+        let rate = match text.split_once('/') {
+            Some((num, den)) => {
+                let num = num.trim().parse().with_context(|| format!("Invalid frame rate '{}'", text))?;
+                let den = den.trim().parse().with_context(|| format!("Invalid frame rate '{}'", text))?;
+                Self::new(num, den)
+            }
+            None => Self::from_f64(text.parse().with_context(|| format!("Invalid frame rate '{}'", text))?),
+        };
+        match rate {
+            Some(rate) => Ok(rate),
+            None => bail!("Frame rate must be positive, got '{}'", text),
+        }
+    }
+}
+
+impl From<FrameRate> for String {
+    fn from(rate: FrameRate) -> String {
+        rate.to_string()
+    }
+}
+
+/// Accepted JSON forms of a frame rate.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum FrameRateRepr {
+    Number(f64),
+    Text(String),
+}
+
+impl TryFrom<FrameRateRepr> for FrameRate {
+    type Error = anyhow::Error;
+
+    fn try_from(repr: FrameRateRepr) -> Result<Self> {
+        match repr {
+            FrameRateRepr::Number(fps) => match Self::from_f64(fps) {
+                Some(rate) => Ok(rate),
+                None => bail!("Frame rate must be positive, got {}", fps),
+            },
+            FrameRateRepr::Text(text) => text.parse(),
+        }
+    }
+}
+
+fn gcd(a: i64, b: i64) -> i64 {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deserialize_float_and_rational() {
+        let ntsc = FrameRate::new(30000, 1001).unwrap();
+        assert_eq!(serde_json::from_str::<FrameRate>("29.97").unwrap(), ntsc);
+        assert_eq!(serde_json::from_str::<FrameRate>("\"30000/1001\"").unwrap(), ntsc);
+        assert_eq!(serde_json::from_str::<FrameRate>("\"29.97\"").unwrap(), ntsc);
+        assert_eq!(serde_json::from_str::<FrameRate>("23.976").unwrap(), FrameRate::new(24000, 1001).unwrap());
+        assert_eq!(serde_json::from_str::<FrameRate>("25").unwrap(), FrameRate::new(25, 1).unwrap());
+        assert_eq!(serde_json::from_str::<FrameRate>("\"50/2\"").unwrap(), FrameRate::new(25, 1).unwrap());
+        assert_eq!(serde_json::from_str::<FrameRate>("12.5").unwrap(), FrameRate::new(25, 2).unwrap());
+
+        for invalid in ["0", "-25", "\"30000/0\"", "\"fast\"", "\"/1001\""] {
+            assert!(serde_json::from_str::<FrameRate>(invalid).is_err(), "{} should be rejected", invalid);
+        }
+
+        // Round trips through the exact form
+        assert_eq!(serde_json::to_string(&ntsc).unwrap(), "\"30000/1001\"");
+        assert!((ntsc.as_f64() - 29.97003).abs() < 1e-5);
+        assert_eq!(ntsc.scaled(2), FrameRate::new(60000, 1001).unwrap());
+    }
+}
@@ -0,0 +1,62 @@
+use serde::{Deserialize, Serialize};
+
+/// Parameters for the film grain pass (AddGrain, optionally luma-adaptive).
+/// Re-adds a natural grain structure after heavy noise reduction.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GrainParameters {
+    /// Whether this pass is enabled.
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Temporarily skip this pass while keeping it enabled and configured.
+    #[serde(default)]
+    pub bypass: bool,
+
+    /// Luma grain variance (0.0-10.0, default 1.0).
+    #[serde(default = "default_strength")]
+    pub strength: f64,
+
+    /// Chroma grain variance (0.0-10.0, default 0.0 = luma only).
+    #[serde(default)]
+    pub chroma_strength: f64,
+
+    /// Grain size as the spatial correlation of neighbouring grain pixels
+    /// (0.0-1.0, default 0.0). Higher values give coarser, softer grain.
+    #[serde(default)]
+    pub size: f64,
+
+    /// Use dynamic grain (changes per frame); static grain looks like a
+    /// dirty lens.
+    #[serde(default = "default_dynamic")]
+    pub dynamic: bool,
+
+    /// Fade the grain out of bright areas with a luma mask (adaptivegrain's
+    /// curve), as film grain is most visible in the shadows and midtones.
+    #[serde(default)]
+    pub adaptive: bool,
+
+    /// Strength of the adaptive mask's falloff towards highlights
+    /// (default 10.0). Only used when `adaptive` is set.
+    #[serde(default = "default_luma_scaling")]
+    pub luma_scaling: f64,
+}
+
+fn default_strength() -> f64 { 1.0 }
+fn default_dynamic() -> bool { true }
+fn default_luma_scaling() -> f64 { 10.0 }
+
+impl Default for GrainParameters {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            bypass: false,
+            strength: default_strength(),
+            chroma_strength: 0.0,
+            size: 0.0,
+            dynamic: default_dynamic(),
+            adaptive: false,
+            luma_scaling: default_luma_scaling(),
+        }
+    }
+}
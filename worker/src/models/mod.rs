@@ -11,8 +11,15 @@ mod crop_resize_parameters;
 mod dehalo_parameters;
 mod deblock_parameters;
 mod deband_parameters;
+mod dedup_parameters;
 mod sharpen_parameters;
+mod grain_parameters;
 mod restoration_pipeline;
+mod dependency_error;
+mod clip_info;
+mod frame_rate;
+mod timecode;
+mod worker_error;
 
 pub use video_job::*;
 pub use qtgmc_parameters::*;
@@ -24,5 +31,12 @@ pub use crop_resize_parameters::*;
 pub use dehalo_parameters::*;
 pub use deblock_parameters::*;
 pub use deband_parameters::*;
+pub use dedup_parameters::*;
 pub use sharpen_parameters::*;
+pub use grain_parameters::*;
 pub use restoration_pipeline::*;
+pub use dependency_error::*;
+pub use clip_info::*;
+pub use frame_rate::*;
+pub use timecode::*;
+pub use worker_error::*;
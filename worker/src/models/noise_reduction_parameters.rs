@@ -32,6 +32,10 @@ pub struct NoiseReductionParameters {
     #[serde(default)]
     pub enabled: bool,
 
+    /// Temporarily skip this pass while keeping it enabled and configured.
+    #[serde(default)]
+    pub bypass: bool,
+
     /// Preset level for simple mode.
     #[serde(default)]
     pub preset: NoiseReductionPreset,
@@ -62,6 +66,14 @@ pub struct NoiseReductionParameters {
     #[serde(default = "default_sm_degrain_prefilter", rename = "smDegrainPrefilter")]
     pub sm_degrain_prefilter: i32,
 
+    /// Scene change threshold 1 (SAD per block). None uses the SMDegrain default (400).
+    #[serde(default, rename = "smDegrainThSCD1", skip_serializing_if = "Option::is_none")]
+    pub sm_degrain_th_scd1: Option<i32>,
+
+    /// Scene change threshold 2 (changed blocks, 0-255). None uses the SMDegrain default (130).
+    #[serde(default, rename = "smDegrainThSCD2", skip_serializing_if = "Option::is_none")]
+    pub sm_degrain_th_scd2: Option<i32>,
+
     // --- MCTemporalDenoise Parameters ---
 
     /// Denoise strength/sigma.
@@ -100,6 +112,7 @@ impl Default for NoiseReductionParameters {
     fn default() -> Self {
         Self {
             enabled: false,
+            bypass: false,
             preset: NoiseReductionPreset::default(),
             method: NoiseReductionMethod::default(),
             sm_degrain_tr: default_sm_degrain_tr(),
@@ -107,6 +120,8 @@ impl Default for NoiseReductionParameters {
             sm_degrain_th_sadc: default_sm_degrain_th_sadc(),
             sm_degrain_refine: true,
             sm_degrain_prefilter: default_sm_degrain_prefilter(),
+            sm_degrain_th_scd1: None,
+            sm_degrain_th_scd2: None,
             mc_temporal_sigma: default_mc_temporal_sigma(),
             mc_temporal_radius: default_mc_temporal_radius(),
             mc_temporal_profile: default_mc_temporal_profile(),
@@ -116,6 +131,30 @@ impl Default for NoiseReductionParameters {
     }
 }
 
+impl NoiseReductionParameters {
+    /// Create parameters from a preset (mirrors the app's preset values).
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub fn from_preset(preset: NoiseReductionPreset) -> Self {
+        let mut params = Self { enabled: preset != NoiseReductionPreset::Off, preset, ..Default::default() };
+        params.apply_preset();
+        params
+    }
+
+    /// Overlay the values `preset` defines; other fields are kept.
+    pub fn apply_preset(&mut self) {
+        let (tr, th_sad, th_sadc) = match self.preset {
+            NoiseReductionPreset::Light => (1, 200, 100),
+            NoiseReductionPreset::Moderate => (2, 300, 150),
+            NoiseReductionPreset::Heavy => (3, 500, 250),
+            NoiseReductionPreset::Off | NoiseReductionPreset::Custom => return,
+        };
+        self.method = NoiseReductionMethod::SmDegrain;
+        self.sm_degrain_tr = tr;
+        self.sm_degrain_th_sad = th_sad;
+        self.sm_degrain_th_sadc = th_sadc;
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -128,6 +167,16 @@ mod tests {
         assert_eq!(params.method, NoiseReductionMethod::SmDegrain);
     }
 
+    #[test]
+    fn test_apply_preset_keeps_other_fields() {
+        let mut params = NoiseReductionParameters { preset: NoiseReductionPreset::Moderate, sm_degrain_prefilter: 3, ..Default::default() };
+        params.apply_preset();
+        let preset = NoiseReductionParameters::from_preset(NoiseReductionPreset::Moderate);
+        assert_eq!((params.sm_degrain_tr, params.sm_degrain_th_sad), (preset.sm_degrain_tr, preset.sm_degrain_th_sad));
+        assert_eq!(params.sm_degrain_prefilter, 3);
+        assert!(!params.enabled, "enabled is left to the caller");
+    }
+
     #[test]
     fn test_serialization() {
         let params = NoiseReductionParameters::default();
@@ -3,6 +3,8 @@
 use serde::{Deserialize, Serialize};
 use chrono::{DateTime, Utc};
 
+use super::{DependencyError, ErrorCode};
+
 /// Progress information reported by the worker process.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -92,9 +94,19 @@ pub enum WorkerMessage {
         message: String,
     },
 
+    /// Phase change (e.g. preview extraction, indexing)
+    Phase {
+        phase: String,
+        message: String,
+    },
+
     /// Error message
     Error {
         message: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        code: Option<ErrorCode>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        dependency: Option<DependencyError>,
     },
 
     /// Job completion
@@ -103,6 +115,28 @@ pub enum WorkerMessage {
         #[serde(rename = "outputPath", skip_serializing_if = "Option::is_none")]
         output_path: Option<String>,
     },
+
+    /// Summary after all jobs in a batch have run
+    #[serde(rename = "batchSummary")]
+    BatchSummary {
+        total: usize,
+        succeeded: usize,
+        failed: usize,
+        skipped: usize,
+    },
+
+    /// Encoder quality loss measured against the processed source
+    #[serde(rename = "qualityReport")]
+    QualityReport {
+        psnr: Option<f64>,
+        ssim: Option<f64>,
+    },
+
+    /// Periodic liveness signal while a job runs, even when no progress is made
+    Heartbeat {
+        #[serde(rename = "uptimeSecs")]
+        uptime_secs: u64,
+    },
 }
 
 impl WorkerMessage {
@@ -124,10 +158,29 @@ impl WorkerMessage {
         }
     }
 
+    /// Create a phase message from a preview or encode phase name.
+    pub fn phase(phase: &str, message: &str) -> Self {
+        WorkerMessage::Phase {
+            phase: phase.to_string(),
+            message: message.to_string(),
+        }
+    }
+
     /// Create an error message.
     pub fn error(message: &str) -> Self {
         WorkerMessage::Error {
             message: message.to_string(),
+            code: None,
+            dependency: None,
+        }
+    }
+
+    /// Create an error message reporting a missing dependency.
+    pub fn dependency_missing(error: &DependencyError) -> Self {
+        WorkerMessage::Error {
+            message: error.to_string(),
+            code: Some(ErrorCode::DependencyMissing),
+            dependency: Some(error.clone()),
         }
     }
 
@@ -138,6 +191,51 @@ impl WorkerMessage {
             output_path: output_path.map(String::from),
         }
     }
+
+    /// Create a batch summary message.
+    pub fn batch_summary(summary: &BatchSummary) -> Self {
+        WorkerMessage::BatchSummary {
+            total: summary.total,
+            succeeded: summary.succeeded,
+            failed: summary.failed,
+            skipped: summary.skipped,
+        }
+    }
+
+    /// Create a quality report message.
+    pub fn quality_report(report: &QualityReport) -> Self {
+        WorkerMessage::QualityReport {
+            psnr: report.psnr,
+            ssim: report.ssim,
+        }
+    }
+
+    /// Create a heartbeat message.
+    pub fn heartbeat(uptime_secs: u64) -> Self {
+        WorkerMessage::Heartbeat { uptime_secs }
+    }
+}
+
+/// Outcome counts for a batch run.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BatchSummary {
+    /// Jobs in the batch
+    pub total: usize,
+    /// Jobs that completed successfully
+    pub succeeded: usize,
+    /// Jobs that failed
+    pub failed: usize,
+    /// Jobs not run (after a failure without --continue-on-error, or cancellation)
+    pub skipped: usize,
+}
+
+/// Output quality compared with the VapourSynth output before encoding.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct QualityReport {
+    /// Average PSNR in dB (`inf` for identical frames)
+    pub psnr: Option<f64>,
+    /// Overall SSIM (0.0-1.0)
+    pub ssim: Option<f64>,
 }
 
 /// Log message from worker.
@@ -179,6 +277,53 @@ impl LogLevel {
     }
 }
 
+/// Phases of preview generation, in the order they occur.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PreviewPhase {
+    /// Extracting frames around the target time with ffmpeg
+    Extracting,
+    /// Indexing the extracted clip in the source filter
+    Indexing,
+    /// Running the filter pipeline in vspipe
+    Processing,
+    /// Encoding the processed frame as PNG
+    Encoding,
+}
+
+impl PreviewPhase {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PreviewPhase::Extracting => "extracting",
+            PreviewPhase::Indexing => "indexing",
+            PreviewPhase::Processing => "processing",
+            PreviewPhase::Encoding => "encoding",
+        }
+    }
+}
+
+/// Phases of an encode that progress messages alone don't convey.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EncodePhase {
+    /// vspipe and ffmpeg are processing frames
+    Encoding,
+    /// All frames delivered, ffmpeg is finishing the output file
+    Finalizing,
+    /// vspipe and ffmpeg are stopped until resumed
+    Paused,
+}
+
+impl EncodePhase {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EncodePhase::Encoding => "encoding",
+            EncodePhase::Finalizing => "finalizing",
+            EncodePhase::Paused => "paused",
+        }
+    }
+}
+
 /// Processing state machine.
 #[derive(Debug, Clone, PartialEq)]
 pub enum ProcessingState {
@@ -238,4 +383,32 @@ mod tests {
         assert!(json.contains("\"type\":\"log\""));
         assert!(json.contains("\"level\":\"info\""));
     }
+
+    #[test]
+    fn test_dependency_missing_serialization() {
+        let error = DependencyError {
+            which: "vspipe".to_string(),
+            searched_paths: vec!["deps/macos-arm64/vapoursynth/vspipe".to_string()],
+        };
+        let json = serde_json::to_string(&WorkerMessage::dependency_missing(&error)).unwrap();
+        assert!(json.contains("\"type\":\"error\""));
+        assert!(json.contains("\"code\":\"dependencyMissing\""));
+        assert!(json.contains("\"which\":\"vspipe\""));
+        assert!(json.contains("\"searchedPaths\":[\"deps/macos-arm64/vapoursynth/vspipe\"]"));
+
+        // Plain errors keep the original shape
+        let json = serde_json::to_string(&WorkerMessage::error("boom")).unwrap();
+        assert_eq!(json, "{\"type\":\"error\",\"message\":\"boom\"}");
+    }
+
+    #[test]
+    fn test_phase_message_serialization() {
+        let msg = WorkerMessage::phase(PreviewPhase::Indexing.as_str(), "Indexing preview clip");
+        let json = serde_json::to_string(&msg).unwrap();
+        assert!(json.contains("\"type\":\"phase\""));
+        assert!(json.contains("\"phase\":\"indexing\""));
+
+        let msg = WorkerMessage::phase(EncodePhase::Finalizing.as_str(), "Finalizing output file");
+        assert!(serde_json::to_string(&msg).unwrap().contains("\"phase\":\"finalizing\""));
+    }
 }
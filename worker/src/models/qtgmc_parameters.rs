@@ -3,8 +3,20 @@
 //! All 70+ QTGMC parameters supported by the VapourSynth implementation.
 //! Parameters with `None` values use preset defaults.
 
+use anyhow::{bail, Result};
 use serde::{Deserialize, Serialize};
 
+/// Deinterlacing method.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum DeinterlaceMethod {
+    /// Full motion-compensated QTGMC
+    #[default]
+    Qtgmc,
+    /// NNEDI3 bob only (no motion compensation); much faster, for clean sources
+    Nnedi3Bob,
+}
+
 /// All QTGMC parameters supported by the VapourSynth implementation.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -13,16 +25,36 @@ pub struct QTGMCParameters {
     #[serde(default = "default_true")]
     pub enabled: bool,
 
+    /// Temporarily skip this pass while keeping it enabled and configured.
+    #[serde(default)]
+    pub bypass: bool,
+
+    /// Deinterlacer to use. QTGMC parameters only apply to `Qtgmc`.
+    #[serde(default)]
+    pub method: DeinterlaceMethod,
+
     // === Preset ===
     /// Master quality/speed preset
     #[serde(default)]
     pub preset: QTGMCPreset,
 
+    /// Named custom preset from `~/.vapourbox/qtgmc_presets/<name>.json`,
+    /// whose parameters replace these (see `with_custom_preset`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preset_name: Option<String>,
+
     // === Input/Output ===
     /// Input type: 0=interlaced, 1=progressive, 2=progressive with combing
     #[serde(default)]
     pub input_type: i32,
 
+    /// Motion SAD threshold for progressive repair (InputType 2/3): where
+    /// the motion search error exceeds it, the reconstructed line is kept over
+    /// the source (0.0 = off, QTGMC's default; ~10.0 for partly combed
+    /// sources). None leaves QTGMC's default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prog_sad_mask: Option<f64>,
+
     /// Top-field-first. None = auto-detect
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tff: Option<bool>,
@@ -48,9 +80,9 @@ pub struct QTGMCParameters {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub rep0: Option<i32>,
 
-    /// Repair mode after TR1 smoothing (0-4)
-    #[serde(default)]
-    pub rep1: i32,
+    /// Repair mode after TR1 smoothing (0-4). None uses the QTGMC default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rep1: Option<i32>,
 
     /// Repair mode after TR2 smoothing (0-4)
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -85,6 +117,13 @@ pub struct QTGMCParameters {
     #[serde(default)]
     pub chroma_edi: String,
 
+    /// Python snippet building an external interpolation clip for QTGMC's
+    /// `EdiExt`, replacing its own interpolation. It runs in a function that
+    /// receives the deinterlacer input as `clip` and must assign
+    /// `edi_ext_clip`, matching QTGMC's output size, format and frame rate.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub edi_ext_script: Option<String>,
+
     // === Motion Analysis ===
     /// Motion analysis block size
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -134,17 +173,17 @@ pub struct QTGMCParameters {
     #[serde(default = "default_true")]
     pub global_motion: bool,
 
-    /// DCT mode for motion analysis (0-10)
-    #[serde(default)]
-    pub dct: i32,
+    /// DCT mode for motion analysis (0-10). None uses the QTGMC default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dct: Option<i32>,
 
     /// Sub-pixel accuracy (1=full, 2=half, 4=quarter)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub sub_pel: Option<i32>,
 
-    /// Sub-pixel interpolation method (1-2)
-    #[serde(default = "default_two")]
-    pub sub_pel_interp: i32,
+    /// Sub-pixel interpolation method (0-2). None uses the QTGMC default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sub_pel_interp: Option<i32>,
 
     // === Motion Thresholds ===
     /// SAD threshold for TR1 temporal smoothing
@@ -180,9 +219,9 @@ pub struct QTGMCParameters {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub sl_rad: Option<i32>,
 
-    /// Sharpening overshoot
-    #[serde(default)]
-    pub s_ovs: i32,
+    /// Sharpening overshoot. None uses the QTGMC default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub s_ovs: Option<i32>,
 
     /// Thin line sharpening (0.0-1.0)
     #[serde(default)]
@@ -257,6 +296,11 @@ pub struct QTGMCParameters {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub stabilize_noise: Option<bool>,
 
+    /// Film grain look; when not `Off` it sets the noise processing group
+    /// (`noise_process`, `grain_restore`, `noise_restore`, `stabilize_noise`)
+    #[serde(default)]
+    pub film_grain_preset: FilmGrainPreset,
+
     // === Source Matching ===
     /// Source matching mode: 0=off, 1=simple, 2=refined, 3=double
     #[serde(default)]
@@ -287,6 +331,11 @@ pub struct QTGMCParameters {
     pub match_enhance: f64,
 
     /// Lossless mode: 0=off, 1=lossless, 2=fake lossless
+    ///
+    /// Lossless modes weave the original source fields back into the output,
+    /// so they need interlaced input (`input_type` 0 or 2) and are only truly
+    /// lossless at double rate (`fps_divisor` 1). With denoising the untouched
+    /// source lines keep their noise while interpolated lines lose it.
     #[serde(default)]
     pub lossless: i32,
 
@@ -323,21 +372,47 @@ pub struct QTGMCParameters {
     #[serde(default)]
     pub refine_motion: bool,
 
+    // === Chroma ===
+    /// Upsample interlaced 4:2:0 chroma per field (to 4:2:2) before deinterlacing,
+    /// so chroma from opposite fields isn't blended (DV/DVD sources)
+    #[serde(default)]
+    pub fix_interlaced_chroma: bool,
+
+    // === Mixed Content ===
+    /// Deinterlace only frames TDeintMod's IsCombed flags as combed and pass
+    /// progressive frames through untouched (mixed film/video sources)
+    #[serde(default)]
+    pub selective_deinterlace: bool,
+
+    /// Source frame ranges to deinterlace, as inclusive (first, last) pairs in
+    /// ascending order (after start/end trimming). Other frames pass through
+    /// untouched. Empty deinterlaces every frame. Not applied to previews.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub deinterlace_ranges: Vec<(i32, i32)>,
+
     // === GPU Acceleration ===
     /// Use OpenCL acceleration
     #[serde(default)]
     pub opencl: bool,
 
-    /// OpenCL device index
-    #[serde(skip_serializing_if = "Option::is_none")]
+    /// OpenCL device index (only used when `opencl` is set). Also selects the
+    /// device for the NNEDI3 bob deinterlacer.
+    #[serde(alias = "openclDevice", skip_serializing_if = "Option::is_none")]
     pub device: Option<i32>,
+
+    /// Re-run on the CPU if the OpenCL device fails to initialise
+    #[serde(default = "default_true")]
+    pub opencl_fallback: bool,
+
+    /// If vspipe runs out of memory, re-run once with the next faster preset
+    #[serde(default)]
+    pub auto_degrade_on_oom: bool,
 }
 
 // Default value functions
 fn default_fps_divisor() -> i32 { 1 }
 fn default_true() -> bool { true }
 fn default_one() -> i32 { 1 }
-fn default_two() -> i32 { 2 }
 fn default_th_sad1() -> i32 { 640 }
 fn default_th_sad2() -> i32 { 256 }
 fn default_th_scd1() -> i32 { 180 }
@@ -351,15 +426,19 @@ impl Default for QTGMCParameters {
     fn default() -> Self {
         Self {
             enabled: true,
+            bypass: false,
+            method: DeinterlaceMethod::default(),
             preset: QTGMCPreset::default(),
+            preset_name: None,
             input_type: 0,
+            prog_sad_mask: None,
             tff: None,
             fps_divisor: 1,
             tr0: None,
             tr1: None,
             tr2: None,
             rep0: None,
-            rep1: 0,
+            rep1: None,
             rep2: None,
             rep_chroma: true,
             edi_mode: None,
@@ -368,6 +447,7 @@ impl Default for QTGMCParameters {
             edi_qual: 1,
             edi_max_d: None,
             chroma_edi: String::new(),
+            edi_ext_script: None,
             block_size: None,
             overlap: None,
             search: None,
@@ -380,9 +460,9 @@ impl Default for QTGMCParameters {
             p_new: None,
             p_level: None,
             global_motion: true,
-            dct: 0,
+            dct: None,
             sub_pel: None,
-            sub_pel_interp: 2,
+            sub_pel_interp: None,
             th_sad1: 640,
             th_sad2: 256,
             th_scd1: 180,
@@ -391,7 +471,7 @@ impl Default for QTGMCParameters {
             s_mode: None,
             sl_mode: None,
             sl_rad: None,
-            s_ovs: 0,
+            s_ovs: None,
             sv_thin: 0.0,
             sbb: None,
             srch_clip_pp: None,
@@ -426,12 +506,156 @@ impl Default for QTGMCParameters {
             fast_ma: false,
             e_search_p: false,
             refine_motion: false,
+            fix_interlaced_chroma: false,
+            selective_deinterlace: false,
+            deinterlace_ranges: Vec::new(),
             opencl: false,
             device: None,
+            opencl_fallback: true,
+            auto_degrade_on_oom: false,
+            film_grain_preset: FilmGrainPreset::default(),
         }
     }
 }
 
+/// Name the `edi_ext_script` snippet must assign its clip to.
+pub const EDI_EXT_CLIP: &str = "edi_ext_clip";
+
+/// `script` cleaned up for injection into the generated script: trimmed, with
+/// Unix line endings. Rejects snippets that don't assign `edi_ext_clip`, set
+/// an output themselves, or contain `{{`/`}}` template markers.
+pub fn sanitize_edi_ext_script(script: &str) -> Result<String> {
+    let script = script.replace("\r\n", "\n").replace('\r', "\n");
+    let script = script.trim_matches('\n').trim_end();
+    if script.trim().is_empty() {
+        bail!("QTGMC: EdiExt script is empty; clear edi_ext_script or assign {}", EDI_EXT_CLIP);
+    }
+    if script.contains('\0') || script.contains("{{") || script.contains("}}") {
+        bail!("QTGMC: EdiExt script must not contain NUL characters or {{{{ }}}} template markers");
+    }
+    if script.contains("set_output") {
+        bail!("QTGMC: EdiExt script must not call set_output; assign {} instead", EDI_EXT_CLIP);
+    }
+    if !script.contains(EDI_EXT_CLIP) {
+        bail!("QTGMC: EdiExt script must assign the interpolated clip to {}", EDI_EXT_CLIP);
+    }
+    Ok(script.to_string())
+}
+
+impl QTGMCParameters {
+    /// Check for parameter combinations QTGMC rejects.
+    pub fn validate(&self) -> Result<()> {
+        let positive = |v: Option<f64>| v.is_some_and(|v| v > 0.0);
+        if positive(self.ez_denoise) && positive(self.ez_keep_grain) {
+            bail!("QTGMC: EZDenoise and EZKeepGrain cannot be used together; set only one of ezDenoise or ezKeepGrain");
+        }
+        if !(0..=2).contains(&self.lossless) {
+            bail!("QTGMC: Lossless must be 0 (off), 1 (lossless) or 2 (fake lossless), got {}", self.lossless);
+        }
+        if self.lossless > 0 && self.input_type == 1 {
+            bail!("QTGMC: Lossless modes are incompatible with InputType=1 (progressive input has no source fields to restore)");
+        }
+        let mut next_frame = 0;
+        for &(first, last) in &self.deinterlace_ranges {
+            if first < next_frame || last < first {
+                bail!(
+                    "Deinterlace ranges must be ascending, non-overlapping (first, last) frame pairs; got ({}, {})",
+                    first, last
+                );
+            }
+            next_frame = last + 1;
+        }
+        if let Some(script) = &self.edi_ext_script {
+            sanitize_edi_ext_script(script)?;
+        }
+        Ok(())
+    }
+
+    /// Combinations QTGMC accepts but that defeat the purpose of a setting.
+    pub fn warnings(&self) -> Vec<String> {
+        let mut warnings = Vec::new();
+        if self.lossless > 0 {
+            if self.fps_divisor > 1 {
+                warnings.push(format!(
+                    "QTGMC: Lossless={} with FPSDivisor={} discards source fields, so the output is not lossless",
+                    self.lossless, self.fps_divisor,
+                ));
+            }
+            let denoising = self.ez_denoise.is_some_and(|v| v > 0.0) || self.noise_process == Some(1);
+            if denoising {
+                warnings.push(format!(
+                    "QTGMC: Lossless={} with denoising keeps noise on source lines only, which can show as line flicker",
+                    self.lossless,
+                ));
+            }
+        }
+        if let Some(mask) = self.prog_sad_mask.filter(|_| self.input_type < 2) {
+            warnings.push(format!(
+                "QTGMC: ProgSADMask={} only applies to progressive repair (InputType 2 or 3)",
+                mask,
+            ));
+        }
+        if let (Some(device), false) = (self.device, self.opencl) {
+            warnings.push(format!(
+                "QTGMC: device={} has no effect without opencl; enable OpenCL or clear the device",
+                device,
+            ));
+        }
+        warnings
+    }
+
+    /// These parameters with a custom preset's values applied.
+    ///
+    /// The preset replaces every tuning parameter, including `preset`; the
+    /// pass state (`enabled`, `bypass`) and the source's field order (`tff`)
+    /// are kept from `self`.
+    pub fn with_custom_preset(&self, custom: &QTGMCParameters) -> QTGMCParameters {
+        QTGMCParameters {
+            enabled: self.enabled,
+            bypass: self.bypass,
+            preset_name: self.preset_name.clone(),
+            tff: self.tff,
+            ..custom.clone()
+        }
+    }
+
+    /// Apply the film grain preset to the noise processing fields.
+    ///
+    /// The presets denoise, then restore a share of the removed grain with noise
+    /// stabilisation on, so restored grain follows the motion-compensated source
+    /// rather than flickering across scene changes. Easy-mode denoise/grain
+    /// settings are cleared since they would override the explicit values.
+    pub fn apply_film_grain_preset(&mut self) {
+        let (grain_restore, noise_restore) = match self.film_grain_preset {
+            FilmGrainPreset::Off => return,
+            FilmGrainPreset::Light => (0.3, 0.1),
+            FilmGrainPreset::Natural => (0.5, 0.2),
+            FilmGrainPreset::Heavy => (0.7, 0.3),
+        };
+        self.noise_process = Some(2);
+        self.grain_restore = Some(grain_restore);
+        self.noise_restore = Some(noise_restore);
+        self.stabilize_noise = Some(true);
+        self.ez_denoise = None;
+        self.ez_keep_grain = None;
+    }
+}
+
+/// High-level film grain presets for QTGMC noise processing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum FilmGrainPreset {
+    /// Leave the noise processing fields as configured
+    #[default]
+    Off,
+    /// Subtle grain, mostly clean
+    Light,
+    /// Natural film look
+    Natural,
+    /// Strong grain retention
+    Heavy,
+}
+
 /// QTGMC quality/speed presets.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
 pub enum QTGMCPreset {
@@ -454,6 +678,27 @@ pub enum QTGMCPreset {
 }
 
 impl QTGMCPreset {
+    /// Every preset, from slowest (highest quality) to fastest.
+    pub const LADDER: [QTGMCPreset; 11] = [
+        QTGMCPreset::Placebo,
+        QTGMCPreset::VerySlow,
+        QTGMCPreset::Slower,
+        QTGMCPreset::Slow,
+        QTGMCPreset::Medium,
+        QTGMCPreset::Fast,
+        QTGMCPreset::Faster,
+        QTGMCPreset::VeryFast,
+        QTGMCPreset::SuperFast,
+        QTGMCPreset::UltraFast,
+        QTGMCPreset::Draft,
+    ];
+
+    /// The next preset down the ladder (faster, less memory), if any.
+    pub fn next_faster(&self) -> Option<QTGMCPreset> {
+        let index = Self::LADDER.iter().position(|preset| preset == self)?;
+        Self::LADDER.get(index + 1).copied()
+    }
+
     /// Get the preset string for VapourSynth.
     pub fn as_str(&self) -> &'static str {
         match self {
@@ -505,6 +750,24 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_preset_ladder_order() {
+        assert_eq!(QTGMCPreset::LADDER.first(), Some(&QTGMCPreset::Placebo));
+        assert_eq!(QTGMCPreset::Placebo.next_faster(), Some(QTGMCPreset::VerySlow));
+        assert_eq!(QTGMCPreset::VerySlow.next_faster(), Some(QTGMCPreset::Slower));
+        assert_eq!(QTGMCPreset::Slower.next_faster(), Some(QTGMCPreset::Slow));
+        assert_eq!(QTGMCPreset::Draft.next_faster(), None);
+
+        // Walking the ladder visits every preset once
+        let mut preset = QTGMCPreset::Placebo;
+        let mut visited = vec![preset];
+        while let Some(next) = preset.next_faster() {
+            visited.push(next);
+            preset = next;
+        }
+        assert_eq!(visited, QTGMCPreset::LADDER);
+    }
+
     #[test]
     fn test_default_parameters() {
         let params = QTGMCParameters::default();
@@ -512,4 +775,121 @@ mod tests {
         assert_eq!(params.fps_divisor, 1);
         assert!(params.tff.is_none());
     }
+
+    #[test]
+    fn test_edi_ext_script_sanitized() {
+        let script = "\r\nedi_ext_clip = core.nnedi3cl.NNEDI3CL(clip, field=3)\r\n  \r\n";
+        assert_eq!(sanitize_edi_ext_script(script).unwrap(), "edi_ext_clip = core.nnedi3cl.NNEDI3CL(clip, field=3)");
+
+        for invalid in ["  \n", "clip = clip", "edi_ext_clip = clip\nedi_ext_clip.set_output()", "edi_ext_clip = {{INPUT}}"] {
+            assert!(sanitize_edi_ext_script(invalid).is_err(), "{:?} should be rejected", invalid);
+        }
+        let params = QTGMCParameters { edi_ext_script: Some("clip = clip".to_string()), ..QTGMCParameters::default() };
+        assert!(params.validate().is_err());
+    }
+
+    #[test]
+    fn test_validate_rejects_ez_denoise_with_ez_keep_grain() {
+        let both = QTGMCParameters {
+            ez_denoise: Some(2.0),
+            ez_keep_grain: Some(1.0),
+            ..QTGMCParameters::default()
+        };
+        let err = both.validate().unwrap_err().to_string();
+        assert!(err.contains("EZDenoise") && err.contains("EZKeepGrain"));
+
+        let denoise = QTGMCParameters { ez_denoise: Some(2.0), ..QTGMCParameters::default() };
+        assert!(denoise.validate().is_ok());
+        let keep_grain = QTGMCParameters { ez_keep_grain: Some(1.0), ..QTGMCParameters::default() };
+        assert!(keep_grain.validate().is_ok());
+        // Zero means off, so it doesn't conflict
+        let zero = QTGMCParameters { ez_denoise: Some(0.0), ..keep_grain };
+        assert!(zero.validate().is_ok());
+    }
+
+    #[test]
+    fn test_lossless_validation() {
+        let valid = QTGMCParameters { lossless: 1, source_match: 3, ..QTGMCParameters::default() };
+        assert!(valid.validate().is_ok());
+        assert!(valid.warnings().is_empty());
+
+        let progressive = QTGMCParameters { lossless: 2, input_type: 1, ..QTGMCParameters::default() };
+        assert!(progressive.validate().unwrap_err().to_string().contains("InputType=1"));
+        let out_of_range = QTGMCParameters { lossless: 3, ..QTGMCParameters::default() };
+        assert!(out_of_range.validate().is_err());
+
+        let single_rate_denoised = QTGMCParameters {
+            lossless: 1,
+            fps_divisor: 2,
+            ez_denoise: Some(2.0),
+            ..QTGMCParameters::default()
+        };
+        assert!(single_rate_denoised.validate().is_ok());
+        let warnings = single_rate_denoised.warnings();
+        assert_eq!(warnings.len(), 2);
+        assert!(warnings[0].contains("FPSDivisor=2"));
+        assert!(warnings[1].contains("denoising"));
+    }
+
+    #[test]
+    fn test_deinterlace_ranges_validation() {
+        let ranges = |deinterlace_ranges: Vec<(i32, i32)>| {
+            QTGMCParameters { deinterlace_ranges, ..QTGMCParameters::default() }.validate()
+        };
+        assert!(ranges(vec![]).is_ok());
+        assert!(ranges(vec![(0, 99), (100, 100), (500, 599)]).is_ok());
+        assert!(ranges(vec![(0, 99), (50, 150)]).is_err(), "Overlapping ranges");
+        assert!(ranges(vec![(500, 599), (0, 99)]).is_err(), "Descending ranges");
+        assert!(ranges(vec![(10, 5)]).is_err(), "Last before first");
+        assert!(ranges(vec![(-1, 5)]).is_err(), "Negative frame");
+    }
+
+    #[test]
+    fn test_device_without_opencl_warns() {
+        let cpu = QTGMCParameters { device: Some(1), ..QTGMCParameters::default() };
+        assert!(cpu.warnings().iter().any(|w| w.contains("device=1")));
+
+        let gpu = QTGMCParameters { device: Some(1), opencl: true, ..QTGMCParameters::default() };
+        assert!(gpu.warnings().is_empty());
+    }
+
+    #[test]
+    fn test_prog_sad_mask_needs_progressive_repair() {
+        let interlaced = QTGMCParameters { prog_sad_mask: Some(10.0), ..QTGMCParameters::default() };
+        assert!(interlaced.warnings().iter().any(|w| w.contains("ProgSADMask=10")));
+
+        let repair = QTGMCParameters { input_type: 2, ..interlaced };
+        assert!(repair.warnings().is_empty());
+    }
+
+    #[test]
+    fn test_film_grain_preset_sets_noise_group() {
+        let mut params = QTGMCParameters {
+            film_grain_preset: FilmGrainPreset::Natural,
+            ez_keep_grain: Some(1.0),
+            ..QTGMCParameters::default()
+        };
+        params.apply_film_grain_preset();
+        assert_eq!(params.noise_process, Some(2));
+        assert_eq!(params.grain_restore, Some(0.5));
+        assert_eq!(params.noise_restore, Some(0.2));
+        assert_eq!(params.stabilize_noise, Some(true));
+        assert_eq!(params.ez_keep_grain, None);
+
+        // Off leaves the fields untouched
+        let mut params = QTGMCParameters { grain_restore: Some(0.9), ..QTGMCParameters::default() };
+        params.apply_film_grain_preset();
+        assert_eq!(params.grain_restore, Some(0.9));
+        assert_eq!(params.noise_process, None);
+    }
+
+    #[test]
+    fn test_sentinel_values_deserialize_as_explicit() {
+        // Existing configs send plain integers; 0 is now an explicit value
+        let params: QTGMCParameters = serde_json::from_str(r#"{"dct": 0, "subPelInterp": 2}"#).unwrap();
+        assert_eq!(params.dct, Some(0));
+        assert_eq!(params.sub_pel_interp, Some(2));
+        assert_eq!(params.rep1, None);
+        assert_eq!(params.s_ovs, None);
+    }
 }
@@ -3,9 +3,10 @@
 use serde::{Deserialize, Serialize};
 
 use super::{
-    ChromaFixParameters, ColorCorrectionParameters, CropResizeParameters,
-    DebandParameters, DeblockParameters, DehaloParameters, SharpenParameters,
-    NoiseReductionParameters, QTGMCParameters,
+    ChromaFixParameters, ColorCorrectionParameters,
+    CropResizeParameters, CropStage, DebandParameters, DeblockMethod, DeblockParameters,
+    DedupParameters, DehaloMethod, DehaloParameters, GrainParameters, SharpenMethod, SharpenParameters, UpscaleMethod, NoiseReductionMethod, NoiseReductionParameters,
+    DeinterlaceMethod, QTGMCParameters,
 };
 
 /// Defines the type of each restoration pass.
@@ -20,6 +21,7 @@ pub enum PassType {
     Sharpen,
     ColorCorrection,
     ChromaFixes,
+    Grain,
     CropResize,
 }
 
@@ -35,6 +37,7 @@ impl PassType {
             PassType::Sharpen => "Sharpen",
             PassType::ColorCorrection => "Color Correction",
             PassType::ChromaFixes => "Chroma Fixes",
+            PassType::Grain => "Grain",
             PassType::CropResize => "Crop / Resize",
         }
     }
@@ -50,11 +53,70 @@ impl PassType {
             PassType::Sharpen => "Sharpen edges and enhance detail",
             PassType::ColorCorrection => "Adjust brightness, contrast, and colors",
             PassType::ChromaFixes => "Fix chroma bleeding and crawl artifacts",
+            PassType::Grain => "Add film grain back after noise reduction",
             PassType::CropResize => "Crop borders and resize output",
         }
     }
 }
 
+/// High-level scene change detection sensitivity, applied to every
+/// scene-aware filter (QTGMC and SMDegrain motion analysis).
+///
+/// Higher sensitivity lowers the thresholds, so more frame pairs count as
+/// scene changes and are not blended across. neo_f3kdb has no scene detection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SceneSensitivity {
+    Low,
+    #[default]
+    Medium,
+    High,
+}
+
+impl SceneSensitivity {
+    /// QTGMC (ThSCD1, ThSCD2). Medium matches the QTGMC defaults.
+    pub fn qtgmc_thresholds(&self) -> (i32, i32) {
+        match self {
+            SceneSensitivity::Low => (250, 130),
+            SceneSensitivity::Medium => (180, 98),
+            SceneSensitivity::High => (120, 70),
+        }
+    }
+
+    /// SMDegrain (thSCD1, thSCD2). Medium matches the SMDegrain defaults.
+    pub fn sm_degrain_thresholds(&self) -> (i32, i32) {
+        match self {
+            SceneSensitivity::Low => (500, 160),
+            SceneSensitivity::Medium => (400, 130),
+            SceneSensitivity::High => (300, 100),
+        }
+    }
+
+    /// Set the scene change thresholds on QTGMC parameters.
+    pub fn apply_to_qtgmc(&self, params: &mut QTGMCParameters) {
+        (params.th_scd1, params.th_scd2) = self.qtgmc_thresholds();
+    }
+}
+
+/// A non-fatal suggestion about a combination of settings that works but is
+/// unlikely to be what the user wants.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Advisory {
+    /// Pass the suggestion concerns; None for encoding settings
+    pub pass: Option<PassType>,
+    /// What to change and why
+    pub message: String,
+}
+
+impl std::fmt::Display for Advisory {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.pass {
+            Some(pass) => write!(f, "{}: {}", pass.display_name(), self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
 /// Container for all restoration pass parameters.
 /// Defines the complete video restoration pipeline.
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -64,6 +126,10 @@ pub struct RestorationPipeline {
     #[serde(default)]
     pub deinterlace: QTGMCParameters,
 
+    /// Duplicate frame removal, applied after deinterlacing.
+    #[serde(default)]
+    pub dedup: DedupParameters,
+
     /// Noise reduction pass parameters.
     #[serde(default)]
     pub noise_reduction: NoiseReductionParameters,
@@ -92,15 +158,25 @@ pub struct RestorationPipeline {
     #[serde(default)]
     pub chroma_fixes: ChromaFixParameters,
 
+    /// Film grain pass parameters, applied after the cleanup passes.
+    #[serde(default)]
+    pub grain: GrainParameters,
+
     /// Crop and resize pass parameters.
     #[serde(default)]
     pub crop_resize: CropResizeParameters,
+
+    /// Scene detection sensitivity; overrides the individual scene change
+    /// thresholds of every scene-aware pass when set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scene_sensitivity: Option<SceneSensitivity>,
 }
 
 impl Default for RestorationPipeline {
     fn default() -> Self {
         Self {
             deinterlace: QTGMCParameters::default(),
+            dedup: DedupParameters::default(),
             noise_reduction: NoiseReductionParameters::default(),
             dehalo: DehaloParameters::default(),
             deblock: DeblockParameters::default(),
@@ -108,7 +184,9 @@ impl Default for RestorationPipeline {
             sharpen: SharpenParameters::default(),
             color_correction: ColorCorrectionParameters::default(),
             chroma_fixes: ChromaFixParameters::default(),
+            grain: GrainParameters::default(),
             crop_resize: CropResizeParameters::default(),
+            scene_sensitivity: None,
         }
     }
 }
@@ -118,6 +196,7 @@ impl RestorationPipeline {
     pub fn from_legacy(qtgmc_params: &QTGMCParameters) -> Self {
         Self {
             deinterlace: qtgmc_params.clone(),
+            dedup: DedupParameters::default(),
             noise_reduction: NoiseReductionParameters { enabled: false, ..Default::default() },
             dehalo: DehaloParameters { enabled: false, ..Default::default() },
             deblock: DeblockParameters { enabled: false, ..Default::default() },
@@ -125,91 +204,445 @@ impl RestorationPipeline {
             sharpen: SharpenParameters { enabled: false, ..Default::default() },
             color_correction: ColorCorrectionParameters { enabled: false, ..Default::default() },
             chroma_fixes: ChromaFixParameters { enabled: false, ..Default::default() },
+            grain: GrainParameters::default(),
             crop_resize: CropResizeParameters { enabled: false, ..Default::default() },
+            scene_sensitivity: None,
+        }
+    }
+
+    /// Expand named pass presets into their concrete parameter values.
+    /// Only the fields a preset defines are overwritten; `enabled`, `bypass`
+    /// and everything else the preset leaves alone keep their values.
+    pub fn resolve_presets(&mut self) {
+        self.noise_reduction.apply_preset();
+        self.color_correction.apply_preset();
+        self.chroma_fixes.apply_preset();
+        self.crop_resize.resolve_preset();
+    }
+
+    /// Apply `scene_sensitivity` (if set) to the scene change thresholds of each pass.
+    pub fn apply_scene_sensitivity(&mut self) {
+        let Some(sensitivity) = self.scene_sensitivity else {
+            return;
+        };
+        sensitivity.apply_to_qtgmc(&mut self.deinterlace);
+        let (th_scd1, th_scd2) = sensitivity.sm_degrain_thresholds();
+        self.noise_reduction.sm_degrain_th_scd1 = Some(th_scd1);
+        self.noise_reduction.sm_degrain_th_scd2 = Some(th_scd2);
+    }
+
+    /// Turn off bypassed passes so script generation skips them.
+    /// Their parameters are left untouched.
+    pub fn apply_bypass(&mut self) {
+        for (enabled, bypass) in [
+            (&mut self.deinterlace.enabled, self.deinterlace.bypass),
+            (&mut self.noise_reduction.enabled, self.noise_reduction.bypass),
+            (&mut self.dehalo.enabled, self.dehalo.bypass),
+            (&mut self.deblock.enabled, self.deblock.bypass),
+            (&mut self.deband.enabled, self.deband.bypass),
+            (&mut self.sharpen.enabled, self.sharpen.bypass),
+            (&mut self.color_correction.enabled, self.color_correction.bypass),
+            (&mut self.chroma_fixes.enabled, self.chroma_fixes.bypass),
+            (&mut self.grain.enabled, self.grain.bypass),
+            (&mut self.crop_resize.enabled, self.crop_resize.bypass),
+        ] {
+            if bypass {
+                *enabled = false;
+            }
         }
     }
 
+    /// Suggestions for pass combinations that run but work against each other.
+    pub fn lint(&self) -> Vec<Advisory> {
+        let mut advisories = Vec::new();
+        let mut advise = |pass, message: &str| advisories.push(Advisory { pass: Some(pass), message: message.to_string() });
+        let enabled = |pass| self.is_pass_enabled(pass);
+
+        if enabled(PassType::Sharpen) && !enabled(PassType::NoiseReduction) {
+            advise(
+                PassType::Sharpen,
+                "sharpening without noise reduction amplifies noise; enable noise reduction or lower the strength",
+            );
+        }
+        if enabled(PassType::NoiseReduction)
+            && self.noise_reduction.method == NoiseReductionMethod::QtgmcBuiltin
+            && !enabled(PassType::Deinterlace)
+        {
+            advise(
+                PassType::NoiseReduction,
+                "QTGMC built-in denoising only runs when deinterlacing; choose SMDegrain or MCTemporalDenoise",
+            );
+        }
+        if let (Some(device), false) = (self.sharpen.opencl_device, self.sharpen.opencl) {
+            if enabled(PassType::Sharpen) {
+                advise(
+                    PassType::Sharpen,
+                    &format!("openclDevice={} has no effect without opencl; enable OpenCL or clear the device", device),
+                );
+            }
+        }
+        let resize = &self.crop_resize;
+        if let (Some(device), false) = (resize.opencl_device, resize.opencl) {
+            if enabled(PassType::CropResize) && resize.use_integer_upscale {
+                advise(
+                    PassType::CropResize,
+                    &format!("openclDevice={} has no effect without opencl; enable OpenCL or clear the device", device),
+                );
+            }
+        }
+        if enabled(PassType::CropResize)
+            && resize.resize_enabled
+            && resize.target_display_aspect.is_none()
+            && !resize.maintain_aspect
+            && resize.keeps_aspect()
+        {
+            advise(
+                PassType::CropResize,
+                "only one of target width/height is set, so the other follows the source aspect; \
+                 set both to stretch to an exact size",
+            );
+        }
+        advisories
+    }
+
     /// Get the ordered list of enabled passes.
+    /// Bypassed passes are excluded.
     pub fn enabled_passes(&self) -> Vec<PassType> {
         let mut passes = Vec::new();
+        let enabled = |pass| self.is_pass_enabled(pass);
 
-        // Order: Crop first (pre-processing), then deinterlace, noise, dehalo, deblock, deband, sharpen, chroma, color, resize last
-        if self.crop_resize.enabled && self.crop_resize.crop_enabled {
+        // Order: Crop first (pre-processing), then deinterlace, noise, dehalo, deblock, deband, sharpen, chroma, color, grain, resize last
+        // (sharpen moves after resize when sharpen_after_resize is set)
+        let crop_resize_enabled = enabled(PassType::CropResize);
+        let crop_active = crop_resize_enabled && self.crop_resize.crop_enabled;
+        if crop_active && self.crop_resize.crop_stage == CropStage::PreDeinterlace {
             passes.push(PassType::CropResize); // Pre-crop
         }
-        if self.deinterlace_enabled() {
+        if enabled(PassType::Deinterlace) {
             passes.push(PassType::Deinterlace);
         }
-        if self.noise_reduction.enabled {
+        if enabled(PassType::NoiseReduction) {
             passes.push(PassType::NoiseReduction);
         }
-        if self.dehalo.enabled {
+        if enabled(PassType::Dehalo) {
             passes.push(PassType::Dehalo);
         }
-        if self.deblock.enabled {
+        if enabled(PassType::Deblock) {
             passes.push(PassType::Deblock);
         }
-        if self.deband.enabled {
+        if enabled(PassType::Deband) {
             passes.push(PassType::Deband);
         }
-        if self.sharpen.enabled {
+        let sharpen_enabled = enabled(PassType::Sharpen);
+        if sharpen_enabled && !self.sharpen.sharpen_after_resize {
             passes.push(PassType::Sharpen);
         }
-        if self.chroma_fixes.enabled {
+        if enabled(PassType::ChromaFixes) {
             passes.push(PassType::ChromaFixes);
         }
-        if self.color_correction.enabled {
+        if enabled(PassType::ColorCorrection) {
             passes.push(PassType::ColorCorrection);
         }
-        if self.crop_resize.enabled && self.crop_resize.resize_enabled {
-            // Resize (post-processing) - if not already added for crop
+        if enabled(PassType::Grain) {
+            passes.push(PassType::Grain);
+        }
+        if crop_active || (crop_resize_enabled && self.crop_resize.resize_enabled) {
+            // Post-crop and/or resize (post-processing) - if not already added for crop
             if !passes.contains(&PassType::CropResize) {
                 passes.push(PassType::CropResize);
             }
         }
+        if sharpen_enabled && self.sharpen.sharpen_after_resize {
+            passes.push(PassType::Sharpen);
+        }
 
         passes
     }
 
-    /// Check if deinterlacing is enabled.
-    fn deinterlace_enabled(&self) -> bool {
-        self.deinterlace.enabled
+    /// Compact one-line summary of the enabled passes in processing order,
+    /// e.g. "QTGMC Slower; SMDegrain tr=2; LSFmod strength=100".
+    pub fn describe(&self) -> String {
+        let mut parts = Vec::new();
+        for pass in self.enabled_passes() {
+            let part = match pass {
+                PassType::Deinterlace => match self.deinterlace.method {
+                    DeinterlaceMethod::Qtgmc => format!("QTGMC {}", self.deinterlace.preset.as_str()),
+                    DeinterlaceMethod::Nnedi3Bob => "NNEDI3 bob".to_string(),
+                },
+                PassType::NoiseReduction => match self.noise_reduction.method {
+                    NoiseReductionMethod::SmDegrain => format!("SMDegrain tr={}", self.noise_reduction.sm_degrain_tr),
+                    NoiseReductionMethod::McTemporalDenoise => "MCTemporalDenoise".to_string(),
+                    NoiseReductionMethod::QtgmcBuiltin => "QTGMC denoise".to_string(),
+                },
+                PassType::Dehalo => self.dehalo.method.as_str().to_string(),
+                PassType::Deblock => self.deblock.method.as_str().to_string(),
+                PassType::Deband => format!("f3kdb range={}", self.deband.range),
+                PassType::Sharpen => format!("{} strength={}", self.sharpen.method.as_str(), self.sharpen.strength),
+                PassType::Grain => format!("AddGrain var={}", self.grain.strength),
+                pass => pass.display_name().to_string(),
+            };
+            parts.push(part);
+            if pass == PassType::Deinterlace && self.dedup.enabled {
+                parts.push(format!("dedup threshold={}%", self.dedup.dup_threshold));
+            }
+        }
+        if parts.is_empty() {
+            "no restoration passes".to_string()
+        } else {
+            parts.join("; ")
+        }
     }
 
-    /// Get count of enabled passes.
+    /// VapourSynth plugin namespaces (`core.<namespace>`) the enabled passes
+    /// call, directly or through havsfunc, sorted. Plugins only used for
+    /// optional settings (e.g. dfttest denoising inside QTGMC) are left out.
+    pub fn required_plugins(&self) -> Vec<&'static str> {
+        let nnedi3 = if self.deinterlace.opencl { "nnedi3cl" } else { "znedi3" };
+        let mut plugins = vec!["bs"];
+        for pass in self.enabled_passes() {
+            match pass {
+                PassType::Deinterlace => {
+                    if self.deinterlace.method == DeinterlaceMethod::Qtgmc {
+                        plugins.extend(["mv", "rgvs"]);
+                    }
+                    plugins.push(nnedi3);
+                    if self.deinterlace.selective_deinterlace {
+                        plugins.push("tdm");
+                    }
+                }
+                PassType::NoiseReduction => {
+                    if self.noise_reduction.method != NoiseReductionMethod::QtgmcBuiltin {
+                        plugins.push("mv");
+                    }
+                }
+                PassType::Dehalo => {
+                    plugins.push("rgvs");
+                    if self.dehalo.method == DehaloMethod::FineDehalo {
+                        plugins.push("ctmf");
+                    }
+                }
+                PassType::Deblock => {
+                    plugins.push("deblock");
+                    if self.deblock.method == DeblockMethod::DeblockQed {
+                        plugins.push("fmtc");
+                    }
+                }
+                PassType::Deband => plugins.push("neo_f3kdb"),
+                PassType::Sharpen => plugins.push(match self.sharpen.method {
+                    SharpenMethod::LSFmod => "rgvs",
+                    SharpenMethod::CAS => "cas",
+                }),
+                PassType::Grain => plugins.push("grain"),
+                PassType::CropResize if self.crop_resize.use_integer_upscale => {
+                    match self.crop_resize.upscale_method {
+                        UpscaleMethod::Nnedi3Rpow2 => {
+                            plugins.push(if self.crop_resize.opencl { "nnedi3cl" } else { "znedi3" })
+                        }
+                        UpscaleMethod::Eedi3Rpow2 => plugins.push("eedi3m"),
+                        UpscaleMethod::Spline36 => {}
+                    }
+                }
+                PassType::CropResize | PassType::ColorCorrection | PassType::ChromaFixes => {}
+            }
+        }
+        plugins.sort_unstable();
+        plugins.dedup();
+        plugins
+    }
+
+    /// Get count of enabled (and not bypassed) passes.
     pub fn enabled_pass_count(&self) -> usize {
-        let mut count = 0;
-        if self.deinterlace.enabled { count += 1; }
-        if self.noise_reduction.enabled { count += 1; }
-        if self.dehalo.enabled { count += 1; }
-        if self.deblock.enabled { count += 1; }
-        if self.deband.enabled { count += 1; }
-        if self.sharpen.enabled { count += 1; }
-        if self.color_correction.enabled { count += 1; }
-        if self.chroma_fixes.enabled { count += 1; }
-        if self.crop_resize.enabled { count += 1; }
-        count
-    }
-
-    /// Check if a specific pass is enabled.
+        [
+            PassType::Deinterlace,
+            PassType::NoiseReduction,
+            PassType::Dehalo,
+            PassType::Deblock,
+            PassType::Deband,
+            PassType::Sharpen,
+            PassType::ColorCorrection,
+            PassType::ChromaFixes,
+            PassType::Grain,
+            PassType::CropResize,
+        ]
+        .into_iter()
+        .filter(|&pass| self.is_pass_enabled(pass))
+        .count()
+    }
+
+    /// Check if a specific pass is enabled and not bypassed.
     pub fn is_pass_enabled(&self, pass: PassType) -> bool {
-        match pass {
-            PassType::Deinterlace => self.deinterlace_enabled(),
-            PassType::NoiseReduction => self.noise_reduction.enabled,
-            PassType::Dehalo => self.dehalo.enabled,
-            PassType::Deblock => self.deblock.enabled,
-            PassType::Deband => self.deband.enabled,
-            PassType::Sharpen => self.sharpen.enabled,
-            PassType::ColorCorrection => self.color_correction.enabled,
-            PassType::ChromaFixes => self.chroma_fixes.enabled,
-            PassType::CropResize => self.crop_resize.enabled,
-        }
+        let (enabled, bypass) = match pass {
+            PassType::Deinterlace => (self.deinterlace.enabled, self.deinterlace.bypass),
+            PassType::NoiseReduction => (self.noise_reduction.enabled, self.noise_reduction.bypass),
+            PassType::Dehalo => (self.dehalo.enabled, self.dehalo.bypass),
+            PassType::Deblock => (self.deblock.enabled, self.deblock.bypass),
+            PassType::Deband => (self.deband.enabled, self.deband.bypass),
+            PassType::Sharpen => (self.sharpen.enabled, self.sharpen.bypass),
+            PassType::ColorCorrection => (self.color_correction.enabled, self.color_correction.bypass),
+            PassType::ChromaFixes => (self.chroma_fixes.enabled, self.chroma_fixes.bypass),
+            PassType::Grain => (self.grain.enabled, self.grain.bypass),
+            PassType::CropResize => (self.crop_resize.enabled, self.crop_resize.bypass),
+        };
+        enabled && !bypass
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::models::{ColorCorrectionPreset, NoiseReductionPreset};
+
+    #[test]
+    fn test_bypassed_pass_excluded_but_kept() {
+        let mut pipeline = RestorationPipeline {
+            dehalo: DehaloParameters { enabled: true, bypass: true, ..Default::default() },
+            deband: DebandParameters { enabled: true, ..Default::default() },
+            ..RestorationPipeline::default()
+        };
+        pipeline.dehalo.dark_str = 0.7;
+
+        assert!(!pipeline.is_pass_enabled(PassType::Dehalo));
+        assert!(!pipeline.enabled_passes().contains(&PassType::Dehalo));
+        assert!(pipeline.enabled_passes().contains(&PassType::Deband));
+
+        // Bypass survives a round trip with its parameters
+        let json = serde_json::to_string(&pipeline).unwrap();
+        let restored: RestorationPipeline = serde_json::from_str(&json).unwrap();
+        assert!(restored.dehalo.enabled && restored.dehalo.bypass);
+        assert_eq!(restored.dehalo.dark_str, 0.7);
+
+        pipeline.apply_bypass();
+        assert!(!pipeline.dehalo.enabled);
+        assert!(pipeline.deband.enabled);
+    }
+
+    #[test]
+    fn test_scene_sensitivity_sets_thresholds() {
+        let mut pipeline = RestorationPipeline {
+            scene_sensitivity: Some(SceneSensitivity::High),
+            ..RestorationPipeline::default()
+        };
+        pipeline.apply_scene_sensitivity();
+        assert_eq!((pipeline.deinterlace.th_scd1, pipeline.deinterlace.th_scd2), (120, 70));
+        assert_eq!(pipeline.noise_reduction.sm_degrain_th_scd1, Some(300));
+        assert_eq!(pipeline.noise_reduction.sm_degrain_th_scd2, Some(100));
+
+        // Medium reproduces the filter defaults
+        let defaults = QTGMCParameters::default();
+        assert_eq!(SceneSensitivity::Medium.qtgmc_thresholds(), (defaults.th_scd1, defaults.th_scd2));
+
+        // Unset leaves explicit thresholds alone
+        let mut pipeline = RestorationPipeline::default();
+        pipeline.deinterlace.th_scd1 = 200;
+        pipeline.apply_scene_sensitivity();
+        assert_eq!(pipeline.deinterlace.th_scd1, 200);
+        assert_eq!(pipeline.noise_reduction.sm_degrain_th_scd1, None);
+    }
+
+    #[test]
+    fn test_lint_pass_combinations() {
+        let mut pipeline = RestorationPipeline::default();
+        pipeline.deinterlace.enabled = true;
+        pipeline.noise_reduction.enabled = false;
+        pipeline.sharpen.enabled = true;
+        let advisories = pipeline.lint();
+        assert_eq!(advisories.len(), 1);
+        assert_eq!(advisories[0].pass, Some(PassType::Sharpen));
+        assert!(advisories[0].to_string().starts_with("Sharpen: sharpening without noise reduction"));
+
+        pipeline.noise_reduction.enabled = true;
+        assert!(pipeline.lint().is_empty());
+
+        pipeline.noise_reduction.method = NoiseReductionMethod::QtgmcBuiltin;
+        pipeline.deinterlace.bypass = true;
+        pipeline.apply_bypass();
+        let advisories = pipeline.lint();
+        assert_eq!(advisories.len(), 1);
+        assert_eq!(advisories[0].pass, Some(PassType::NoiseReduction));
+
+        pipeline.noise_reduction.enabled = false;
+        pipeline.sharpen.enabled = false;
+        pipeline.crop_resize.enabled = true;
+        pipeline.crop_resize.resize_enabled = true;
+        pipeline.crop_resize.maintain_aspect = false;
+        pipeline.crop_resize.target_width = Some(960);
+        let advisories = pipeline.lint();
+        assert_eq!(advisories.len(), 1);
+        assert_eq!(advisories[0].pass, Some(PassType::CropResize));
+    }
+
+    #[test]
+    fn test_required_plugins() {
+        let mut pipeline = RestorationPipeline::default();
+        pipeline.deinterlace.enabled = true;
+        assert_eq!(pipeline.required_plugins(), vec!["bs", "mv", "rgvs", "znedi3"]);
+
+        pipeline.deinterlace.opencl = true;
+        pipeline.deband.enabled = true;
+        pipeline.sharpen.enabled = true;
+        pipeline.sharpen.method = SharpenMethod::CAS;
+        assert_eq!(pipeline.required_plugins(), vec!["bs", "cas", "mv", "neo_f3kdb", "nnedi3cl", "rgvs"]);
+
+        pipeline.deinterlace.enabled = false;
+        pipeline.deband.enabled = false;
+        pipeline.sharpen.enabled = false;
+        assert_eq!(pipeline.required_plugins(), vec!["bs"]);
+
+        pipeline.grain.enabled = true;
+        pipeline.grain.adaptive = true;
+        assert_eq!(pipeline.required_plugins(), vec!["bs", "grain"], "The adaptive mask only needs std");
+
+        pipeline.grain.enabled = false;
+        pipeline.crop_resize.enabled = true;
+        pipeline.crop_resize.resize_enabled = true;
+        pipeline.crop_resize.use_integer_upscale = true;
+        assert_eq!(pipeline.required_plugins(), vec!["bs", "znedi3"]);
+        pipeline.crop_resize.opencl = true;
+        assert_eq!(pipeline.required_plugins(), vec!["bs", "nnedi3cl"]);
+    }
+
+    #[test]
+    fn test_grain_runs_after_cleanup_before_resize() {
+        let mut pipeline = RestorationPipeline::default();
+        pipeline.noise_reduction.enabled = true;
+        pipeline.color_correction.enabled = true;
+        pipeline.grain.enabled = true;
+        pipeline.crop_resize.enabled = true;
+        pipeline.crop_resize.resize_enabled = true;
+        assert_eq!(
+            pipeline.enabled_passes(),
+            vec![
+                PassType::Deinterlace,
+                PassType::NoiseReduction,
+                PassType::ColorCorrection,
+                PassType::Grain,
+                PassType::CropResize,
+            ]
+        );
+
+        pipeline.grain.bypass = true;
+        pipeline.apply_bypass();
+        assert!(!pipeline.enabled_passes().contains(&PassType::Grain));
+    }
+
+    #[test]
+    fn test_describe() {
+        let mut pipeline = RestorationPipeline::default();
+        pipeline.deinterlace.enabled = false;
+        assert_eq!(pipeline.describe(), "no restoration passes");
+
+        pipeline.deinterlace.enabled = true;
+        pipeline.noise_reduction.enabled = true;
+        pipeline.noise_reduction.sm_degrain_tr = 2;
+        pipeline.sharpen.enabled = true;
+        pipeline.sharpen.strength = 100;
+        pipeline.crop_resize.enabled = true;
+        pipeline.crop_resize.resize_enabled = true;
+        assert_eq!(
+            pipeline.describe(),
+            "QTGMC Slower; SMDegrain tr=2; LSFmod strength=100; Crop / Resize"
+        );
+    }
 
     #[test]
     fn test_default_pipeline() {
@@ -233,6 +666,72 @@ mod tests {
         assert!(!passes.contains(&PassType::ChromaFixes));
     }
 
+    #[test]
+    fn test_enabled_passes_post_crop_runs_last() {
+        let mut pipeline = RestorationPipeline::default();
+        pipeline.crop_resize.enabled = true;
+        pipeline.crop_resize.crop_enabled = true;
+        pipeline.crop_resize.crop_stage = CropStage::PostProcess;
+
+        let passes = pipeline.enabled_passes();
+        assert_eq!(passes.first(), Some(&PassType::Deinterlace));
+        assert_eq!(passes.last(), Some(&PassType::CropResize));
+    }
+
+    #[test]
+    fn test_enabled_passes_sharpen_after_resize() {
+        let mut pipeline = RestorationPipeline::default();
+        pipeline.sharpen.enabled = true;
+        pipeline.crop_resize.enabled = true;
+        pipeline.crop_resize.resize_enabled = true;
+
+        let passes = pipeline.enabled_passes();
+        assert_eq!(passes.last(), Some(&PassType::CropResize));
+
+        pipeline.sharpen.sharpen_after_resize = true;
+        let passes = pipeline.enabled_passes();
+        assert_eq!(passes.last(), Some(&PassType::Sharpen));
+        assert_eq!(passes.iter().filter(|p| **p == PassType::Sharpen).count(), 1);
+    }
+
+    #[test]
+    fn test_resolve_presets_expands_named_preset() {
+        let mut pipeline = RestorationPipeline::default();
+        pipeline.noise_reduction.enabled = true;
+        pipeline.noise_reduction.preset = NoiseReductionPreset::Heavy;
+        pipeline.color_correction.preset = ColorCorrectionPreset::Custom;
+        pipeline.color_correction.contrast = 1.3;
+
+        pipeline.resolve_presets();
+
+        assert!(pipeline.noise_reduction.enabled);
+        assert_eq!(pipeline.noise_reduction.sm_degrain_tr, 3);
+        assert_eq!(pipeline.noise_reduction.sm_degrain_th_sad, 500);
+        assert_eq!(pipeline.color_correction.contrast, 1.3);
+    }
+
+    #[test]
+    fn test_resolve_presets_keeps_bypass_and_user_fields() {
+        let mut pipeline = RestorationPipeline::default();
+        pipeline.noise_reduction.enabled = true;
+        pipeline.noise_reduction.bypass = true;
+        pipeline.noise_reduction.preset = NoiseReductionPreset::Light;
+        pipeline.noise_reduction.sm_degrain_prefilter = 3;
+        pipeline.color_correction.enabled = true;
+        pipeline.color_correction.bypass = true;
+        pipeline.color_correction.preset = ColorCorrectionPreset::BroadcastSafe;
+        pipeline.color_correction.brightness = 4.0;
+
+        pipeline.resolve_presets();
+
+        assert!(pipeline.noise_reduction.bypass, "Bypass survives preset expansion");
+        assert_eq!(pipeline.noise_reduction.sm_degrain_tr, 1, "Preset values are applied");
+        assert_eq!(pipeline.noise_reduction.sm_degrain_prefilter, 3, "Fields the preset leaves alone are kept");
+        assert!(pipeline.color_correction.bypass);
+        assert!(pipeline.color_correction.coring);
+        assert_eq!(pipeline.color_correction.brightness, 4.0);
+    }
+
     #[test]
     fn test_serialization() {
         let pipeline = RestorationPipeline::default();
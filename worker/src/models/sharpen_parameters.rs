@@ -27,6 +27,10 @@ pub struct SharpenParameters {
     #[serde(default)]
     pub enabled: bool,
 
+    /// Temporarily skip this pass while keeping it enabled and configured.
+    #[serde(default)]
+    pub bypass: bool,
+
     /// Sharpening method to use.
     #[serde(default)]
     pub method: SharpenMethod,
@@ -54,6 +58,21 @@ pub struct SharpenParameters {
     /// CAS sharpening amount (0.0-1.0).
     #[serde(default = "default_cas_sharpness")]
     pub cas_sharpness: f64,
+
+    /// Run CAS on the GPU when the installed plugin supports OpenCL.
+    /// Falls back to the CPU implementation otherwise.
+    #[serde(default)]
+    pub opencl: bool,
+
+    /// OpenCL device index for CAS (only used when `opencl` is set and the
+    /// plugin accepts a device).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub opencl_device: Option<i32>,
+
+    /// Sharpen after resize instead of before it.
+    /// Useful after an upscale; sharpening before a downscale amplifies aliasing.
+    #[serde(default)]
+    pub sharpen_after_resize: bool,
 }
 
 fn default_strength() -> i32 { 100 }
@@ -65,12 +84,16 @@ impl Default for SharpenParameters {
     fn default() -> Self {
         Self {
             enabled: false,
+            bypass: false,
             method: SharpenMethod::default(),
             strength: default_strength(),
             overshoot: default_overshoot(),
             undershoot: default_undershoot(),
             soft_edge: 0,
             cas_sharpness: default_cas_sharpness(),
+            opencl: false,
+            opencl_device: None,
+            sharpen_after_resize: false,
         }
     }
 }
@@ -0,0 +1,95 @@
+//! Conversion between `HH:MM:SS.mmm` timecodes and frame numbers.
+
+use anyhow::{bail, Context, Result};
+
+/// Parse a timecode (`HH:MM:SS.mmm`, `MM:SS.mmm` or `SS.mmm`) into seconds.
+pub fn parse_timecode(text: &str) -> Result<f64> {
+    let parts: Vec<&str> = text.trim().split(':').collect();
+    if parts.len() > 3 || parts.iter().any(|p| p.is_empty()) {
+        bail!("Invalid timecode '{}': expected HH:MM:SS.mmm", text);
+    }
+
+    let (seconds, units) = parts.split_last().expect("split yields at least one part");
+    let seconds: f64 = seconds.parse()
+        .with_context(|| format!("Invalid seconds in timecode '{}'", text))?;
+    if !seconds.is_finite() || seconds < 0.0 || (!units.is_empty() && seconds >= 60.0) {
+        bail!("Invalid seconds in timecode '{}'", text);
+    }
+
+    let mut total = 0.0;
+    for (i, unit) in units.iter().enumerate() {
+        let value: u32 = unit.parse()
+            .with_context(|| format!("Invalid timecode '{}': expected HH:MM:SS.mmm", text))?;
+        // Minutes after an hour field are limited to 0-59
+        if i > 0 && value >= 60 {
+            bail!("Invalid minutes in timecode '{}'", text);
+        }
+        total = total * 60.0 + value as f64;
+    }
+    Ok(total * 60.0 + seconds)
+}
+
+/// Frame shown at `seconds` into a clip at `fps`.
+///
+/// Timecodes are usually rounded to the millisecond, so half a millisecond
+/// is allowed for a timecode printed slightly before the frame starts.
+pub fn seconds_to_frame(seconds: f64, fps: f64) -> i32 {
+    ((seconds + 0.0005) * fps).floor() as i32
+}
+
+/// Start time of `frame` in a clip at `fps`, in seconds.
+pub fn frame_to_seconds(frame: i32, fps: f64) -> f64 {
+    frame as f64 / fps
+}
+
+/// Format `seconds` as `HH:MM:SS.mmm`, rounded to the millisecond.
+pub fn format_timecode(seconds: f64) -> String {
+    let millis = (seconds.max(0.0) * 1000.0).round() as u64;
+    format!(
+        "{:02}:{:02}:{:02}.{:03}",
+        millis / 3_600_000,
+        millis / 60_000 % 60,
+        millis / 1000 % 60,
+        millis % 1000
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_timecode() {
+        assert_eq!(parse_timecode("00:00:00.000").unwrap(), 0.0);
+        assert!((parse_timecode("01:02:03.450").unwrap() - 3723.45).abs() < 1e-9);
+        assert!((parse_timecode("02:03.5").unwrap() - 123.5).abs() < 1e-9);
+        assert_eq!(parse_timecode("42").unwrap(), 42.0);
+        assert_eq!(parse_timecode("90:00:00").unwrap(), 324_000.0);
+
+        for invalid in ["", "1:2:3:4", "00:60:00", "00:00:60.000", "00::01", "ab:00:00", "-1", "00:00:-1"] {
+            assert!(parse_timecode(invalid).is_err(), "{:?} should be rejected", invalid);
+        }
+    }
+
+    #[test]
+    fn test_timecode_frame_conversion() {
+        // 25 fps: frames fall on exact 40 ms boundaries
+        assert_eq!(seconds_to_frame(parse_timecode("00:00:01.000").unwrap(), 25.0), 25);
+        assert_eq!(seconds_to_frame(parse_timecode("00:00:01.039").unwrap(), 25.0), 25);
+        assert_eq!(seconds_to_frame(parse_timecode("00:01:00.040").unwrap(), 25.0), 1501);
+
+        // 29.97 fps: frame 30 starts at 1.001 s, frame 29 at 0.96763 s
+        assert_eq!(seconds_to_frame(parse_timecode("00:00:01.001").unwrap(), 29.97), 30);
+        assert_eq!(seconds_to_frame(parse_timecode("00:00:00.968").unwrap(), 29.97), 29);
+        assert_eq!(seconds_to_frame(parse_timecode("00:10:00.000").unwrap(), 29.97), 17982);
+
+        // Printing a frame's start and parsing it back lands on the same frame
+        for fps in [25.0, 29.97, 59.94, 23.976] {
+            for frame in [0, 1, 29, 1799, 107_892] {
+                let timecode = format_timecode(frame_to_seconds(frame, fps));
+                assert_eq!(seconds_to_frame(parse_timecode(&timecode).unwrap(), fps), frame, "{} at {}", timecode, fps);
+            }
+        }
+        assert_eq!(format_timecode(3723.45), "01:02:03.450");
+    }
+}
@@ -1,9 +1,12 @@
 //! Video job configuration and encoding settings.
 
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-use super::{QTGMCParameters, RestorationPipeline};
+use super::{Advisory, ClipInfo, FrameRate, PassType, QTGMCParameters, QTGMCPreset, RestorationPipeline};
 
 /// Represents a complete video processing job.
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -15,9 +18,16 @@ pub struct VideoJob {
     /// Input video file path
     pub input_path: String,
 
-    /// Output video file path
+    /// Output video file path. With `output_template` set this may instead be
+    /// a directory, or empty for the input's directory.
+    #[serde(default)]
     pub output_path: String,
 
+    /// Output file name template, e.g. `{stem}_qtgmc_{preset}.{ext}`, used when
+    /// `output_path` is a directory or empty (see `render_output_path`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_template: Option<String>,
+
     /// Legacy QTGMC deinterlacing parameters (for backwards compatibility)
     pub qtgmc_parameters: QTGMCParameters,
 
@@ -36,9 +46,10 @@ pub struct VideoJob {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub total_frames: Option<i32>,
 
-    /// Input video frame rate
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub input_frame_rate: Option<f64>,
+    /// Input video frame rate, as a number (`29.97`) or an exact `"num/den"`
+    /// string (`"30000/1001"`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub input_frame_rate: Option<FrameRate>,
 
     /// Start frame for partial export (inclusive). None means start from beginning.
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -47,16 +58,380 @@ pub struct VideoJob {
     /// End frame for partial export (inclusive). None means export to end.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub end_frame: Option<i32>,
+
+    /// Decoder threads for the source filter. None uses the source filter default.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source_threads: Option<i32>,
+
+    /// Frames the source filter decodes before a seek target (BestSource `seekpreroll`).
+    /// Raising it helps sources that produce corrupt frames after seeking.
+    /// None uses the source filter default.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source_seek_preroll: Option<i32>,
+
+    /// VapourSynth frame cache limit in MB. Larger caches keep big temporal-radius
+    /// pipelines from re-rendering frames (higher throughput) at the cost of RAM;
+    /// too small a cache thrashes. None uses the worker default of 1024 MB.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vs_max_cache_mb: Option<i32>,
+
+    /// Create the output file's directory if it doesn't exist
+    #[serde(default = "default_true")]
+    pub create_output_dirs: bool,
+
+    /// After encoding, measure PSNR/SSIM of the output against the processed
+    /// (pre-encode) video to quantify encoder quality loss. Runs the script again.
+    #[serde(default)]
+    pub measure_quality: bool,
+
+    /// Input range assumed when converting preview frames to full-range RGB PNG
+    #[serde(default)]
+    pub preview_range: PreviewRange,
+
+    /// Warn when deinterlacing is enabled on input detected as progressive,
+    /// where QTGMC only softens the picture
+    #[serde(default = "default_true")]
+    pub warn_unnecessary_deinterlace: bool,
+
+    /// Fail before encoding when the output volume has less free space than
+    /// the estimated output size
+    #[serde(default)]
+    pub check_disk_space: bool,
+
+    /// Process only the source keyframes, for a sparse proxy that is much
+    /// faster to render. Temporal filters see the keyframes as consecutive
+    /// frames, and the proxy has no audio.
+    #[serde(default)]
+    pub keyframes_only: bool,
+
+    /// Source keyframe indices used by `keyframes_only`; probed when not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keyframes: Option<Vec<i32>>,
+
+    /// Shell command run after the job succeeds, with `{output}` replaced by
+    /// the quoted output path. Only runs when the worker is started with
+    /// `--allow-hooks`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub on_complete_command: Option<String>,
 }
 
+/// VapourSynth frame cache size used when the job doesn't set one.
+pub const DEFAULT_VS_MAX_CACHE_MB: i32 = 1024;
+
+/// Warning logged for jobs that only supply legacy `qtgmcParameters`.
+pub const LEGACY_PARAMETERS_WARNING: &str = "Job uses legacy qtgmcParameters without a restorationPipeline; \
+     migrate to restorationPipeline (qtgmcParameters support may be removed)";
+
 impl VideoJob {
     /// Get the effective restoration pipeline.
     /// Uses restoration_pipeline if set, otherwise creates one from legacy qtgmc_parameters.
+    /// The deinterlace film grain preset is expanded into its noise settings,
+    /// and bypassed passes are turned off.
     pub fn effective_pipeline(&self) -> RestorationPipeline {
-        self.restoration_pipeline
+        let mut pipeline = self.configured_pipeline();
+        pipeline.apply_bypass();
+        pipeline
+    }
+
+    /// The pipeline as configured, with bypassed passes still enabled.
+    fn configured_pipeline(&self) -> RestorationPipeline {
+        let mut pipeline = self.restoration_pipeline
             .clone()
-            .unwrap_or_else(|| RestorationPipeline::from_legacy(&self.qtgmc_parameters))
+            .unwrap_or_else(|| RestorationPipeline::from_legacy(&self.qtgmc_parameters));
+        pipeline.deinterlace.apply_film_grain_preset();
+        pipeline.crop_resize.resolve_preset();
+        pipeline.apply_scene_sensitivity();
+        pipeline
+    }
+
+    /// Frame rate of the processed video: the input rate, doubled by
+    /// double-rate deinterlacing. None when the input rate is unknown.
+    pub fn output_frame_rate(&self) -> Option<FrameRate> {
+        let rate = self.input_frame_rate?;
+        let params = &self.qtgmc_parameters;
+        let double_rate = self.effective_pipeline().deinterlace.enabled
+            && params.input_type == 0
+            && params.fps_divisor == 1;
+        Some(if double_rate { rate.scaled(2) } else { rate })
+    }
+
+    /// Check that `output_fps` only re-tags the processed frame rate rather
+    /// than retiming the video, which would drift out of sync with the audio.
+    ///
+    /// With a known frame count the retimed duration may differ by less than
+    /// one frame; otherwise the rates must match within 0.1%.
+    pub fn validate_output_fps(&self) -> Result<()> {
+        let Some((num, den)) = self.encoding_settings.output_fps else {
+            return Ok(());
+        };
+        if num <= 0 || den <= 0 {
+            bail!("Output fps must be positive, got {}/{}", num, den);
+        }
+        let (Some(input), Some(output)) = (self.input_frame_rate, self.output_frame_rate()) else {
+            return Ok(());
+        };
+        let actual = output.as_f64();
+        let forced = num as f64 / den as f64;
+
+        // Output frames: the exported source range, scaled like the rate
+        let rate_multiplier = actual / input.as_f64();
+        let frames = self.total_frames.map(|total| {
+            let start = self.start_frame.unwrap_or(0);
+            let end = self.end_frame.map_or(total, |end| end + 1);
+            (end - start).max(0) as f64 * rate_multiplier
+        });
+        let compatible = match frames {
+            Some(frames) => (frames / actual - frames / forced).abs() < 1.0 / actual,
+            None => ((forced - actual) / actual).abs() < 0.001,
+        };
+        if !compatible {
+            bail!(
+                "Output fps {}/{} ({:.3}) doesn't match the processed frame rate ({:.3}); \
+                 the video would drift out of sync with the audio",
+                num, den, forced, actual
+            );
+        }
+        Ok(())
+    }
+
+    /// Warning for deinterlacing input whose field order was detected as progressive.
+    ///
+    /// QTGMC's progressive input modes (`input_type` 1-3) are meant for such
+    /// sources and don't warn.
+    pub fn unnecessary_deinterlace_warning(&self, detected: FieldOrder) -> Option<&'static str> {
+        let needless = self.warn_unnecessary_deinterlace
+            && detected == FieldOrder::Progressive
+            && self.effective_pipeline().deinterlace.enabled
+            && self.qtgmc_parameters.input_type == 0;
+        needless.then_some(
+            "Deinterlacing is enabled but the input is progressive; QTGMC will only soften it. \
+             Disable deinterlacing, or use a progressive InputType for repair",
+        )
+    }
+
+    /// Whether the pipeline is built from legacy `qtgmc_parameters`
+    /// (no `restoration_pipeline` was supplied).
+    pub fn uses_legacy_parameters(&self) -> bool {
+        self.restoration_pipeline.is_none()
+    }
+
+    /// Deprecation warning to report before resolving the job, if any.
+    /// Resolving fills in `restoration_pipeline`, so check the job as loaded.
+    pub fn legacy_warning(&self) -> Option<&'static str> {
+        self.uses_legacy_parameters().then_some(LEGACY_PARAMETERS_WARNING)
+    }
+
+    /// Copy of the job with `total_frames` and `input_frame_rate` filled in
+    /// from probed clip metadata where the job didn't set them.
+    pub fn with_clip_info(&self, info: &ClipInfo) -> VideoJob {
+        let mut job = self.clone();
+        if job.total_frames.is_none() && info.frames > 0 {
+            job.total_frames = Some(info.frames);
+        }
+        if job.input_frame_rate.is_none() {
+            job.input_frame_rate = info.frame_rate();
+        }
+        job
     }
+
+    /// Check the settings that depend on the source frame size (the crop
+    /// and resize pass) against the probed `info`.
+    pub fn validate_for_clip(&self, info: &ClipInfo) -> Result<()> {
+        self.effective_pipeline().crop_resize.validate(Some((info.width, info.height)))
+    }
+
+    /// Suggestions for settings that run but have no or an adverse effect:
+    /// the pipeline's [`RestorationPipeline::lint`] plus encoding rules.
+    pub fn lint(&self) -> Vec<Advisory> {
+        let pipeline = self.effective_pipeline();
+        let mut advisories = pipeline.lint();
+        let settings = &self.encoding_settings;
+        let codec = settings.codec;
+
+        let takes_crf = codec.default_quality().is_some();
+        let ignored: Vec<&str> = [
+            settings.quality.filter(|_| !takes_crf).map(|_| "quality (CRF)"),
+            settings.max_bitrate_kbps.filter(|_| !takes_crf).map(|_| "maxBitrateKbps"),
+            settings.prores_qscale.filter(|_| !codec.is_prores()).map(|_| "proresQscale"),
+            settings.prores_bits_per_mb.filter(|_| !codec.is_prores()).map(|_| "proresBitsPerMb"),
+        ].into_iter().flatten().collect();
+        if !ignored.is_empty() {
+            advisories.push(Advisory {
+                pass: None,
+                message: format!("{} has no effect with {}", ignored.join(" and "), codec.display_name()),
+            });
+        }
+
+        let container = Path::new(&self.output_path)
+            .extension()
+            .and_then(|ext| ContainerFormat::from_extension(&ext.to_string_lossy()));
+        if let Some(container) = container.filter(|&container| !codec.supports_container(container)) {
+            advisories.push(Advisory {
+                pass: None,
+                message: format!("{} can't be stored in {}; ffmpeg will refuse to mux it", codec.display_name(), container.display_name()),
+            });
+        }
+
+        let deband = &pipeline.deband;
+        if pipeline.is_pass_enabled(PassType::Deband) && deband.output_depth < codec.max_bit_depth() {
+            advisories.push(Advisory {
+                pass: Some(PassType::Deband),
+                message: format!(
+                    "output is {}-bit but {} takes {}-bit; set outputDepth to {} so gradients are only dithered once, at the end",
+                    deband.output_depth, codec.display_name(), codec.max_bit_depth(), codec.max_bit_depth(),
+                ),
+            });
+        }
+        advisories
+    }
+
+    /// Rough size in bytes of the encoded output for a `source` frame size, or
+    /// None when the frame count is unknown.
+    ///
+    /// Counts the frames written (trimmed, doubled by double-rate
+    /// deinterlacing) at the output frame size, times the codec's typical
+    /// bytes per pixel. Audio is not counted.
+    pub fn estimated_output_bytes(&self, source: (i32, i32)) -> Option<u64> {
+        let total = self.total_frames.filter(|&frames| frames > 0)?;
+        let end = self.end_frame.map_or(total, |end| (end + 1).min(total));
+        let mut frames = (end - self.start_frame.unwrap_or(0)).max(0) as u64;
+
+        let pipeline = self.effective_pipeline();
+        let params = &pipeline.deinterlace;
+        if params.enabled && params.input_type == 0 && params.fps_divisor == 1 {
+            frames *= 2;
+        }
+        let (width, height) = pipeline.crop_resize.output_dimensions(source);
+        let pixels = width.max(0) as u64 * height.max(0) as u64;
+        Some((frames as f64 * pixels as f64 * self.encoding_settings.codec.bytes_per_pixel()) as u64)
+    }
+
+    /// Copy of the job with `output_path` rendered from `output_template` when
+    /// the path is a directory or empty. `source` is the input frame size, for
+    /// the `{width}` and `{height}` tokens.
+    pub fn with_rendered_output_path(&self, source: Option<(i32, i32)>) -> Result<VideoJob> {
+        let output_dir = self.output_path.is_empty()
+            || self.output_path.ends_with(['/', '\\'])
+            || Path::new(&self.output_path).is_dir();
+        let Some(template) = self.output_template.as_deref().filter(|_| output_dir) else {
+            if self.output_path.is_empty() {
+                bail!("No output path: set outputPath or outputTemplate");
+            }
+            return Ok(self.clone());
+        };
+
+        let dir = if self.output_path.is_empty() {
+            Path::new(&self.input_path).parent().unwrap_or(Path::new(""))
+        } else {
+            Path::new(&self.output_path)
+        };
+        let dimensions = source.map(|size| self.effective_pipeline().crop_resize.output_dimensions(size));
+        let file_name = render_output_path(template, self, dimensions)?;
+
+        let mut job = self.clone();
+        job.output_path = dir.join(file_name).to_string_lossy().to_string();
+        Ok(job)
+    }
+
+    /// Copy of the job with OpenCL acceleration turned off.
+    pub fn without_opencl(&self) -> VideoJob {
+        let mut job = self.clone();
+        job.qtgmc_parameters.opencl = false;
+        if let Some(pipeline) = job.restoration_pipeline.as_mut() {
+            pipeline.deinterlace.opencl = false;
+            pipeline.sharpen.opencl = false;
+            pipeline.crop_resize.opencl = false;
+        }
+        job
+    }
+
+    /// Copy of the job using QTGMC `preset`.
+    pub fn with_qtgmc_preset(&self, preset: QTGMCPreset) -> VideoJob {
+        let mut job = self.clone();
+        job.qtgmc_parameters.preset = preset;
+        if let Some(pipeline) = job.restoration_pipeline.as_mut() {
+            pipeline.deinterlace.preset = preset;
+        }
+        job
+    }
+
+    /// Resolve the job into the exact configuration the worker acts on.
+    ///
+    /// Migrates legacy `qtgmc_parameters` into a full restoration pipeline,
+    /// expands named pass presets, and defaults QTGMC's TFF from the
+    /// detected field order when it was not set explicitly.
+    pub fn resolved(&self) -> VideoJob {
+        let mut job = self.clone();
+        // Keep bypassed passes enabled so the resolved job can be toggled back
+        let mut pipeline = job.configured_pipeline();
+        pipeline.resolve_presets();
+        pipeline.apply_scene_sensitivity();
+        job.qtgmc_parameters.apply_film_grain_preset();
+        if let Some(sensitivity) = pipeline.scene_sensitivity {
+            sensitivity.apply_to_qtgmc(&mut job.qtgmc_parameters);
+        }
+
+        if let Some(tff) = job.detected_field_order.and_then(|order| order.tff_value()) {
+            if job.qtgmc_parameters.tff.is_none() {
+                job.qtgmc_parameters.tff = Some(tff);
+            }
+            if pipeline.deinterlace.tff.is_none() {
+                pipeline.deinterlace.tff = Some(tff);
+            }
+        }
+
+        job.restoration_pipeline = Some(pipeline);
+        job
+    }
+}
+
+/// Expand the tokens of an output file name template for `job`.
+///
+/// Tokens: `{stem}` (input file name without extension), `{ext}` (container
+/// extension), `{codec}`, `{preset}` (custom QTGMC preset name, else the
+/// built-in preset) and `{width}`/`{height}` (output frame size, which must be
+/// known to use them). A name without an extension gets the container's.
+pub fn render_output_path(template: &str, job: &VideoJob, dimensions: Option<(i32, i32)>) -> Result<String> {
+    let settings = &job.encoding_settings;
+    let params = &job.qtgmc_parameters;
+    let dimension = |index: usize| {
+        dimensions
+            .map(|size| if index == 0 { size.0 } else { size.1 }.to_string())
+            .context("Output template uses {width}/{height}, but the frame size is unknown")
+    };
+
+    let mut name = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        name.push_str(&rest[..start]);
+        let end = rest[start..].find('}')
+            .with_context(|| format!("Unclosed token in output template: {}", template))?;
+        let token = &rest[start + 1..start + end];
+        let value = match token {
+            "stem" => Path::new(&job.input_path)
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            "ext" => settings.container.extension().to_string(),
+            "codec" => settings.codec.file_tag().to_string(),
+            "preset" => params.preset_name.clone()
+                .unwrap_or_else(|| params.preset.as_str().to_lowercase().replace(' ', "_")),
+            "width" => dimension(0)?,
+            "height" => dimension(1)?,
+            _ => bail!("Unknown token {{{}}} in output template: {}", token, template),
+        };
+        name.push_str(&value);
+        rest = &rest[start + end + 1..];
+    }
+    name.push_str(rest);
+
+    if name.is_empty() {
+        bail!("Output template renders an empty file name: {}", template);
+    }
+    if Path::new(&name).extension().is_none() {
+        name = format!("{}.{}", name, settings.container.extension());
+    }
+    Ok(name)
 }
 
 /// Video encoding settings for FFmpeg output.
@@ -71,9 +446,36 @@ pub struct EncodingSettings {
     #[serde(default = "default_encoder_preset")]
     pub encoder_preset: String,
 
-    /// Quality setting (CRF for H.264/H.265, quality level for ProRes)
-    #[serde(default = "default_quality")]
-    pub quality: i32,
+    /// Quality setting (CRF for H.264/H.265). None uses the codec's default;
+    /// ignored for lossless FFV1 and ProRes (which uses its profile instead).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quality: Option<i32>,
+
+    /// Maximum video bitrate in kbps (VBV ceiling, combined with CRF for constrained quality).
+    /// Ignored for codecs that don't take a CRF.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_bitrate_kbps: Option<i32>,
+
+    /// VBV buffer size in kbps. Defaults to twice the max bitrate when only that is set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bufsize_kbps: Option<i32>,
+
+    /// ProRes quantizer (`-qscale:v`, lower is better quality, 2-31) for
+    /// constant quality within the profile. Ignored for other codecs.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prores_qscale: Option<i32>,
+
+    /// ProRes bits per macroblock (`-bits_per_mb`), overriding the profile's
+    /// target bitrate. Ignored for other codecs.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prores_bits_per_mb: Option<i32>,
+
+    /// Chroma subsampling to deliver, e.g. 4:2:2 for a ProRes 422 master from
+    /// a 4:2:0 source. The script resamples the chroma before output (keeping
+    /// the bit depth) and ffmpeg encodes with the matching pixel format. None
+    /// keeps the codec's default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_chroma: Option<ChromaSubsampling>,
 
     /// Copy audio stream without re-encoding
     #[serde(default = "default_true")]
@@ -87,10 +489,60 @@ pub struct EncodingSettings {
     #[serde(default = "default_audio_bitrate")]
     pub audio_bitrate: i32,
 
+    /// Keep only audio streams tagged with this language (ISO 639-2, e.g. "eng").
+    /// None keeps every audio stream.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub audio_language: Option<String>,
+
+    /// Audio sample rate in Hz when transcoding (e.g. 48000); None keeps the
+    /// source rate. Ignored when copying audio.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub audio_sample_rate: Option<i32>,
+
+    /// Audio channel count when transcoding (e.g. 2 to downmix to stereo);
+    /// None keeps the source layout. Ignored when copying audio.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub audio_channels: Option<i32>,
+
+    /// Sample (pixel) aspect ratio to signal in the output, as (num, den),
+    /// e.g. (10, 11) for 4:3 NTSC DVD. None passes the source signalling through.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_sar: Option<(i32, i32)>,
+
+    /// Frame rate to tag the output with, as (num, den), e.g. (30000, 1001).
+    /// Must match the processed frame rate; it fixes the container tag for
+    /// editors that misread it rather than changing the speed. None keeps the
+    /// clip's own rate.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_fps: Option<(i32, i32)>,
+
+    /// ffmpeg log level. Messages at this level or above are forwarded as
+    /// worker logs; progress is read separately.
+    #[serde(default)]
+    pub ffmpeg_loglevel: FfmpegLogLevel,
+
     /// Additional FFmpeg arguments
     #[serde(default)]
     pub custom_ffmpeg_args: String,
 
+    /// Write a summary of the restoration pipeline into the output's
+    /// `comment` metadata, so the processing history travels with the file.
+    #[serde(default)]
+    pub embed_pipeline_metadata: bool,
+
+    /// Move the MP4/MOV index to the front of the file (`-movflags
+    /// +faststart`) so web players can start before the download finishes.
+    #[serde(default)]
+    pub faststart: bool,
+
+    /// How often encoding progress is reported.
+    #[serde(default)]
+    pub progress_mode: ProgressMode,
+
+    /// Logo or watermark image composited over the processed video by ffmpeg.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub overlay: Option<OverlaySpec>,
+
     /// Output container format
     #[serde(default)]
     pub container: ContainerFormat,
@@ -100,10 +552,6 @@ fn default_encoder_preset() -> String {
     "medium".to_string()
 }
 
-fn default_quality() -> i32 {
-    18
-}
-
 fn default_true() -> bool {
     true
 }
@@ -116,21 +564,240 @@ fn default_audio_bitrate() -> i32 {
     192
 }
 
+impl EncodingSettings {
+    /// Lossless FFV1 in Matroska, for archiving a capture.
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub fn for_archival() -> Self {
+        Self {
+            codec: VideoCodec::FFV1,
+            container: ContainerFormat::Mkv,
+            ..Self::default()
+        }
+    }
+
+    /// H.264 CRF 20 with AAC audio in a faststart MP4, for streaming and sharing.
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub fn for_web() -> Self {
+        Self {
+            codec: VideoCodec::H264,
+            quality: Some(20),
+            audio_copy: false,
+            faststart: true,
+            container: ContainerFormat::Mp4,
+            ..Self::default()
+        }
+    }
+
+    /// ProRes 422 in QuickTime MOV, for editing in an NLE.
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub fn for_editing() -> Self {
+        Self {
+            codec: VideoCodec::ProRes422,
+            container: ContainerFormat::Mov,
+            ..Self::default()
+        }
+    }
+
+    /// ffmpeg `-pix_fmt` to encode with: the codec default with its chroma
+    /// subsampling replaced by `output_chroma`. None keeps the pipe format.
+    pub fn pix_fmt(&self) -> Option<String> {
+        let default = self.codec.default_pix_fmt()?;
+        let Some(chroma) = self.output_chroma else {
+            return Some(default.to_string());
+        };
+        // Default formats are all `yuv` + three subsampling digits + depth suffix
+        Some(format!("yuv{}{}", chroma.pix_fmt_digits(), &default[6..]))
+    }
+
+    /// Check that the codec can carry `output_chroma`. The ProRes 422
+    /// profiles only take 4:2:2.
+    pub fn validate_output_chroma(&self) -> Result<()> {
+        match self.output_chroma {
+            Some(chroma) if self.codec.is_prores() && chroma != ChromaSubsampling::Yuv422 => bail!(
+                "{} only encodes 4:2:2 chroma, not {}",
+                self.codec.display_name(),
+                chroma.display_name()
+            ),
+            _ => Ok(()),
+        }
+    }
+
+    /// CRF to pass to the encoder: the explicit quality, or the codec default.
+    /// None for codecs that don't take a CRF.
+    pub fn effective_quality(&self) -> Option<i32> {
+        let default = self.codec.default_quality()?;
+        Some(self.quality.unwrap_or(default))
+    }
+
+    /// ffmpeg video quality arguments for the codec: the ProRes profile and
+    /// its optional quantizer/bitrate, or CRF and preset (with the VBV cap)
+    /// for x264/x265. Lossless FFV1 takes none.
+    pub fn video_quality_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+
+        if let Some(profile) = self.codec.prores_profile() {
+            args.extend(["-profile:v".to_string(), profile.to_string()]);
+            if let Some(qscale) = self.prores_qscale {
+                args.extend(["-qscale:v".to_string(), qscale.to_string()]);
+            }
+            if let Some(bits) = self.prores_bits_per_mb {
+                args.extend(["-bits_per_mb".to_string(), bits.to_string()]);
+            }
+        }
+
+        if let Some(quality) = self.effective_quality() {
+            args.extend(["-crf".to_string(), quality.to_string()]);
+            args.extend(["-preset".to_string(), self.encoder_preset.clone()]);
+
+            // Constrained quality: cap the bitrate with VBV
+            if let Some(max_bitrate) = self.max_bitrate_kbps {
+                let bufsize = self.bufsize_kbps.unwrap_or(max_bitrate * 2);
+                args.extend(["-maxrate".to_string(), format!("{}k", max_bitrate)]);
+                args.extend(["-bufsize".to_string(), format!("{}k", bufsize)]);
+            }
+        }
+        args
+    }
+
+    /// ffmpeg `-map` selecting audio from the original file (input 1).
+    ///
+    /// The trailing `?` makes the audio optional, so sources without audio
+    /// (or without a stream in the requested language) still encode.
+    pub fn audio_map(&self) -> String {
+        match self.audio_language.as_deref() {
+            Some(language) => format!("1:a:m:language:{}?", language),
+            None => "1:a?".to_string(),
+        }
+    }
+}
+
 impl Default for EncodingSettings {
     fn default() -> Self {
         Self {
             codec: VideoCodec::default(),
             encoder_preset: default_encoder_preset(),
-            quality: default_quality(),
+            quality: None,
+            max_bitrate_kbps: None,
+            bufsize_kbps: None,
+            prores_qscale: None,
+            prores_bits_per_mb: None,
+            output_chroma: None,
+            output_sar: None,
+            output_fps: None,
             audio_copy: true,
             audio_codec: default_audio_codec(),
             audio_bitrate: default_audio_bitrate(),
+            audio_language: None,
+            audio_sample_rate: None,
+            audio_channels: None,
+            ffmpeg_loglevel: FfmpegLogLevel::default(),
             custom_ffmpeg_args: String::new(),
+            embed_pipeline_metadata: false,
+            faststart: false,
+            progress_mode: ProgressMode::default(),
+            overlay: None,
             container: ContainerFormat::default(),
         }
     }
 }
 
+/// An image (e.g. a PNG logo with alpha) overlaid on every output frame.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OverlaySpec {
+    /// Image file to overlay.
+    pub path: String,
+
+    /// Horizontal offset in pixels from the left edge, or from the right edge
+    /// when negative.
+    #[serde(default)]
+    pub x: i32,
+
+    /// Vertical offset in pixels from the top edge, or from the bottom edge
+    /// when negative.
+    #[serde(default)]
+    pub y: i32,
+
+    /// Opacity (0.0-1.0, default 1.0), multiplied with the image's own alpha.
+    #[serde(default = "default_opacity")]
+    pub opacity: f64,
+}
+
+fn default_opacity() -> f64 {
+    1.0
+}
+
+impl OverlaySpec {
+    /// Check the path is set and the opacity is in range.
+    pub fn validate(&self) -> Result<()> {
+        if self.path.trim().is_empty() {
+            bail!("Overlay path is empty");
+        }
+        if !(0.0..=1.0).contains(&self.opacity) {
+            bail!("Overlay opacity must be between 0.0 and 1.0, got {}", self.opacity);
+        }
+        Ok(())
+    }
+
+    /// ffmpeg `-filter_complex` graph compositing ffmpeg input `input` (the
+    /// image) over the processed video (input 0), then tagging `sar` when
+    /// set. The result is labelled `[vout]`.
+    pub fn filtergraph(&self, input: usize, sar: Option<(i32, i32)>) -> String {
+        let position = |offset: i32, main: &str, overlay: &str| {
+            if offset < 0 {
+                format!("{}-{}-{}", main, overlay, -offset)
+            } else {
+                offset.to_string()
+            }
+        };
+        let mut graph = if self.opacity < 1.0 {
+            format!("[{}:v]format=rgba,colorchannelmixer=aa={}[logo];[0:v][logo]", input, self.opacity)
+        } else {
+            format!("[0:v][{}:v]", input)
+        };
+        // format=auto keeps the video's own subsampling and bit depth
+        graph.push_str(&format!(
+            "overlay=x={}:y={}:format=auto",
+            position(self.x, "main_w", "overlay_w"),
+            position(self.y, "main_h", "overlay_h"),
+        ));
+        if let Some((num, den)) = sar {
+            graph.push_str(&format!(",setsar={}/{}", num, den));
+        }
+        graph.push_str("[vout]");
+        graph
+    }
+}
+
+/// How often progress updates are sent while encoding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ProgressMode {
+    /// At most one update per this many milliseconds.
+    TimeMs(u64),
+    /// One update each time the encode passes a multiple of this many frames,
+    /// for short clips that would otherwise only report once or twice.
+    EveryNFrames(i32),
+}
+
+impl Default for ProgressMode {
+    fn default() -> Self {
+        ProgressMode::TimeMs(500)
+    }
+}
+
+impl ProgressMode {
+    /// Check that a frame interval is positive.
+    pub fn validate(&self) -> Result<()> {
+        if let ProgressMode::EveryNFrames(frames) = *self {
+            if frames < 1 {
+                bail!("Progress frame interval must be at least 1, got {}", frames);
+            }
+        }
+        Ok(())
+    }
+}
+
 /// Supported video codecs.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
 pub enum VideoCodec {
@@ -182,6 +849,77 @@ impl VideoCodec {
         }
     }
 
+    /// Default CRF for this codec, or None if the codec doesn't use one
+    /// (lossless FFV1, ProRes which is controlled by profile).
+    pub fn default_quality(&self) -> Option<i32> {
+        match self {
+            VideoCodec::H264 => Some(18),
+            // x265 CRF is more efficient; 20 is roughly equivalent to x264 CRF 18
+            VideoCodec::H265 => Some(20),
+            _ => None,
+        }
+    }
+
+    /// Highest bit depth the encoder accepts from the y4m pipe.
+    /// Scripts that output more are dithered down to this depth.
+    pub fn max_bit_depth(&self) -> i32 {
+        match self {
+            VideoCodec::H264 => 8,
+            VideoCodec::H265 => 10,
+            VideoCodec::FFV1 => 16,
+            VideoCodec::ProResProxy
+            | VideoCodec::ProResLT
+            | VideoCodec::ProRes422
+            | VideoCodec::ProResHQ => 10,
+        }
+    }
+
+    /// Typical encoded bytes per output pixel, for disk space estimates. Errs
+    /// on the high side (FFV1 at 10-bit, ProRes at SD frame rates).
+    pub fn bytes_per_pixel(&self) -> f64 {
+        match self {
+            VideoCodec::H264 => 0.05,
+            VideoCodec::H265 => 0.03,
+            VideoCodec::FFV1 => 1.0,
+            VideoCodec::ProResProxy => 0.1,
+            VideoCodec::ProResLT => 0.25,
+            VideoCodec::ProRes422 => 0.35,
+            VideoCodec::ProResHQ => 0.5,
+        }
+    }
+
+    /// Short codec name for file names.
+    pub fn file_tag(&self) -> &'static str {
+        match self {
+            VideoCodec::H264 => "h264",
+            VideoCodec::H265 => "h265",
+            VideoCodec::FFV1 => "ffv1",
+            VideoCodec::ProResProxy => "prores_proxy",
+            VideoCodec::ProResLT => "prores_lt",
+            VideoCodec::ProRes422 => "prores_422",
+            VideoCodec::ProResHQ => "prores_hq",
+        }
+    }
+
+    /// ffmpeg `-pix_fmt` to encode `source` material with, or None to keep the
+    /// format vspipe delivers.
+    ///
+    /// ProRes (all supported profiles are 4:2:2) always gets 10-bit 4:2:2, so
+    /// 4:2:2 sources keep their chroma. FFV1 is lossless and accepts any
+    /// subsampling, so it keeps the source format. H.264 and H.265 use 4:2:0 for
+    /// player compatibility, halving the chroma of 4:2:2 and 4:4:4 sources.
+    pub fn default_pix_fmt(&self) -> Option<&'static str> {
+        match self {
+            VideoCodec::H264 => Some("yuv420p"),
+            VideoCodec::H265 => Some("yuv420p10le"),
+            VideoCodec::FFV1 => None,
+            VideoCodec::ProResProxy
+            | VideoCodec::ProResLT
+            | VideoCodec::ProRes422
+            | VideoCodec::ProResHQ => Some("yuv422p10le"),
+        }
+    }
+
     /// Check if this is a ProRes codec.
     pub fn is_prores(&self) -> bool {
         self.prores_profile().is_some()
@@ -203,6 +941,16 @@ impl VideoCodec {
         }
     }
 
+    /// Whether ffmpeg can mux this codec into `container`.
+    pub fn supports_container(&self, container: ContainerFormat) -> bool {
+        match container {
+            ContainerFormat::Mkv => true,
+            ContainerFormat::Mp4 => matches!(self, VideoCodec::H264 | VideoCodec::H265),
+            ContainerFormat::Mov => !self.is_ffv1(),
+            ContainerFormat::Avi => matches!(self, VideoCodec::H264 | VideoCodec::FFV1),
+        }
+    }
+
     /// Human-readable display name.
     pub fn display_name(&self) -> &'static str {
         match self {
@@ -229,6 +977,17 @@ pub enum ContainerFormat {
 }
 
 impl ContainerFormat {
+    /// Container for a file extension (`mp4`, `MOV`), or None if unknown.
+    pub fn from_extension(extension: &str) -> Option<ContainerFormat> {
+        match extension.to_ascii_lowercase().as_str() {
+            "mp4" | "m4v" => Some(ContainerFormat::Mp4),
+            "mov" => Some(ContainerFormat::Mov),
+            "mkv" => Some(ContainerFormat::Mkv),
+            "avi" => Some(ContainerFormat::Avi),
+            _ => None,
+        }
+    }
+
     /// File extension for this container.
     pub fn extension(&self) -> &'static str {
         match self {
@@ -250,6 +1009,59 @@ impl ContainerFormat {
     }
 }
 
+/// Chroma subsampling of a YUV format, from least to most chroma detail.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum ChromaSubsampling {
+    #[serde(rename = "420")]
+    Yuv420,
+    #[serde(rename = "422")]
+    Yuv422,
+    #[serde(rename = "444")]
+    Yuv444,
+}
+
+impl ChromaSubsampling {
+    /// Subsampling of an ffmpeg pixel format name (`yuv422p10le`, `yuvj420p`),
+    /// or None for formats that aren't planar YUV 4:2:0/4:2:2/4:4:4.
+    pub fn from_pix_fmt(pix_fmt: &str) -> Option<ChromaSubsampling> {
+        let pix_fmt = pix_fmt.trim();
+        let rest = pix_fmt.strip_prefix("yuvj").or_else(|| pix_fmt.strip_prefix("yuv"))?;
+        match rest.get(..3)? {
+            "420" => Some(ChromaSubsampling::Yuv420),
+            "422" => Some(ChromaSubsampling::Yuv422),
+            "444" => Some(ChromaSubsampling::Yuv444),
+            _ => None,
+        }
+    }
+
+    /// Human-readable name ("4:2:2").
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            ChromaSubsampling::Yuv420 => "4:2:0",
+            ChromaSubsampling::Yuv422 => "4:2:2",
+            ChromaSubsampling::Yuv444 => "4:4:4",
+        }
+    }
+
+    /// The digits ffmpeg pixel formats use for it (`422` in `yuv422p10le`).
+    pub fn pix_fmt_digits(&self) -> &'static str {
+        match self {
+            ChromaSubsampling::Yuv420 => "420",
+            ChromaSubsampling::Yuv422 => "422",
+            ChromaSubsampling::Yuv444 => "444",
+        }
+    }
+
+    /// VapourSynth (`subsampling_w`, `subsampling_h`) as log2 factors.
+    pub fn vs_subsampling(&self) -> (i32, i32) {
+        match self {
+            ChromaSubsampling::Yuv420 => (1, 1),
+            ChromaSubsampling::Yuv422 => (1, 0),
+            ChromaSubsampling::Yuv444 => (0, 0),
+        }
+    }
+}
+
 /// Video field order.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
@@ -275,6 +1087,17 @@ impl FieldOrder {
         }
     }
 
+    /// Parse an ffprobe `field_order` stream value (tt/bb/tb/bt/progressive).
+    /// `tb`/`bt` (coded and displayed order differ) follow the coded order.
+    pub fn from_ffprobe(value: &str) -> FieldOrder {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "tt" | "tb" => FieldOrder::TopFieldFirst,
+            "bb" | "bt" => FieldOrder::BottomFieldFirst,
+            "progressive" => FieldOrder::Progressive,
+            _ => FieldOrder::Unknown,
+        }
+    }
+
     /// Convert to QTGMC TFF parameter value.
     pub fn tff_value(&self) -> Option<bool> {
         match self {
@@ -285,9 +1108,103 @@ impl FieldOrder {
     }
 }
 
+/// Input range of preview frames before conversion to full-range RGB.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum PreviewRange {
+    /// Limited range (16-235)
+    Tv,
+    /// Full range (0-255)
+    Pc,
+    /// Follow the source's tagged range, assuming limited when untagged
+    #[default]
+    Auto,
+}
+
+impl PreviewRange {
+    /// ffmpeg `scale` `in_range` value, given the source's ffprobe `color_range` tag.
+    pub fn in_range(&self, tagged: Option<&str>) -> &'static str {
+        match self {
+            PreviewRange::Tv => "tv",
+            PreviewRange::Pc => "pc",
+            PreviewRange::Auto => match tagged.map(str::trim) {
+                Some("pc") | Some("jpeg") => "pc",
+                _ => "tv",
+            },
+        }
+    }
+}
+
+/// ffmpeg `-loglevel` names, from least to most verbose.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum FfmpegLogLevel {
+    Quiet,
+    Panic,
+    Fatal,
+    #[default]
+    Error,
+    Warning,
+    Info,
+    Verbose,
+    Debug,
+    Trace,
+}
+
+impl FfmpegLogLevel {
+    /// Level name as passed to `-loglevel`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FfmpegLogLevel::Quiet => "quiet",
+            FfmpegLogLevel::Panic => "panic",
+            FfmpegLogLevel::Fatal => "fatal",
+            FfmpegLogLevel::Error => "error",
+            FfmpegLogLevel::Warning => "warning",
+            FfmpegLogLevel::Info => "info",
+            FfmpegLogLevel::Verbose => "verbose",
+            FfmpegLogLevel::Debug => "debug",
+            FfmpegLogLevel::Trace => "trace",
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::models::{CropResizeParameters, CropResizePreset};
+
+    #[test]
+    fn test_ffmpeg_loglevel_names() {
+        let settings: EncodingSettings = serde_json::from_str(r#"{"ffmpegLoglevel": "warning"}"#).unwrap();
+        assert_eq!(settings.ffmpeg_loglevel, FfmpegLogLevel::Warning);
+        assert_eq!(serde_json::from_str::<EncodingSettings>("{}").unwrap().ffmpeg_loglevel, FfmpegLogLevel::Error);
+        assert!(serde_json::from_str::<EncodingSettings>(r#"{"ffmpegLoglevel": "loud"}"#).is_err());
+        assert!(serde_json::from_str::<EncodingSettings>(r#"{"ffmpegLoglevel": "error -y"}"#).is_err());
+        assert_eq!(FfmpegLogLevel::Verbose.as_str(), "verbose");
+    }
+
+    #[test]
+    fn test_preview_range_in_range() {
+        assert_eq!(PreviewRange::Tv.in_range(Some("pc")), "tv");
+        assert_eq!(PreviewRange::Pc.in_range(Some("tv")), "pc");
+        assert_eq!(PreviewRange::Pc.in_range(None), "pc");
+        assert_eq!(PreviewRange::Auto.in_range(Some("pc")), "pc");
+        assert_eq!(PreviewRange::Auto.in_range(Some("tv")), "tv");
+        assert_eq!(PreviewRange::Auto.in_range(Some("unknown")), "tv");
+        assert_eq!(PreviewRange::Auto.in_range(None), "tv");
+        assert_eq!(serde_json::to_string(&PreviewRange::Pc).unwrap(), "\"pc\"");
+    }
+
+    #[test]
+    fn test_field_order_from_ffprobe() {
+        assert_eq!(FieldOrder::from_ffprobe("tt"), FieldOrder::TopFieldFirst);
+        assert_eq!(FieldOrder::from_ffprobe("tb\n"), FieldOrder::TopFieldFirst);
+        assert_eq!(FieldOrder::from_ffprobe("bb"), FieldOrder::BottomFieldFirst);
+        assert_eq!(FieldOrder::from_ffprobe("BT"), FieldOrder::BottomFieldFirst);
+        assert_eq!(FieldOrder::from_ffprobe("progressive"), FieldOrder::Progressive);
+        assert_eq!(FieldOrder::from_ffprobe("unknown"), FieldOrder::Unknown);
+        assert_eq!(FieldOrder::from_ffprobe(""), FieldOrder::Unknown);
+    }
 
     #[test]
     fn test_video_codec_serialization() {
@@ -301,6 +1218,73 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_default_quality_per_codec() {
+        assert_eq!(VideoCodec::H264.default_quality(), Some(18));
+        assert!(VideoCodec::H265.default_quality() > VideoCodec::H264.default_quality());
+        assert_eq!(VideoCodec::FFV1.default_quality(), None);
+        assert_eq!(VideoCodec::ProResHQ.default_quality(), None);
+    }
+
+    #[test]
+    fn test_effective_quality() {
+        let json = r#"{"codec": "libx265"}"#;
+        let settings: EncodingSettings = serde_json::from_str(json).unwrap();
+        assert_eq!(settings.quality, None);
+        assert_eq!(settings.effective_quality(), Some(20));
+
+        let explicit = EncodingSettings { quality: Some(24), ..settings };
+        assert_eq!(explicit.effective_quality(), Some(24));
+
+        // FFV1 is lossless: any explicit quality is ignored
+        let ffv1 = EncodingSettings { codec: VideoCodec::FFV1, quality: Some(18), ..EncodingSettings::default() };
+        assert_eq!(ffv1.effective_quality(), None);
+    }
+
+    #[test]
+    fn test_max_bit_depth_per_codec() {
+        assert_eq!(VideoCodec::H264.max_bit_depth(), 8);
+        assert_eq!(VideoCodec::H265.max_bit_depth(), 10);
+        assert_eq!(VideoCodec::ProResHQ.max_bit_depth(), 10);
+        assert_eq!(VideoCodec::FFV1.max_bit_depth(), 16);
+    }
+
+    #[test]
+    fn test_default_pix_fmt_per_codec() {
+        assert_eq!(VideoCodec::H264.default_pix_fmt(), Some("yuv420p"));
+        assert_eq!(VideoCodec::H265.default_pix_fmt(), Some("yuv420p10le"));
+        assert_eq!(VideoCodec::FFV1.default_pix_fmt(), None, "FFV1 keeps the source subsampling");
+        for codec in [VideoCodec::ProResProxy, VideoCodec::ProResLT, VideoCodec::ProRes422, VideoCodec::ProResHQ] {
+            assert_eq!(codec.default_pix_fmt(), Some("yuv422p10le"), "{:?}", codec);
+        }
+
+        assert_eq!(ChromaSubsampling::from_pix_fmt("yuv422p10le"), Some(ChromaSubsampling::Yuv422));
+        assert_eq!(ChromaSubsampling::from_pix_fmt("yuvj420p"), Some(ChromaSubsampling::Yuv420));
+        assert_eq!(ChromaSubsampling::from_pix_fmt("yuv444p16le"), Some(ChromaSubsampling::Yuv444));
+        assert_eq!(ChromaSubsampling::from_pix_fmt("yuv411p"), None);
+        assert_eq!(ChromaSubsampling::from_pix_fmt("rgb24"), None);
+        assert!(ChromaSubsampling::Yuv420 < ChromaSubsampling::Yuv422);
+    }
+
+    #[test]
+    fn test_output_chroma_pix_fmt() {
+        let mut settings = EncodingSettings { output_chroma: Some(ChromaSubsampling::Yuv422), ..EncodingSettings::default() };
+        assert_eq!(settings.pix_fmt().as_deref(), Some("yuv422p"));
+        settings.codec = VideoCodec::H265;
+        assert_eq!(settings.pix_fmt().as_deref(), Some("yuv422p10le"));
+        settings.codec = VideoCodec::FFV1;
+        assert_eq!(settings.pix_fmt(), None, "FFV1 takes the resampled pipe format");
+        assert!(settings.validate_output_chroma().is_ok());
+
+        settings.codec = VideoCodec::ProResHQ;
+        assert!(settings.validate_output_chroma().is_ok());
+        settings.output_chroma = Some(ChromaSubsampling::Yuv444);
+        let err = settings.validate_output_chroma().unwrap_err().to_string();
+        assert!(err.contains("4:4:4"), "{}", err);
+        settings.output_chroma = None;
+        assert_eq!(settings.pix_fmt().as_deref(), Some("yuv422p10le"));
+    }
+
     #[test]
     fn test_container_format_serialization() {
         assert_eq!(
@@ -309,6 +1293,384 @@ mod tests {
         );
     }
 
+    fn create_test_job() -> VideoJob {
+        VideoJob {
+            id: Uuid::new_v4(),
+            input_path: "input.mp4".to_string(),
+            output_path: "output.mp4".to_string(),
+            output_template: None,
+            qtgmc_parameters: QTGMCParameters::default(),
+            restoration_pipeline: None,
+            encoding_settings: EncodingSettings::default(),
+            detected_field_order: None,
+            total_frames: None,
+            input_frame_rate: None,
+            start_frame: None,
+            end_frame: None,
+            source_threads: None,
+            source_seek_preroll: None,
+            vs_max_cache_mb: None,
+            create_output_dirs: true,
+            measure_quality: false,
+            preview_range: PreviewRange::Auto,
+            warn_unnecessary_deinterlace: true,
+            check_disk_space: false,
+            keyframes_only: false,
+            keyframes: None,
+            on_complete_command: None,
+        }
+    }
+
+    #[test]
+    fn test_resolved_migrates_legacy_and_defaults_tff() {
+        let mut job = create_test_job();
+        job.detected_field_order = Some(FieldOrder::BottomFieldFirst);
+
+        let resolved = job.resolved();
+        let pipeline = resolved.restoration_pipeline.as_ref().unwrap();
+        assert_eq!(resolved.qtgmc_parameters.tff, Some(false));
+        assert_eq!(pipeline.deinterlace.tff, Some(false));
+        assert!(!pipeline.noise_reduction.enabled);
+    }
+
+    #[test]
+    fn test_legacy_warning_only_without_pipeline() {
+        let job = create_test_job();
+        assert_eq!(job.legacy_warning(), Some(LEGACY_PARAMETERS_WARNING));
+
+        // Resolving migrates to a pipeline, so the warning is not repeated
+        assert_eq!(job.resolved().legacy_warning(), None);
+
+        let mut job = create_test_job();
+        job.restoration_pipeline = Some(RestorationPipeline::default());
+        assert_eq!(job.legacy_warning(), None);
+    }
+
+    #[test]
+    fn test_with_clip_info_fills_missing_fields() {
+        let info = ClipInfo {
+            width: 720,
+            height: 576,
+            frames: 250,
+            fps_num: 25,
+            fps_den: 1,
+            format: "YUV420P8".to_string(),
+        };
+        let job = create_test_job().with_clip_info(&info);
+        assert_eq!(job.total_frames, Some(250));
+        assert_eq!(job.input_frame_rate, FrameRate::new(25, 1));
+
+        // Values supplied by the app win
+        let mut job = create_test_job();
+        job.total_frames = Some(100);
+        job.input_frame_rate = FrameRate::from_f64(29.97);
+        let job = job.with_clip_info(&info);
+        assert_eq!(job.total_frames, Some(100));
+        assert_eq!(job.input_frame_rate, FrameRate::new(30000, 1001));
+    }
+
+    #[test]
+    fn test_validate_for_clip_checks_crop_against_source() {
+        let info = ClipInfo {
+            width: 720,
+            height: 576,
+            frames: 250,
+            fps_num: 25,
+            fps_den: 1,
+            format: "YUV420P8".to_string(),
+        };
+        let mut job = create_test_job();
+        job.restoration_pipeline = Some(RestorationPipeline {
+            crop_resize: CropResizeParameters {
+                enabled: true,
+                crop_enabled: true,
+                crop_left: 400,
+                crop_right: 400,
+                ..Default::default()
+            },
+            ..RestorationPipeline::default()
+        });
+        let err = job.validate_for_clip(&info).unwrap_err().to_string();
+        assert!(err.contains("720x576"), "{}", err);
+
+        if let Some(pipeline) = job.restoration_pipeline.as_mut() {
+            pipeline.crop_resize.crop_left = 8;
+            pipeline.crop_resize.crop_right = 8;
+        }
+        assert!(job.validate_for_clip(&info).is_ok());
+    }
+
+    #[test]
+    fn test_unnecessary_deinterlace_warning() {
+        let mut job = create_test_job();
+        job.qtgmc_parameters.enabled = true;
+        assert!(job.unnecessary_deinterlace_warning(FieldOrder::Progressive).is_some());
+        assert!(job.unnecessary_deinterlace_warning(FieldOrder::TopFieldFirst).is_none());
+        assert!(job.unnecessary_deinterlace_warning(FieldOrder::Unknown).is_none());
+
+        // Progressive repair mode is intended for progressive input
+        job.qtgmc_parameters.input_type = 1;
+        assert!(job.unnecessary_deinterlace_warning(FieldOrder::Progressive).is_none());
+
+        job.qtgmc_parameters.input_type = 0;
+        job.warn_unnecessary_deinterlace = false;
+        assert!(job.unnecessary_deinterlace_warning(FieldOrder::Progressive).is_none());
+
+        job.warn_unnecessary_deinterlace = true;
+        job.qtgmc_parameters.enabled = false;
+        assert!(job.unnecessary_deinterlace_warning(FieldOrder::Progressive).is_none());
+    }
+
+    #[test]
+    fn test_validate_output_fps() {
+        let mut job = create_test_job();
+        job.restoration_pipeline = Some(RestorationPipeline {
+            deinterlace: QTGMCParameters { enabled: false, ..QTGMCParameters::default() },
+            ..RestorationPipeline::default()
+        });
+        job.input_frame_rate = FrameRate::from_f64(29.97);
+        job.total_frames = Some(1000);
+
+        job.encoding_settings.output_fps = Some((30000, 1001));
+        assert!(job.validate_output_fps().is_ok(), "29.97 is a re-tag of 30000/1001");
+
+        job.encoding_settings.output_fps = Some((25, 1));
+        assert!(job.validate_output_fps().unwrap_err().to_string().contains("out of sync"));
+
+        // Double-rate deinterlacing doubles the rate the tag must match
+        job.restoration_pipeline = Some(RestorationPipeline {
+            deinterlace: QTGMCParameters { enabled: true, ..QTGMCParameters::default() },
+            ..RestorationPipeline::default()
+        });
+        job.encoding_settings.output_fps = Some((60000, 1001));
+        assert!(job.validate_output_fps().is_ok());
+
+        job.encoding_settings.output_fps = Some((0, 1));
+        assert!(job.validate_output_fps().is_err());
+    }
+
+    #[test]
+    fn test_resolved_preset_job_prints_numeric_values() {
+        let json = r#"{
+            "id": "00000000-0000-0000-0000-000000000001",
+            "inputPath": "input.mp4",
+            "outputPath": "output.mp4",
+            "qtgmcParameters": {},
+            "restorationPipeline": {
+                "noiseReduction": { "enabled": true, "preset": "light" },
+                "cropResize": { "enabled": true, "preset": "resize720p" }
+            },
+            "encodingSettings": {}
+        }"#;
+        let job: VideoJob = serde_json::from_str(json).unwrap();
+
+        let printed = serde_json::to_string(&job.resolved()).unwrap();
+        assert!(printed.contains("\"smDegrainTr\":1"));
+        assert!(printed.contains("\"smDegrainThSAD\":200"));
+        assert!(printed.contains("\"targetWidth\":1280"));
+        assert!(printed.contains("\"targetHeight\":720"));
+    }
+
+    #[test]
+    fn test_resolved_keeps_bypass_on_preset_passes() {
+        let json = r#"{
+            "id": "00000000-0000-0000-0000-000000000001",
+            "inputPath": "input.mp4",
+            "outputPath": "output.mp4",
+            "qtgmcParameters": {},
+            "restorationPipeline": {
+                "noiseReduction": { "enabled": true, "bypass": true, "preset": "heavy" },
+                "colorCorrection": { "enabled": true, "bypass": true, "preset": "desaturate", "brightness": 4.0 }
+            },
+            "encodingSettings": {}
+        }"#;
+        let job: VideoJob = serde_json::from_str(json).unwrap();
+
+        let resolved = job.resolved();
+        let pipeline = resolved.restoration_pipeline.as_ref().unwrap();
+        assert!(pipeline.noise_reduction.bypass);
+        assert_eq!(pipeline.noise_reduction.sm_degrain_tr, 3);
+        assert!(pipeline.color_correction.bypass);
+        assert_eq!(pipeline.color_correction.saturation, 0.0);
+        assert_eq!(pipeline.color_correction.brightness, 4.0);
+    }
+
+    #[test]
+    fn test_resolved_and_effective_pipeline_map_resize_presets_alike() {
+        let mut job = create_test_job();
+        job.restoration_pipeline = Some(RestorationPipeline {
+            crop_resize: CropResizeParameters { enabled: true, preset: CropResizePreset::Resize4k, ..Default::default() },
+            ..RestorationPipeline::default()
+        });
+
+        let resolved = job.resolved().restoration_pipeline.unwrap().crop_resize;
+        let effective = job.effective_pipeline().crop_resize;
+        assert_eq!(serde_json::to_value(&resolved).unwrap(), serde_json::to_value(&effective).unwrap());
+        assert_eq!((effective.target_width, effective.target_height), (Some(3840), Some(2160)));
+    }
+
+    #[test]
+    fn test_lint_encoding_settings() {
+        let mut job = create_test_job();
+        job.output_path = "output.mkv".to_string();
+        job.encoding_settings.codec = VideoCodec::FFV1;
+        job.encoding_settings.quality = Some(18);
+        let advisories = job.lint();
+        assert_eq!(advisories.len(), 1);
+        assert_eq!(advisories[0].to_string(), "quality (CRF) has no effect with FFV1 (Lossless)");
+
+        job.encoding_settings.codec = VideoCodec::H264;
+        assert!(job.lint().is_empty(), "CRF applies to H.264");
+
+        job.encoding_settings.prores_qscale = Some(9);
+        let advisories = job.lint();
+        assert_eq!(advisories.len(), 1);
+        assert_eq!(advisories[0].to_string(), "proresQscale has no effect with H.264");
+        job.encoding_settings.prores_qscale = None;
+
+        // ProRes can't go into MP4
+        job.output_path = "output.mp4".to_string();
+        job.encoding_settings.codec = VideoCodec::ProResHQ;
+        job.encoding_settings.quality = None;
+        let advisories = job.lint();
+        assert_eq!(advisories.len(), 1);
+        assert_eq!(advisories[0].to_string(), "ProRes 422 HQ can't be stored in MP4; ffmpeg will refuse to mux it");
+        job.output_path = "output.mov".to_string();
+
+        // 8-bit deband into a 10-bit codec
+        job.encoding_settings.codec = VideoCodec::ProResHQ;
+        job.encoding_settings.quality = None;
+        let mut pipeline = job.effective_pipeline();
+        pipeline.deband.enabled = true;
+        pipeline.deband.output_depth = 8;
+        job.restoration_pipeline = Some(pipeline);
+        let advisories = job.lint();
+        assert_eq!(advisories.len(), 1);
+        assert!(advisories[0].to_string().starts_with("Deband: output is 8-bit"), "{}", advisories[0]);
+    }
+
+    #[test]
+    fn test_overlay_spec_validate() {
+        let overlay: OverlaySpec = serde_json::from_str(r#"{"path": "logo.png", "x": -16}"#).unwrap();
+        assert_eq!((overlay.x, overlay.y, overlay.opacity), (-16, 0, 1.0));
+        assert!(overlay.validate().is_ok());
+
+        assert!(OverlaySpec { opacity: 1.5, ..overlay.clone() }.validate().is_err());
+        assert!(OverlaySpec { path: " ".to_string(), ..overlay }.validate().is_err());
+    }
+
+    #[test]
+    fn test_progress_mode_serde() {
+        assert_eq!(EncodingSettings::default().progress_mode, ProgressMode::TimeMs(500));
+        let mode: ProgressMode = serde_json::from_str(r#"{"everyNFrames": 10}"#).unwrap();
+        assert_eq!(mode, ProgressMode::EveryNFrames(10));
+        assert_eq!(serde_json::to_string(&ProgressMode::TimeMs(250)).unwrap(), r#"{"timeMs":250}"#);
+
+        assert!(mode.validate().is_ok());
+        assert!(ProgressMode::EveryNFrames(0).validate().is_err());
+    }
+
+    #[test]
+    fn test_use_case_encoding_settings() {
+        for (settings, codec, container) in [
+            (EncodingSettings::for_archival(), VideoCodec::FFV1, ContainerFormat::Mkv),
+            (EncodingSettings::for_web(), VideoCodec::H264, ContainerFormat::Mp4),
+            (EncodingSettings::for_editing(), VideoCodec::ProRes422, ContainerFormat::Mov),
+        ] {
+            assert_eq!((settings.codec, settings.container), (codec, container));
+            assert!(codec.supports_container(container), "{:?} in {:?}", codec, container);
+            assert!(settings.validate_output_chroma().is_ok());
+
+            let mut job = create_test_job();
+            job.output_path = format!("output.{}", container.extension());
+            job.encoding_settings = settings;
+            assert!(job.lint().is_empty(), "{:?}: {:?}", codec, job.lint());
+        }
+
+        let web = EncodingSettings::for_web();
+        assert_eq!(web.effective_quality(), Some(20));
+        assert!(web.faststart && !web.audio_copy);
+
+        assert!(!VideoCodec::ProRes422.supports_container(ContainerFormat::Mp4));
+        assert!(!VideoCodec::FFV1.supports_container(ContainerFormat::Mov));
+        assert_eq!(ContainerFormat::from_extension("MOV"), Some(ContainerFormat::Mov));
+        assert_eq!(ContainerFormat::from_extension("webm"), None);
+    }
+
+    #[test]
+    fn test_estimated_output_bytes() {
+        let mut job = create_test_job();
+        job.encoding_settings.codec = VideoCodec::FFV1;
+        job.qtgmc_parameters.enabled = false;
+        job.restoration_pipeline = None;
+        assert_eq!(job.estimated_output_bytes((720, 480)), None, "Unknown frame count");
+
+        job.total_frames = Some(1000);
+        assert_eq!(job.estimated_output_bytes((720, 480)), Some(1000 * 720 * 480));
+
+        // Trimmed to 100 frames, double-rate deinterlaced
+        job.start_frame = Some(100);
+        job.end_frame = Some(199);
+        job.qtgmc_parameters.enabled = true;
+        assert_eq!(job.estimated_output_bytes((720, 480)), Some(200 * 720 * 480));
+
+        job.encoding_settings.codec = VideoCodec::H264;
+        assert_eq!(job.estimated_output_bytes((720, 480)), Some((200.0 * 720.0 * 480.0 * 0.05) as u64));
+    }
+
+    #[test]
+    fn test_render_output_path_tokens() {
+        let mut job = create_test_job();
+        job.input_path = "/captures/tape 01.avi".to_string();
+        job.encoding_settings.codec = VideoCodec::FFV1;
+        job.encoding_settings.container = ContainerFormat::Mkv;
+        job.qtgmc_parameters.preset = QTGMCPreset::VerySlow;
+
+        let render = |template: &str, job: &VideoJob| render_output_path(template, job, Some((720, 480)));
+        assert_eq!(render("{stem}_qtgmc_{preset}.{ext}", &job).unwrap(), "tape 01_qtgmc_very_slow.mkv");
+        assert_eq!(render("{stem}_{codec}_{width}x{height}.{ext}", &job).unwrap(), "tape 01_ffv1_720x480.mkv");
+        assert!(render("{stem}_{bitrate}", &job).is_err(), "Unknown tokens are rejected");
+        assert!(render("{stem", &job).is_err());
+        assert!(render_output_path("{width}x{height}", &job, None).is_err());
+
+        job.qtgmc_parameters.preset_name = Some("vhs_clean".to_string());
+        assert_eq!(render("{stem}_{preset}.{ext}", &job).unwrap(), "tape 01_vhs_clean.mkv");
+    }
+
+    #[test]
+    fn test_rendered_output_path_extension_and_directory() {
+        let mut job = create_test_job();
+        job.input_path = "/captures/tape.avi".to_string();
+        job.output_template = Some("{stem}_restored".to_string());
+        job.encoding_settings.container = ContainerFormat::Mov;
+
+        // No output path: the input's directory, with the container's extension
+        job.output_path = String::new();
+        let rendered = job.with_rendered_output_path(None).unwrap();
+        assert_eq!(Path::new(&rendered.output_path), Path::new("/captures/tape_restored.mov"));
+
+        job.output_path = "/exports/".to_string();
+        let rendered = job.with_rendered_output_path(None).unwrap();
+        assert_eq!(Path::new(&rendered.output_path), Path::new("/exports/tape_restored.mov"));
+
+        // The size tokens follow the crop/resize pass
+        job.output_template = Some("{stem}_{width}x{height}".to_string());
+        job.restoration_pipeline = Some(RestorationPipeline {
+            crop_resize: CropResizeParameters::from_preset(CropResizePreset::Resize720p),
+            ..RestorationPipeline::default()
+        });
+        let rendered = job.with_rendered_output_path(Some((720, 480))).unwrap();
+        assert_eq!(Path::new(&rendered.output_path), Path::new("/exports/tape_1080x720.mov"));
+
+        // An explicit file path wins over the template
+        job.output_path = "/exports/final.mp4".to_string();
+        assert_eq!(job.with_rendered_output_path(None).unwrap().output_path, "/exports/final.mp4");
+
+        job.output_template = None;
+        job.output_path = String::new();
+        assert!(job.with_rendered_output_path(None).is_err());
+    }
+
     #[test]
     fn test_field_order_serialization() {
         assert_eq!(
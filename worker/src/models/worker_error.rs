@@ -0,0 +1,93 @@
+//! Failure classes of a worker run and their process exit codes.
+
+use thiserror::Error;
+
+use super::DependencyError;
+
+/// Why a job failed, attached to an error as context and mapped to the
+/// process exit code so shell and CI callers can branch on it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+pub enum WorkerError {
+    /// A required external tool is missing
+    #[error("Required dependency missing")]
+    DependencyMissing,
+
+    /// The job configuration could not be read or is invalid
+    #[error("Invalid job configuration")]
+    InvalidConfig,
+
+    /// The VapourSynth script could not be generated
+    #[error("Failed to generate VapourSynth script")]
+    ScriptGeneration,
+
+    /// vspipe or ffmpeg failed while encoding
+    #[error("Encoding pipeline failed")]
+    Pipeline,
+
+    /// The user cancelled the job
+    #[error("Job cancelled")]
+    Cancelled,
+}
+
+impl WorkerError {
+    /// Process exit code for this failure class.
+    pub fn exit_code(self) -> u8 {
+        match self {
+            WorkerError::DependencyMissing => 2,
+            WorkerError::InvalidConfig => 3,
+            WorkerError::ScriptGeneration => 4,
+            WorkerError::Pipeline => 5,
+            WorkerError::Cancelled => 130, // Standard exit code for SIGINT
+        }
+    }
+
+    /// The failure class of `error`, if it has one.
+    ///
+    /// A [`DependencyError`] anywhere in the chain is `DependencyMissing`;
+    /// otherwise the outermost `WorkerError` context applies.
+    pub fn classify(error: &anyhow::Error) -> Option<WorkerError> {
+        if error.downcast_ref::<DependencyError>().is_some() {
+            return Some(WorkerError::DependencyMissing);
+        }
+        error.downcast_ref::<WorkerError>().copied()
+    }
+
+    /// Exit code for `error`: its failure class's code, or 1 when unclassified.
+    pub fn exit_code_for(error: &anyhow::Error) -> u8 {
+        Self::classify(error).map_or(1, WorkerError::exit_code)
+    }
+
+    /// Attach this failure class to `error` unless it already has one, so the
+    /// most specific class (set closest to the failure) wins.
+    pub fn wrap(self, error: anyhow::Error) -> anyhow::Error {
+        if Self::classify(&error).is_some() {
+            error
+        } else {
+            error.context(self)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::anyhow;
+
+    #[test]
+    fn test_exit_codes() {
+        let code = |e: anyhow::Error| WorkerError::exit_code_for(&e);
+        let dependency = DependencyError { which: "vspipe".to_string(), searched_paths: Vec::new() };
+
+        assert_eq!(code(anyhow::Error::new(dependency.clone())), 2);
+        assert_eq!(code(anyhow!("bad json").context(WorkerError::InvalidConfig)), 3);
+        assert_eq!(code(WorkerError::ScriptGeneration.wrap(anyhow!("template missing"))), 4);
+        assert_eq!(code(WorkerError::Pipeline.wrap(anyhow!("ffmpeg exited with 1"))), 5);
+        assert_eq!(code(WorkerError::Cancelled.into()), 130);
+        assert_eq!(code(anyhow!("disk full")), 1);
+
+        // The class set closest to the failure is kept
+        assert_eq!(code(WorkerError::Pipeline.wrap(WorkerError::Cancelled.into())), 130);
+        assert_eq!(code(WorkerError::Pipeline.wrap(anyhow::Error::new(dependency))), 2);
+        assert_eq!(code(WorkerError::ScriptGeneration.wrap(WorkerError::InvalidConfig.wrap(anyhow!("lossless=3")))), 3);
+    }
+}
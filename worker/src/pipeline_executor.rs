@@ -1,10 +1,13 @@
 //! Pipeline executor for vspipe | ffmpeg.
 
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
-use std::io::{BufRead, BufReader};
-use std::path::Path;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
 use std::process::{Child, Command, Stdio};
-use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicI32, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
 use std::sync::Arc;
 use std::thread;
 use std::time::{Duration, Instant};
@@ -12,16 +15,837 @@ use std::time::{Duration, Instant};
 use anyhow::{bail, Context, Result};
 
 use crate::dependency_locator::DependencyLocator;
-use crate::models::{LogLevel, ProgressInfo, VideoJob};
+use crate::index_cache;
+use crate::models::{
+    ChromaSubsampling, ClipInfo, EncodePhase, EncodingSettings, FieldOrder, FrameRate, LogLevel, PreviewPhase, PreviewRange, ProgressInfo,
+    ProgressMode,
+    QTGMCParameters, QTGMCPreset, QualityReport, VideoJob, WorkerError,
+};
+use crate::platform::{self, ProcessTree, TempPath};
 use crate::progress_reporter::ProgressReporter;
-use crate::script_generator::{PreviewParams, ScriptGenerator};
+use crate::script_generator::{self, PreviewParams, ScriptGenerator};
+
+/// Open the destination for a preview PNG: the given file, or stdout when `None`.
+pub fn preview_output(path: Option<&Path>) -> Result<Box<dyn Write>> {
+    match path {
+        Some(path) => {
+            let file = fs::File::create(path)
+                .with_context(|| format!("Failed to create preview output: {:?}", path))?;
+            Ok(Box::new(std::io::BufWriter::new(file)))
+        }
+        None => Ok(Box::new(std::io::stdout())),
+    }
+}
+
+/// Format a command line for logging, quoting arguments so it can be pasted into a shell.
+///
+/// Any argument with a character outside `[A-Za-z0-9_./:=+-]` is single-quoted,
+/// so shell metacharacters such as `$`, `;` or `*` are passed through literally.
+fn quote_argv(program: &Path, args: &[String]) -> String {
+    let is_safe = |c: char| c.is_ascii_alphanumeric() || "_./:=+-".contains(c);
+    std::iter::once(program.to_string_lossy().to_string())
+        .chain(args.iter().cloned())
+        .map(|arg| {
+            if arg.is_empty() || !arg.chars().all(is_safe) {
+                format!("'{}'", arg.replace('\'', "'\\''"))
+            } else {
+                arg
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Parse a `DEP_VERSIONS:{json}` line printed by the script header into name -> version.
+fn parse_dep_versions(line: &str) -> Option<BTreeMap<String, String>> {
+    let json = line.strip_prefix("DEP_VERSIONS:")?;
+    serde_json::from_str(json.trim()).ok()
+}
+
+/// Commands and environment for one vspipe | ffmpeg run.
+///
+/// Built without spawning anything, so tests can check the exact commands.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PipelinePlan {
+    /// vspipe program followed by its arguments
+    pub vspipe_cmd: Vec<String>,
+    /// ffmpeg program followed by its arguments; reads vspipe's stdout
+    pub ffmpeg_cmd: Vec<String>,
+    /// Environment for both processes
+    pub env: HashMap<String, String>,
+}
+
+impl PipelinePlan {
+    /// Plan rendering `script_path` with `vspipe` and encoding `job` with `ffmpeg`.
+    pub fn new(
+        vspipe: &Path,
+        ffmpeg: &Path,
+        env: HashMap<String, String>,
+        script_path: &Path,
+        job: &VideoJob,
+    ) -> Self {
+        let vspipe_cmd = [vspipe, Path::new("-c"), Path::new("y4m"), script_path, Path::new("-")]
+            .map(|arg| arg.to_string_lossy().to_string());
+        let ffmpeg_cmd = std::iter::once(ffmpeg.to_string_lossy().to_string())
+            .chain(build_ffmpeg_args(job))
+            .collect();
+        Self { vspipe_cmd: vspipe_cmd.to_vec(), ffmpeg_cmd, env }
+    }
+
+    /// The vspipe command, ready to spawn.
+    fn vspipe_command(&self) -> Command {
+        self.command(&self.vspipe_cmd)
+    }
+
+    /// The ffmpeg command, ready to spawn.
+    fn ffmpeg_command(&self) -> Command {
+        self.command(&self.ffmpeg_cmd)
+    }
+
+    fn command(&self, cmd: &[String]) -> Command {
+        let mut command = Command::new(&cmd[0]);
+        command.args(&cmd[1..]).envs(&self.env);
+        command
+    }
+}
+
+/// ffmpeg input index of the overlay image, after the vspipe video (0) and
+/// the original file for audio (1).
+const OVERLAY_INPUT: usize = 2;
+
+/// Build FFmpeg command-line arguments.
+fn build_ffmpeg_args(job: &VideoJob) -> Vec<String> {
+    let mut args = Vec::new();
+    let settings = &job.encoding_settings;
+
+    // Input 0: Processed video from vspipe (Y4M pipe)
+    args.extend(["-f".to_string(), "yuv4mpegpipe".to_string()]);
+    args.extend(["-i".to_string(), "-".to_string()]);
+
+    // Input 1: Original file for audio stream
+    // (Y4M from vspipe contains only video, so we need the original file for audio)
+    args.extend(["-i".to_string(), job.input_path.clone()]);
+
+    // Input 2: Overlay image, composited in the filtergraph below
+    if let Some(overlay) = &settings.overlay {
+        args.extend(["-i".to_string(), platform::long_path(&overlay.path)]);
+    }
+
+    // Progress output to stderr, with log messages tagged by level for routing
+    args.extend(["-loglevel".to_string(), format!("level+{}", settings.ffmpeg_loglevel.as_str())]);
+    args.extend(["-progress".to_string(), "pipe:2".to_string()]);
+    if let ProgressMode::EveryNFrames(_) = settings.progress_mode {
+        // ffmpeg writes progress every 0.5s by default; sample finely enough to see each boundary
+        args.extend(["-stats_period".to_string(), "0.1".to_string()]);
+    }
+
+    // Video filters: the overlay graph (which also tags the SAR), or just the SAR
+    // (anamorphic content)
+    if let Some(overlay) = &settings.overlay {
+        args.extend(["-filter_complex".to_string(), overlay.filtergraph(OVERLAY_INPUT, settings.output_sar)]);
+    } else if let Some((num, den)) = settings.output_sar {
+        args.extend(["-vf".to_string(), format!("setsar={}/{}", num, den)]);
+    }
+
+    // Map streams: video from input 0 (processed, or the overlay graph), audio from input 1 (original)
+    let video_map = if settings.overlay.is_some() { "[vout]" } else { "0:v" }; // Video from Y4M pipe
+    args.extend(["-map".to_string(), video_map.to_string()]);
+    if !job.keyframes_only {
+        args.extend(["-map".to_string(), settings.audio_map()]); // Audio from original (? = optional, skip if no audio)
+    }
+
+    // Video codec
+    args.extend(["-c:v".to_string(), settings.codec.ffmpeg_codec().to_string()]);
+
+    // Quality: ProRes profile (and quantizer), or CRF for H.264/H.265
+    args.extend(settings.video_quality_args());
+
+    // Pixel format (keeps 4:2:2 chroma where the codec supports it, or the requested output chroma)
+    if let Some(pix_fmt) = settings.pix_fmt() {
+        args.extend(["-pix_fmt".to_string(), pix_fmt]);
+    }
+
+    // Container frame rate tag
+    if let Some((num, den)) = settings.output_fps {
+        args.extend(["-r".to_string(), format!("{}/{}", num, den)]);
+    }
+
+    // Audio handling (a keyframes-only proxy has no audio that would stay in sync)
+    if job.keyframes_only {
+        args.push("-an".to_string());
+    } else if settings.audio_copy {
+        args.extend(["-c:a".to_string(), "copy".to_string()]);
+    } else {
+        args.extend(["-c:a".to_string(), settings.audio_codec.clone()]);
+        args.extend(["-b:a".to_string(), format!("{}k", settings.audio_bitrate)]);
+        if let Some(rate) = settings.audio_sample_rate {
+            args.extend(["-ar".to_string(), rate.to_string()]);
+        }
+        if let Some(channels) = settings.audio_channels {
+            args.extend(["-ac".to_string(), channels.to_string()]);
+        }
+    }
+
+    // Pipeline summary in the output metadata
+    if settings.embed_pipeline_metadata {
+        args.extend([
+            "-metadata".to_string(),
+            format!("comment=VapourBox: {}", job.effective_pipeline().describe()),
+        ]);
+    }
+
+    // Index at the front of MP4/MOV for progressive playback
+    if settings.faststart {
+        args.extend(["-movflags".to_string(), "+faststart".to_string()]);
+    }
+
+    // Custom arguments
+    if !settings.custom_ffmpeg_args.is_empty() {
+        args.extend(settings.custom_ffmpeg_args.split_whitespace().map(String::from));
+    }
+
+    // Output file (force overwrite)
+    args.push("-y".to_string());
+    args.push(platform::long_path(&job.output_path));
+
+    args
+}
+
+/// Warning for encoding `source` chroma with `settings`, if its pixel format
+/// has less chroma detail than the source.
+fn chroma_downsample_warning(settings: &EncodingSettings, source: ChromaSubsampling) -> Option<String> {
+    let output = ChromaSubsampling::from_pix_fmt(&settings.pix_fmt()?)?;
+    (output < source).then(|| format!(
+        "Source is {} but {} encodes {}; chroma resolution will be reduced",
+        source.display_name(),
+        settings.codec.display_name(),
+        output.display_name(),
+    ))
+}
+
+/// Whether VapourSynth loaded a plugin, from [`PipelineExecutor::list_plugins`].
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PluginStatus {
+    /// Plugin namespace (`core.<namespace>`)
+    pub namespace: String,
+    pub loaded: bool,
+}
+
+/// Plugin statuses from the `PLUGINS:` line of the probe script's stderr,
+/// in the order `namespaces` were requested.
+fn parse_plugin_report(stderr: &str, namespaces: &[&str]) -> Result<Vec<PluginStatus>> {
+    let report = stderr.lines()
+        .find_map(|line| line.strip_prefix("PLUGINS:"))
+        .context("Plugin probe printed no report")?;
+    let loaded: HashMap<String, bool> = serde_json::from_str(report)
+        .with_context(|| format!("Invalid plugin report: {}", report))?;
+    Ok(namespaces.iter()
+        .map(|&namespace| PluginStatus {
+            namespace: namespace.to_string(),
+            loaded: loaded.get(namespace).copied().unwrap_or(false),
+        })
+        .collect())
+}
+
+/// Failures classified from vspipe's stderr.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct VspipeErrors {
+    /// An OpenCL device or platform failed
+    opencl_device: bool,
+    /// A memory allocation failed
+    out_of_memory: bool,
+    /// The source plugin blamed a decoding failure on its index
+    stale_index: bool,
+}
+
+/// Log and interpret vspipe's stderr until it closes, storing the source
+/// frame count from `INPUT_INFO:` in `total_frames` and the filtered count
+/// from `OUTPUT_INFO:` in `output_frames`. Returns the failures reported.
+fn read_vspipe_stderr<R: BufRead>(
+    reader: R,
+    reporter: &ProgressReporter,
+    total_frames: &AtomicI32,
+    output_frames: &AtomicI32,
+) -> VspipeErrors {
+    let mut errors = VspipeErrors::default();
+    for line in reader.lines().map_while(Result::ok) {
+        // Log all stderr for debugging
+        reporter.send_log(LogLevel::Debug, &format!("vspipe stderr: {}", line));
+
+        errors.opencl_device |= is_opencl_device_error(&line);
+        errors.out_of_memory |= is_out_of_memory_error(&line);
+        errors.stale_index |= is_stale_index_error(&line);
+
+        if let Some(message) = line.strip_prefix("DEPTH_CONVERT:").or_else(|| line.strip_prefix("DEDUP:")) {
+            reporter.send_log(LogLevel::Info, message);
+        }
+        if let Some(message) = line.strip_prefix("DEBLOCK_FALLBACK:") {
+            reporter.send_log(LogLevel::Warning, message);
+        }
+
+        if let Some(versions) = parse_dep_versions(&line) {
+            let summary: Vec<String> = versions.iter()
+                .map(|(name, version)| format!("{}={}", name, version))
+                .collect();
+            reporter.send_log(
+                LogLevel::Info,
+                &format!("Dependency versions: {}", summary.join(", ")),
+            );
+        }
+
+        if let Some(frames) = parse_info_frames(&line, "INPUT_INFO:") {
+            total_frames.store(frames, Ordering::SeqCst);
+        }
+        if let Some(frames) = parse_info_frames(&line, "OUTPUT_INFO:") {
+            output_frames.store(frames, Ordering::SeqCst);
+        }
+    }
+    errors
+}
+
+/// Whether `child` has exited with status 0, without waiting for it.
+fn exited_successfully(child: &mut Child) -> bool {
+    matches!(child.try_wait(), Ok(Some(status)) if status.success())
+}
+
+/// Frame count from a script info line such as
+/// `INPUT_INFO:frames=1234,fps_num=25,fps_den=1`, if `line` starts with `prefix`.
+fn parse_info_frames(line: &str, prefix: &str) -> Option<i32> {
+    line.strip_prefix(prefix)?
+        .split(',')
+        .find_map(|part| part.strip_prefix("frames="))
+        .and_then(|frames| frames.trim().parse().ok())
+}
+
+/// Declared field order of `job` (true = TFF): QTGMC's `tff`, else the
+/// detected order. None when neither is known.
+fn preview_tff(job: &VideoJob) -> Option<bool> {
+    job.qtgmc_parameters.tff
+        .or_else(|| job.detected_field_order.and_then(|order| order.tff_value()))
+}
+
+/// How often a pause flag is checked while encoding.
+const PAUSE_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How often an encode checks for cancellation and vspipe's exit while
+/// ffmpeg prints nothing.
+const ENCODE_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Change to apply to the encoder processes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PauseTransition {
+    Pause,
+    Resume,
+}
+
+impl PauseTransition {
+    /// Phase and message to report after this transition; a resumed encode
+    /// returns to finalizing if vspipe had already finished.
+    fn phase(self, finalizing: bool) -> (EncodePhase, &'static str) {
+        match self {
+            PauseTransition::Pause => (EncodePhase::Paused, "Encode paused"),
+            PauseTransition::Resume if finalizing => (EncodePhase::Finalizing, "Encode resumed, finalizing output file"),
+            PauseTransition::Resume => (EncodePhase::Encoding, "Encode resumed"),
+        }
+    }
+}
+
+/// Whether the encoder processes are currently stopped.
+#[derive(Debug, Default)]
+struct PauseState {
+    paused: bool,
+}
+
+impl PauseState {
+    /// Follow the `requested` state, returning the transition when it changes.
+    fn update(&mut self, requested: bool) -> Option<PauseTransition> {
+        if requested == self.paused {
+            return None;
+        }
+        self.paused = requested;
+        Some(if requested { PauseTransition::Pause } else { PauseTransition::Resume })
+    }
+}
+
+/// Thread that stops and continues `pids` as `paused` changes, reporting
+/// whether a resumed encode is still `finalizing`; resumes them and stops
+/// watching when dropped.
+struct PauseWatcher {
+    done: Arc<AtomicBool>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl PauseWatcher {
+    fn start(paused: Arc<AtomicBool>, finalizing: Arc<AtomicBool>, pids: Vec<u32>, reporter: ProgressReporter) -> Self {
+        let done = Arc::new(AtomicBool::new(false));
+        let done_clone = done.clone();
+        let thread = thread::spawn(move || {
+            let mut state = PauseState::default();
+            loop {
+                let finished = done_clone.load(Ordering::SeqCst);
+                let requested = paused.load(Ordering::SeqCst) && !finished;
+                if let Some(transition) = state.update(requested) {
+                    let pause = transition == PauseTransition::Pause;
+                    for &pid in &pids {
+                        if let Err(e) = platform::suspend_process(pid, pause) {
+                            reporter.send_log(LogLevel::Warning, &format!("{:#}", e));
+                        }
+                    }
+                    let (phase, message) = transition.phase(finalizing.load(Ordering::SeqCst));
+                    reporter.send_encode_phase(phase, message);
+                }
+                if finished {
+                    break;
+                }
+                thread::sleep(PAUSE_POLL_INTERVAL);
+            }
+        });
+        Self { done, thread: Some(thread) }
+    }
+}
+
+impl Drop for PauseWatcher {
+    fn drop(&mut self) {
+        self.done.store(true, Ordering::SeqCst);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Split a stderr stream into records on both `\n` and `\r`.
+///
+/// ffmpeg rewrites its stats line in place with `\r`, so reading by line alone
+/// would only deliver progress once the encode has finished.
+fn stderr_records<R: BufRead>(reader: R) -> impl Iterator<Item = String> {
+    reader.split(b'\r')
+        .map_while(Result::ok)
+        .flat_map(|chunk| {
+            String::from_utf8_lossy(&chunk)
+                .split('\n')
+                .map(str::to_string)
+                .collect::<Vec<_>>()
+        })
+        .filter(|record| !record.trim().is_empty())
+}
+
+/// Log level for an ffmpeg stderr record, or None for progress output.
+///
+/// ffmpeg runs with `-loglevel level+...`, so messages carry a `[error]`,
+/// `[warning]`, ... tag; untagged messages are logged as info.
+fn ffmpeg_log_level(record: &str) -> Option<LogLevel> {
+    let record = record.trim();
+    let key_value = record.contains('=') && !record.contains(char::is_whitespace);
+    if key_value || record.starts_with("frame=") {
+        return None;
+    }
+
+    let tagged = |tags: &[&str]| tags.iter().any(|tag| record.contains(&format!("[{}] ", tag)));
+    Some(if tagged(&["panic", "fatal", "error"]) {
+        LogLevel::Error
+    } else if tagged(&["warning"]) {
+        LogLevel::Warning
+    } else if tagged(&["verbose", "debug", "trace"]) {
+        LogLevel::Debug
+    } else {
+        LogLevel::Info
+    })
+}
+
+/// One block of ffmpeg's `-progress` output: the `key=value` lines up to and
+/// including its closing `progress=continue` or `progress=end`.
+#[derive(Debug, Clone, Default, PartialEq)]
+struct FfmpegProgress {
+    frame: i32,
+    fps: f64,
+    /// Output timestamp in microseconds, None while ffmpeg reports `N/A`.
+    out_time_us: Option<i64>,
+    /// Bytes written so far.
+    total_size: Option<u64>,
+    /// Output bitrate in kbit/s (`2046.0kbits/s`).
+    bitrate_kbps: Option<f64>,
+    /// Encoding speed relative to real time (`1.5x`).
+    speed: Option<f64>,
+    /// `progress=end`: the encode has finished.
+    end: bool,
+}
+
+/// Assembles [`FfmpegProgress`] blocks from stderr records.
+#[derive(Debug, Default)]
+struct FfmpegProgressParser {
+    block: FfmpegProgress,
+}
+
+impl FfmpegProgressParser {
+    /// Feed one stderr record, returning the block it completes.
+    ///
+    /// Only bare `key=value` records are read; ffmpeg's human-readable stats
+    /// line (`frame=  123 fps= 45.0 ...`) and log messages are ignored.
+    fn push(&mut self, record: &str) -> Option<FfmpegProgress> {
+        let (key, value) = record.trim().split_once('=')?;
+        if key.is_empty() || key.contains(char::is_whitespace) || value.contains(char::is_whitespace) {
+            return None;
+        }
+        let block = &mut self.block;
+        match key {
+            "frame" => block.frame = value.parse().unwrap_or(block.frame),
+            "fps" => block.fps = value.parse().unwrap_or(block.fps),
+            "out_time_us" => block.out_time_us = value.parse().ok(),
+            "total_size" => block.total_size = value.parse().ok(),
+            "bitrate" => block.bitrate_kbps = value.strip_suffix("kbits/s").and_then(|v| v.parse().ok()),
+            "speed" => block.speed = value.strip_suffix('x').and_then(|v| v.parse().ok()),
+            "progress" => {
+                block.end = value == "end";
+                return Some(std::mem::take(&mut self.block));
+            }
+            _ => {}
+        }
+        None
+    }
+}
+
+/// Log line for ffmpeg's final progress block.
+fn encode_summary(block: &FfmpegProgress) -> String {
+    let mut summary = format!("Encoded {} frames", block.frame);
+    if let Some(bytes) = block.total_size {
+        summary.push_str(&format!(", {:.1} MB", bytes as f64 / 1_000_000.0));
+    }
+    if let Some(kbps) = block.bitrate_kbps {
+        summary.push_str(&format!(" at {:.0} kbit/s", kbps));
+    }
+    if let Some(speed) = block.speed {
+        summary.push_str(&format!(" ({:.2}x real time)", speed));
+    }
+    summary
+}
+
+/// Decides when the encode loop sends a progress update.
+struct ProgressThrottle {
+    mode: ProgressMode,
+    last_time: Instant,
+    last_frame: i32,
+}
+
+impl ProgressThrottle {
+    fn new(mode: ProgressMode) -> Self {
+        Self { mode, last_time: Instant::now(), last_frame: 0 }
+    }
+
+    /// Whether to report `frame` at `now`: once the interval has elapsed
+    /// since the last update, or when `frame` reaches the next multiple of
+    /// the frame interval (once per multiple, however far ffmpeg skips).
+    fn should_emit(&mut self, frame: i32, now: Instant) -> bool {
+        let emit = match self.mode {
+            ProgressMode::TimeMs(ms) => now.duration_since(self.last_time) >= Duration::from_millis(ms),
+            ProgressMode::EveryNFrames(n) => {
+                let n = n.max(1);
+                frame / n > self.last_frame / n
+            }
+        };
+        if emit {
+            self.last_time = now;
+            self.last_frame = frame;
+        }
+        emit
+    }
+}
+
+/// Number of output frames to report progress against.
+///
+/// `output_frames` is the filtered count reported by the script and is exact,
+/// so it wins when known. Otherwise it is estimated from `source_frames` (0 if
+/// not yet known, in which case `fallback` from the job is used as-is): only
+/// true double-rate deinterlacing (interlaced input, `fps_divisor == 1`)
+/// doubles the source count. Dedup drops a content-dependent number of
+/// frames, so until the script reports its count the estimate is an upper bound.
+fn effective_total_frames(
+    output_frames: i32,
+    source_frames: i32,
+    fallback: Option<i32>,
+    deinterlace_enabled: bool,
+    params: &QTGMCParameters,
+) -> i32 {
+    if output_frames > 0 {
+        return output_frames;
+    }
+    if source_frames <= 0 {
+        return fallback.unwrap_or(0);
+    }
+    let double_rate = deinterlace_enabled && params.input_type == 0 && params.fps_divisor == 1;
+    if double_rate { source_frames * 2 } else { source_frames }
+}
+
+/// Seconds of the source decoded by [`PipelineExecutor::check_decodable`].
+const DECODE_PROBE_SECONDS: &str = "0.1";
+
+/// ffmpeg arguments decoding the start of `input_path`'s video and discarding
+/// it, printing only errors on stderr and `-progress` (for the decoded frame
+/// count) on stdout. Audio is skipped so a glitch there doesn't fail a usable
+/// source.
+fn decode_probe_args(input_path: &str) -> Vec<String> {
+    [
+        "-v", "error", "-nostdin", "-nostats", "-progress", "pipe:1", "-i", input_path,
+        "-map", "0:v:0", "-t", DECODE_PROBE_SECONDS, "-f", "null", "-",
+    ]
+        .iter()
+        .map(|arg| arg.to_string())
+        .collect()
+}
+
+/// Result of the ffmpeg decode probe from its stderr, `-progress` output and
+/// exit status: the error lines to warn about when the source is usable, or
+/// the failure when ffmpeg failed or decoded no frame.
+///
+/// Decoders report recoverable glitches (e.g. a damaged first GOP) as errors
+/// while still producing frames, so error lines alone don't fail the probe.
+fn parse_decode_errors(stderr: &str, progress: &str, success: bool) -> Result<Vec<String>, String> {
+    let errors: Vec<String> = stderr.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(str::to_string)
+        .collect();
+    let frames = progress.lines()
+        .rev()
+        .find_map(|line| line.trim().strip_prefix("frame=")?.trim().parse::<i64>().ok())
+        .unwrap_or(0);
+    if success && frames > 0 {
+        return Ok(errors);
+    }
+    if !errors.is_empty() {
+        return Err(errors.join("\n"));
+    }
+    Err(if success { "no video frame could be decoded" } else { "ffmpeg exited with an error" }.to_string())
+}
+
+/// Pixel formats listed on the `Supported pixel formats:` line of
+/// `ffmpeg -h encoder=<name>`, or None when the encoder doesn't list them.
+fn parse_encoder_pix_fmts(help: &str) -> Option<Vec<String>> {
+    let line = help.lines().find_map(|line| line.trim().strip_prefix("Supported pixel formats:"))?;
+    Some(line.split_whitespace().map(str::to_string).collect())
+}
+
+/// Keyframe indices from ffprobe `packet=pts,flags` CSV lines (`pts,K__`).
+///
+/// Packets arrive in decode order, so they are sorted by timestamp to get
+/// display-order frame numbers; packets without a timestamp keep their place.
+fn parse_keyframes(csv: &str) -> Vec<i32> {
+    let mut packets: Vec<(Option<i64>, bool)> = csv
+        .lines()
+        .filter_map(|line| line.trim().split_once(','))
+        .map(|(pts, flags)| (pts.parse().ok(), flags.starts_with('K')))
+        .collect();
+    if packets.iter().all(|(pts, _)| pts.is_some()) {
+        packets.sort_by_key(|&(pts, _)| pts);
+    }
+    packets.iter()
+        .enumerate()
+        .filter(|(_, &(_, key))| key)
+        .map(|(index, _)| index as i32)
+        .collect()
+}
+
+/// Whether a vspipe stderr line reports an OpenCL device or platform failure.
+fn is_opencl_device_error(line: &str) -> bool {
+    if line.starts_with("DEP_VERSIONS:") {
+        return false;
+    }
+    let line = line.to_ascii_lowercase();
+    let mentions_opencl = ["opencl", "nnedi3cl", "cl_device_not"].iter().any(|k| line.contains(k));
+    mentions_opencl && ["device", "platform", "error", "failed"].iter().any(|k| line.contains(k))
+}
+
+/// ffmpeg arguments comparing the encoded `output_path` (input 1) against the
+/// processed video piped from vspipe (input 0) with the ssim and psnr filters.
+fn build_quality_args(output_path: &str) -> Vec<String> {
+    [
+        "-hide_banner", "-nostats",
+        "-f", "yuv4mpegpipe", "-i", "-",
+        "-i", output_path,
+        "-lavfi", "[1:v]split[enc0][enc1];[0:v]split[ref0][ref1];[enc0][ref0]ssim;[enc1][ref1]psnr",
+        "-f", "null", "-",
+    ]
+    .map(String::from)
+    .to_vec()
+}
+
+/// Parse the ssim/psnr filter summaries from ffmpeg stderr.
+///
+/// Formats: `[Parsed_ssim_4 @ 0x..] SSIM Y:0.98 (17.1) ... All:0.981 (17.3)` and
+/// `[Parsed_psnr_5 @ 0x..] PSNR y:44.1 u:46.2 v:46.0 average:44.8 min:40.1 max:49.9`.
+fn parse_quality_report(stderr: &str) -> QualityReport {
+    let value_after = |line: &str, key: &str| {
+        let rest = &line[line.find(key)? + key.len()..];
+        rest.split_whitespace().next()?.parse::<f64>().ok()
+    };
+
+    let mut report = QualityReport::default();
+    for line in stderr.lines() {
+        if line.contains("] SSIM ") {
+            report.ssim = value_after(line, "All:").or(report.ssim);
+        } else if line.contains("] PSNR ") {
+            report.psnr = value_after(line, "average:").or(report.psnr);
+        }
+    }
+    report
+}
+
+/// Whether a vspipe stderr line reports a failed memory allocation.
+fn is_out_of_memory_error(line: &str) -> bool {
+    if line.starts_with("DEP_VERSIONS:") {
+        return false;
+    }
+    let line = line.to_ascii_lowercase();
+    ["out of memory", "bad_alloc", "memoryerror", "cannot allocate memory", "failed to allocate"]
+        .iter()
+        .any(|k| line.contains(k))
+}
+
+/// Whether a vspipe stderr line is BestSource's bad-frame error, which asks
+/// for the index to be deleted. BestSource reindexes by itself when an index
+/// is from another version or unreadable, so this is the only index failure
+/// that reaches vspipe.
+fn is_stale_index_error(line: &str) -> bool {
+    line.contains("Try deleting the index")
+}
+
+/// The index directory to clear before retrying a failure, when vspipe
+/// rejected a stale index and the job indexes into a known directory.
+fn stale_index_dir<'a>(index_dir: Option<&'a Path>, error: &anyhow::Error) -> Option<&'a Path> {
+    index_dir.filter(|_| error.is::<StaleIndex>())
+}
+
+/// The job to retry an OpenCL device failure with: the same job on the CPU,
+/// when it used OpenCL with `opencl_fallback` set.
+fn opencl_fallback(job: &VideoJob, error: &anyhow::Error) -> Option<VideoJob> {
+    let params = &job.qtgmc_parameters;
+    if !params.opencl || !params.opencl_fallback || !error.is::<OpenClUnavailable>() {
+        return None;
+    }
+    Some(job.without_opencl())
+}
+
+/// The preset to retry an out-of-memory failure with: one step faster, when
+/// `auto_degrade_on_oom` is set and the failure was classified as OOM.
+fn oom_downgrade(params: &QTGMCParameters, error: &anyhow::Error) -> Option<QTGMCPreset> {
+    if !params.auto_degrade_on_oom || !error.is::<OutOfMemory>() {
+        return None;
+    }
+    params.preset.next_faster()
+}
+
+/// vspipe failed after reporting a failed memory allocation.
+#[derive(Debug)]
+struct OutOfMemory;
+
+impl std::fmt::Display for OutOfMemory {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "vspipe ran out of memory")
+    }
+}
+
+impl std::error::Error for OutOfMemory {}
+
+/// vspipe failed on a frame the source plugin blamed on its index.
+#[derive(Debug)]
+struct StaleIndex;
+
+impl std::fmt::Display for StaleIndex {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "source index is stale or corrupt")
+    }
+}
+
+impl std::error::Error for StaleIndex {}
+
+/// vspipe failed after reporting an OpenCL device error.
+#[derive(Debug)]
+struct OpenClUnavailable;
+
+impl std::fmt::Display for OpenClUnavailable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "OpenCL device unavailable")
+    }
+}
+
+impl std::error::Error for OpenClUnavailable {}
+
+/// Frames extracted around the preview time (QTGMC needs temporal context).
+pub const PREVIEW_WINDOW_FRAMES: u64 = 11;
+
+/// Most frames a preview may extract; more only slows the preview down.
+pub const MAX_PREVIEW_FRAMES: u64 = 101;
+
+/// Check a requested preview window size: odd, so the previewed frame is the
+/// true middle, and between 1 and [`MAX_PREVIEW_FRAMES`].
+pub fn validate_preview_frames(frames: u64) -> Result<u64> {
+    if frames == 0 || frames > MAX_PREVIEW_FRAMES {
+        bail!("Preview frames must be between 1 and {}, got {}", MAX_PREVIEW_FRAMES, frames);
+    }
+    if frames.is_multiple_of(2) {
+        bail!("Preview frames must be odd so the previewed frame is in the middle, got {}", frames);
+    }
+    Ok(frames)
+}
+
+/// Frames to extract for a preview, clamped to what the source contains.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct PreviewWindow {
+    /// First source frame to extract
+    start_frame: u64,
+    /// Number of frames to extract
+    count: u64,
+    /// Index of the previewed frame within the extracted clip
+    middle: u64,
+}
+
+/// Centre a window of `requested` frames on `target_frame`, keeping it inside
+/// `total_frames` when the source length is known. A window shifted back at the
+/// end of the source still points `middle` at the target frame.
+fn preview_window(target_frame: u64, requested: u64, total_frames: Option<u64>) -> PreviewWindow {
+    let count = match total_frames {
+        Some(total) => requested.min(total).max(1),
+        None => requested.max(1),
+    };
+    let mut start_frame = target_frame.saturating_sub(count / 2);
+    if let Some(total) = total_frames {
+        start_frame = start_frame.min(total.saturating_sub(count));
+    }
+    PreviewWindow {
+        start_frame,
+        count,
+        middle: target_frame.saturating_sub(start_frame).min(count - 1),
+    }
+}
+
+/// ffmpeg arguments extracting `num_frames` frames of `input_path` from
+/// `start_time` seconds into a lossless FFV1 clip at `clip_path`.
+fn preview_extract_args(input_path: &str, start_time: f64, num_frames: u64, clip_path: &Path) -> Vec<String> {
+    [
+        "-ss", &format!("{:.3}", start_time),
+        "-i", input_path,
+        "-vframes", &num_frames.to_string(),
+        "-c:v", "ffv1",
+        "-level", "1",
+        "-an",
+        clip_path.to_string_lossy().as_ref(),
+    ].map(String::from).to_vec()
+}
 
 /// Executes the vspipe | ffmpeg pipeline.
 pub struct PipelineExecutor {
     reporter: ProgressReporter,
     deps: DependencyLocator,
-    vspipe_process: Option<Child>,
-    ffmpeg_process: Option<Child>,
+    /// Keep generated scripts and preview temp dirs for debugging
+    keep_temp: bool,
+    /// Raised to stop the running encode, lowered to continue it
+    paused: Option<Arc<AtomicBool>>,
+    /// Source index directory used by the script (see [`crate::index_cache`])
+    index_cache_dir: Option<PathBuf>,
+    /// Frames extracted around the previewed frame
+    preview_frames: u64,
+    vspipe_process: Option<ProcessTree>,
+    ffmpeg_process: Option<ProcessTree>,
 }
 
 impl PipelineExecutor {
@@ -31,19 +855,127 @@ impl PipelineExecutor {
         Ok(Self {
             reporter,
             deps,
+            keep_temp: false,
+            paused: None,
+            index_cache_dir: None,
+            preview_frames: PREVIEW_WINDOW_FRAMES,
             vspipe_process: None,
             ffmpeg_process: None,
         })
     }
 
+    /// Keep generated scripts and preview temp dirs instead of deleting them.
+    pub fn with_keep_temp(mut self, keep_temp: bool) -> Self {
+        self.keep_temp = keep_temp;
+        self
+    }
+
+    /// Pause encodes while `paused` is raised, without losing progress.
+    pub fn with_pause_flag(mut self, paused: Arc<AtomicBool>) -> Self {
+        self.paused = Some(paused);
+        self
+    }
+
+    /// Index directory the script writes to; retries reuse it, and it is
+    /// cleared when vspipe rejects a stale index.
+    pub fn with_index_cache_dir(mut self, dir: Option<&Path>) -> Self {
+        self.index_cache_dir = dir.map(Path::to_path_buf);
+        self
+    }
+
+    /// Extract `frames` frames around the previewed frame (see
+    /// [`validate_preview_frames`]); more context helps filters with a large
+    /// temporal radius settle.
+    pub fn with_preview_frames(mut self, frames: u64) -> Self {
+        self.preview_frames = frames;
+        self
+    }
+
     /// Execute the deinterlacing pipeline.
-    pub fn execute<F>(&mut self, script_path: &Path, job: &VideoJob, on_cancel: F) -> Result<()>
+    ///
+    /// If BestSource fails on a frame and asks for its index to be deleted,
+    /// the index directory is cleared and the script re-run once to reindex. If the job uses OpenCL with `opencl_fallback` set and vspipe fails with an
+    /// OpenCL device error, the script is regenerated without OpenCL and re-run.
+    /// If vspipe then runs out of memory with `auto_degrade_on_oom` set, it is
+    /// re-run once more with the next faster QTGMC preset.
+    ///
+    /// Returns the regenerated script when a fallback produced the output, so
+    /// quality measurement renders the same video that was encoded.
+    pub fn execute<F>(&mut self, script_path: &Path, job: &VideoJob, on_cancel: F) -> Result<Option<TempPath>>
+    where
+        F: Fn() -> bool,
+    {
+        let source_chroma = self.probe_pix_fmt(&job.input_path).as_deref().and_then(ChromaSubsampling::from_pix_fmt);
+        if let Some(warning) = source_chroma.and_then(|c| chroma_downsample_warning(&job.encoding_settings, c)) {
+            self.reporter.send_log(LogLevel::Warning, &warning);
+        }
+
+        let mut job = Cow::Borrowed(job);
+        let mut result = self.execute_script(script_path, &job, &on_cancel).map(|()| None);
+
+        let stale_index = result.as_ref().err().and_then(|e| stale_index_dir(self.index_cache_dir.as_deref(), e));
+        if let (Some(dir), Err(error)) = (stale_index, &result) {
+            self.reporter.send_log(LogLevel::Warning, "Source index is stale, reindexing");
+            index_cache::clear(dir)
+                .with_context(|| format!("Failed to clear the source index after: {:#}", error))?;
+            result = self.execute_script(script_path, &job, &on_cancel).map(|()| None);
+        }
+
+        let cpu_job = result.as_ref().err().and_then(|e| opencl_fallback(&job, e));
+        if let Some(cpu_job) = cpu_job {
+            self.reporter.send_log(
+                LogLevel::Warning,
+                "OpenCL device unavailable, retrying with OpenCL disabled",
+            );
+            job = Cow::Owned(cpu_job);
+            result = self.rerun(&job, "CPU fallback", &on_cancel).map(Some);
+        }
+
+        let downgrade = result.as_ref().err().and_then(|e| oom_downgrade(&job.qtgmc_parameters, e));
+        if let Some(preset) = downgrade {
+            self.reporter.send_log(
+                LogLevel::Warning,
+                &format!(
+                    "vspipe ran out of memory with QTGMC preset {}, retrying with {}",
+                    job.qtgmc_parameters.preset.as_str(),
+                    preset.as_str(),
+                ),
+            );
+            job = Cow::Owned(job.with_qtgmc_preset(preset));
+            result = self.rerun(&job, "reduced preset", &on_cancel).map(Some);
+        }
+        result
+    }
+
+    /// Generate a new script for `job` and run it, returning the script; `purpose`
+    /// names the retry in errors.
+    fn rerun<F>(&mut self, job: &VideoJob, purpose: &str, on_cancel: &F) -> Result<TempPath>
+    where
+        F: Fn() -> bool,
+    {
+        let script = ScriptGenerator::new()?
+            .with_keep_temp(self.keep_temp)
+            .with_index_cache_dir(self.index_cache_dir.as_deref())
+            .generate(job)
+            .with_context(|| format!("Failed to generate {} script", purpose))?;
+        self.execute_script(&script, job, on_cancel)?;
+        Ok(script)
+    }
+
+    /// Run a script through vspipe | ffmpeg, reporting progress.
+    fn execute_script<F>(&mut self, script_path: &Path, job: &VideoJob, on_cancel: &F) -> Result<()>
     where
         F: Fn() -> bool,
     {
         let vspipe_path = self.deps.vspipe_path()?;
         let ffmpeg_path = self.deps.ffmpeg_path()?;
-        let env = self.deps.build_environment();
+        let plan = PipelinePlan::new(
+            &vspipe_path,
+            &ffmpeg_path,
+            self.deps.build_environment(),
+            script_path,
+            &self.with_audio_language_fallback(job),
+        );
 
         self.reporter.send_log(
             LogLevel::Debug,
@@ -53,117 +985,137 @@ impl PipelineExecutor {
         // Debug: log environment
         self.reporter.send_log(
             LogLevel::Debug,
-            &format!("PYTHONHOME: {:?}", env.get("PYTHONHOME")),
+            &format!("PYTHONHOME: {:?}", plan.env.get("PYTHONHOME")),
         );
         self.reporter.send_log(
             LogLevel::Debug,
-            &format!("PYTHONPATH: {:?}", env.get("PYTHONPATH")),
+            &format!("PYTHONPATH: {:?}", plan.env.get("PYTHONPATH")),
         );
         self.reporter.send_log(
             LogLevel::Debug,
-            &format!("VAPOURSYNTH_PLUGIN_PATH: {:?}", env.get("VAPOURSYNTH_PLUGIN_PATH")),
+            &format!("VAPOURSYNTH_PLUGIN_PATH: {:?}", plan.env.get("VAPOURSYNTH_PLUGIN_PATH")),
         );
 
         // Start vspipe process
-        let mut vspipe = Command::new(&vspipe_path)
-            .args(["-c", "y4m", script_path.to_string_lossy().as_ref(), "-"])
-            .envs(&env)
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .spawn()
+        self.reporter.send_log(
+            LogLevel::Debug,
+            &format!("vspipe command: {}", quote_argv(&vspipe_path, &plan.vspipe_cmd[1..])),
+        );
+        let mut vspipe = platform::spawn_tree(
+            plan.vspipe_command()
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped()),
+        )
             .with_context(|| format!("Failed to start vspipe: {:?}", vspipe_path))?;
 
         // Get vspipe stdout for piping to ffmpeg
         let vspipe_stdout = vspipe.stdout.take().context("Failed to get vspipe stdout")?;
         let vspipe_stderr = vspipe.stderr.take().context("Failed to get vspipe stderr")?;
 
-        // Build FFmpeg arguments
-        let ffmpeg_args = self.build_ffmpeg_args(job);
+        self.reporter.send_log(
+            LogLevel::Debug,
+            &format!("ffmpeg command: {}", quote_argv(&ffmpeg_path, &plan.ffmpeg_cmd[1..])),
+        );
 
         // Start ffmpeg process
-        let mut ffmpeg = Command::new(&ffmpeg_path)
-            .args(&ffmpeg_args)
-            .envs(&env)
-            .stdin(vspipe_stdout)
-            .stdout(Stdio::null())
-            .stderr(Stdio::piped())
-            .spawn()
+        let mut ffmpeg = platform::spawn_tree(
+            plan.ffmpeg_command()
+                .stdin(vspipe_stdout)
+                .stdout(Stdio::null())
+                .stderr(Stdio::piped()),
+        )
             .with_context(|| format!("Failed to start ffmpeg: {:?}", ffmpeg_path))?;
 
         let ffmpeg_stderr = ffmpeg.stderr.take().context("Failed to get ffmpeg stderr")?;
 
+        let finalizing = Arc::new(AtomicBool::new(false));
+        let _pause_watcher = self.paused.clone().map(|paused| {
+            PauseWatcher::start(paused, finalizing.clone(), vec![vspipe.id(), ffmpeg.id()], self.reporter.clone())
+        });
+
         self.vspipe_process = Some(vspipe);
         self.ffmpeg_process = Some(ffmpeg);
 
+        let pipeline = job.effective_pipeline();
+        let deinterlace_enabled = pipeline.deinterlace.enabled;
+
         // Parse vspipe stderr for input info (in background thread)
         let total_frames = Arc::new(AtomicI32::new(0));
+        let output_frames = Arc::new(AtomicI32::new(0));
         let total_frames_clone = total_frames.clone();
+        let output_frames_clone = output_frames.clone();
         let reporter_clone = self.reporter.clone();
 
         let vspipe_thread = thread::spawn(move || {
-            let reader = BufReader::new(vspipe_stderr);
-            for line in reader.lines().map_while(Result::ok) {
-                // Log all stderr for debugging
-                reporter_clone.send_log(LogLevel::Debug, &format!("vspipe stderr: {}", line));
-
-                if line.starts_with("INPUT_INFO:") {
-                    // Parse: INPUT_INFO:frames=1234,fps_num=25,fps_den=1
-                    for part in line["INPUT_INFO:".len()..].split(',') {
-                        if let Some(frames_str) = part.strip_prefix("frames=") {
-                            if let Ok(frames) = frames_str.parse::<i32>() {
-                                total_frames_clone.store(frames, Ordering::SeqCst);
-                            }
-                        }
-                    }
+            read_vspipe_stderr(
+                BufReader::new(vspipe_stderr),
+                &reporter_clone,
+                &total_frames_clone,
+                &output_frames_clone,
+            )
+        });
+
+        // Read ffmpeg stderr in the background so the encode can be cancelled
+        // and finalizing announced while ffmpeg is quiet
+        let (record_tx, records) = mpsc::channel();
+        let ffmpeg_thread = thread::spawn(move || {
+            for record in stderr_records(BufReader::new(ffmpeg_stderr)) {
+                if record_tx.send(record).is_err() {
+                    break;
                 }
             }
         });
 
         // Parse ffmpeg stderr for progress
         let reporter = self.reporter.clone();
-        let progress_interval = Duration::from_millis(500);
-        let mut last_progress_time = Instant::now();
-        let mut current_frame = 0i32;
-        let mut current_fps = 0.0f64;
+        let mut throttle = ProgressThrottle::new(job.encoding_settings.progress_mode);
+        let mut parser = FfmpegProgressParser::default();
+
+        loop {
+            let record = match records.recv_timeout(ENCODE_POLL_INTERVAL) {
+                Ok(record) => Some(record),
+                Err(RecvTimeoutError::Timeout) => None,
+                Err(RecvTimeoutError::Disconnected) => break,
+            };
 
-        let ffmpeg_reader = BufReader::new(ffmpeg_stderr);
-        for line in ffmpeg_reader.lines().map_while(Result::ok) {
             // Check for cancellation
             if on_cancel() {
                 self.terminate();
-                bail!("Job cancelled");
+                return Err(WorkerError::Cancelled.into());
             }
 
-            // Parse ffmpeg progress output
-            // Format: frame=  123 fps= 45.0 ...
-            if line.starts_with("frame=") {
-                if let Some(frame_str) = line.split_whitespace().nth(0) {
-                    if let Some(frame_num) = frame_str.strip_prefix("frame=") {
-                        if let Ok(f) = frame_num.trim().parse::<i32>() {
-                            current_frame = f;
-                        }
-                    }
-                }
+            // Once vspipe has exited cleanly ffmpeg has every frame, but may
+            // still spend a long time writing the trailer (e.g. an MP4 index)
+            if !finalizing.load(Ordering::SeqCst)
+                && vspipe_thread.is_finished()
+                && self.vspipe_process.as_mut().is_some_and(|vspipe| exited_successfully(vspipe))
+            {
+                finalizing.store(true, Ordering::SeqCst);
+                reporter.send_encode_phase(EncodePhase::Finalizing, "Finalizing output file");
+                reporter.send_log(LogLevel::Info, "Finalizing output file (this can take a while for large files)...");
             }
-            if line.contains("fps=") {
-                for part in line.split_whitespace() {
-                    if let Some(fps_str) = part.strip_prefix("fps=") {
-                        if let Ok(f) = fps_str.trim().parse::<f64>() {
-                            current_fps = f;
-                        }
-                    }
-                }
+
+            let Some(record) = record else { continue };
+            if let Some(level) = ffmpeg_log_level(&record) {
+                reporter.send_log(level, &format!("ffmpeg: {}", record.trim()));
+                continue;
             }
 
-            // Send progress update (throttled)
-            if last_progress_time.elapsed() >= progress_interval {
-                let total = total_frames.load(Ordering::SeqCst);
-                let effective_total = if total > 0 {
-                    // Double frames for double-rate output
-                    if job.qtgmc_parameters.fps_divisor == 1 { total * 2 } else { total }
-                } else {
-                    job.total_frames.unwrap_or(0)
-                };
+            let Some(block) = parser.push(&record) else { continue };
+            let (current_frame, current_fps) = (block.frame, block.fps);
+            if block.end {
+                reporter.send_log(LogLevel::Info, &encode_summary(&block));
+            }
+
+            // Send progress update (throttled, but always for the final block)
+            if throttle.should_emit(current_frame, Instant::now()) || block.end {
+                let effective_total = effective_total_frames(
+                    output_frames.load(Ordering::SeqCst),
+                    total_frames.load(Ordering::SeqCst),
+                    job.total_frames,
+                    deinterlace_enabled,
+                    &job.qtgmc_parameters,
+                );
 
                 let eta = if current_fps > 0.0 && effective_total > current_frame {
                     ((effective_total - current_frame) as f64) / current_fps
@@ -173,12 +1125,12 @@ impl PipelineExecutor {
 
                 let progress = ProgressInfo::new(current_frame, effective_total, current_fps, eta);
                 reporter.send_progress(&progress);
-                last_progress_time = Instant::now();
             }
         }
 
         // Wait for threads to finish
-        let _ = vspipe_thread.join();
+        let _ = ffmpeg_thread.join();
+        let vspipe_errors = vspipe_thread.join().unwrap_or_default();
 
         // Wait for processes to exit
         let vspipe_status = self
@@ -200,6 +1152,18 @@ impl PipelineExecutor {
             let code = status.code().unwrap_or(-1);
             // Allow SIGTERM (130), SIGPIPE (141)
             if code != 0 && code != 130 && code != 141 {
+                if vspipe_errors.opencl_device {
+                    return Err(anyhow::Error::new(OpenClUnavailable)
+                        .context(format!("vspipe exited with code {}", code)));
+                }
+                if vspipe_errors.out_of_memory {
+                    return Err(anyhow::Error::new(OutOfMemory)
+                        .context(format!("vspipe exited with code {}", code)));
+                }
+                if vspipe_errors.stale_index {
+                    return Err(anyhow::Error::new(StaleIndex)
+                        .context(format!("vspipe exited with code {}", code)));
+                }
                 bail!("vspipe exited with code {}", code);
             }
         }
@@ -214,144 +1178,498 @@ impl PipelineExecutor {
         Ok(())
     }
 
-    /// Build FFmpeg command-line arguments.
-    fn build_ffmpeg_args(&self, job: &VideoJob) -> Vec<String> {
-        let mut args = Vec::new();
-        let settings = &job.encoding_settings;
+    /// Generate a preview frame as PNG, written to `out`.
+    ///
+    /// This extracts frames around the target time using ffmpeg (fast keyframe seek),
+    /// then processes them through VapourSynth with the filter pipeline.
+    pub fn generate_preview(&self, job: &VideoJob, time_seconds: f64, out: &mut dyn Write) -> Result<()> {
+        // Create temp directory for extracted frames (removed on drop unless kept)
+        let temp_dir = TempPath::new(
+            platform::writable_temp_dir()?.join(format!("vapourbox_preview_{}", job.id)),
+            self.keep_temp,
+        );
+        let (temp_video_path, window) = self.extract_preview_clip(job, time_seconds, &temp_dir)?;
+        let job = &*self.with_preview_field_order(job);
 
-        // Input 0: Processed video from vspipe (Y4M pipe)
-        args.extend(["-f".to_string(), "yuv4mpegpipe".to_string()]);
-        args.extend(["-i".to_string(), "-".to_string()]);
+        // Generate preview script using the script generator
+        let script_generator = ScriptGenerator::new()?.with_keep_temp(self.keep_temp);
+        let preview_params = Self::preview_params(job, &temp_video_path, window.middle);
+        let script_path = script_generator.generate_preview(job, &preview_params)?;
 
-        // Input 1: Original file for audio stream
-        // (Y4M from vspipe contains only video, so we need the original file for audio)
-        args.extend(["-i".to_string(), job.input_path.clone()]);
+        eprintln!("Generated preview script: {:?}", script_path.path());
 
-        // Progress output to stderr
-        args.extend(["-progress".to_string(), "pipe:2".to_string()]);
+        let png = self.render_preview_png(&script_path, self.preview_in_range(job))?;
+        Self::write_png(&png, out)
+    }
 
-        // Map streams: video from input 0 (processed), audio from input 1 (original)
-        args.extend(["-map".to_string(), "0:v".to_string()]);  // Video from Y4M pipe
-        args.extend(["-map".to_string(), "1:a?".to_string()]); // Audio from original (? = optional, skip if no audio)
+    /// Generate an A/B comparison preview as PNG, written to `out`.
+    ///
+    /// Both jobs must use the same input. The frames are extracted once, then
+    /// both pipelines are run in a single script and stacked side by side
+    /// (A on the left, B on the right).
+    pub fn generate_ab_preview(
+        &self,
+        job_a: &VideoJob,
+        job_b: &VideoJob,
+        time_seconds: f64,
+        out: &mut dyn Write,
+    ) -> Result<()> {
+        if job_a.input_path != job_b.input_path {
+            bail!(
+                "A/B preview requires both jobs to use the same input (got {} and {})",
+                job_a.input_path, job_b.input_path
+            );
+        }
 
-        // Video codec
-        args.extend(["-c:v".to_string(), settings.codec.ffmpeg_codec().to_string()]);
+        // Create temp directory for extracted frames (shared by both pipelines)
+        let temp_dir = TempPath::new(
+            platform::writable_temp_dir()?.join(format!("vapourbox_preview_{}", job_a.id)),
+            self.keep_temp,
+        );
+        let (temp_video_path, window) = self.extract_preview_clip(job_a, time_seconds, &temp_dir)?;
 
-        // ProRes profile
-        if let Some(profile) = settings.codec.prores_profile() {
-            args.extend(["-profile:v".to_string(), profile.to_string()]);
-        } else {
-            // Quality (CRF for H.264/H.265)
-            args.extend(["-crf".to_string(), settings.quality.to_string()]);
-            args.extend(["-preset".to_string(), settings.encoder_preset.clone()]);
-        }
+        let job_a = &*self.with_preview_field_order(job_a);
+        let job_b = &*self.with_preview_field_order(job_b);
 
-        // Audio handling
-        if settings.audio_copy {
-            args.extend(["-c:a".to_string(), "copy".to_string()]);
-        } else {
-            args.extend(["-c:a".to_string(), settings.audio_codec.clone()]);
-            args.extend(["-b:a".to_string(), format!("{}k", settings.audio_bitrate)]);
+        let script_generator = ScriptGenerator::new()?.with_keep_temp(self.keep_temp);
+        let params_a = Self::preview_params(job_a, &temp_video_path, window.middle);
+        let params_b = Self::preview_params(job_b, &temp_video_path, window.middle);
+        let script_path = script_generator.generate_ab_preview(job_a, &params_a, job_b, &params_b)?;
+
+        eprintln!("Generated A/B preview script: {:?}", script_path.path());
+
+        let png = self.render_preview_png(&script_path, self.preview_in_range(job_a))?;
+        Self::write_png(&png, out)
+    }
+
+    /// Extract frames around the target time to a lossless clip in `temp_dir`.
+    /// Returns the path to the extracted clip and the window it holds.
+    fn extract_preview_clip(&self, job: &VideoJob, time_seconds: f64, temp_dir: &Path) -> Result<(PathBuf, PreviewWindow)> {
+        let ffmpeg_path = self.deps.ffmpeg_path()?;
+
+        fs::create_dir_all(temp_dir)
+            .with_context(|| format!("Failed to create temp dir: {:?}", temp_dir))?;
+
+        // Clamp the window to the source so short clips still yield a valid middle frame
+        let frame_rate = job.input_frame_rate.unwrap_or(FrameRate::NTSC).as_f64();
+        let total_frames = self.probe_frame_count(&job.input_path, frame_rate);
+        let target_frame = (time_seconds.max(0.0) * frame_rate).round() as u64;
+        let window = preview_window(target_frame, self.preview_frames, total_frames);
+        let num_frames = window.count;
+        let start_time = window.start_frame as f64 / frame_rate;
+
+        eprintln!("Extracting {} frames starting at {:.3}s", num_frames, start_time);
+        self.reporter.send_phase(
+            PreviewPhase::Extracting,
+            &format!("Extracting {} frames at {:.3}s", num_frames, start_time),
+        );
+
+        // Extract frames to a temporary lossless video file (FFV1)
+        // Using a video file instead of images because BestSource is available but imwri is not
+        let temp_video_path = temp_dir.join("preview_clip.mkv");
+        let extract_args = preview_extract_args(&job.input_path, start_time, num_frames, &temp_video_path);
+        self.reporter.send_log(
+            LogLevel::Debug,
+            &format!("ffmpeg extract command: {}", quote_argv(&ffmpeg_path, &extract_args)),
+        );
+        let extract_result = Command::new(&ffmpeg_path)
+            .args(&extract_args)
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .output()
+            .with_context(|| "Failed to run ffmpeg for frame extraction")?;
+
+        if !extract_result.status.success() {
+            let stderr = String::from_utf8_lossy(&extract_result.stderr);
+            bail!("Failed to extract frames: {}", stderr);
         }
 
-        // Custom arguments
-        if !settings.custom_ffmpeg_args.is_empty() {
-            args.extend(settings.custom_ffmpeg_args.split_whitespace().map(String::from));
+        // Verify the file was created
+        if !temp_video_path.exists() {
+            bail!("Failed to create preview clip");
         }
 
-        // Output file (force overwrite)
-        args.push("-y".to_string());
-        args.push(job.output_path.clone());
+        eprintln!("Extracted frames to {:?}", temp_video_path);
 
-        args
+        Ok((temp_video_path, window))
     }
 
-    /// Generate a preview frame as PNG to stdout.
+    /// Query the number of video frames in `input_path` with ffprobe.
     ///
-    /// This extracts frames around the target time using ffmpeg (fast keyframe seek),
-    /// then processes them through VapourSynth with the filter pipeline.
-    pub fn generate_preview(&self, job: &VideoJob, time_seconds: f64) -> Result<()> {
-        use std::io::Write;
+    /// Uses the container frame count when present, otherwise estimates it from
+    /// the stream duration. Returns `None` if ffprobe is unavailable or reports neither.
+    fn probe_frame_count(&self, input_path: &str, frame_rate: f64) -> Option<u64> {
+        let ffprobe_path = self.deps.ffprobe_path().ok()?;
+        let output = Command::new(&ffprobe_path)
+            .args([
+                "-v", "error",
+                "-select_streams", "v:0",
+                "-show_entries", "stream=nb_frames,duration",
+                "-of", "default=noprint_wrappers=1",
+                input_path,
+            ])
+            .stderr(Stdio::null())
+            .output()
+            .ok()?;
+        if !output.status.success() {
+            return None;
+        }
 
-        let ffmpeg_path = self.deps.ffmpeg_path()?;
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let field = |name: &str| {
+            stdout.lines()
+                .find_map(|line| line.strip_prefix(name)?.strip_prefix('='))
+                .map(str::trim)
+        };
+        field("nb_frames")
+            .and_then(|v| v.parse::<u64>().ok())
+            .or_else(|| {
+                let duration = field("duration")?.parse::<f64>().ok()?;
+                Some((duration * frame_rate).round() as u64)
+            })
+    }
+
+    /// Read clip metadata (size, frame count, frame rate, format) for `input_path`
+    /// by running `vspipe --info` on a script that only loads the source,
+    /// indexing into the index cache directory when one is set.
+    pub fn probe(&self, input_path: &str) -> Result<ClipInfo> {
         let vspipe_path = self.deps.vspipe_path()?;
-        let env = self.deps.build_environment();
+        let script = script_generator::clip_probe_script(input_path, self.index_cache_dir.as_deref());
+        let script_path = TempPath::new(
+            platform::writable_temp_dir()?.join(format!("probe_{}.vpy", uuid::Uuid::new_v4())),
+            self.keep_temp,
+        );
+        fs::write(&script_path, script)
+            .with_context(|| format!("Failed to write probe script to {:?}", script_path.path()))?;
 
-        // Create temp directory for extracted frames
-        let temp_dir = std::env::temp_dir().join(format!("vapourbox_preview_{}", job.id));
-        fs::create_dir_all(&temp_dir)
-            .with_context(|| format!("Failed to create temp dir: {:?}", temp_dir))?;
+        let output = Command::new(&vspipe_path)
+            .arg("--info")
+            .arg(script_path.path())
+            .envs(self.deps.build_environment())
+            .output()
+            .with_context(|| format!("Failed to run vspipe: {:?}", vspipe_path))?;
+        if !output.status.success() {
+            bail!("vspipe --info failed: {}", String::from_utf8_lossy(&output.stderr).trim());
+        }
+        ClipInfo::from_vspipe_info(&String::from_utf8_lossy(&output.stdout))
+    }
+
+    /// Check which of the plugin `namespaces` VapourSynth actually loads, by
+    /// running a probe script with the worker's environment. A plugin file
+    /// that is present but fails to load is reported as missing.
+    pub fn list_plugins(&self, namespaces: &[&str]) -> Result<Vec<PluginStatus>> {
+        let vspipe_path = self.deps.vspipe_path()?;
+        let script_path = TempPath::new(
+            platform::writable_temp_dir()?.join(format!("plugins_{}.vpy", uuid::Uuid::new_v4())),
+            self.keep_temp,
+        );
+        fs::write(&script_path, script_generator::plugin_probe_script(namespaces))
+            .with_context(|| format!("Failed to write plugin probe script to {:?}", script_path.path()))?;
 
-        // Number of frames to extract (need enough for QTGMC temporal processing)
-        let num_frames = 11; // Extract 11 frames, use middle one
-        let frame_rate = job.input_frame_rate.unwrap_or(29.97);
-        let frame_duration = 1.0 / frame_rate;
+        let output = Command::new(&vspipe_path)
+            .arg("--info")
+            .arg(script_path.path())
+            .envs(self.deps.build_environment())
+            .output()
+            .with_context(|| format!("Failed to run vspipe: {:?}", vspipe_path))?;
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if !output.status.success() {
+            bail!("vspipe --info failed: {}", stderr.trim());
+        }
+        parse_plugin_report(&stderr, namespaces)
+    }
 
-        // Calculate start time (go back half the frames)
-        let start_time = (time_seconds - (num_frames as f64 / 2.0) * frame_duration).max(0.0);
+    /// ffmpeg `in_range` for converting `job`'s preview frames to RGB,
+    /// probing the source's tagged color range for `PreviewRange::Auto`.
+    fn preview_in_range(&self, job: &VideoJob) -> &'static str {
+        let tagged = match job.preview_range {
+            PreviewRange::Auto => self.probe_color_range(&job.input_path),
+            _ => None,
+        };
+        job.preview_range.in_range(tagged.as_deref())
+    }
 
-        eprintln!("Extracting {} frames starting at {:.3}s", num_frames, start_time);
+    /// Clear `audio_language` when no audio stream in the input is tagged with it,
+    /// so the output keeps all audio instead of none.
+    ///
+    /// If ffprobe is unavailable the language map is kept as requested.
+    fn with_audio_language_fallback<'a>(&self, job: &'a VideoJob) -> Cow<'a, VideoJob> {
+        let Some(language) = job.encoding_settings.audio_language.as_deref() else {
+            return Cow::Borrowed(job);
+        };
+        let Some(languages) = self.probe_audio_languages(&job.input_path) else {
+            return Cow::Borrowed(job);
+        };
+        if languages.is_empty() || languages.iter().any(|l| l.eq_ignore_ascii_case(language)) {
+            return Cow::Borrowed(job);
+        }
 
-        // Extract frames to a temporary lossless video file (FFV1)
-        // Using a video file instead of images because ffms2 is available but imwri is not
-        let temp_video_path = temp_dir.join("preview_clip.mkv");
-        let extract_result = Command::new(&ffmpeg_path)
+        self.reporter.send_log(
+            LogLevel::Warning,
+            &format!(
+                "No audio stream tagged '{}' (found: {}), keeping all audio",
+                language,
+                languages.iter().map(|l| if l.is_empty() { "untagged" } else { l }).collect::<Vec<_>>().join(", "),
+            ),
+        );
+        let mut job = job.clone();
+        job.encoding_settings.audio_language = None;
+        Cow::Owned(job)
+    }
+
+    /// Query the language tag of each audio stream in `input_path` with ffprobe
+    /// (empty for untagged streams).
+    fn probe_audio_languages(&self, input_path: &str) -> Option<Vec<String>> {
+        let ffprobe_path = self.deps.ffprobe_path().ok()?;
+        let output = Command::new(&ffprobe_path)
+            .args([
+                "-v", "error",
+                "-select_streams", "a",
+                "-show_entries", "stream_tags=language",
+                "-of", "csv=p=0",
+                input_path,
+            ])
+            .stderr(Stdio::null())
+            .output()
+            .ok()?;
+        if !output.status.success() {
+            return None;
+        }
+        Some(String::from_utf8_lossy(&output.stdout).lines().map(|l| l.trim().to_string()).collect())
+    }
+
+    /// Query the pixel format (e.g. `yuv422p`) of the first video stream in `input_path` with ffprobe.
+    fn probe_pix_fmt(&self, input_path: &str) -> Option<String> {
+        let ffprobe_path = self.deps.ffprobe_path().ok()?;
+        let output = Command::new(&ffprobe_path)
+            .args([
+                "-v", "error",
+                "-select_streams", "v:0",
+                "-show_entries", "stream=pix_fmt",
+                "-of", "csv=p=0",
+                input_path,
+            ])
+            .stderr(Stdio::null())
+            .output()
+            .ok()?;
+        output.status.success().then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+
+    /// Query the tagged color range (`tv`, `pc`, `unknown`) of `input_path` with ffprobe.
+    fn probe_color_range(&self, input_path: &str) -> Option<String> {
+        let ffprobe_path = self.deps.ffprobe_path().ok()?;
+        let output = Command::new(&ffprobe_path)
+            .args([
+                "-v", "error",
+                "-select_streams", "v:0",
+                "-show_entries", "stream=color_range",
+                "-of", "csv=p=0",
+                input_path,
+            ])
+            .stderr(Stdio::null())
+            .output()
+            .ok()?;
+        output.status.success().then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+
+    /// Detect the field order of the first video stream in `input_path` with ffprobe.
+    pub fn detect_field_order(&self, input_path: &str) -> Result<FieldOrder> {
+        let ffprobe_path = self.deps.ffprobe_path()?;
+        let output = Command::new(&ffprobe_path)
+            .args([
+                "-v", "error",
+                "-select_streams", "v:0",
+                "-show_entries", "stream=field_order",
+                "-of", "csv=p=0",
+                input_path,
+            ])
+            .output()
+            .with_context(|| format!("Failed to run ffprobe: {:?}", ffprobe_path))?;
+        if !output.status.success() {
+            bail!("ffprobe failed: {}", String::from_utf8_lossy(&output.stderr).trim());
+        }
+        Ok(FieldOrder::from_ffprobe(&String::from_utf8_lossy(&output.stdout)))
+    }
+
+    /// Decode the first moments of `input_path` with ffmpeg, failing with the
+    /// decoder's errors if the source is corrupt or unreadable.
+    ///
+    /// Broken headers otherwise only surface when vspipe first decodes a
+    /// frame, after the source has been indexed.
+    pub fn check_decodable(&self, input_path: &str) -> Result<()> {
+        let ffmpeg_path = self.deps.ffmpeg_path()?;
+        let output = Command::new(&ffmpeg_path)
+            .args(decode_probe_args(input_path))
+            .stdin(Stdio::null())
+            .output()
+            .with_context(|| format!("Failed to run ffmpeg: {:?}", ffmpeg_path))?;
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let progress = String::from_utf8_lossy(&output.stdout);
+        match parse_decode_errors(&stderr, &progress, output.status.success()) {
+            Ok(warnings) => {
+                for warning in warnings {
+                    self.reporter.send_log(LogLevel::Warning, &format!("Source decode: {}", warning));
+                }
+                Ok(())
+            }
+            Err(errors) => bail!("Source can't be decoded ({}):\n{}", input_path, errors),
+        }
+    }
+
+    /// Check that this ffmpeg build's encoder for `settings.codec` accepts the
+    /// pixel format for `output_chroma` (e.g. libx264 built without 4:2:2).
+    /// Passes when no output chroma is set or the encoder doesn't list its formats.
+    pub fn check_output_chroma(&self, settings: &EncodingSettings) -> Result<()> {
+        let (Some(chroma), Some(pix_fmt)) = (settings.output_chroma, settings.pix_fmt()) else {
+            return Ok(());
+        };
+        let ffmpeg_path = self.deps.ffmpeg_path()?;
+        let encoder = settings.codec.ffmpeg_codec();
+        let output = Command::new(&ffmpeg_path)
+            .args(["-hide_banner", "-h", &format!("encoder={}", encoder)])
+            .stdin(Stdio::null())
+            .output()
+            .with_context(|| format!("Failed to run ffmpeg: {:?}", ffmpeg_path))?;
+        match parse_encoder_pix_fmts(&String::from_utf8_lossy(&output.stdout)) {
+            Some(formats) if !formats.contains(&pix_fmt) => bail!(
+                "This ffmpeg build's {} can't encode {} ({}); supported formats: {}",
+                encoder,
+                chroma.display_name(),
+                pix_fmt,
+                formats.join(" ")
+            ),
+            _ => Ok(()),
+        }
+    }
+
+    /// Find the keyframe indices (in display order) of the first video stream
+    /// in `input_path` with ffprobe, from the packet flags.
+    pub fn probe_keyframes(&self, input_path: &str) -> Result<Vec<i32>> {
+        let ffprobe_path = self.deps.ffprobe_path()?;
+        let output = Command::new(&ffprobe_path)
             .args([
-                "-ss", &format!("{:.3}", start_time),
-                "-i", &job.input_path,
-                "-vframes", &num_frames.to_string(),
-                "-c:v", "ffv1",
-                "-level", "1",
-                "-an",
-                temp_video_path.to_string_lossy().as_ref(),
+                "-v", "error",
+                "-select_streams", "v:0",
+                "-show_entries", "packet=pts,flags",
+                "-of", "csv=p=0",
+                input_path,
             ])
+            .output()
+            .with_context(|| format!("Failed to run ffprobe: {:?}", ffprobe_path))?;
+        if !output.status.success() {
+            bail!("ffprobe failed: {}", String::from_utf8_lossy(&output.stderr).trim());
+        }
+        Ok(parse_keyframes(&String::from_utf8_lossy(&output.stdout)))
+    }
+
+    /// Measure the encoded output against the processed video from `script_path`.
+    ///
+    /// Renders the script again and compares it with the output file using
+    /// ffmpeg's ssim and psnr filters.
+    pub fn measure_quality(&self, script_path: &Path, job: &VideoJob) -> Result<QualityReport> {
+        let vspipe_path = self.deps.vspipe_path()?;
+        let ffmpeg_path = self.deps.ffmpeg_path()?;
+        let env = self.deps.build_environment();
+
+        let mut vspipe = Command::new(&vspipe_path)
+            .args(["-c", "y4m"])
+            .arg(script_path)
+            .arg("-")
+            .envs(&env)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .with_context(|| format!("Failed to start vspipe: {:?}", vspipe_path))?;
+        let vspipe_stdout = vspipe.stdout.take().context("Failed to get vspipe stdout")?;
+
+        let ffmpeg_args = build_quality_args(&job.output_path);
+        self.reporter.send_log(
+            LogLevel::Debug,
+            &format!("quality command: {}", quote_argv(&ffmpeg_path, &ffmpeg_args)),
+        );
+        let output = Command::new(&ffmpeg_path)
+            .args(&ffmpeg_args)
+            .envs(&env)
+            .stdin(vspipe_stdout)
             .stdout(Stdio::null())
             .stderr(Stdio::piped())
             .output()
-            .with_context(|| "Failed to run ffmpeg for frame extraction")?;
+            .with_context(|| format!("Failed to run ffmpeg: {:?}", ffmpeg_path))?;
+        let _ = vspipe.wait();
 
-        if !extract_result.status.success() {
-            let stderr = String::from_utf8_lossy(&extract_result.stderr);
-            // Clean up
-            let _ = fs::remove_dir_all(&temp_dir);
-            bail!("Failed to extract frames: {}", stderr);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if !output.status.success() {
+            bail!("Quality measurement failed: {}", stderr.lines().last().unwrap_or("").trim());
+        }
+        let report = parse_quality_report(&stderr);
+        if report.psnr.is_none() && report.ssim.is_none() {
+            bail!("ffmpeg reported no PSNR/SSIM results");
         }
+        Ok(report)
+    }
 
-        // Verify the file was created
-        if !temp_video_path.exists() {
-            let _ = fs::remove_dir_all(&temp_dir);
-            bail!("Failed to create preview clip");
+    /// The job with its field order detected when neither the job nor the app
+    /// supplied one, so preview fields are marked correctly. Warns when the
+    /// order stays unknown and the preview falls back to TFF.
+    fn with_preview_field_order<'a>(&self, job: &'a VideoJob) -> Cow<'a, VideoJob> {
+        if preview_tff(job).is_some() {
+            return Cow::Borrowed(job);
         }
+        match self.detect_field_order(&job.input_path) {
+            Ok(order) if order.tff_value().is_some() => {
+                let mut job = job.clone();
+                job.detected_field_order = Some(order);
+                Cow::Owned(job.resolved())
+            }
+            Ok(_) | Err(_) => {
+                eprintln!("Warning: field order unknown; preview assumes top field first");
+                Cow::Borrowed(job)
+            }
+        }
+    }
 
-        eprintln!("Extracted frames to {:?}", temp_video_path);
+    /// Build the preview script parameters for a job and an extracted clip.
+    fn preview_params(job: &VideoJob, clip_path: &Path, middle_frame: u64) -> PreviewParams {
+        let frame_rate = job.input_frame_rate.unwrap_or(FrameRate::NTSC);
 
-        // Determine field order for interlaced content
-        let field_based = if job.qtgmc_parameters.tff == Some(true) {
+        // Field order for interlaced content; unknown order defaults to TFF
+        let field_based = if preview_tff(job).unwrap_or(true) {
             2 // TFF
         } else {
             1 // BFF
         };
 
-        // Generate preview script using the script generator
-        let script_generator = ScriptGenerator::new()?;
-        let preview_params = PreviewParams {
-            video_path: temp_video_path.to_string_lossy().to_string(),
-            fps_num: (frame_rate * 1000.0) as i32,
-            fps_den: 1000,
+        PreviewParams {
+            video_path: clip_path.to_string_lossy().to_string(),
+            fps_num: frame_rate.num() as i32,
+            fps_den: frame_rate.den() as i32,
             field_based,
-        };
+            middle_frame,
+        }
+    }
 
-        let script_path = script_generator.generate_preview(job, &preview_params)?;
+    /// Run a preview script through vspipe and encode the output frame as PNG,
+    /// converting from `in_range` (`tv` or `pc`) to full-range RGB.
+    fn render_preview_png(&self, script_path: &Path, in_range: &str) -> Result<Vec<u8>> {
+        let ffmpeg_path = self.deps.ffmpeg_path()?;
+        let vspipe_path = self.deps.vspipe_path()?;
+        let env = self.deps.build_environment();
 
-        eprintln!("Generated preview script: {:?}", script_path);
+        // The source filter indexes the clip before vspipe produces any output
+        self.reporter.send_phase(PreviewPhase::Indexing, "Indexing preview clip");
 
         // Run vspipe on the preview script (outputs single frame)
+        let vspipe_args = [
+            "-c", "y4m",
+            script_path.to_string_lossy().as_ref(),
+            "-",
+        ].map(String::from);
+        self.reporter.send_log(
+            LogLevel::Debug,
+            &format!("vspipe command: {}", quote_argv(&vspipe_path, &vspipe_args)),
+        );
         let mut vspipe = Command::new(&vspipe_path)
-            .args([
-                "-c", "y4m",
-                script_path.to_string_lossy().as_ref(),
-                "-",
-            ])
+            .args(&vspipe_args)
             .envs(&env)
             .stdout(Stdio::piped())
             .stderr(Stdio::piped())
@@ -362,16 +1680,22 @@ impl PipelineExecutor {
         let vspipe_stderr = vspipe.stderr.take();
 
         // Start ffmpeg to encode as PNG to stdout
+        let scale = format!("scale=in_range={}:out_range=pc", in_range);
+        let ffmpeg_args = [
+            "-f", "yuv4mpegpipe",
+            "-i", "-",
+            "-vframes", "1",
+            "-vf", &scale,
+            "-f", "image2pipe",
+            "-vcodec", "png",
+            "-",
+        ].map(String::from);
+        self.reporter.send_log(
+            LogLevel::Debug,
+            &format!("ffmpeg command: {}", quote_argv(&ffmpeg_path, &ffmpeg_args)),
+        );
         let ffmpeg = Command::new(&ffmpeg_path)
-            .args([
-                "-f", "yuv4mpegpipe",
-                "-i", "-",
-                "-vframes", "1",
-                "-vf", "scale=in_range=tv:out_range=pc",
-                "-f", "image2pipe",
-                "-vcodec", "png",
-                "-",
-            ])
+            .args(&ffmpeg_args)
             .envs(&env)
             .stdin(vspipe_stdout)
             .stdout(Stdio::piped())
@@ -380,12 +1704,21 @@ impl PipelineExecutor {
             .with_context(|| format!("Failed to start ffmpeg: {:?}", ffmpeg_path))?;
 
         // Read vspipe stderr in background for error messages
+        let reporter = self.reporter.clone();
         let stderr_thread = if let Some(stderr) = vspipe_stderr {
             Some(thread::spawn(move || {
                 let reader = BufReader::new(stderr);
                 let mut errors = Vec::new();
+                let mut processing_reported = false;
                 for line in reader.lines().map_while(Result::ok) {
+                    // vspipe prints the script info once the source is loaded and filters are built
+                    if !processing_reported && line.starts_with("INPUT_INFO:") {
+                        reporter.send_phase(PreviewPhase::Processing, "Processing preview frame");
+                        processing_reported = true;
+                    }
                     if !line.starts_with("INPUT_INFO:") &&
+                       !line.starts_with("DEPTH_CONVERT:") &&
+                       !line.starts_with("DEBLOCK_FALLBACK:") &&
                        !line.starts_with("Loaded template") &&
                        !line.trim().is_empty() {
                         errors.push(line);
@@ -400,16 +1733,16 @@ impl PipelineExecutor {
         // Wait for vspipe to finish
         let vspipe_status = vspipe.wait().context("Failed to wait for vspipe")?;
 
+        // Join the stderr reader so its phase messages precede the encoding phase
+        let errors = stderr_thread.and_then(|t| t.join().ok()).unwrap_or_default();
+
+        self.reporter.send_phase(PreviewPhase::Encoding, "Encoding preview PNG");
+
         // Read PNG output from ffmpeg
         let output = ffmpeg.wait_with_output().context("Failed to wait for ffmpeg")?;
 
-        // Clean up temp files
-        let _ = fs::remove_dir_all(&temp_dir);
-        let _ = fs::remove_file(&script_path);
-
         // Check for errors
         if !vspipe_status.success() {
-            let errors = stderr_thread.map(|t| t.join().ok()).flatten().unwrap_or_default();
             if !errors.is_empty() {
                 bail!("vspipe failed: {}", errors.join("\n"));
             }
@@ -420,20 +1753,24 @@ impl PipelineExecutor {
             bail!("ffmpeg exited with code {}", output.status.code().unwrap_or(-1));
         }
 
-        // Write PNG to stdout
-        std::io::stdout().write_all(&output.stdout)?;
-        std::io::stdout().flush()?;
+        Ok(output.stdout)
+    }
+
+    /// Write PNG data to the preview output.
+    fn write_png(png: &[u8], out: &mut dyn Write) -> Result<()> {
+        out.write_all(png).context("Failed to write preview PNG")?;
+        out.flush().context("Failed to flush preview PNG")?;
 
         Ok(())
     }
 
-    /// Terminate both processes.
+    /// Kill both process trees.
     fn terminate(&mut self) {
-        if let Some(ref mut vspipe) = self.vspipe_process {
-            let _ = vspipe.kill();
-        }
-        if let Some(ref mut ffmpeg) = self.ffmpeg_process {
-            let _ = ffmpeg.kill();
+        // Whole trees, so no helper process keeps encoding after a cancel
+        for process in [&mut self.vspipe_process, &mut self.ffmpeg_process].into_iter().flatten() {
+            if let Err(e) = platform::kill_tree(process) {
+                self.reporter.send_log(LogLevel::Debug, &format!("{:#}", e));
+            }
         }
     }
 }
@@ -447,75 +1784,580 @@ impl Drop for PipelineExecutor {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::models::{EncodingSettings, QTGMCParameters, VideoCodec, ContainerFormat};
+    use crate::models::{QTGMCParameters, VideoCodec, ContainerFormat, OverlaySpec, FfmpegLogLevel, RestorationPipeline};
     use uuid::Uuid;
 
-    /// Helper to build FFmpeg args without requiring a full PipelineExecutor.
-    /// This mirrors the logic in PipelineExecutor::build_ffmpeg_args for testing.
-    fn build_ffmpeg_args_for_test(job: &VideoJob) -> Vec<String> {
-        let mut args = Vec::new();
-        let settings = &job.encoding_settings;
+    fn create_test_job(output_path: &str) -> VideoJob {
+        VideoJob {
+            id: Uuid::new_v4(),
+            input_path: "input.mp4".to_string(),
+            output_path: output_path.to_string(),
+            output_template: None,
+            qtgmc_parameters: QTGMCParameters::default(),
+            restoration_pipeline: None,
+            encoding_settings: EncodingSettings::default(),
+            detected_field_order: None,
+            total_frames: None,
+            input_frame_rate: None,
+            start_frame: None,
+            end_frame: None,
+            source_threads: None,
+            source_seek_preroll: None,
+            vs_max_cache_mb: None,
+            create_output_dirs: true,
+            measure_quality: false,
+            preview_range: PreviewRange::Auto,
+            warn_unnecessary_deinterlace: true,
+            check_disk_space: false,
+            keyframes_only: false,
+            keyframes: None,
+            on_complete_command: None,
+        }
+    }
+
+    fn plan_for(job: &VideoJob, script: &str) -> PipelinePlan {
+        let env = HashMap::from([("PYTHONHOME".to_string(), "/deps/python".to_string())]);
+        PipelinePlan::new(Path::new("/deps/vspipe"), Path::new("/deps/ffmpeg"), env, Path::new(script), job)
+    }
 
-        // Input 0: Processed video from vspipe (Y4M pipe)
-        args.extend(["-f".to_string(), "yuv4mpegpipe".to_string()]);
-        args.extend(["-i".to_string(), "-".to_string()]);
+    #[test]
+    fn test_pipeline_plan_for_typical_job() {
+        let mut job = create_test_job("output.mkv");
+        job.encoding_settings.codec = VideoCodec::H264;
+        let plan = plan_for(&job, "/tmp/job.vpy");
+
+        assert_eq!(plan.vspipe_cmd, ["/deps/vspipe", "-c", "y4m", "/tmp/job.vpy", "-"]);
+        assert_eq!(plan.ffmpeg_cmd[0], "/deps/ffmpeg");
+        assert_eq!(plan.ffmpeg_cmd[1..], build_ffmpeg_args(&job)[..]);
+        assert_eq!(plan.ffmpeg_cmd[1..5], ["-f", "yuv4mpegpipe", "-i", "-"]);
+        assert_eq!(plan.ffmpeg_cmd[plan.ffmpeg_cmd.len() - 2..], ["-y", "output.mkv"]);
+        assert_eq!(plan.env.get("PYTHONHOME").map(String::as_str), Some("/deps/python"));
+
+        let command = plan.vspipe_command();
+        assert_eq!(command.get_program(), "/deps/vspipe");
+        assert_eq!(command.get_args().count(), 4);
+        assert_eq!(command.get_envs().count(), 1);
+    }
 
-        // Input 1: Original file for audio stream
-        args.extend(["-i".to_string(), job.input_path.clone()]);
+    #[test]
+    fn test_pipeline_plan_for_opencl_fallback_rerun() {
+        // The CPU fallback is the only second vspipe | ffmpeg run: same encode,
+        // different script.
+        let mut job = create_test_job("output.mkv");
+        job.qtgmc_parameters.opencl = true;
+        let first = plan_for(&job, "/tmp/job.vpy");
+        let rerun = plan_for(&job.without_opencl(), "/tmp/job_cpu.vpy");
+
+        assert_eq!(rerun.vspipe_cmd[3], "/tmp/job_cpu.vpy");
+        assert_eq!(rerun.ffmpeg_cmd, first.ffmpeg_cmd);
+        assert_eq!(rerun.env, first.env);
+    }
 
-        // Progress output to stderr
-        args.extend(["-progress".to_string(), "pipe:2".to_string()]);
+    #[test]
+    fn test_parse_dep_versions() {
+        let line = r#"DEP_VERSIONS:{"havsfunc": "sha256:0123456789ab", "vapoursynth": "70"}"#;
+        let versions = parse_dep_versions(line).unwrap();
+        assert_eq!(versions.get("vapoursynth").map(String::as_str), Some("70"));
+        assert_eq!(versions.get("havsfunc").map(String::as_str), Some("sha256:0123456789ab"));
+
+        assert!(parse_dep_versions("INPUT_INFO:frames=10,fps_num=30000,fps_den=1001").is_none());
+        assert!(parse_dep_versions("DEP_VERSIONS:not json").is_none());
+    }
 
-        // Map streams: video from input 0 (processed), audio from input 1 (original)
-        args.extend(["-map".to_string(), "0:v".to_string()]);
-        args.extend(["-map".to_string(), "1:a?".to_string()]);
+    #[test]
+    fn test_opencl_device_error_classification() {
+        assert!(is_opencl_device_error("vapoursynth.Error: NNEDI3CL: No OpenCL device available"));
+        assert!(is_opencl_device_error("OpenCL error: clGetPlatformIDs failed (-1001)"));
+        assert!(is_opencl_device_error("Error: CL_DEVICE_NOT_AVAILABLE"));
+
+        assert!(!is_opencl_device_error("vapoursynth.Error: Resize error: invalid dimensions"));
+        assert!(!is_opencl_device_error("INPUT_INFO:frames=10,fps_num=30000,fps_den=1001"));
+        assert!(!is_opencl_device_error(r#"DEP_VERSIONS:{"nnedi3cl": "error-handling-build"}"#));
+    }
 
-        // Video codec
-        args.extend(["-c:v".to_string(), settings.codec.ffmpeg_codec().to_string()]);
+    #[test]
+    fn test_opencl_fallback_decision() {
+        let mut job = create_test_job("output.mkv");
+        job.qtgmc_parameters.opencl = true;
+        job.restoration_pipeline = Some(RestorationPipeline::from_legacy(&job.qtgmc_parameters));
+        let unavailable = anyhow::Error::new(OpenClUnavailable).context("vspipe exited with code 1");
+
+        let cpu = opencl_fallback(&job, &unavailable).expect("OpenCL failures fall back to the CPU");
+        assert!(!cpu.qtgmc_parameters.opencl);
+        assert!(!cpu.restoration_pipeline.unwrap().deinterlace.opencl);
+
+        let other = anyhow::anyhow!("vspipe exited with code 1");
+        assert!(opencl_fallback(&job, &other).is_none(), "Only OpenCL failures are retried");
+        let off = VideoJob { qtgmc_parameters: QTGMCParameters { opencl_fallback: false, ..job.qtgmc_parameters.clone() }, ..job.clone() };
+        assert!(opencl_fallback(&off, &unavailable).is_none(), "Fallback is opt-out");
+        let cpu_only = VideoJob { qtgmc_parameters: QTGMCParameters { opencl: false, ..job.qtgmc_parameters.clone() }, ..job };
+        assert!(opencl_fallback(&cpu_only, &unavailable).is_none(), "Nothing to fall back from");
+    }
 
-        // ProRes profile
-        if let Some(profile) = settings.codec.prores_profile() {
-            args.extend(["-profile:v".to_string(), profile.to_string()]);
-        } else {
-            // Quality (CRF for H.264/H.265)
-            args.extend(["-crf".to_string(), settings.quality.to_string()]);
-            args.extend(["-preset".to_string(), settings.encoder_preset.clone()]);
-        }
+    #[test]
+    fn test_oom_downgrade_decision() {
+        let stderr = "INPUT_INFO:frames=100,fps_num=25,fps_den=1\n\
+            vapoursynth.Error: mv.Analyse: failed to allocate 2147483648 bytes (out of memory)\n";
+        let errors = read_vspipe_stderr(stderr.as_bytes(), &ProgressReporter::disabled(), &AtomicI32::new(0), &AtomicI32::new(0));
+        assert_eq!(errors, VspipeErrors { out_of_memory: true, ..VspipeErrors::default() });
+        assert!(is_out_of_memory_error("terminate called after throwing an instance of 'std::bad_alloc'"));
+        assert!(!is_out_of_memory_error("vapoursynth.Error: Resize error: invalid dimensions"));
+
+        let oom = anyhow::Error::new(OutOfMemory).context("vspipe exited with code 1");
+        let params = QTGMCParameters {
+            preset: QTGMCPreset::Placebo,
+            auto_degrade_on_oom: true,
+            ..QTGMCParameters::default()
+        };
+        assert_eq!(oom_downgrade(&params, &oom), Some(QTGMCPreset::VerySlow));
+
+        let other = anyhow::anyhow!("vspipe exited with code 1");
+        assert_eq!(oom_downgrade(&params, &other), None, "Only OOM failures are retried");
+        let off = QTGMCParameters { auto_degrade_on_oom: false, ..params.clone() };
+        assert_eq!(oom_downgrade(&off, &oom), None, "Retry is opt-in");
+        let fastest = QTGMCParameters { preset: QTGMCPreset::Draft, ..params };
+        assert_eq!(oom_downgrade(&fastest, &oom), None, "Nothing faster than Draft");
+    }
 
-        // Audio handling
-        if settings.audio_copy {
-            args.extend(["-c:a".to_string(), "copy".to_string()]);
-        } else {
-            args.extend(["-c:a".to_string(), settings.audio_codec.clone()]);
-            args.extend(["-b:a".to_string(), format!("{}k", settings.audio_bitrate)]);
+    #[test]
+    fn test_stale_index_reindex_decision() {
+        let stderr = "Error: Failed to retrieve frame 120 with error: Linear decoding returned a bad frame, \
+            this should be impossible so I'll just return nothing now. \
+            Try deleting the index and using threads=1 if you haven't already done so.\n";
+        let errors = read_vspipe_stderr(stderr.as_bytes(), &ProgressReporter::disabled(), &AtomicI32::new(0), &AtomicI32::new(0));
+        assert_eq!(errors, VspipeErrors { stale_index: true, ..VspipeErrors::default() });
+        assert!(!is_stale_index_error("vapoursynth.Error: VideoSource: Indexing of 'in.mkv' track #0 failed"));
+        assert!(!is_stale_index_error("vapoursynth.Error: VideoSource: Failed to write index to '/cache/in.bsindex'"));
+
+        let dir = Path::new("/cache/index/abc");
+        let stale = anyhow::Error::new(StaleIndex).context("vspipe exited with code 1");
+        assert_eq!(stale_index_dir(Some(dir), &stale), Some(dir));
+        assert_eq!(stale_index_dir(None, &stale), None, "Nothing to clear without a known directory");
+        let other = anyhow::anyhow!("vspipe exited with code 1");
+        assert_eq!(stale_index_dir(Some(dir), &other), None, "Only stale index failures are retried");
+    }
+
+    #[test]
+    fn test_quality_args_and_parsing() {
+        let args = build_quality_args("/tmp/out put.mkv");
+        let input = |i: usize| args.iter().enumerate().filter(|(_, a)| *a == "-i").nth(i).map(|(p, _)| &args[p + 1]);
+        assert_eq!(input(0).map(String::as_str), Some("-"));
+        assert_eq!(input(1).map(String::as_str), Some("/tmp/out put.mkv"));
+        let graph = &args[args.iter().position(|a| a == "-lavfi").unwrap() + 1];
+        assert!(graph.contains("[enc0][ref0]ssim") && graph.contains("[enc1][ref1]psnr"));
+        assert_eq!(&args[args.len() - 3..], ["-f", "null", "-"]);
+
+        let stderr = "frame=  100 fps=50 q=-0.0 Lsize=N/A\n\
+            [Parsed_ssim_4 @ 0x6000] SSIM Y:0.987654 (19.083) U:0.991 (20.4) V:0.990 (20.0) All:0.988812 (19.512)\n\
+            [Parsed_psnr_5 @ 0x6001] PSNR y:44.12 u:46.20 v:46.01 average:44.803917 min:40.10 max:49.90\n";
+        let report = parse_quality_report(stderr);
+        assert_eq!(report.ssim, Some(0.988812));
+        assert_eq!(report.psnr, Some(44.803917));
+
+        let lossless = parse_quality_report("[Parsed_psnr_5 @ 0x1] PSNR y:inf u:inf v:inf average:inf min:inf max:inf");
+        assert_eq!(lossless.psnr, Some(f64::INFINITY));
+        assert_eq!(lossless.ssim, None);
+        assert_eq!(parse_quality_report("no metrics here"), QualityReport::default());
+    }
+
+    #[test]
+    fn test_ffmpeg_progress_blocks() {
+        let stderr = "Input #0, yuv4mpegpipe, from 'fd:':\n\
+            frame=200\nfps=50.00\nstream_0_0_q=28.0\nbitrate=2046.0kbits/s\ntotal_size=1048576\n\
+            out_time_us=8000000\nout_time=00:00:08.000000\ndup_frames=0\ndrop_frames=0\nspeed=2.0x\nprogress=continue\n\
+            frame=  190 fps= 48.0 q=28.0 size=    2048kB dup=0 drop=0 speed=1.9x\r\
+            frame=250\nfps=50.50\nbitrate=N/A\ntotal_size=2097152\nout_time_us=N/A\nspeed=2.02x\nprogress=end\n";
+
+        let mut parser = FfmpegProgressParser::default();
+        let blocks: Vec<FfmpegProgress> = stderr_records(stderr.as_bytes())
+            .filter_map(|record| parser.push(&record))
+            .collect();
+        assert_eq!(blocks, vec![
+            FfmpegProgress {
+                frame: 200,
+                fps: 50.0,
+                out_time_us: Some(8_000_000),
+                total_size: Some(1_048_576),
+                bitrate_kbps: Some(2046.0),
+                speed: Some(2.0),
+                end: false,
+            },
+            FfmpegProgress {
+                frame: 250,
+                fps: 50.5,
+                out_time_us: None,
+                total_size: Some(2_097_152),
+                bitrate_kbps: None,
+                speed: Some(2.02),
+                end: true,
+            },
+        ], "The stats line must not leak into a block");
+
+        assert_eq!(encode_summary(&blocks[1]), "Encoded 250 frames, 2.1 MB (2.02x real time)");
+    }
+
+    #[test]
+    fn test_stderr_records_split_carriage_returns() {
+        let stderr = "Input #0, yuv4mpegpipe, from 'fd:':\n\
+            frame=   10 fps=0.0 q=-0.0 size=       0kB time=00:00:00.40 bitrate=N/A speed=0.8x\r\
+            frame=  123 fps= 45.0 q=28.0 size=    1024kB time=00:00:04.10 bitrate=2046.0kbits/s dup=2 drop=1 speed=1.5x\r";
+        let records: Vec<String> = stderr_records(stderr.as_bytes()).collect();
+        assert_eq!(records.len(), 3);
+        assert!(records[2].starts_with("frame=  123"));
+    }
+
+    #[test]
+    fn test_ffmpeg_stderr_routing() {
+        let stderr = "[matroska @ 0x7f8] [warning] Codec for stream 1 does not use global headers\n\
+            frame=12\nfps=24.00\nprogress=continue\n\
+            [aac @ 0x7f9] [error] Too many bits per frame requested\n\
+            [fatal] Conversion failed!\n\
+            [info] Stream mapping:\n\
+            [debug] Opening an output file\n\
+            frame=   10 fps=0.0 q=-0.0 size=       0kB speed=0.8x\r";
+        let levels: Vec<Option<LogLevel>> = stderr_records(stderr.as_bytes())
+            .map(|record| ffmpeg_log_level(&record))
+            .collect();
+        assert_eq!(levels, [
+            Some(LogLevel::Warning),
+            None,
+            None,
+            None,
+            Some(LogLevel::Error),
+            Some(LogLevel::Error),
+            Some(LogLevel::Info),
+            Some(LogLevel::Debug),
+            None,
+        ]);
+    }
+
+    #[test]
+    fn test_pause_state_transitions() {
+        let mut state = PauseState::default();
+        assert_eq!(state.update(false), None, "Running and not paused: nothing to do");
+        assert_eq!(state.update(true), Some(PauseTransition::Pause));
+        assert_eq!(state.update(true), None, "Already paused");
+        assert_eq!(state.update(false), Some(PauseTransition::Resume));
+        assert_eq!(state.update(false), None, "Already running");
+        assert_eq!(state.update(true), Some(PauseTransition::Pause), "Pausing again after resume");
+    }
+
+    #[test]
+    fn test_resume_reports_current_encode_phase() {
+        assert_eq!(PauseTransition::Pause.phase(false).0, EncodePhase::Paused);
+        assert_eq!(PauseTransition::Pause.phase(true).0, EncodePhase::Paused);
+        assert_eq!(PauseTransition::Resume.phase(false).0, EncodePhase::Encoding);
+        assert_eq!(PauseTransition::Resume.phase(true).0, EncodePhase::Finalizing);
+    }
+
+    #[test]
+    fn test_vspipe_output_end_does_not_announce_finalizing() {
+        use crate::models::WorkerMessage;
+
+        let reporter = ProgressReporter::capturing();
+        let (total_frames, output_frames) = (AtomicI32::new(0), AtomicI32::new(0));
+        let stderr = "INPUT_INFO:frames=100,fps_num=25,fps_den=1\nOUTPUT_INFO:frames=160\nScript evaluation done\n";
+        assert_eq!(
+            read_vspipe_stderr(stderr.as_bytes(), &reporter, &total_frames, &output_frames),
+            VspipeErrors::default()
+        );
+        assert_eq!(total_frames.load(Ordering::SeqCst), 100);
+        assert_eq!(output_frames.load(Ordering::SeqCst), 160);
+
+        // Finalizing waits for vspipe's exit status, not the end of its stderr
+        assert!(!reporter.captured().iter().any(|m| matches!(m, WorkerMessage::Phase { .. })));
+
+        // The Deblock_QED stand-in is surfaced as a warning
+        let reporter = ProgressReporter::capturing();
+        let stderr = "DEBLOCK_FALLBACK:havsfunc has no Deblock_QED; using core.deblock.Deblock(quant=25) instead\n";
+        read_vspipe_stderr(stderr.as_bytes(), &reporter, &AtomicI32::new(0), &AtomicI32::new(0));
+        assert!(reporter.captured().iter().any(|m| matches!(
+            m,
+            WorkerMessage::Log { level, message } if level == "warning" && message.starts_with("havsfunc has no Deblock_QED")
+        )));
+
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_generate_preview_emits_phases_in_order() {
+        use crate::models::WorkerMessage;
+        use std::os::unix::fs::PermissionsExt;
+
+        // Stand-in tools: ffmpeg writes the extracted clip or passes the frame through,
+        // vspipe reports its script info and emits one frame, ffprobe knows nothing
+        let deps = tempfile::tempdir().unwrap();
+        let platform_dir = deps.path().join(DependencyLocator::at(deps.path().to_path_buf()).platform_suffix());
+        let tools = [
+            ("ffmpeg/ffmpeg", "case \"$*\" in *image2pipe*) cat ;; *) for last; do :; done; : > \"$last\" ;; esac"),
+            ("ffmpeg/ffprobe", "exit 1"),
+            ("vapoursynth/vspipe", "echo INPUT_INFO:frames=1,fps_num=25,fps_den=1 >&2; printf frame"),
+        ];
+        for (name, body) in tools {
+            let path = platform_dir.join(name);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, format!("#!/bin/sh\n{}\n", body)).unwrap();
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
         }
 
-        // Custom arguments
-        if !settings.custom_ffmpeg_args.is_empty() {
-            args.extend(settings.custom_ffmpeg_args.split_whitespace().map(String::from));
+        let reporter = ProgressReporter::capturing();
+        let mut executor = PipelineExecutor::new(reporter.clone()).unwrap();
+        executor.deps = DependencyLocator::at(deps.path().to_path_buf());
+
+        let mut png = Vec::new();
+        executor.generate_preview(&create_test_job("out.mkv"), 1.0, &mut png).unwrap();
+        assert_eq!(png, b"frame");
+
+        let phases: Vec<String> = reporter.captured().into_iter()
+            .filter_map(|m| match m {
+                WorkerMessage::Phase { phase, .. } => Some(phase),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(phases, ["extracting", "indexing", "processing", "encoding"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_finalizing_requires_successful_vspipe_exit() {
+        let exited = |script: &str| {
+            let mut child = Command::new("sh").args(["-c", script]).spawn().unwrap();
+            child.wait().unwrap();
+            exited_successfully(&mut child)
+        };
+        assert!(exited("exit 0"));
+        assert!(!exited("exit 1"), "A failed script is not finalizing");
+
+        let mut running = Command::new("sh").args(["-c", "sleep 5"]).spawn().unwrap();
+        assert!(!exited_successfully(&mut running), "Still running");
+        running.kill().unwrap();
+        running.wait().unwrap();
+        assert!(!exited_successfully(&mut running), "Killed");
+    }
+
+    #[test]
+    fn test_progress_throttle_every_n_frames() {
+        let now = Instant::now();
+        let mut throttle = ProgressThrottle::new(ProgressMode::EveryNFrames(10));
+        let emitted: Vec<i32> = (0..=35).filter(|&frame| throttle.should_emit(frame, now)).collect();
+        assert_eq!(emitted, vec![10, 20, 30]);
+
+        // Coarse records emit once per boundary crossed
+        let mut throttle = ProgressThrottle::new(ProgressMode::EveryNFrames(10));
+        let emitted: Vec<i32> = [0, 7, 14, 21, 28, 35, 60].into_iter().filter(|&frame| throttle.should_emit(frame, now)).collect();
+        assert_eq!(emitted, vec![14, 21, 35, 60]);
+
+        let mut throttle = ProgressThrottle::new(ProgressMode::TimeMs(500));
+        let start = throttle.last_time;
+        assert!(!throttle.should_emit(100, start + Duration::from_millis(499)));
+        assert!(throttle.should_emit(100, start + Duration::from_millis(500)));
+        assert!(!throttle.should_emit(200, start + Duration::from_millis(900)), "Interval restarts after an update");
+    }
+
+    #[test]
+    fn test_effective_total_frames() {
+        let interlaced = QTGMCParameters::default();
+        assert_eq!(effective_total_frames(0, 100, None, true, &interlaced), 200);
+        // Deinterlacing disabled: output matches source
+        assert_eq!(effective_total_frames(0, 100, None, false, &interlaced), 100);
+
+        // Single-rate output
+        let single_rate = QTGMCParameters { fps_divisor: 2, ..QTGMCParameters::default() };
+        assert_eq!(effective_total_frames(0, 100, None, true, &single_rate), 100);
+
+        // Progressive input never doubles, even with fps_divisor == 1
+        let progressive = QTGMCParameters { input_type: 1, ..QTGMCParameters::default() };
+        assert_eq!(effective_total_frames(0, 100, None, true, &progressive), 100);
+
+        // Unknown source count falls back to the job's total
+        assert_eq!(effective_total_frames(0, 0, Some(500), true, &interlaced), 500);
+        assert_eq!(effective_total_frames(0, 0, None, true, &interlaced), 0);
+
+        // The script's filtered count (e.g. after dedup) is exact and beats the estimate
+        assert_eq!(effective_total_frames(157, 100, Some(500), true, &interlaced), 157);
+    }
+
+    #[test]
+    fn test_parse_info_frames() {
+        let input = "INPUT_INFO:frames=1234,fps_num=25,fps_den=1";
+        assert_eq!(parse_info_frames(input, "INPUT_INFO:"), Some(1234));
+        assert_eq!(parse_info_frames(input, "OUTPUT_INFO:"), None);
+        assert_eq!(parse_info_frames("OUTPUT_INFO:frames=2468", "OUTPUT_INFO:"), Some(2468));
+        assert_eq!(parse_info_frames("OUTPUT_INFO:frames=", "OUTPUT_INFO:"), None);
+        assert_eq!(parse_info_frames("vspipe: OUTPUT_INFO:frames=10", "OUTPUT_INFO:"), None);
+    }
+
+    #[test]
+    fn test_decode_probe() {
+        assert_eq!(
+            decode_probe_args("tape 1.avi"),
+            [
+                "-v", "error", "-nostdin", "-nostats", "-progress", "pipe:1", "-i", "tape 1.avi",
+                "-map", "0:v:0", "-t", "0.1", "-f", "null", "-",
+            ]
+        );
+
+        let decoded = "frame=0\nprogress=continue\nframe=3\nprogress=end\n";
+        assert_eq!(parse_decode_errors("", decoded, true), Ok(vec![]));
+        assert_eq!(parse_decode_errors("\n  \n", decoded, true), Ok(vec![]));
+
+        // Decoder noise on a source that still decodes is only a warning
+        let stderr = "[h264 @ 0x7f] Invalid NAL unit size (1234 > 567).\n\
+                      [h264 @ 0x7f] Error splitting the input into NAL units.\n";
+        assert_eq!(
+            parse_decode_errors(stderr, decoded, true),
+            Ok(vec![
+                "[h264 @ 0x7f] Invalid NAL unit size (1234 > 567).".to_string(),
+                "[h264 @ 0x7f] Error splitting the input into NAL units.".to_string(),
+            ])
+        );
+
+        // No frame decoded fails even when ffmpeg exits cleanly
+        assert_eq!(
+            parse_decode_errors(stderr, "frame=0\nprogress=end\n", true).unwrap_err(),
+            "[h264 @ 0x7f] Invalid NAL unit size (1234 > 567).\n[h264 @ 0x7f] Error splitting the input into NAL units."
+        );
+        assert_eq!(parse_decode_errors("", "", true).unwrap_err(), "no video frame could be decoded");
+
+        assert_eq!(
+            parse_decode_errors("tape.avi: Invalid data found when processing input\n", "", false).unwrap_err(),
+            "tape.avi: Invalid data found when processing input"
+        );
+        assert_eq!(parse_decode_errors("", decoded, false).unwrap_err(), "ffmpeg exited with an error");
+    }
+
+    #[test]
+    fn test_parse_encoder_pix_fmts() {
+        let help = "Encoder libx264 [libx264 H.264 / AVC / MPEG-4 AVC / MPEG-4 part 10]:\n    \
+                    General capabilities: dr1 delay threads \n    \
+                    Supported pixel formats: yuv420p yuvj420p yuv422p yuvj422p yuv444p nv12\n";
+        let formats = parse_encoder_pix_fmts(help).unwrap();
+        assert!(formats.contains(&"yuv422p".to_string()));
+        assert_eq!(formats.len(), 6);
+        assert_eq!(parse_encoder_pix_fmts("Codec 'libx264' is not recognized by FFmpeg."), None);
+    }
+
+    #[test]
+    fn test_preview_field_based_follows_detected_order() {
+        let clip = Path::new("clip.mkv");
+        let mut job = create_test_job("output.mkv");
+        job.detected_field_order = Some(FieldOrder::TopFieldFirst);
+        assert_eq!(job.qtgmc_parameters.tff, None);
+        assert_eq!(PipelineExecutor::preview_params(&job, clip, 0).field_based, 2);
+
+        job.detected_field_order = Some(FieldOrder::BottomFieldFirst);
+        assert_eq!(PipelineExecutor::preview_params(&job, clip, 0).field_based, 1);
+
+        // An explicit tff wins over detection
+        job.qtgmc_parameters.tff = Some(true);
+        assert_eq!(PipelineExecutor::preview_params(&job, clip, 0).field_based, 2);
+
+        // Unknown order no longer silently becomes BFF
+        job.qtgmc_parameters.tff = None;
+        job.detected_field_order = None;
+        assert_eq!(preview_tff(&job), None);
+        assert_eq!(PipelineExecutor::preview_params(&job, clip, 0).field_based, 2);
+    }
+
+    #[test]
+    fn test_preview_params_use_exact_frame_rate() {
+        let clip = Path::new("clip.mkv");
+        let mut job = create_test_job("output.mkv");
+        job.input_frame_rate = serde_json::from_str("29.97").unwrap();
+        let params = PipelineExecutor::preview_params(&job, clip, 0);
+        assert_eq!((params.fps_num, params.fps_den), (30000, 1001));
+
+        job.input_frame_rate = serde_json::from_str("\"25/1\"").unwrap();
+        let params = PipelineExecutor::preview_params(&job, clip, 0);
+        assert_eq!((params.fps_num, params.fps_den), (25, 1));
+    }
+
+    #[test]
+    fn test_preview_params_carry_window_middle() {
+        let window = preview_window(2, PREVIEW_WINDOW_FRAMES, Some(7));
+        let job = create_test_job("output.mkv");
+        let params = PipelineExecutor::preview_params(&job, Path::new("clip.mkv"), window.middle);
+        assert_eq!(params.middle_frame, 2);
+    }
+
+    #[test]
+    fn test_preview_window_clamped_to_short_clip() {
+        // A 3-frame clip cannot supply the 11-frame window
+        let window = preview_window(1, PREVIEW_WINDOW_FRAMES, Some(3));
+        assert_eq!(window, PreviewWindow { start_frame: 0, count: 3, middle: 1 });
+        assert!(window.middle < window.count);
+
+        // Near the end of a long clip the window is shifted back, not truncated,
+        // and still previews the requested frame
+        let window = preview_window(998, PREVIEW_WINDOW_FRAMES, Some(1000));
+        assert_eq!(window, PreviewWindow { start_frame: 989, count: 11, middle: 9 });
+
+        // Unknown length keeps the full window centred on the target
+        let window = preview_window(100, PREVIEW_WINDOW_FRAMES, None);
+        assert_eq!(window, PreviewWindow { start_frame: 95, count: 11, middle: 5 });
+
+        // An empty probe result still yields a valid single-frame window
+        assert_eq!(preview_window(0, PREVIEW_WINDOW_FRAMES, Some(0)).count, 1);
+    }
+
+    #[test]
+    fn test_configured_preview_frames_reach_extraction() {
+        assert_eq!(validate_preview_frames(31).unwrap(), 31);
+        assert_eq!(validate_preview_frames(1).unwrap(), 1);
+        for invalid in [0, 12, MAX_PREVIEW_FRAMES + 2] {
+            assert!(validate_preview_frames(invalid).is_err(), "{} should be rejected", invalid);
         }
 
-        // Output file (force overwrite)
-        args.push("-y".to_string());
-        args.push(job.output_path.clone());
+        let frames = validate_preview_frames(31).unwrap();
+        let window = preview_window(100, frames, Some(1000));
+        assert_eq!(window, PreviewWindow { start_frame: 85, count: 31, middle: 15 });
+        let args = preview_extract_args("tape.avi", window.start_frame as f64 / 25.0, window.count, Path::new("clip.mkv"));
+        assert_eq!(args[..6], ["-ss", "3.400", "-i", "tape.avi", "-vframes", "31"]);
 
-        args
+        // Clamped to a short source
+        let window = preview_window(2, frames, Some(7));
+        let args = preview_extract_args("tape.avi", 0.0, window.count, Path::new("clip.mkv"));
+        assert!(args.windows(2).any(|w| w == ["-vframes", "7"]));
     }
 
-    fn create_test_job(output_path: &str) -> VideoJob {
-        VideoJob {
-            id: Uuid::new_v4(),
-            input_path: "input.mp4".to_string(),
-            output_path: output_path.to_string(),
-            qtgmc_parameters: QTGMCParameters::default(),
-            restoration_pipeline: None,
-            encoding_settings: EncodingSettings::default(),
-            detected_field_order: None,
-            total_frames: None,
-            input_frame_rate: None,
-            start_frame: None,
-            end_frame: None,
+    #[test]
+    fn test_quote_argv_quotes_args_with_spaces() {
+        let args = vec![
+            "-i".to_string(),
+            "/Users/me/My Videos/tape 1.avi".to_string(),
+            "-y".to_string(),
+            String::new(),
+        ];
+        let quoted = quote_argv(Path::new("/opt/ffmpeg"), &args);
+        assert_eq!(quoted, "/opt/ffmpeg -i '/Users/me/My Videos/tape 1.avi' -y ''");
+    }
+
+    #[test]
+    fn test_quote_argv_quotes_shell_metacharacters() {
+        let args = [
+            "scale=in_range=tv:out_range=pc",
+            "out$1.mkv",
+            "a;b",
+            "*.png",
+            "it's",
+            "(x)",
+        ].map(String::from);
+        let quoted = quote_argv(Path::new("ffmpeg"), &args);
+        assert_eq!(quoted, r#"ffmpeg scale=in_range=tv:out_range=pc 'out$1.mkv' 'a;b' '*.png' 'it'\''s' '(x)'"#);
+    }
+
+    #[test]
+    fn test_preview_png_written_to_given_path() {
+        let path = std::env::temp_dir().join(format!("vapourbox_preview_out_{}.png", Uuid::new_v4()));
+        let png = b"\x89PNG\r\n\x1a\nfake";
+
+        {
+            let mut out = preview_output(Some(&path)).unwrap();
+            PipelineExecutor::write_png(png, &mut *out).unwrap();
         }
+
+        assert_eq!(fs::read(&path).unwrap(), png);
+        let _ = fs::remove_file(&path);
     }
 
     #[test]
@@ -532,7 +2374,7 @@ mod tests {
         let mut job = create_test_job("output.mp4");
         job.encoding_settings.audio_copy = true;
 
-        let args = build_ffmpeg_args_for_test(&job);
+        let args = build_ffmpeg_args(&job);
 
         // Find the audio codec argument
         let audio_codec_idx = args.iter().position(|a| a == "-c:a");
@@ -552,6 +2394,21 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_ffmpeg_args_audio_language_map() {
+        let mut job = create_test_job("/output/video.mkv");
+        let args = build_ffmpeg_args(&job);
+        assert!(args.windows(2).any(|w| w[0] == "-map" && w[1] == "1:a?"), "All audio by default");
+
+        job.encoding_settings.audio_language = Some("jpn".to_string());
+        let args = build_ffmpeg_args(&job);
+        assert!(
+            args.windows(2).any(|w| w[0] == "-map" && w[1] == "1:a:m:language:jpn?"),
+            "Audio should be selected by language tag: {:?}", args
+        );
+        assert!(!args.contains(&"1:a?".to_string()), "Only the language map should select audio");
+    }
+
     #[test]
     fn test_ffmpeg_args_audio_reencode_uses_codec_and_bitrate() {
         let mut job = create_test_job("output.mp4");
@@ -559,7 +2416,7 @@ mod tests {
         job.encoding_settings.audio_codec = "aac".to_string();
         job.encoding_settings.audio_bitrate = 256;
 
-        let args = build_ffmpeg_args_for_test(&job);
+        let args = build_ffmpeg_args(&job);
 
         // Find the audio codec argument
         let audio_codec_idx = args.iter().position(|a| a == "-c:a");
@@ -588,7 +2445,7 @@ mod tests {
     #[test]
     fn test_ffmpeg_args_contains_input_and_output() {
         let job = create_test_job("output_test.mp4");
-        let args = build_ffmpeg_args_for_test(&job);
+        let args = build_ffmpeg_args(&job);
 
         // Check for yuv4mpegpipe input (from vspipe)
         assert!(
@@ -621,10 +2478,10 @@ mod tests {
     fn test_ffmpeg_args_video_codec_h264() {
         let mut job = create_test_job("output.mp4");
         job.encoding_settings.codec = VideoCodec::H264;
-        job.encoding_settings.quality = 18;
+        job.encoding_settings.quality = Some(18);
         job.encoding_settings.encoder_preset = "medium".to_string();
 
-        let args = build_ffmpeg_args_for_test(&job);
+        let args = build_ffmpeg_args(&job);
 
         // Check video codec
         let video_codec_idx = args.iter().position(|a| a == "-c:v");
@@ -642,12 +2499,228 @@ mod tests {
         assert_eq!(args[preset_idx.unwrap() + 1], "medium");
     }
 
+    #[test]
+    fn test_ffmpeg_args_prores_variants() {
+        let mut job = create_test_job("output.mov");
+        job.encoding_settings.codec = VideoCodec::ProResHQ;
+        job.encoding_settings.quality = Some(18);
+        job.encoding_settings.max_bitrate_kbps = Some(50_000);
+
+        // Profile only: CRF, preset and VBV don't apply to ProRes
+        let args = build_ffmpeg_args(&job);
+        assert!(args.windows(2).any(|w| w == ["-profile:v", "3"]));
+        for flag in ["-crf", "-preset", "-maxrate", "-qscale:v", "-bits_per_mb"] {
+            assert!(!args.contains(&flag.to_string()), "{} should not be set", flag);
+        }
+
+        // Quantizer and bitrate alongside the profile
+        job.encoding_settings.prores_qscale = Some(9);
+        job.encoding_settings.prores_bits_per_mb = Some(4000);
+        let args = build_ffmpeg_args(&job);
+        let profile = args.iter().position(|a| a == "-profile:v").unwrap();
+        assert_eq!(args[profile..profile + 6], ["-profile:v", "3", "-qscale:v", "9", "-bits_per_mb", "4000"]);
+        assert!(!args.contains(&"-crf".to_string()));
+
+        // Custom flags still reach the ProRes encoder
+        job.encoding_settings.custom_ffmpeg_args = "-vendor apl0".to_string();
+        let args = build_ffmpeg_args(&job);
+        assert!(args.windows(2).any(|w| w == ["-vendor", "apl0"]));
+
+        // ProRes options are ignored for other codecs
+        job.encoding_settings.codec = VideoCodec::H264;
+        let args = build_ffmpeg_args(&job);
+        assert!(!args.contains(&"-qscale:v".to_string()) && !args.contains(&"-profile:v".to_string()));
+        assert!(args.windows(2).any(|w| w == ["-crf", "18"]));
+    }
+
+    #[test]
+    fn test_ffmpeg_args_vbv_with_crf_for_h264() {
+        let mut job = create_test_job("output.mp4");
+        job.encoding_settings.codec = VideoCodec::H264;
+        job.encoding_settings.max_bitrate_kbps = Some(8000);
+
+        let args = build_ffmpeg_args(&job);
+
+        assert!(args.contains(&"-crf".to_string()), "VBV is used alongside CRF");
+        let maxrate_idx = args.iter().position(|a| a == "-maxrate").expect("-maxrate should be set");
+        assert_eq!(args[maxrate_idx + 1], "8000k");
+        let bufsize_idx = args.iter().position(|a| a == "-bufsize").expect("-bufsize should be set");
+        assert_eq!(args[bufsize_idx + 1], "16000k", "bufsize defaults to twice the max bitrate");
+    }
+
+    #[test]
+    fn test_ffmpeg_args_vbv_ignored_for_ffv1() {
+        let mut job = create_test_job("output.avi");
+        job.encoding_settings.codec = VideoCodec::FFV1;
+        job.encoding_settings.max_bitrate_kbps = Some(8000);
+        job.encoding_settings.bufsize_kbps = Some(4000);
+
+        let args = build_ffmpeg_args(&job);
+
+        assert!(!args.contains(&"-maxrate".to_string()));
+        assert!(!args.contains(&"-bufsize".to_string()));
+    }
+
+    #[test]
+    fn test_ffmpeg_args_output_sar() {
+        let mut job = create_test_job("output.mkv");
+        let args = build_ffmpeg_args(&job);
+        assert!(!args.contains(&"-vf".to_string()), "SAR passes through by default");
+
+        job.encoding_settings.output_sar = Some((10, 11));
+        let args = build_ffmpeg_args(&job);
+        let vf_idx = args.iter().position(|a| a == "-vf").expect("Should set SAR");
+        assert_eq!(args[vf_idx + 1], "setsar=10/11");
+    }
+
+    #[test]
+    fn test_ffmpeg_args_overlay() {
+        let mut job = create_test_job("output.mkv");
+        assert!(!build_ffmpeg_args(&job).contains(&"-filter_complex".to_string()), "No overlay by default");
+
+        job.encoding_settings.overlay = Some(OverlaySpec { path: "logo.png".to_string(), x: -20, y: 16, opacity: 0.5 });
+        job.encoding_settings.output_sar = Some((10, 11));
+        let args = build_ffmpeg_args(&job);
+        let inputs: Vec<&String> = args.windows(2).filter(|w| w[0] == "-i").map(|w| &w[1]).collect();
+        assert_eq!(inputs, ["-", "input.mp4", "logo.png"], "Overlay follows the audio source");
+
+        let graph_idx = args.iter().position(|a| a == "-filter_complex").expect("Should composite the overlay");
+        assert_eq!(
+            args[graph_idx + 1],
+            "[2:v]format=rgba,colorchannelmixer=aa=0.5[logo];[0:v][logo]overlay=x=main_w-overlay_w-20:y=16:format=auto,setsar=10/11[vout]"
+        );
+        assert!(!args.contains(&"-vf".to_string()), "SAR is tagged inside the filtergraph");
+        assert!(args.windows(2).any(|w| w == ["-map", "[vout]"]));
+        assert!(args.windows(2).any(|w| w == ["-map", "1:a?"]), "Audio still comes from input 1");
+        assert!(!args.windows(2).any(|w| w == ["-map", "0:v"]));
+
+        job.encoding_settings.overlay = Some(OverlaySpec { path: "logo.png".to_string(), x: 8, y: -8, opacity: 1.0 });
+        job.encoding_settings.output_sar = None;
+        let args = build_ffmpeg_args(&job);
+        let graph_idx = args.iter().position(|a| a == "-filter_complex").unwrap();
+        assert_eq!(args[graph_idx + 1], "[0:v][2:v]overlay=x=8:y=main_h-overlay_h-8:format=auto[vout]");
+    }
+
+    #[test]
+    fn test_ffmpeg_args_audio_resample_only_when_transcoding() {
+        let mut job = create_test_job("output.mkv");
+        job.encoding_settings.audio_sample_rate = Some(48000);
+        job.encoding_settings.audio_channels = Some(2);
+
+        job.encoding_settings.audio_copy = true;
+        let args = build_ffmpeg_args(&job);
+        assert!(!args.contains(&"-ar".to_string()), "Copied audio can't be resampled");
+        assert!(!args.contains(&"-ac".to_string()), "Copied audio can't be downmixed");
+
+        job.encoding_settings.audio_copy = false;
+        let args = build_ffmpeg_args(&job);
+        assert!(args.windows(2).any(|w| w == ["-ar", "48000"]));
+        assert!(args.windows(2).any(|w| w == ["-ac", "2"]));
+    }
+
+    #[test]
+    fn test_parse_plugin_report() {
+        let stderr = "Some warning\nPLUGINS:{\"mv\": true, \"neo_f3kdb\": false}\n";
+        let report = parse_plugin_report(stderr, &["mv", "neo_f3kdb", "tdm"]).unwrap();
+        let loaded: Vec<(&str, bool)> = report.iter().map(|s| (s.namespace.as_str(), s.loaded)).collect();
+        assert_eq!(loaded, vec![("mv", true), ("neo_f3kdb", false), ("tdm", false)]);
+        assert!(parse_plugin_report("Script evaluation failed", &["mv"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_keyframes() {
+        // Decode order I P B B P B B I: display order puts the B-frames first
+        let csv = "0,K__\n3000,___\n1000,___\n2000,___\n6000,___\n4000,___\n5000,___\n7000,K_\n";
+        assert_eq!(parse_keyframes(csv), vec![0, 7]);
+        assert_eq!(parse_keyframes("N/A,K__\nN/A,___\nN/A,K__\n"), vec![0, 2]);
+        assert!(parse_keyframes("").is_empty());
+
+        let mut job = create_test_job("output.mkv");
+        job.keyframes_only = true;
+        let args = build_ffmpeg_args(&job);
+        assert!(args.contains(&"-an".to_string()), "A keyframe proxy has no audio");
+        assert!(!args.contains(&"1:a?".to_string()));
+    }
+
+    #[test]
+    fn test_ffmpeg_args_embed_pipeline_metadata() {
+        let mut job = create_test_job("output.mkv");
+        assert!(!build_ffmpeg_args(&job).contains(&"-metadata".to_string()), "Off by default");
+
+        job.encoding_settings.embed_pipeline_metadata = true;
+        let args = build_ffmpeg_args(&job);
+        let idx = args.iter().position(|a| a == "-metadata").expect("Should tag the output");
+        assert_eq!(args[idx + 1], format!("comment=VapourBox: {}", job.effective_pipeline().describe()));
+        assert!(args[idx + 1].contains("QTGMC"), "{}", args[idx + 1]);
+        assert!(idx < args.len() - 2, "Metadata must precede the output path");
+    }
+
+    #[test]
+    fn test_ffmpeg_args_faststart() {
+        let mut job = create_test_job("output.mp4");
+        assert!(!build_ffmpeg_args(&job).contains(&"-movflags".to_string()), "Off by default");
+
+        job.encoding_settings = EncodingSettings::for_web();
+        let args = build_ffmpeg_args(&job);
+        assert!(args.windows(2).any(|w| w == ["-movflags", "+faststart"]));
+        assert!(args.windows(2).any(|w| w == ["-crf", "20"]));
+    }
+
+    #[test]
+    fn test_ffmpeg_args_loglevel() {
+        let mut job = create_test_job("output.mkv");
+        let args = build_ffmpeg_args(&job);
+        let idx = args.iter().position(|a| a == "-loglevel").expect("FFmpeg args should set -loglevel");
+        assert_eq!(args[idx + 1], "level+error");
+        assert!(idx < args.iter().position(|a| a == "-y").unwrap(), "-loglevel is a global option");
+
+        job.encoding_settings.ffmpeg_loglevel = FfmpegLogLevel::Warning;
+        let args = build_ffmpeg_args(&job);
+        assert!(args.windows(2).any(|w| w == ["-loglevel", "level+warning"]));
+    }
+
+    #[test]
+    fn test_ffmpeg_args_output_fps() {
+        let mut job = create_test_job("output.mkv");
+        assert!(!build_ffmpeg_args(&job).contains(&"-r".to_string()), "No rate tag by default");
+
+        job.encoding_settings.output_fps = Some((30000, 1001));
+        let args = build_ffmpeg_args(&job);
+        let r_idx = args.iter().position(|a| a == "-r").expect("Should tag the frame rate");
+        assert_eq!(args[r_idx + 1], "30000/1001");
+        let last_input = args.iter().rposition(|a| a == "-i").unwrap();
+        assert!(r_idx > last_input, "-r must be an output option, after the inputs");
+    }
+
+    #[test]
+    fn test_ffmpeg_args_pix_fmt_and_downsample_warning() {
+        let mut job = create_test_job("output.mov");
+        job.encoding_settings.codec = VideoCodec::ProRes422;
+        let args = build_ffmpeg_args(&job);
+        assert!(args.windows(2).any(|w| w == ["-pix_fmt", "yuv422p10le"]));
+
+        job.encoding_settings.codec = VideoCodec::FFV1;
+        assert!(!build_ffmpeg_args(&job).contains(&"-pix_fmt".to_string()), "FFV1 keeps the pipe format");
+
+        let settings = |codec| EncodingSettings { codec, ..EncodingSettings::default() };
+        let warning = chroma_downsample_warning(&settings(VideoCodec::H264), ChromaSubsampling::Yuv422).unwrap();
+        assert!(warning.contains("4:2:2") && warning.contains("4:2:0"), "{}", warning);
+        assert_eq!(chroma_downsample_warning(&settings(VideoCodec::H264), ChromaSubsampling::Yuv420), None);
+        assert_eq!(chroma_downsample_warning(&settings(VideoCodec::ProRes422), ChromaSubsampling::Yuv422), None);
+        assert_eq!(chroma_downsample_warning(&settings(VideoCodec::FFV1), ChromaSubsampling::Yuv444), None);
+
+        job.encoding_settings.codec = VideoCodec::H264;
+        job.encoding_settings.output_chroma = Some(ChromaSubsampling::Yuv422);
+        assert!(build_ffmpeg_args(&job).windows(2).any(|w| w == ["-pix_fmt", "yuv422p"]));
+        assert_eq!(chroma_downsample_warning(&job.encoding_settings, ChromaSubsampling::Yuv422), None);
+    }
+
     #[test]
     fn test_ffmpeg_args_video_codec_ffv1_lossless() {
         let mut job = create_test_job("output.avi");
         job.encoding_settings.codec = VideoCodec::FFV1;
 
-        let args = build_ffmpeg_args_for_test(&job);
+        let args = build_ffmpeg_args(&job);
 
         // Check video codec
         let video_codec_idx = args.iter().position(|a| a == "-c:v");
@@ -656,5 +2729,6 @@ mod tests {
             args[video_codec_idx.unwrap() + 1], "ffv1",
             "FFV1 codec should be used for lossless encoding"
         );
+        assert!(!args.contains(&"-crf".to_string()), "FFV1 is lossless and takes no CRF");
     }
 }
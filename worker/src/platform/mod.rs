@@ -12,3 +12,376 @@ pub use macos::*;
 
 #[cfg(target_os = "windows")]
 pub use windows::*;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command};
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+
+use anyhow::{bail, Context, Result};
+
+/// Get a writable directory for temporary scripts and preview clips.
+///
+/// Prefers the system temp directory and falls back to the platform cache
+/// directory when temp is not writable (e.g. in sandboxed environments).
+pub fn writable_temp_dir() -> Result<PathBuf> {
+    writable_dir_from(&std::env::temp_dir(), cache_dir().as_deref())
+}
+
+/// Return the first writable directory of `primary` and `fallback`.
+///
+/// Directories are created if missing. Fails with an error naming every
+/// location attempted when none of them can be written to.
+pub fn writable_dir_from(primary: &Path, fallback: Option<&Path>) -> Result<PathBuf> {
+    let mut attempts = Vec::new();
+
+    for dir in std::iter::once(primary).chain(fallback) {
+        match probe_writable(dir) {
+            Ok(()) => return Ok(dir.to_path_buf()),
+            Err(e) => attempts.push(format!("{} ({})", dir.display(), e)),
+        }
+    }
+
+    if fallback.is_none() {
+        attempts.push("platform cache directory (unavailable)".to_string());
+    }
+
+    bail!(
+        "No writable temporary directory found. Tried:\n  {}\n\
+         Check permissions or set TMPDIR/TEMP to a writable location.",
+        attempts.join("\n  ")
+    )
+}
+
+/// Path in the form external tools should receive it.
+///
+/// On Windows, absolute paths at or beyond `MAX_PATH` get the extended-length
+/// `\\?\` prefix; elsewhere the path is returned unchanged.
+pub fn long_path(path: &str) -> String {
+    #[cfg(target_os = "windows")]
+    {
+        windows::extended_length_path(path)
+    }
+    #[cfg(not(target_os = "windows"))]
+    {
+        path.to_string()
+    }
+}
+
+/// Toggle `paused` each time the worker receives SIGUSR1.
+///
+/// SIGUSR1 is blocked for the calling thread and the threads it starts later,
+/// and a listener thread takes it instead, so call this before starting any
+/// other threads.
+#[cfg(unix)]
+pub fn toggle_on_sigusr1(paused: Arc<AtomicBool>) -> Result<()> {
+    use nix::sys::signal::{SigSet, Signal};
+    use std::sync::atomic::Ordering;
+
+    let mut signals = SigSet::empty();
+    signals.add(Signal::SIGUSR1);
+    signals.thread_block().context("Failed to block SIGUSR1")?;
+    std::thread::spawn(move || {
+        while signals.wait().is_ok() {
+            paused.fetch_xor(true, Ordering::SeqCst);
+        }
+    });
+    Ok(())
+}
+
+/// Toggle `paused` on a signal; not available on this platform.
+#[cfg(not(unix))]
+pub fn toggle_on_sigusr1(_paused: Arc<AtomicBool>) -> Result<()> {
+    bail!("Pausing by signal is not supported on this platform")
+}
+
+/// Stop (`suspend`) or continue the process `pid`, keeping its state.
+#[cfg(unix)]
+pub fn suspend_process(pid: u32, suspend: bool) -> Result<()> {
+    use nix::sys::signal::{kill, Signal};
+    use nix::unistd::Pid;
+
+    let signal = if suspend { Signal::SIGSTOP } else { Signal::SIGCONT };
+    kill(Pid::from_raw(pid as i32), signal)
+        .with_context(|| format!("Failed to send {} to process {}", signal, pid))
+}
+
+/// Stop or continue a process; not available on this platform.
+#[cfg(not(any(unix, windows)))]
+pub fn suspend_process(pid: u32, _suspend: bool) -> Result<()> {
+    bail!("Pausing process {} is not supported on this platform", pid)
+}
+
+/// A process started by [`spawn_tree`], used like the [`Child`] it wraps.
+///
+/// On Windows it owns the job object holding the process and its
+/// descendants, which is closed when the tree is dropped.
+#[derive(Debug)]
+pub struct ProcessTree {
+    child: Child,
+    #[cfg(windows)]
+    job: Option<JobObject>,
+}
+
+impl std::ops::Deref for ProcessTree {
+    type Target = Child;
+
+    fn deref(&self) -> &Child {
+        &self.child
+    }
+}
+
+impl std::ops::DerefMut for ProcessTree {
+    fn deref_mut(&mut self) -> &mut Child {
+        &mut self.child
+    }
+}
+
+/// Spawn `command` in a new process group, so [`kill_tree`] can stop it
+/// together with every process it starts.
+#[cfg(unix)]
+pub fn spawn_tree(command: &mut Command) -> std::io::Result<ProcessTree> {
+    use std::os::unix::process::CommandExt;
+
+    Ok(ProcessTree { child: command.process_group(0).spawn()? })
+}
+
+/// Kill `child` and its descendants (its process group, see [`spawn_tree`]).
+#[cfg(unix)]
+pub fn kill_tree(child: &mut ProcessTree) -> Result<()> {
+    use nix::errno::Errno;
+    use nix::sys::signal::{killpg, Signal};
+    use nix::unistd::Pid;
+
+    let group = Pid::from_raw(child.id() as i32);
+    let result = match killpg(group, Signal::SIGKILL) {
+        // The whole group has already exited
+        Ok(()) | Err(Errno::ESRCH) => Ok(()),
+        Err(e) => Err(e).with_context(|| format!("Failed to kill process group {}", group)),
+    };
+    // Spawned without a group of its own, or not yet in it
+    let _ = child.kill();
+    result
+}
+
+/// Bytes available to the current user on the volume holding `dir`.
+#[cfg(unix)]
+pub fn available_space(dir: &Path) -> Result<u64> {
+    let stats = nix::sys::statvfs::statvfs(dir)
+        .with_context(|| format!("Failed to query free space of {}", dir.display()))?;
+    Ok(stats.blocks_available() as u64 * stats.fragment_size() as u64)
+}
+
+/// Bytes available to the current user on the volume holding `dir`.
+#[cfg(windows)]
+pub fn available_space(dir: &Path) -> Result<u64> {
+    use windows::core::HSTRING;
+    use windows::Win32::Storage::FileSystem::GetDiskFreeSpaceExW;
+
+    let mut available = 0u64;
+    // SAFETY: the path is a valid wide string and `available` outlives the call
+    unsafe { GetDiskFreeSpaceExW(&HSTRING::from(dir), Some(&mut available), None, None) }
+        .with_context(|| format!("Failed to query free space of {}", dir.display()))?;
+    Ok(available)
+}
+
+/// Fail when `available` bytes can't hold `required` bytes of output.
+pub fn check_free_space(required: u64, available: u64) -> Result<()> {
+    const GB: f64 = 1024.0 * 1024.0 * 1024.0;
+    if available < required {
+        bail!(
+            "Not enough free disk space for the output: about {:.1} GB needed, {:.1} GB available",
+            required as f64 / GB,
+            available as f64 / GB,
+        );
+    }
+    Ok(())
+}
+
+/// A temporary file or directory that is deleted when dropped, unless kept
+/// (`--keep-temp`, for debugging generated scripts).
+#[derive(Debug)]
+pub struct TempPath {
+    path: PathBuf,
+    keep: bool,
+}
+
+impl TempPath {
+    /// Take ownership of `path`, deleting it on drop unless `keep` is set.
+    pub fn new(path: PathBuf, keep: bool) -> Self {
+        Self { path, keep }
+    }
+
+    /// The guarded path.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl std::ops::Deref for TempPath {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl AsRef<Path> for TempPath {
+    fn as_ref(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TempPath {
+    fn drop(&mut self) {
+        if self.keep {
+            return;
+        }
+        let _ = if self.path.is_dir() {
+            fs::remove_dir_all(&self.path)
+        } else {
+            fs::remove_file(&self.path)
+        };
+    }
+}
+
+/// Make sure the directory that will hold `output_path` exists.
+///
+/// Missing directories are created when `create` is set; otherwise a missing
+/// directory is an error (rather than a late ffmpeg failure).
+pub fn ensure_output_dir(output_path: &Path, create: bool) -> Result<()> {
+    let Some(parent) = output_path.parent().filter(|p| !p.as_os_str().is_empty()) else {
+        return Ok(());
+    };
+    if parent.is_dir() {
+        return Ok(());
+    }
+    if !create {
+        bail!("Output directory does not exist: {}", parent.display());
+    }
+    fs::create_dir_all(parent)
+        .with_context(|| format!("Failed to create output directory: {}", parent.display()))
+}
+
+/// Check that a directory exists (creating it if needed) and accepts new files.
+fn probe_writable(dir: &Path) -> std::io::Result<()> {
+    fs::create_dir_all(dir)?;
+    let probe = dir.join(format!(".vapourbox_write_test_{}", std::process::id()));
+    fs::write(&probe, b"")?;
+    fs::remove_file(&probe)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A path that can never be created because its parent is a regular file.
+    fn unwritable_dir(name: &str) -> PathBuf {
+        let blocker = std::env::temp_dir().join(format!("vapourbox_blocker_{}_{}", name, std::process::id()));
+        fs::write(&blocker, b"").unwrap();
+        blocker.join("sub")
+    }
+
+    #[test]
+    fn test_writable_dir_prefers_primary() {
+        let primary = std::env::temp_dir().join(format!("vapourbox_primary_{}", std::process::id()));
+        let result = writable_dir_from(&primary, None).unwrap();
+        assert_eq!(result, primary);
+        let _ = fs::remove_dir_all(&primary);
+    }
+
+    #[test]
+    fn test_writable_dir_falls_back_when_primary_unwritable() {
+        let primary = unwritable_dir("fallback");
+        let fallback = std::env::temp_dir().join(format!("vapourbox_fallback_{}", std::process::id()));
+
+        let result = writable_dir_from(&primary, Some(&fallback)).unwrap();
+        assert_eq!(result, fallback);
+
+        let _ = fs::remove_dir_all(&fallback);
+        let _ = fs::remove_file(primary.parent().unwrap());
+    }
+
+    #[test]
+    fn test_ensure_output_dir_creates_nested_dirs() {
+        let root = std::env::temp_dir().join(format!("vapourbox_output_{}", std::process::id()));
+        let output = root.join("a").join("b").join("out.mkv");
+
+        assert!(ensure_output_dir(&output, false).is_err());
+        assert!(!output.parent().unwrap().exists());
+
+        ensure_output_dir(&output, true).unwrap();
+        assert!(output.parent().unwrap().is_dir());
+        assert!(!output.exists());
+
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_temp_path_removed_on_drop_unless_kept() {
+        let dir = std::env::temp_dir().join(format!("vapourbox_temp_path_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("script.vpy");
+        fs::write(&file, b"").unwrap();
+
+        drop(TempPath::new(file.clone(), true));
+        assert!(file.exists(), "Kept file should remain");
+
+        drop(TempPath::new(file.clone(), false));
+        assert!(!file.exists(), "File should be removed");
+
+        drop(TempPath::new(dir.clone(), false));
+        assert!(!dir.exists(), "Directory should be removed");
+    }
+
+    #[test]
+    fn test_check_free_space() {
+        assert!(check_free_space(10, 10).is_ok());
+        assert!(check_free_space(0, 0).is_ok());
+        let err = check_free_space(3 * 1024 * 1024 * 1024, 1024 * 1024 * 1024).unwrap_err().to_string();
+        assert!(err.contains("3.0 GB needed") && err.contains("1.0 GB available"), "{}", err);
+
+        #[cfg(unix)]
+        assert!(available_space(&std::env::temp_dir()).unwrap() > 0);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_kill_tree_kills_grandchildren() {
+        use std::io::Read;
+        use std::process::Stdio;
+        use std::sync::mpsc;
+        use std::time::Duration;
+
+        // The shell's background sleeper holds stdout open until it dies
+        let mut child = spawn_tree(Command::new("sh").args(["-c", "sleep 30 & wait"]).stdout(Stdio::piped())).unwrap();
+        let mut stdout = child.stdout.take().unwrap();
+        let (done, closed) = mpsc::channel();
+        std::thread::spawn(move || {
+            let _ = stdout.read_to_end(&mut Vec::new());
+            let _ = done.send(());
+        });
+
+        std::thread::sleep(Duration::from_millis(100));
+        kill_tree(&mut child).unwrap();
+        assert!(!child.wait().unwrap().success());
+        assert!(closed.recv_timeout(Duration::from_secs(5)).is_ok(), "Grandchild should be killed too");
+
+        // Killing an exited tree is not an error
+        kill_tree(&mut child).unwrap();
+    }
+
+    #[test]
+    fn test_writable_dir_error_names_both_locations() {
+        let primary = unwritable_dir("primary_fail");
+        let fallback = unwritable_dir("fallback_fail");
+
+        let err = writable_dir_from(&primary, Some(&fallback)).unwrap_err().to_string();
+        assert!(err.contains(&primary.display().to_string()));
+        assert!(err.contains(&fallback.display().to_string()));
+
+        let _ = fs::remove_file(primary.parent().unwrap());
+        let _ = fs::remove_file(fallback.parent().unwrap());
+    }
+}
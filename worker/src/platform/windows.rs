@@ -1,6 +1,25 @@
 //! Windows-specific functionality.
 
+use std::os::windows::io::AsRawHandle;
+use std::os::windows::process::CommandExt;
 use std::path::PathBuf;
+use std::process::{Child, Command};
+
+use anyhow::{bail, Context, Result};
+use windows::core::PCWSTR;
+use windows::Win32::Foundation::{CloseHandle, HANDLE};
+use windows::Win32::System::Diagnostics::ToolHelp::{
+    CreateToolhelp32Snapshot, Thread32First, Thread32Next, TH32CS_SNAPTHREAD, THREADENTRY32,
+};
+use windows::Win32::System::JobObjects::{
+    AssignProcessToJobObject, CreateJobObjectW, JobObjectExtendedLimitInformation, SetInformationJobObject,
+    TerminateJobObject, JOBOBJECT_EXTENDED_LIMIT_INFORMATION, JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE,
+};
+use windows::Win32::System::Threading::{
+    OpenThread, ResumeThread, SuspendThread, CREATE_SUSPENDED, THREAD_SUSPEND_RESUME,
+};
+
+use super::ProcessTree;
 
 /// Get the user's home directory.
 pub fn home_dir() -> Option<PathBuf> {
@@ -18,3 +37,174 @@ pub fn app_data_dir() -> Option<PathBuf> {
 pub fn cache_dir() -> Option<PathBuf> {
     app_data_dir().map(|p| p.join("Cache"))
 }
+
+/// Job object holding a process started by [`spawn_tree`] and its
+/// descendants. Closing it kills any that are still running.
+#[derive(Debug)]
+pub struct JobObject {
+    /// Stored as an integer since `HANDLE` is not `Send`
+    handle: isize,
+}
+
+impl JobObject {
+    /// A new job that kills its processes when closed, holding `child`;
+    /// None if the job can't be set up.
+    fn holding(child: &Child) -> Option<Self> {
+        // SAFETY: creating a job object has no preconditions
+        let job = Self { handle: unsafe { CreateJobObjectW(None, PCWSTR::null()) }.ok()?.0 as isize };
+        let mut limits = JOBOBJECT_EXTENDED_LIMIT_INFORMATION::default();
+        limits.BasicLimitInformation.LimitFlags = JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE;
+        // SAFETY: `limits` outlives the call and its size is passed along; the
+        // process handle is owned by `child`, which is still alive
+        unsafe {
+            SetInformationJobObject(
+                job.handle(),
+                JobObjectExtendedLimitInformation,
+                &limits as *const _ as *const std::ffi::c_void,
+                std::mem::size_of_val(&limits) as u32,
+            )
+            .and_then(|()| AssignProcessToJobObject(job.handle(), HANDLE(child.as_raw_handle())))
+        }
+        .ok()?;
+        Some(job)
+    }
+
+    fn handle(&self) -> HANDLE {
+        HANDLE(self.handle as *mut std::ffi::c_void)
+    }
+}
+
+impl Drop for JobObject {
+    fn drop(&mut self) {
+        // SAFETY: the handle was created in `holding` and is closed exactly once here
+        unsafe {
+            let _ = CloseHandle(self.handle());
+        }
+    }
+}
+
+/// Spawn `command` inside a new job object, so [`kill_tree`] can stop it
+/// together with every process it starts. The job is also killed if the
+/// worker exits without closing it.
+///
+/// The process starts suspended and only runs once it is in the job, so
+/// none of its children can escape it.
+pub fn spawn_tree(command: &mut Command) -> std::io::Result<ProcessTree> {
+    let mut child = command.creation_flags(CREATE_SUSPENDED.0).spawn()?;
+    // Without a job the process is still usable; kill_tree falls back to it alone
+    let job = JobObject::holding(&child);
+    if let Err(e) = suspend_process(child.id(), false) {
+        let _ = child.kill();
+        let _ = child.wait();
+        return Err(std::io::Error::other(format!("{:#}", e)));
+    }
+    Ok(ProcessTree { child, job })
+}
+
+/// Kill `child` and its descendants (its job object, see [`spawn_tree`]).
+pub fn kill_tree(child: &mut ProcessTree) -> Result<()> {
+    let Some(job) = &child.job else {
+        let _ = child.kill();
+        return Ok(());
+    };
+    // SAFETY: the job handle stays open while `child` owns it
+    unsafe { TerminateJobObject(job.handle(), 1) }
+        .with_context(|| format!("Failed to kill process tree of {}", child.id()))
+}
+
+/// Stop (`suspend`) or continue the process `pid`, keeping its state.
+///
+/// Windows has no process-wide stop, so each thread of `pid` in a Toolhelp
+/// snapshot is suspended or resumed. Suspension is counted per thread, so
+/// every pause must be matched by one resume.
+pub fn suspend_process(pid: u32, suspend: bool) -> Result<()> {
+    // SAFETY: taking a snapshot has no preconditions; the handle is closed below
+    let snapshot = unsafe { CreateToolhelp32Snapshot(TH32CS_SNAPTHREAD, 0) }
+        .with_context(|| format!("Failed to list threads of process {}", pid))?;
+    let mut entry = THREADENTRY32 {
+        dwSize: std::mem::size_of::<THREADENTRY32>() as u32,
+        ..Default::default()
+    };
+    let mut failed = 0;
+    // SAFETY: `entry` is a THREADENTRY32 with `dwSize` set, as the Thread32
+    // functions require, and `snapshot` is open until the end of the loop
+    let mut next = unsafe { Thread32First(snapshot, &mut entry) };
+    while next.is_ok() {
+        if entry.th32OwnerProcessID == pid && set_thread_suspended(entry.th32ThreadID, suspend).is_none() {
+            failed += 1;
+        }
+        next = unsafe { Thread32Next(snapshot, &mut entry) };
+    }
+    // SAFETY: the snapshot was opened above and is not used again
+    unsafe {
+        let _ = CloseHandle(snapshot);
+    }
+    if failed > 0 {
+        bail!(
+            "Failed to {} {} thread(s) of process {}",
+            if suspend { "suspend" } else { "resume" },
+            failed,
+            pid,
+        );
+    }
+    Ok(())
+}
+
+/// Suspend or resume the thread `thread_id`; None when it can't be opened or changed.
+fn set_thread_suspended(thread_id: u32, suspend: bool) -> Option<()> {
+    // SAFETY: the handle is only used for the call below and then closed
+    unsafe {
+        let thread = OpenThread(THREAD_SUSPEND_RESUME, false, thread_id).ok()?;
+        let previous = if suspend { SuspendThread(thread) } else { ResumeThread(thread) };
+        let _ = CloseHandle(thread);
+        (previous != u32::MAX).then_some(())
+    }
+}
+
+/// Longest path the classic Win32 APIs accept without a `\\?\` prefix.
+const MAX_PATH: usize = 260;
+
+/// Add the extended-length prefix to an absolute path of `MAX_PATH` or more.
+///
+/// `C:\long\path` becomes `\\?\C:\long\path` and `\\server\share\path`
+/// becomes `\\?\UNC\server\share\path`. Shorter, relative and already
+/// prefixed paths are returned unchanged. Extended-length paths are not
+/// normalised by Windows, so forward slashes are converted to backslashes.
+pub fn extended_length_path(path: &str) -> String {
+    if path.len() < MAX_PATH || path.starts_with(r"\\?\") {
+        return path.to_string();
+    }
+    let path = path.replace('/', "\\");
+    if let Some(unc) = path.strip_prefix(r"\\") {
+        return format!(r"\\?\UNC\{}", unc);
+    }
+    let bytes = path.as_bytes();
+    if bytes.len() > 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':' && bytes[2] == b'\\' {
+        return format!(r"\\?\{}", path);
+    }
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extended_length_path() {
+        let short = r"C:\Videos\tape.avi";
+        assert_eq!(extended_length_path(short), short);
+
+        let long_dir = "d".repeat(MAX_PATH);
+        let drive = format!(r"C:\{}\tape.avi", long_dir);
+        assert_eq!(extended_length_path(&drive), format!(r"\\?\C:\{}\tape.avi", long_dir));
+
+        let unc = format!(r"\\server\share\{}\tape.avi", long_dir);
+        assert_eq!(extended_length_path(&unc), format!(r"\\?\UNC\server\share\{}\tape.avi", long_dir));
+
+        let prefixed = format!(r"\\?\C:\{}", long_dir);
+        assert_eq!(extended_length_path(&prefixed), prefixed);
+
+        let relative = format!(r"{}\tape.avi", long_dir);
+        assert_eq!(extended_length_path(&relative), relative);
+    }
+}
@@ -0,0 +1,231 @@
+//! On-disk cache of rendered preview frames.
+//!
+//! Tweaking one parameter and re-previewing the same frame is the common
+//! case, and toggling back to an earlier setting should not pay for a second
+//! render. Each PNG is stored under a hash of the source, the frame and
+//! everything in the job that changes how the preview looks. The least
+//! recently used entries are evicted once the cache holds `capacity` frames.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use anyhow::{Context, Result};
+use serde_json::json;
+
+use crate::index_cache::{source_key, Fnv1a};
+use crate::models::VideoJob;
+use crate::platform;
+
+/// Previews kept before the least recently used are evicted.
+pub const DEFAULT_CAPACITY: usize = 64;
+
+/// Rendered previews keyed by [`PreviewCache::key`].
+#[derive(Debug)]
+pub struct PreviewCache {
+    dir: PathBuf,
+    capacity: usize,
+}
+
+impl PreviewCache {
+    /// Cache under the platform cache directory (or the temp directory when
+    /// the cache is not writable).
+    pub fn open() -> Result<Self> {
+        let temp = std::env::temp_dir().join("vapourbox_preview_cache");
+        let dir = platform::writable_dir_from(
+            &platform::cache_dir().map_or_else(|| temp.clone(), |dir| dir.join("preview")),
+            Some(&temp),
+        )?;
+        Ok(Self::at(dir, DEFAULT_CAPACITY))
+    }
+
+    /// Cache in `dir` holding at most `capacity` previews.
+    pub fn at(dir: PathBuf, capacity: usize) -> Self {
+        Self { dir, capacity: capacity.max(1) }
+    }
+
+    /// Key for previewing `frame` of `job`, optionally against `compare`,
+    /// from a window of `preview_frames` source frames.
+    ///
+    /// Covers the source contents, the effective pipeline, the legacy QTGMC
+    /// parameters and the job fields that affect preview rendering, plus the worker version so changed
+    /// templates never serve stale frames. Of the encoding settings only the
+    /// codec's bit depth limit and the output chroma subsampling are included,
+    /// since the preview dithers and resamples to them; the output path and
+    /// the rest of the encoding don't change the preview.
+    pub fn key(job: &VideoJob, compare: Option<&VideoJob>, frame: i32, preview_frames: u64) -> Result<String> {
+        let preview_inputs = |job: &VideoJob| {
+            json!({
+                "pipeline": job.effective_pipeline(),
+                "qtgmc": job.qtgmc_parameters,
+                "fieldOrder": job.detected_field_order,
+                "frameRate": job.input_frame_rate,
+                "previewRange": job.preview_range,
+                "maxOutputDepth": job.encoding_settings.codec.max_bit_depth(),
+                "outputChroma": job.encoding_settings.output_chroma,
+            })
+        };
+        let inputs = json!({
+            "version": env!("CARGO_PKG_VERSION"),
+            "source": source_key(Path::new(&job.input_path))?,
+            "frame": frame,
+            "previewFrames": preview_frames,
+            "a": preview_inputs(job),
+            "b": compare.map(preview_inputs),
+        });
+
+        let mut hash = Fnv1a::default();
+        hash.write(&serde_json::to_vec(&inputs)?);
+        Ok(format!("{:016x}", hash.finish()))
+    }
+
+    /// Cached PNG for `key`, marking it as recently used.
+    pub fn get(&self, key: &str) -> Option<Vec<u8>> {
+        let path = self.entry(key);
+        let png = fs::read(&path).ok()?;
+        if let Ok(file) = fs::File::options().write(true).open(&path) {
+            let _ = file.set_modified(SystemTime::now());
+        }
+        Some(png)
+    }
+
+    /// Store `png` under `key`, evicting the least recently used previews
+    /// beyond capacity.
+    pub fn put(&self, key: &str, png: &[u8]) -> Result<()> {
+        fs::create_dir_all(&self.dir)
+            .with_context(|| format!("Failed to create preview cache: {}", self.dir.display()))?;
+        let path = self.entry(key);
+        fs::write(&path, png).with_context(|| format!("Failed to write cached preview: {}", path.display()))?;
+        self.evict();
+        Ok(())
+    }
+
+    fn entry(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.png", key))
+    }
+
+    fn evict(&self) {
+        let Ok(entries) = fs::read_dir(&self.dir) else { return };
+        let mut previews: Vec<(SystemTime, PathBuf)> = entries
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "png"))
+            .filter_map(|path| Some((fs::metadata(&path).ok()?.modified().ok()?, path)))
+            .collect();
+        if previews.len() <= self.capacity {
+            return;
+        }
+        previews.sort();
+        for (_, path) in &previews[..previews.len() - self.capacity] {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{ChromaSubsampling, VideoCodec};
+    use std::time::Duration;
+
+    fn job(input: &Path, denoise: bool) -> VideoJob {
+        let json = json!({
+            "id": "00000000-0000-0000-0000-000000000001",
+            "inputPath": input,
+            "outputPath": "output.mkv",
+            "qtgmcParameters": {},
+            "restorationPipeline": { "noiseReduction": { "enabled": denoise } },
+            "encodingSettings": {}
+        });
+        serde_json::from_value(json).unwrap()
+    }
+
+    #[test]
+    fn test_changed_parameter_misses_cache() {
+        let root = tempfile::tempdir().unwrap();
+        let input = root.path().join("tape.avi");
+        fs::write(&input, b"interlaced video").unwrap();
+        let cache = PreviewCache::at(root.path().join("preview"), 8);
+
+        let key = PreviewCache::key(&job(&input, false), None, 100, 11).unwrap();
+        assert_eq!(cache.get(&key), None);
+        cache.put(&key, b"png").unwrap();
+
+        let mut same = job(&input, false);
+        same.output_path = "elsewhere.mkv".to_string();
+        let same_key = PreviewCache::key(&same, None, 100, 11).unwrap();
+        assert_eq!(cache.get(&same_key).as_deref(), Some(&b"png"[..]));
+
+        let changed = PreviewCache::key(&job(&input, true), None, 100, 11).unwrap();
+        assert_eq!(cache.get(&changed), None);
+        assert_ne!(PreviewCache::key(&job(&input, false), None, 101, 11).unwrap(), key);
+        assert_ne!(PreviewCache::key(&job(&input, false), Some(&job(&input, true)), 100, 11).unwrap(), key);
+        assert_ne!(PreviewCache::key(&job(&input, false), None, 100, 21).unwrap(), key);
+    }
+
+    #[test]
+    fn test_changed_qtgmc_parameters_miss_cache() {
+        let root = tempfile::tempdir().unwrap();
+        let input = root.path().join("tape.avi");
+        fs::write(&input, b"interlaced video").unwrap();
+        let key = PreviewCache::key(&job(&input, false), None, 100, 11).unwrap();
+
+        let mut changed = job(&input, false);
+        changed.qtgmc_parameters.opencl = !changed.qtgmc_parameters.opencl;
+        assert_ne!(PreviewCache::key(&changed, None, 100, 11).unwrap(), key);
+
+        let mut changed = job(&input, false);
+        changed.qtgmc_parameters.tr2 = Some(3);
+        assert_ne!(PreviewCache::key(&changed, None, 100, 11).unwrap(), key);
+    }
+
+    #[test]
+    fn test_changed_output_depth_misses_cache() {
+        let root = tempfile::tempdir().unwrap();
+        let input = root.path().join("tape.avi");
+        fs::write(&input, b"interlaced video").unwrap();
+        let key = PreviewCache::key(&job(&input, false), None, 100, 11).unwrap();
+
+        // H.264 previews dither to 8 bits, H.265 to 10
+        let mut changed = job(&input, false);
+        changed.encoding_settings.codec = VideoCodec::H265;
+        assert_ne!(PreviewCache::key(&changed, None, 100, 11).unwrap(), key);
+    }
+
+    #[test]
+    fn test_changed_output_chroma_misses_cache() {
+        let root = tempfile::tempdir().unwrap();
+        let input = root.path().join("tape.avi");
+        fs::write(&input, b"interlaced video").unwrap();
+        let key = PreviewCache::key(&job(&input, false), None, 100, 11).unwrap();
+
+        let mut changed = job(&input, false);
+        changed.encoding_settings.output_chroma = Some(ChromaSubsampling::Yuv422);
+        let chroma_422 = PreviewCache::key(&changed, None, 100, 11).unwrap();
+        assert_ne!(chroma_422, key);
+
+        changed.encoding_settings.output_chroma = Some(ChromaSubsampling::Yuv444);
+        assert_ne!(PreviewCache::key(&changed, None, 100, 11).unwrap(), chroma_422);
+    }
+
+    #[test]
+    fn test_evicts_least_recently_used() {
+        let root = tempfile::tempdir().unwrap();
+        let cache = PreviewCache::at(root.path().to_path_buf(), 2);
+        let age = |key: &str, secs: u64| {
+            let file = fs::File::options().write(true).open(cache.entry(key)).unwrap();
+            file.set_modified(SystemTime::now() - Duration::from_secs(secs)).unwrap();
+        };
+
+        cache.put("a", b"a").unwrap();
+        age("a", 30);
+        cache.put("b", b"b").unwrap();
+        age("b", 20);
+        assert!(cache.get("a").is_some(), "Reading marks the entry as used");
+
+        cache.put("c", b"c").unwrap();
+        assert!(cache.get("b").is_none(), "Least recently used entry is evicted");
+        assert!(cache.get("a").is_some());
+        assert!(cache.get("c").is_some());
+    }
+}
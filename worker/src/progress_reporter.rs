@@ -1,27 +1,117 @@
 //! Progress reporting via JSON on stdout.
 
 use std::io::{self, Write};
-use std::sync::Mutex;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
 
-use crate::models::{LogLevel, ProgressInfo, WorkerMessage};
+use crate::models::{
+    BatchSummary, DependencyError, EncodePhase, LogLevel, PreviewPhase, ProgressInfo, QualityReport, WorkerMessage,
+};
+
+/// Interval between heartbeat messages while a job runs.
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Receiver for worker messages when the worker is embedded in-process.
+#[cfg(feature = "async")]
+pub trait ProgressSink: Send + Sync {
+    /// Handle one message. Called from worker threads, one message at a time.
+    fn send(&self, message: &WorkerMessage);
+}
 
 /// Thread-safe progress reporter that outputs JSON messages to stdout.
 #[derive(Clone)]
 pub struct ProgressReporter {
     inner: std::sync::Arc<ProgressReporterInner>,
+    /// Batch job index added to every message as `jobIndex`
+    job_index: Option<usize>,
 }
 
 struct ProgressReporterInner {
     output_lock: Mutex<()>,
+    output: Output,
+    /// When the reporter was created, for heartbeat uptime
+    started: Instant,
+}
+
+/// Where reporter messages are written.
+enum Output {
+    /// JSON lines on stdout (normal job mode)
+    Stdout,
+    /// JSON lines on stderr (preview mode, where stdout carries the PNG)
+    Stderr,
+    /// Messages are dropped
+    Disabled,
+    /// Messages are passed to an embedding application
+    #[cfg(feature = "async")]
+    #[allow(dead_code)] // only the library's `async_job` sends to a sink, not the binary
+    Sink(Arc<dyn ProgressSink>),
+    /// Messages are collected in memory
+    #[cfg(test)]
+    Memory(Mutex<Vec<(Option<usize>, WorkerMessage)>>),
 }
 
 impl ProgressReporter {
     /// Create a new progress reporter.
     pub fn new() -> Self {
+        Self::with_output(Output::Stdout)
+    }
+
+    /// Create a progress reporter that writes JSON messages to stderr.
+    pub fn stderr() -> Self {
+        Self::with_output(Output::Stderr)
+    }
+
+    /// Create a progress reporter that discards all messages.
+    pub fn disabled() -> Self {
+        Self::with_output(Output::Disabled)
+    }
+
+    /// Create a progress reporter that passes messages to `sink`.
+    #[cfg(feature = "async")]
+    #[allow(dead_code)] // only the library's `async_job` sends to a sink, not the binary
+    pub fn with_sink(sink: Arc<dyn ProgressSink>) -> Self {
+        Self::with_output(Output::Sink(sink))
+    }
+
+    /// Create a progress reporter that records messages for inspection.
+    #[cfg(test)]
+    pub fn capturing() -> Self {
+        Self::with_output(Output::Memory(Mutex::new(Vec::new())))
+    }
+
+    /// Messages recorded by a capturing reporter.
+    #[cfg(test)]
+    pub fn captured(&self) -> Vec<WorkerMessage> {
+        self.captured_with_job_index().into_iter().map(|(_, message)| message).collect()
+    }
+
+    /// Messages recorded by a capturing reporter, with their batch job index.
+    #[cfg(test)]
+    pub fn captured_with_job_index(&self) -> Vec<(Option<usize>, WorkerMessage)> {
+        match &self.inner.output {
+            Output::Memory(messages) => messages.lock().unwrap().clone(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// A reporter sharing this one's output that tags every message with
+    /// the index of a job within a batch.
+    pub fn for_job(&self, index: usize) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            job_index: Some(index),
+        }
+    }
+
+    fn with_output(output: Output) -> Self {
         Self {
             inner: std::sync::Arc::new(ProgressReporterInner {
                 output_lock: Mutex::new(()),
+                output,
+                started: Instant::now(),
             }),
+            job_index: None,
         }
     }
 
@@ -37,24 +127,86 @@ impl ProgressReporter {
         self.send_message(&msg);
     }
 
+    /// Send a preview phase change message.
+    pub fn send_phase(&self, phase: PreviewPhase, message: &str) {
+        let msg = WorkerMessage::phase(phase.as_str(), message);
+        self.send_message(&msg);
+    }
+
+    /// Send an encode phase change message.
+    pub fn send_encode_phase(&self, phase: EncodePhase, message: &str) {
+        let msg = WorkerMessage::phase(phase.as_str(), message);
+        self.send_message(&msg);
+    }
+
     /// Send an error message.
     pub fn send_error(&self, message: &str) {
         let msg = WorkerMessage::error(message);
         self.send_message(&msg);
     }
 
+    /// Send an error message reporting a missing dependency.
+    pub fn send_dependency_missing(&self, error: &DependencyError) {
+        let msg = WorkerMessage::dependency_missing(error);
+        self.send_message(&msg);
+    }
+
     /// Send a completion message.
     pub fn send_complete(&self, success: bool, output_path: Option<&str>) {
         let msg = WorkerMessage::complete(success, output_path);
         self.send_message(&msg);
     }
 
+    /// Send the summary of a finished batch.
+    pub fn send_batch_summary(&self, summary: &BatchSummary) {
+        let msg = WorkerMessage::batch_summary(summary);
+        self.send_message(&msg);
+    }
+
+    /// Send the measured output quality.
+    pub fn send_quality_report(&self, report: &QualityReport) {
+        let msg = WorkerMessage::quality_report(report);
+        self.send_message(&msg);
+    }
+
+    /// Send a heartbeat every `interval` until the returned guard is dropped,
+    /// so the app can tell a busy worker (e.g. indexing) from a hung one.
+    pub fn start_heartbeat(&self, interval: Duration) -> Heartbeat {
+        let stop = Arc::new((Mutex::new(false), Condvar::new()));
+        let reporter = self.clone();
+        let thread_stop = stop.clone();
+        let thread = thread::spawn(move || {
+            let (stopped, wake) = &*thread_stop;
+            let mut stopped = stopped.lock().unwrap();
+            loop {
+                let (guard, timeout) = wake.wait_timeout(stopped, interval).unwrap();
+                stopped = guard;
+                if *stopped {
+                    break;
+                }
+                if timeout.timed_out() {
+                    let uptime = reporter.inner.started.elapsed().as_secs();
+                    reporter.send_message(&WorkerMessage::heartbeat(uptime));
+                }
+            }
+        });
+        Heartbeat { stop, thread: Some(thread) }
+    }
+
     /// Send a raw message (thread-safe).
     fn send_message(&self, message: &WorkerMessage) {
         let _lock = self.inner.output_lock.lock().unwrap();
 
-        match serde_json::to_string(message) {
-            Ok(json) => {
+        let json = match self.serialize(message) {
+            Ok(json) => json,
+            Err(e) => {
+                eprintln!("Failed to serialize message: {}", e);
+                return;
+            }
+        };
+
+        match &self.inner.output {
+            Output::Stdout => {
                 let stdout = io::stdout();
                 let mut handle = stdout.lock();
                 if let Err(e) = writeln!(handle, "{}", json) {
@@ -62,11 +214,31 @@ impl ProgressReporter {
                 }
                 let _ = handle.flush();
             }
-            Err(e) => {
-                eprintln!("Failed to serialize message: {}", e);
+            Output::Stderr => {
+                let stderr = io::stderr();
+                let mut handle = stderr.lock();
+                let _ = writeln!(handle, "{}", json);
+                let _ = handle.flush();
             }
+            Output::Disabled => {}
+            #[cfg(feature = "async")]
+            Output::Sink(sink) => sink.send(message),
+            #[cfg(test)]
+            Output::Memory(messages) => messages.lock().unwrap().push((self.job_index, message.clone())),
         }
     }
+
+    /// Serialize a message, adding `jobIndex` for batch job reporters.
+    fn serialize(&self, message: &WorkerMessage) -> serde_json::Result<String> {
+        let Some(index) = self.job_index else {
+            return serde_json::to_string(message);
+        };
+        let mut value = serde_json::to_value(message)?;
+        if let Some(object) = value.as_object_mut() {
+            object.insert("jobIndex".to_string(), index.into());
+        }
+        serde_json::to_string(&value)
+    }
 }
 
 impl Default for ProgressReporter {
@@ -75,6 +247,23 @@ impl Default for ProgressReporter {
     }
 }
 
+/// Running heartbeat timer; stops (and joins its thread) when dropped.
+pub struct Heartbeat {
+    stop: Arc<(Mutex<bool>, Condvar)>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Drop for Heartbeat {
+    fn drop(&mut self) {
+        let (stopped, wake) = &*self.stop;
+        *stopped.lock().unwrap() = true;
+        wake.notify_one();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -85,4 +274,47 @@ mod tests {
         // Just verify it can be created and cloned
         let _clone = reporter.clone();
     }
+
+    #[test]
+    fn test_capturing_reporter_records_messages() {
+        let reporter = ProgressReporter::capturing();
+        reporter.clone().send_log(LogLevel::Info, "hello");
+        assert_eq!(reporter.captured().len(), 1);
+    }
+
+    #[test]
+    fn test_job_reporter_adds_job_index() {
+        let reporter = ProgressReporter::capturing();
+        reporter.for_job(1).send_complete(true, Some("out.mkv"));
+
+        let captured = reporter.captured_with_job_index();
+        assert_eq!(captured.len(), 1);
+        assert_eq!(captured[0].0, Some(1));
+
+        let json = reporter.for_job(1).serialize(&captured[0].1).unwrap();
+        assert!(json.contains("\"jobIndex\":1"));
+        assert!(!reporter.serialize(&captured[0].1).unwrap().contains("jobIndex"));
+    }
+
+    #[test]
+    fn test_heartbeat_emitted_at_interval() {
+        let reporter = ProgressReporter::capturing();
+        let count = |r: &ProgressReporter| {
+            r.captured().iter().filter(|m| matches!(m, WorkerMessage::Heartbeat { .. })).count()
+        };
+
+        // Wait for a beat rather than counting beats in a fixed window, which a busy machine skews
+        let heartbeat = reporter.start_heartbeat(Duration::from_millis(10));
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while count(&reporter) == 0 {
+            assert!(Instant::now() < deadline, "No heartbeat within 5s at a 10ms interval");
+            thread::sleep(Duration::from_millis(5));
+        }
+        drop(heartbeat);
+        let sent = count(&reporter);
+
+        // Nothing is sent once the guard is dropped
+        thread::sleep(Duration::from_millis(100));
+        assert_eq!(count(&reporter), sent);
+    }
 }
@@ -0,0 +1,168 @@
+//! User-defined QTGMC presets.
+//!
+//! A custom preset is a full `QTGMCParameters` snapshot saved as JSON under
+//! `~/.vapourbox/qtgmc_presets/`; the file stem is its name. Jobs select one
+//! with `presetName`, in `qtgmcParameters` or the pipeline's `deinterlace`.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+
+use crate::models::{QTGMCParameters, VideoJob};
+
+/// Custom QTGMC presets by name.
+#[derive(Debug, Default)]
+pub struct QTGMCPresetLibrary {
+    presets: HashMap<String, QTGMCParameters>,
+}
+
+impl QTGMCPresetLibrary {
+    /// Create an empty library.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load the presets in the user's preset directory, if it exists.
+    pub fn load_user_presets() -> Result<Self> {
+        let mut library = Self::new();
+        if let Some(user_dir) = Self::get_user_preset_directory() {
+            library.load_from_directory(&user_dir)?;
+        }
+        Ok(library)
+    }
+
+    /// Load every `*.json` preset in `dir`. Unreadable files are skipped with a warning.
+    pub fn load_from_directory(&mut self, dir: &Path) -> Result<()> {
+        if !dir.exists() {
+            return Ok(());
+        }
+
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+
+            if path.extension().map(|e| e == "json").unwrap_or(false) {
+                if let Err(e) = self.load_from_file(&path) {
+                    eprintln!("Warning: Failed to load QTGMC preset from {:?}: {:#}", path, e);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Load a single preset, named after the file stem.
+    pub fn load_from_file(&mut self, path: &Path) -> Result<()> {
+        let name = path.file_stem()
+            .with_context(|| format!("QTGMC preset file has no name: {:?}", path))?
+            .to_string_lossy()
+            .to_string();
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read QTGMC preset: {:?}", path))?;
+        let params: QTGMCParameters = serde_json::from_str(&content)
+            .with_context(|| format!("Failed to parse QTGMC preset: {:?}", path))?;
+
+        self.register(&name, params);
+        Ok(())
+    }
+
+    /// Add or replace a preset.
+    pub fn register(&mut self, name: &str, params: QTGMCParameters) {
+        self.presets.insert(name.to_string(), params);
+    }
+
+    /// Get a preset by name.
+    pub fn get(&self, name: &str) -> Option<&QTGMCParameters> {
+        self.presets.get(name)
+    }
+
+    /// Apply the custom presets `job` names to its QTGMC parameters.
+    ///
+    /// Fails when a named preset is not in the library.
+    pub fn apply_to_job(&self, job: &VideoJob) -> Result<VideoJob> {
+        let mut job = job.clone();
+        job.qtgmc_parameters = self.apply(&job.qtgmc_parameters)?;
+        if let Some(pipeline) = job.restoration_pipeline.as_mut() {
+            pipeline.deinterlace = self.apply(&pipeline.deinterlace)?;
+        }
+        Ok(job)
+    }
+
+    fn apply(&self, params: &QTGMCParameters) -> Result<QTGMCParameters> {
+        let Some(name) = params.preset_name.as_deref() else {
+            return Ok(params.clone());
+        };
+        let custom = self.get(name)
+            .with_context(|| format!("Unknown QTGMC preset {:?}; presets are loaded from ~/.vapourbox/qtgmc_presets", name))?;
+        Ok(params.with_custom_preset(custom))
+    }
+
+    /// Get the user preset directory path.
+    fn get_user_preset_directory() -> Option<PathBuf> {
+        #[cfg(windows)]
+        {
+            std::env::var("USERPROFILE")
+                .ok()
+                .map(|home| PathBuf::from(home).join(".vapourbox").join("qtgmc_presets"))
+        }
+
+        #[cfg(not(windows))]
+        {
+            std::env::var("HOME")
+                .ok()
+                .map(|home| PathBuf::from(home).join(".vapourbox").join("qtgmc_presets"))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{QTGMCPreset, RestorationPipeline};
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_user_preset_applies_to_job() {
+        let dir = tempdir().unwrap();
+        fs::write(
+            dir.path().join("vhs_clean.json"),
+            r#"{"preset": "Slow", "tr2": 3, "sharpness": 0.4, "tff": false, "enabled": false}"#,
+        ).unwrap();
+        fs::write(dir.path().join("notes.txt"), "not a preset").unwrap();
+
+        let mut library = QTGMCPresetLibrary::new();
+        library.load_from_directory(dir.path()).unwrap();
+        assert!(library.get("vhs_clean").is_some());
+        assert!(library.get("notes").is_none());
+
+        let named = QTGMCParameters {
+            preset_name: Some("vhs_clean".to_string()),
+            tff: Some(true),
+            tr2: Some(1),
+            ..QTGMCParameters::default()
+        };
+        let job: VideoJob = serde_json::from_value(serde_json::json!({
+            "id": "00000000-0000-0000-0000-000000000000",
+            "inputPath": "in.mp4",
+            "outputPath": "out.mkv",
+            "qtgmcParameters": named,
+            "restorationPipeline": RestorationPipeline { deinterlace: named.clone(), ..RestorationPipeline::default() },
+            "encodingSettings": {},
+        })).unwrap();
+
+        let applied = library.apply_to_job(&job).unwrap();
+        for params in [&applied.qtgmc_parameters, &applied.restoration_pipeline.unwrap().deinterlace] {
+            assert_eq!(params.preset, QTGMCPreset::Slow);
+            assert_eq!(params.tr2, Some(3));
+            assert_eq!(params.sharpness, Some(0.4));
+            // The job's pass state and field order win over the snapshot
+            assert!(params.enabled);
+            assert_eq!(params.tff, Some(true));
+        }
+
+        let mut unknown = job.clone();
+        unknown.qtgmc_parameters.preset_name = Some("missing".to_string());
+        assert!(library.apply_to_job(&unknown).is_err());
+    }
+}
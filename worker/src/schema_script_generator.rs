@@ -3,12 +3,29 @@
 //! Generates VapourSynth filter calls from filter schemas and dynamic parameters.
 
 use std::collections::HashMap;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
 
 use crate::filter_schema::{FilterSchema, DynamicParameters, ParameterType};
 
 /// Generates VapourSynth code from filter schemas.
 pub struct SchemaScriptGenerator;
 
+/// The code a filter's settings produce, for display in the app.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FilterExplanation {
+    /// Generated filter code (None when the filter is disabled)
+    pub call: Option<String>,
+
+    /// Import statements the code needs
+    pub imports: Vec<String>,
+
+    /// Required VapourSynth plugins that are not installed
+    pub missing_deps: Vec<String>,
+}
+
 impl SchemaScriptGenerator {
     /// Generate a VapourSynth filter call from a schema and parameters.
     ///
@@ -83,6 +100,20 @@ impl SchemaScriptGenerator {
         Self::generate_filter_call(schema, params)
     }
 
+    /// Explain what `params` produce for `schema`: the generated code, its
+    /// imports, and any required plugins missing from `plugin_dir`.
+    pub fn explain(
+        schema: &FilterSchema,
+        params: &DynamicParameters,
+        plugin_dir: &Path,
+    ) -> FilterExplanation {
+        FilterExplanation {
+            call: Self::generate_filter_block(schema, params),
+            imports: Self::generate_imports(schema),
+            missing_deps: schema.missing_vs_plugins(plugin_dir),
+        }
+    }
+
     /// Validate that required dependencies are documented.
     pub fn get_required_imports(schemas: &[&FilterSchema]) -> Vec<String> {
         let mut imports = Vec::new();
@@ -220,6 +251,10 @@ mod tests {
                     "default": 32
                 }
             },
+            "dependencies": {
+                "plugins": ["havsfunc"],
+                "vs_plugins": ["RemoveGrainVS.dll", "CTMF.dll"]
+            },
             "codeTemplate": {
                 "imports": ["import havsfunc as haf"]
             }
@@ -292,6 +327,30 @@ mod tests {
         assert!(result.is_none());
     }
 
+    #[test]
+    fn test_explain_dehalo() {
+        let schema = create_test_schema();
+        let plugin_dir = tempfile::tempdir().unwrap();
+        std::fs::write(plugin_dir.path().join("CTMF.dll"), b"").unwrap();
+
+        let mut values = HashMap::new();
+        values.insert("method".to_string(), serde_json::json!("dehalo_alpha"));
+        values.insert("rx".to_string(), serde_json::json!(2.5));
+        let params = DynamicParameters {
+            filter_id: "dehalo".to_string(),
+            enabled: true,
+            values,
+        };
+
+        let explanation = SchemaScriptGenerator::explain(&schema, &params, plugin_dir.path());
+        assert_eq!(explanation.call.as_deref(), Some("clip = haf.DeHalo_alpha(clip, rx=2.5)"));
+        assert_eq!(explanation.imports, ["import havsfunc as haf"]);
+        assert_eq!(explanation.missing_deps, ["RemoveGrainVS.dll"]);
+
+        let json = serde_json::to_string(&explanation).unwrap();
+        assert!(json.contains("\"missingDeps\""));
+    }
+
     #[test]
     fn test_format_values() {
         assert_eq!(format_value(&serde_json::json!(true), &ParameterType::Boolean), "True");
@@ -2,6 +2,7 @@
 //!
 //! Generates .vpy scripts from templates by substituting pipeline parameters.
 
+use std::collections::HashSet;
 use std::env;
 use std::fs;
 use std::path::{Path, PathBuf};
@@ -10,13 +11,19 @@ use anyhow::{Context, Result};
 
 use crate::models::{
     VideoJob, RestorationPipeline, NoiseReductionMethod, ResizeKernel, UpscaleMethod,
-    DehaloMethod, DeblockMethod, SharpenMethod,
+    DehaloMethod, DeblockMethod, SharpenMethod, CropStage, DeinterlaceMethod,
+    QTGMCParameters, WorkerError, DEFAULT_VS_MAX_CACHE_MB, sanitize_edi_ext_script,
 };
+use crate::platform::{self, TempPath};
 
 /// Generates VapourSynth scripts from templates.
 pub struct ScriptGenerator {
     template: String,
     preview_template: String,
+    /// Keep generated scripts after their `TempPath` is dropped
+    keep_temp: bool,
+    /// BestSource index directory (`cachepath`); None uses BestSource's default
+    index_cache_dir: Option<PathBuf>,
 }
 
 /// Parameters for preview script generation.
@@ -29,6 +36,8 @@ pub struct PreviewParams {
     pub fps_den: i32,
     /// Field order: 1 = BFF, 2 = TFF
     pub field_based: i32,
+    /// Index of the previewed source frame within the extracted clip
+    pub middle_frame: u64,
 }
 
 impl ScriptGenerator {
@@ -36,51 +45,182 @@ impl ScriptGenerator {
     pub fn new() -> Result<Self> {
         let template = Self::load_template()?;
         let preview_template = Self::load_preview_template()?;
-        Ok(Self { template, preview_template })
+        Ok(Self { template, preview_template, keep_temp: false, index_cache_dir: None })
+    }
+
+    /// Keep generated scripts on disk instead of deleting them when dropped.
+    pub fn with_keep_temp(mut self, keep_temp: bool) -> Self {
+        self.keep_temp = keep_temp;
+        self
+    }
+
+    /// Write the source index to `dir` (see [`crate::index_cache`]).
+    pub fn with_index_cache_dir(mut self, dir: Option<&Path>) -> Self {
+        self.index_cache_dir = dir.map(Path::to_path_buf);
+        self
     }
 
     /// Generate a .vpy script file for the given job.
-    /// Returns the path to the generated script.
-    pub fn generate(&self, job: &VideoJob) -> Result<PathBuf> {
-        let pipeline = job.effective_pipeline();
-        let script = self.substitute_parameters(&self.template, job, &pipeline);
+    /// Returns the path to the generated script, deleted on drop unless kept.
+    pub fn generate(&self, job: &VideoJob) -> Result<TempPath> {
+        let script = self.generate_to_string(job)?;
 
         // Write to temp file
-        let temp_dir = env::temp_dir();
+        let temp_dir = platform::writable_temp_dir()?;
         let script_path = temp_dir.join(format!("{}.vpy", job.id));
 
         fs::write(&script_path, &script)
             .with_context(|| format!("Failed to write script to {:?}", script_path))?;
 
-        Ok(script_path)
+        Ok(TempPath::new(script_path, self.keep_temp))
+    }
+
+    /// Generate the encoding script for the given job as a string.
+    pub fn generate_to_string(&self, job: &VideoJob) -> Result<String> {
+        validate_job(job)?;
+        let pipeline = job.effective_pipeline();
+        self.substitute_parameters(&self.template, job, &pipeline)
+    }
+
+    /// Generate a standalone script for opening in vspreview or vsedit: the
+    /// encoding script with a commented summary of the pipeline on top and the
+    /// blank lines left by removed blocks collapsed.
+    pub fn generate_standalone(&self, job: &VideoJob) -> Result<String> {
+        let script = self.generate_to_string(job)?;
+        let passes: Vec<&str> = job.effective_pipeline().enabled_passes().iter().map(|p| p.display_name()).collect();
+
+        let mut out = format!(
+            "# VapourBox restoration pipeline\n# Input: {}\n# Passes: {}\n\n",
+            job.input_path,
+            if passes.is_empty() { "none".to_string() } else { passes.join(", ") },
+        );
+        let mut blank_lines = 0;
+        for line in script.lines().map(str::trim_end) {
+            blank_lines = if line.is_empty() { blank_lines + 1 } else { 0 };
+            if blank_lines < 2 {
+                out.push_str(line);
+                out.push('\n');
+            }
+        }
+        Ok(out)
     }
 
     /// Generate a preview .vpy script that loads from extracted frames.
     /// Returns the path to the generated script.
-    pub fn generate_preview(&self, job: &VideoJob, preview_params: &PreviewParams) -> Result<PathBuf> {
+    pub fn generate_preview(&self, job: &VideoJob, preview_params: &PreviewParams) -> Result<TempPath> {
+        validate_job(job)?;
+        let script = self.build_preview_script(job, preview_params)?;
+
+        // Write to temp file
+        let temp_dir = platform::writable_temp_dir()?;
+        let script_path = temp_dir.join(format!("{}_preview.vpy", job.id));
+
+        fs::write(&script_path, &script)
+            .with_context(|| format!("Failed to write preview script to {:?}", script_path))?;
+
+        Ok(TempPath::new(script_path, self.keep_temp))
+    }
+
+    /// Generate an A/B comparison preview script.
+    ///
+    /// Runs both pipelines on the same extracted clip and stacks the processed
+    /// middle frames side by side (A on the left, B on the right).
+    /// Returns the path to the generated script.
+    pub fn generate_ab_preview(
+        &self,
+        job_a: &VideoJob,
+        params_a: &PreviewParams,
+        job_b: &VideoJob,
+        params_b: &PreviewParams,
+    ) -> Result<TempPath> {
+        validate_job(job_a)?;
+        validate_job(job_b)?;
+        let script = self.build_ab_preview_script(job_a, params_a, job_b, params_b)?;
+
+        // Write to temp file
+        let temp_dir = platform::writable_temp_dir()?;
+        let script_path = temp_dir.join(format!("{}_ab_preview.vpy", job_a.id));
+
+        fs::write(&script_path, &script)
+            .with_context(|| format!("Failed to write A/B preview script to {:?}", script_path))?;
+
+        Ok(TempPath::new(script_path, self.keep_temp))
+    }
+
+    /// Build a preview script from the preview template.
+    fn build_preview_script(&self, job: &VideoJob, preview_params: &PreviewParams) -> Result<String> {
         let pipeline = job.effective_pipeline();
 
         // Start with preview template and substitute preview-specific params
         let mut script = self.preview_template.clone();
 
-        // Escape backslashes for Python
-        let escaped_video_path = preview_params.video_path.replace('\\', "\\\\");
+        let escaped_video_path = python_string_contents(&platform::long_path(&preview_params.video_path));
         script = script.replace("{{VIDEO_PATH}}", &escaped_video_path);
         script = script.replace("{{FPS_NUM}}", &preview_params.fps_num.to_string());
         script = script.replace("{{FPS_DEN}}", &preview_params.fps_den.to_string());
         script = script.replace("{{FIELD_BASED}}", &preview_params.field_based.to_string());
+        script = script.replace("{{PREVIEW_MIDDLE_FRAME}}", &preview_params.middle_frame.to_string());
 
         // Now apply the same pipeline substitutions
-        script = self.substitute_parameters_on(&script, job, &pipeline);
+        self.substitute_parameters_on(&script, job, &pipeline)
+    }
 
-        // Write to temp file
-        let temp_dir = env::temp_dir();
-        let script_path = temp_dir.join(format!("{}_preview.vpy", job.id));
+    /// Build the combined A/B preview script.
+    ///
+    /// Each pipeline is wrapped in its own function so that variables from
+    /// one pipeline cannot leak into the other.
+    fn build_ab_preview_script(
+        &self,
+        job_a: &VideoJob,
+        params_a: &PreviewParams,
+        job_b: &VideoJob,
+        params_b: &PreviewParams,
+    ) -> Result<String> {
+        let mut script = String::from(
+            "\"\"\"\nVapourBox A/B Comparison Preview\n\
+             Pipeline A (left) and pipeline B (right) processed from the same clip.\n\"\"\"\n\n\
+             import vapoursynth as vs\n\n\
+             core = vs.core\n\n",
+        );
+
+        for (name, job, params) in [("pipeline_a", job_a, params_a), ("pipeline_b", job_b, params_b)] {
+            let body = self.build_preview_script(job, params)?
+                .replace("clip.set_output()", "return clip");
+            script.push_str(&format!("def {}():\n", name));
+            for line in body.lines() {
+                if line.trim().is_empty() {
+                    script.push('\n');
+                } else {
+                    script.push_str(&format!("    {}\n", line));
+                }
+            }
+            script.push('\n');
+        }
 
-        fs::write(&script_path, &script)
-            .with_context(|| format!("Failed to write preview script to {:?}", script_path))?;
+        script.push_str(
+            "clip_a = pipeline_a()\n\
+             clip_b = pipeline_b()\n\n\
+             # Match B to A's height and format so the frames can be stacked\n\
+             if clip_b.height != clip_a.height or clip_b.format.id != clip_a.format.id:\n\
+             \x20   width_b = int(clip_b.width * clip_a.height / clip_b.height)\n\
+             \x20   width_b = width_b - (width_b % 2)  # Ensure even\n\
+             \x20   clip_b = core.resize.Spline36(clip_b, width=width_b, height=clip_a.height, format=clip_a.format.id)\n\n\
+             clip = core.std.StackHorizontal([clip_a, clip_b])\n\
+             clip.set_output()\n",
+        );
+
+        Ok(script)
+    }
 
-        Ok(script_path)
+    /// Check both templates for unbalanced `{{#X}}`/`{{/X}}` tags and for
+    /// placeholders no substitution in this module fills.
+    ///
+    /// Returns one message per problem (empty when the templates are sound).
+    pub fn validate_template(&self) -> Vec<String> {
+        let known = known_placeholders();
+        let mut problems = check_template("pipeline_template.vpy", &self.template, &known);
+        problems.extend(check_template("preview_template.vpy", &self.preview_template, &known));
+        problems
     }
 
     /// Load the template from various locations.
@@ -130,12 +270,12 @@ impl ScriptGenerator {
     }
 
     /// Substitute parameters in a script string.
-    fn substitute_parameters(&self, template: &str, job: &VideoJob, pipeline: &RestorationPipeline) -> String {
+    fn substitute_parameters(&self, template: &str, job: &VideoJob, pipeline: &RestorationPipeline) -> Result<String> {
         let mut script = template.to_string();
         let params = &job.qtgmc_parameters;
 
-        // Input path (escape backslashes for Python)
-        let escaped_input = job.input_path.replace('\\', "\\\\");
+        // Input path, escaped for a Python string literal
+        let escaped_input = python_string_contents(&platform::long_path(&job.input_path));
         script = script.replace("{{INPUT_PATH}}", &escaped_input);
 
         // Frame trimming (start/end frame range)
@@ -154,34 +294,179 @@ impl ScriptGenerator {
             script = remove_block("{{#FRAME_TRIM}}", "{{/FRAME_TRIM}}", script);
         }
 
+        // Keyframes-only proxy (indices relative to the trimmed clip)
+        match job.keyframes.as_deref().filter(|_| job.keyframes_only) {
+            Some(keyframes) => {
+                script = script.replace("{{#KEYFRAMES_ONLY}}", "");
+                script = script.replace("{{/KEYFRAMES_ONLY}}", "");
+                let slices: Vec<String> = keyframe_slices(keyframes, job.start_frame.unwrap_or(0), job.end_frame)
+                    .iter()
+                    .map(|(first, end)| format!("({}, {})", first, end))
+                    .collect();
+                script = script.replace("{{KEYFRAME_SLICES}}", &format!("[{}]", slices.join(", ")));
+            }
+            None => script = remove_block("{{#KEYFRAMES_ONLY}}", "{{/KEYFRAMES_ONLY}}", script),
+        }
+
         self.substitute_parameters_on(&script, job, pipeline)
     }
 
     /// Substitute pipeline parameters on an already-prepared script.
-    fn substitute_parameters_on(&self, script: &str, job: &VideoJob, pipeline: &RestorationPipeline) -> String {
+    fn substitute_parameters_on(&self, script: &str, job: &VideoJob, pipeline: &RestorationPipeline) -> Result<String> {
         let mut script = script.to_string();
         let params = &job.qtgmc_parameters;
 
+        // Output frame rate tag
+        match job.encoding_settings.output_fps {
+            Some((num, den)) => {
+                script = script.replace("{{#OUTPUT_FPS}}", "");
+                script = script.replace("{{/OUTPUT_FPS}}", "");
+                script = script.replace("{{OUTPUT_FPS_NUM}}", &num.to_string());
+                script = script.replace("{{OUTPUT_FPS_DEN}}", &den.to_string());
+            }
+            None => script = remove_block("{{#OUTPUT_FPS}}", "{{/OUTPUT_FPS}}", script),
+        }
+
+        // Output chroma subsampling for the deliverable (shared by preview and encode)
+        match job.encoding_settings.output_chroma {
+            Some(chroma) => {
+                let (ssw, ssh) = chroma.vs_subsampling();
+                script = script.replace("{{#OUTPUT_CHROMA}}", "");
+                script = script.replace("{{/OUTPUT_CHROMA}}", "");
+                script = script.replace("{{OUTPUT_CHROMA_NAME}}", chroma.display_name());
+                script = script.replace("{{OUTPUT_CHROMA_SSW}}", &ssw.to_string());
+                script = script.replace("{{OUTPUT_CHROMA_SSH}}", &ssh.to_string());
+            }
+            None => script = remove_block("{{#OUTPUT_CHROMA}}", "{{/OUTPUT_CHROMA}}", script),
+        }
+
+        // Output bit depth limit for the target encoder (shared by preview and encode)
+        script = script.replace(
+            "{{MAX_OUTPUT_DEPTH}}",
+            &job.encoding_settings.codec.max_bit_depth().to_string(),
+        );
+
+        // Frame cache limit
+        script = script.replace(
+            "{{VS_MAX_CACHE_MB}}",
+            &job.vs_max_cache_mb.unwrap_or(DEFAULT_VS_MAX_CACHE_MB).to_string(),
+        );
+
+        // Source filter options (omitted when unset so BestSource defaults apply)
+        script = process_optional_int("SOURCE_THREADS", job.source_threads, script);
+        script = process_optional_int("SOURCE_SEEK_PREROLL", job.source_seek_preroll, script);
+        let cache_path = self.index_cache_dir.as_ref()
+            .map(|dir| python_string_contents(&platform::long_path(&dir.to_string_lossy())));
+        script = process_optional("SOURCE_CACHE_PATH", cache_path.as_deref(), script);
+
+        // Linear light conversions around dehalo and/or resize
+        let linearize_dehalo = pipeline.dehalo.enabled && pipeline.dehalo.linearize;
+        let crop_resize = &pipeline.crop_resize;
+        let linearize_resize = crop_resize.enabled && crop_resize.linearize &&
+            (crop_resize.resize_enabled || crop_resize.use_integer_upscale);
+        for (block, active) in [
+            ("LINEAR_LIGHT", linearize_dehalo || linearize_resize),
+            ("DEHALO_LINEARIZE", linearize_dehalo),
+            ("RESIZE_LINEARIZE", linearize_resize),
+        ] {
+            let (start_tag, end_tag) = (format!("{{{{#{}}}}}", block), format!("{{{{/{}}}}}", block));
+            if active {
+                script = script.replace(&start_tag, "");
+                script = script.replace(&end_tag, "");
+            } else {
+                script = remove_block(&start_tag, &end_tag, script);
+            }
+        }
+
         // ====================================================================
-        // PRE-CROP PASS
+        // CROP PASS (pre-deinterlace or post-process)
         // ====================================================================
         let crop = &pipeline.crop_resize;
         if crop.enabled && crop.crop_enabled &&
            (crop.crop_left > 0 || crop.crop_right > 0 || crop.crop_top > 0 || crop.crop_bottom > 0) {
-            script = script.replace("{{#PRE_CROP}}", "");
-            script = script.replace("{{/PRE_CROP}}", "");
+            let (active, inactive) = match crop.crop_stage {
+                CropStage::PreDeinterlace => ("PRE_CROP", "POST_CROP"),
+                CropStage::PostProcess => ("POST_CROP", "PRE_CROP"),
+            };
+            script = script.replace(&format!("{{{{#{}}}}}", active), "");
+            script = script.replace(&format!("{{{{/{}}}}}", active), "");
+            script = remove_block(&format!("{{{{#{}}}}}", inactive), &format!("{{{{/{}}}}}", inactive), script);
             script = script.replace("{{CROP_LEFT}}", &crop.crop_left.to_string());
             script = script.replace("{{CROP_RIGHT}}", &crop.crop_right.to_string());
             script = script.replace("{{CROP_TOP}}", &crop.crop_top.to_string());
             script = script.replace("{{CROP_BOTTOM}}", &crop.crop_bottom.to_string());
         } else {
             script = remove_block("{{#PRE_CROP}}", "{{/PRE_CROP}}", script);
+            script = remove_block("{{#POST_CROP}}", "{{/POST_CROP}}", script);
         }
 
         // ====================================================================
-        // DEINTERLACE PASS (QTGMC)
+        // DEINTERLACE PASS (QTGMC or NNEDI3 bob)
         // ====================================================================
-        if pipeline.deinterlace.enabled {
+        if pipeline.deinterlace.enabled && params.fix_interlaced_chroma {
+            script = script.replace("{{#INTERLACED_CHROMA_FIX}}", "");
+            script = script.replace("{{/INTERLACED_CHROMA_FIX}}", "");
+            // _FieldBased: 2 = TFF, 1 = BFF; unknown order defaults to TFF
+            let field_based = if params.tff.unwrap_or(true) { 2 } else { 1 };
+            script = script.replace("{{INTERLACED_CHROMA_FIELD_BASED}}", &field_based.to_string());
+        } else {
+            script = remove_block("{{#INTERLACED_CHROMA_FIX}}", "{{/INTERLACED_CHROMA_FIX}}", script);
+        }
+
+        if pipeline.deinterlace.enabled && !params.deinterlace_ranges.is_empty() {
+            script = script.replace("{{#DEINTERLACE_RANGES}}", "");
+            script = script.replace("{{/DEINTERLACE_RANGES}}", "");
+            let ranges: Vec<String> = params.deinterlace_ranges.iter()
+                .map(|(first, last)| format!("({}, {})", first, last))
+                .collect();
+            script = script.replace("{{DEINTERLACE_RANGES}}", &format!("[{}]", ranges.join(", ")));
+        } else {
+            script = remove_block("{{#DEINTERLACE_RANGES}}", "{{/DEINTERLACE_RANGES}}", script);
+        }
+
+        // Duplicate frame removal (pipeline template only: it renumbers frames)
+        let dedup = &pipeline.dedup;
+        if dedup.enabled {
+            script = script.replace("{{#DEDUP}}", "");
+            script = script.replace("{{/DEDUP}}", "");
+            script = script.replace("{{DEDUP_DUP_THRESHOLD}}", &format_double(dedup.dup_threshold));
+        } else {
+            script = remove_block("{{#DEDUP}}", "{{/DEDUP}}", script);
+        }
+
+        if pipeline.deinterlace.enabled && params.selective_deinterlace {
+            script = script.replace("{{#SELECTIVE_DEINTERLACE}}", "");
+            script = script.replace("{{/SELECTIVE_DEINTERLACE}}", "");
+        } else {
+            script = remove_block("{{#SELECTIVE_DEINTERLACE}}", "{{/SELECTIVE_DEINTERLACE}}", script);
+        }
+
+        if pipeline.deinterlace.enabled && params.method == DeinterlaceMethod::Nnedi3Bob {
+            script = remove_block("{{#DEINTERLACE}}", "{{/DEINTERLACE}}", script);
+            script = script.replace("{{#DEINTERLACE_NNEDI3_BOB}}", "");
+            script = script.replace("{{/DEINTERLACE_NNEDI3_BOB}}", "");
+
+            // Double-rate fields 3/2, single-rate 1/0; unknown order defaults to TFF
+            let tff = params.tff.unwrap_or(true);
+            let field = match (params.fps_divisor == 2, tff) {
+                (false, true) => 3,
+                (false, false) => 2,
+                (true, true) => 1,
+                (true, false) => 0,
+            };
+            script = script.replace("{{NNEDI3_FIELD}}", &field.to_string());
+            script = process_optional_int("NNEDI3_DEVICE", params.device.filter(|_| params.opencl), script);
+
+            let (active, inactive) = if params.opencl {
+                ("NNEDI3_OPENCL", "NNEDI3_CPU")
+            } else {
+                ("NNEDI3_CPU", "NNEDI3_OPENCL")
+            };
+            script = script.replace(&format!("{{{{#{}}}}}", active), "");
+            script = script.replace(&format!("{{{{/{}}}}}", active), "");
+            script = remove_block(&format!("{{{{#{}}}}}", inactive), &format!("{{{{/{}}}}}", inactive), script);
+        } else if pipeline.deinterlace.enabled {
+            script = remove_block("{{#DEINTERLACE_NNEDI3_BOB}}", "{{/DEINTERLACE_NNEDI3_BOB}}", script);
             script = script.replace("{{#DEINTERLACE}}", "");
             script = script.replace("{{/DEINTERLACE}}", "");
 
@@ -189,97 +474,27 @@ impl ScriptGenerator {
             script = script.replace("{{PRESET}}", params.preset.as_str());
 
             // Process optional QTGMC parameters
-            script = process_optional_bool("TFF", params.tff, script);
-            script = process_optional_int("INPUT_TYPE", if params.input_type != 0 { Some(params.input_type) } else { None }, script);
-            script = process_optional_int("FPS_DIVISOR", if params.fps_divisor != 1 { Some(params.fps_divisor) } else { None }, script);
-
-            // Quality parameters
-            script = process_optional_int("TR0", params.tr0, script);
-            script = process_optional_int("TR1", params.tr1, script);
-            script = process_optional_int("TR2", params.tr2, script);
-            script = process_optional_int("REP0", params.rep0, script);
-            script = process_optional_int("REP1", if params.rep1 != 0 { Some(params.rep1) } else { None }, script);
-            script = process_optional_int("REP2", params.rep2, script);
-            script = process_optional_bool("REP_CHROMA", if !params.rep_chroma { Some(false) } else { None }, script);
-
-            // Interpolation
-            script = process_optional_string("EDI_MODE", params.edi_mode.as_deref(), script);
-            script = process_optional_int("NN_SIZE", params.nn_size, script);
-            script = process_optional_int("NN_NEURONS", params.nn_neurons, script);
-            script = process_optional_int("EDI_QUAL", if params.edi_qual != 1 { Some(params.edi_qual) } else { None }, script);
-            script = process_optional_int("EDI_MAX_D", params.edi_max_d, script);
-            script = process_optional_string("CHROMA_EDI", if params.chroma_edi.is_empty() { None } else { Some(&params.chroma_edi) }, script);
-
-            // Motion analysis
-            script = process_optional_int("BLOCK_SIZE", params.block_size, script);
-            script = process_optional_int("OVERLAP", params.overlap, script);
-            script = process_optional_int("SEARCH", params.search, script);
-            script = process_optional_int("SEARCH_PARAM", params.search_param, script);
-            script = process_optional_int("PEL_SEARCH", params.pel_search, script);
-            script = process_optional_bool("CHROMA_MOTION", params.chroma_motion, script);
-            script = process_optional_bool("TRUE_MOTION", if params.true_motion { Some(true) } else { None }, script);
-            script = process_optional_int("LAMBDA", params.lambda, script);
-            script = process_optional_int("LSAD", params.lsad, script);
-            script = process_optional_int("P_NEW", params.p_new, script);
-            script = process_optional_int("P_LEVEL", params.p_level, script);
-            script = process_optional_bool("GLOBAL_MOTION", if !params.global_motion { Some(false) } else { None }, script);
-            script = process_optional_int("DCT", if params.dct != 0 { Some(params.dct) } else { None }, script);
-            script = process_optional_int("SUB_PEL", params.sub_pel, script);
-            script = process_optional_int("SUB_PEL_INTERP", if params.sub_pel_interp != 2 { Some(params.sub_pel_interp) } else { None }, script);
-
-            // Thresholds
-            script = process_optional_int("TH_SAD1", if params.th_sad1 != 640 { Some(params.th_sad1) } else { None }, script);
-            script = process_optional_int("TH_SAD2", if params.th_sad2 != 256 { Some(params.th_sad2) } else { None }, script);
-            script = process_optional_int("TH_SCD1", if params.th_scd1 != 180 { Some(params.th_scd1) } else { None }, script);
-            script = process_optional_int("TH_SCD2", if params.th_scd2 != 98 { Some(params.th_scd2) } else { None }, script);
-
-            // Sharpening
-            script = process_optional_double("SHARPNESS", params.sharpness, script);
-            script = process_optional_int("S_MODE", params.s_mode, script);
-            script = process_optional_int("SL_MODE", params.sl_mode, script);
-            script = process_optional_int("SL_RAD", params.sl_rad, script);
-            script = process_optional_int("S_OVS", if params.s_ovs != 0 { Some(params.s_ovs) } else { None }, script);
-            script = process_optional_double("SV_THIN", if params.sv_thin != 0.0 { Some(params.sv_thin) } else { None }, script);
-            script = process_optional_int("SBB", params.sbb, script);
-            script = process_optional_int("SRCH_CLIP_PP", params.srch_clip_pp, script);
-
-            // Noise processing
-            script = process_optional_int("NOISE_PROCESS", params.noise_process, script);
-            script = process_optional_double("EZ_DENOISE", params.ez_denoise, script);
-            script = process_optional_double("EZ_KEEP_GRAIN", params.ez_keep_grain, script);
-            script = process_optional_string("NOISE_PRESET", if params.noise_preset != "Fast" { Some(&params.noise_preset) } else { None }, script);
-            script = process_optional_string("DENOISER", params.denoiser.as_deref(), script);
-            script = process_optional_int("FFT_THREADS", if params.fft_threads != 1 { Some(params.fft_threads) } else { None }, script);
-            script = process_optional_bool("DENOISE_MC", params.denoise_mc, script);
-            script = process_optional_int("NOISE_TR", params.noise_tr, script);
-            script = process_optional_double("SIGMA", params.sigma, script);
-            script = process_optional_bool("CHROMA_NOISE", if params.chroma_noise { Some(true) } else { None }, script);
-            script = process_optional_double("SHOW_NOISE", if params.show_noise != 0.0 { Some(params.show_noise) } else { None }, script);
-            script = process_optional_double("GRAIN_RESTORE", params.grain_restore, script);
-            script = process_optional_double("NOISE_RESTORE", params.noise_restore, script);
-            script = process_optional_string("NOISE_DEINT", params.noise_deint.as_deref(), script);
-            script = process_optional_bool("STABILIZE_NOISE", params.stabilize_noise, script);
-
-            // Source matching
-            script = process_optional_int("SOURCE_MATCH", if params.source_match != 0 { Some(params.source_match) } else { None }, script);
-            script = process_optional_string("MATCH_PRESET", params.match_preset.as_deref(), script);
-            script = process_optional_string("MATCH_EDI", params.match_edi.as_deref(), script);
-            script = process_optional_string("MATCH_PRESET2", params.match_preset2.as_deref(), script);
-            script = process_optional_string("MATCH_EDI2", params.match_edi2.as_deref(), script);
-            script = process_optional_int("MATCH_TR2", if params.match_tr2 != 1 { Some(params.match_tr2) } else { None }, script);
-            script = process_optional_double("MATCH_ENHANCE", if (params.match_enhance - 0.5).abs() > 0.001 { Some(params.match_enhance) } else { None }, script);
-            script = process_optional_int("LOSSLESS", if params.lossless != 0 { Some(params.lossless) } else { None }, script);
-
-            // Advanced
-            script = process_optional_bool("BORDER", if params.border { Some(true) } else { None }, script);
-            script = process_optional_bool("PRECISE", params.precise, script);
-            script = process_optional_int("FORCE_TR", if params.force_tr != 0 { Some(params.force_tr) } else { None }, script);
-
-            // GPU
-            script = process_optional_bool("OPENCL", Some(params.opencl), script);
-            script = process_optional_int("DEVICE", params.device, script);
+            script = qtgmc_params(params).apply(script);
+
+            // External interpolation: the snippet runs in a function so it can't rebind `clip`
+            let snippet = params.edi_ext_script.as_deref()
+                .map(sanitize_edi_ext_script)
+                .transpose()
+                .map_err(|e| WorkerError::InvalidConfig.wrap(e))?;
+            match snippet {
+                Some(snippet) => {
+                    let body: Vec<String> = snippet.lines()
+                        .map(|line| if line.trim().is_empty() { String::new() } else { format!("    {}", line) })
+                        .collect();
+                    script = script.replace("{{#EDI_EXT}}", "");
+                    script = script.replace("{{/EDI_EXT}}", "");
+                    script = script.replace("{{EDI_EXT_SCRIPT}}", &body.join("\n"));
+                }
+                None => script = remove_block("{{#EDI_EXT}}", "{{/EDI_EXT}}", script),
+            }
         } else {
             script = remove_block("{{#DEINTERLACE}}", "{{/DEINTERLACE}}", script);
+            script = remove_block("{{#DEINTERLACE_NNEDI3_BOB}}", "{{/DEINTERLACE_NNEDI3_BOB}}", script);
         }
 
         // ====================================================================
@@ -295,20 +510,20 @@ impl ScriptGenerator {
                     script = script.replace("{{#NR_SMDEGRAIN}}", "");
                     script = script.replace("{{/NR_SMDEGRAIN}}", "");
                     script = remove_block("{{#NR_MCTD}}", "{{/NR_MCTD}}", script);
-                    script = remove_block("{{#NR_BM3D}}", "{{/NR_BM3D}}", script);
 
                     script = process_optional_int("NR_TR", Some(nr.sm_degrain_tr), script);
                     script = process_optional_int("NR_TH_SAD", Some(nr.sm_degrain_th_sad), script);
                     script = process_optional_int("NR_TH_SADC", if nr.sm_degrain_th_sadc != nr.sm_degrain_th_sad { Some(nr.sm_degrain_th_sadc) } else { None }, script);
                     script = process_optional_bool("NR_REFINE_MOTION", Some(nr.sm_degrain_refine), script);
                     script = process_optional_int("NR_PREFILTER", if nr.sm_degrain_prefilter != 2 { Some(nr.sm_degrain_prefilter) } else { None }, script);
+                    script = process_optional_int("NR_TH_SCD1", nr.sm_degrain_th_scd1, script);
+                    script = process_optional_int("NR_TH_SCD2", nr.sm_degrain_th_scd2, script);
                     script = process_optional_bool("NR_CONTRASHARP", None, script); // Not in current model
                 }
                 NoiseReductionMethod::McTemporalDenoise => {
                     script = remove_block("{{#NR_SMDEGRAIN}}", "{{/NR_SMDEGRAIN}}", script);
                     script = script.replace("{{#NR_MCTD}}", "");
                     script = script.replace("{{/NR_MCTD}}", "");
-                    script = remove_block("{{#NR_BM3D}}", "{{/NR_BM3D}}", script);
 
                     script = process_optional_double("NR_SIGMA", Some(nr.mc_temporal_sigma), script);
                     script = process_optional_int("NR_RADIUS", Some(nr.mc_temporal_radius), script);
@@ -317,7 +532,6 @@ impl ScriptGenerator {
                     // QTGMC built-in denoising is handled in the QTGMC pass itself
                     script = remove_block("{{#NR_SMDEGRAIN}}", "{{/NR_SMDEGRAIN}}", script);
                     script = remove_block("{{#NR_MCTD}}", "{{/NR_MCTD}}", script);
-                    script = remove_block("{{#NR_BM3D}}", "{{/NR_BM3D}}", script);
                 }
             }
         } else {
@@ -386,6 +600,8 @@ impl ScriptGenerator {
                     script = process_optional_int("DEBLOCK_QUANT2", Some(deblock.quant2), script);
                     script = process_optional_int("DEBLOCK_AOFFSET1", Some(deblock.a_offset1), script);
                     script = process_optional_int("DEBLOCK_AOFFSET2", Some(deblock.a_offset2), script);
+                    let fallback_quant = deblock.fallback_quant();
+                    script = process_optional_int("DEBLOCK_FALLBACK_QUANT", Some(fallback_quant).filter(|_| deblock.deblock_fallback), script);
                 }
                 DeblockMethod::Deblock => {
                     script = remove_block("{{#DEBLOCK_QED}}", "{{/DEBLOCK_QED}}", script);
@@ -423,6 +639,12 @@ impl ScriptGenerator {
         // SHARPEN PASS
         // ====================================================================
         let sharpen = &pipeline.sharpen;
+        if sharpen.enabled && sharpen.sharpen_after_resize {
+            // Sharpen the resized image instead (avoids amplifying aliasing before a downscale)
+            script = move_block("{{#SHARPEN}}", "{{/SHARPEN}}", "{{SHARPEN_POST_RESIZE}}", script);
+        } else {
+            script = script.replace("{{SHARPEN_POST_RESIZE}}\n", "");
+        }
         if sharpen.enabled {
             script = script.replace("{{#SHARPEN}}", "");
             script = script.replace("{{/SHARPEN}}", "");
@@ -444,6 +666,13 @@ impl ScriptGenerator {
                     script = script.replace("{{/SHARPEN_CAS}}", "");
 
                     script = process_optional_double("SHARPEN_CAS_SHARPNESS", Some(sharpen.cas_sharpness), script);
+                    if sharpen.opencl {
+                        script = script.replace("{{#SHARPEN_CAS_OPENCL}}", "");
+                        script = script.replace("{{/SHARPEN_CAS_OPENCL}}", "");
+                        script = process_optional_int("SHARPEN_CAS_DEVICE", sharpen.opencl_device, script);
+                    } else {
+                        script = remove_block("{{#SHARPEN_CAS_OPENCL}}", "{{/SHARPEN_CAS_OPENCL}}", script);
+                    }
                 }
             }
         } else {
@@ -517,6 +746,16 @@ impl ScriptGenerator {
                 script = process_optional_double("COLOR_CONTRAST", if color.contrast != 1.0 { Some(color.contrast) } else { None }, script);
                 script = process_optional_double("COLOR_SATURATION", if color.saturation != 1.0 { Some(color.saturation) } else { None }, script);
                 script = process_optional_double("COLOR_HUE", if color.hue != 0.0 { Some(color.hue) } else { None }, script);
+                if color.has_range_limits() {
+                    script = script.replace("{{#COLOR_RANGE}}", "");
+                    script = script.replace("{{/COLOR_RANGE}}", "");
+                    script = process_optional_double("COLOR_START_HUE", color.tweak_start_hue, script);
+                    script = process_optional_double("COLOR_END_HUE", color.tweak_end_hue, script);
+                    script = process_optional_double("COLOR_MAX_SAT", color.tweak_max_sat, script);
+                    script = process_optional_double("COLOR_MIN_SAT", color.tweak_min_sat, script);
+                } else {
+                    script = remove_block("{{#COLOR_RANGE}}", "{{/COLOR_RANGE}}", script);
+                }
             } else {
                 script = remove_block("{{#COLOR_TWEAK}}", "{{/COLOR_TWEAK}}", script);
             }
@@ -539,10 +778,42 @@ impl ScriptGenerator {
             } else {
                 script = remove_block("{{#COLOR_LEVELS}}", "{{/COLOR_LEVELS}}", script);
             }
+
+            // Chroma magnitude limit, then coring (legal range clamp) after the adjustments
+            script = process_optional_double("COLOR_CHROMA_LIMIT", color.chroma_limit, script);
+            if color.coring {
+                script = script.replace("{{#COLOR_CORING}}", "");
+                script = script.replace("{{/COLOR_CORING}}", "");
+            } else {
+                script = remove_block("{{#COLOR_CORING}}", "{{/COLOR_CORING}}", script);
+            }
         } else {
             script = remove_block("{{#COLOR_CORRECTION}}", "{{/COLOR_CORRECTION}}", script);
         }
 
+        // ====================================================================
+        // GRAIN PASS (AddGrain, optionally luma-adaptive)
+        // ====================================================================
+        let grain = &pipeline.grain;
+        if grain.enabled {
+            script = script.replace("{{#GRAIN}}", "");
+            script = script.replace("{{/GRAIN}}", "");
+
+            script = process_optional_double("GRAIN_VAR", Some(grain.strength), script);
+            script = process_optional_double("GRAIN_UVAR", (grain.chroma_strength > 0.0).then_some(grain.chroma_strength), script);
+            script = process_optional_double("GRAIN_SIZE", (grain.size > 0.0).then_some(grain.size), script);
+            script = process_optional_bool("GRAIN_CONSTANT", (!grain.dynamic).then_some(true), script);
+            if grain.adaptive {
+                script = script.replace("{{#GRAIN_ADAPTIVE}}", "");
+                script = script.replace("{{/GRAIN_ADAPTIVE}}", "");
+                script = script.replace("{{GRAIN_LUMA_SCALING}}", &format_double(grain.luma_scaling));
+            } else {
+                script = remove_block("{{#GRAIN_ADAPTIVE}}", "{{/GRAIN_ADAPTIVE}}", script);
+            }
+        } else {
+            script = remove_block("{{#GRAIN}}", "{{/GRAIN}}", script);
+        }
+
         // ====================================================================
         // RESIZE PASS
         // ====================================================================
@@ -561,6 +832,15 @@ impl ScriptGenerator {
                     UpscaleMethod::Nnedi3Rpow2 => {
                         script = script.replace("{{#UPSCALE_NNEDI3}}", "");
                         script = script.replace("{{/UPSCALE_NNEDI3}}", "");
+                        let (active, inactive) = if resize.opencl {
+                            ("UPSCALE_NNEDI3_OPENCL", "UPSCALE_NNEDI3_CPU")
+                        } else {
+                            ("UPSCALE_NNEDI3_CPU", "UPSCALE_NNEDI3_OPENCL")
+                        };
+                        script = script.replace(&format!("{{{{#{}}}}}", active), "");
+                        script = script.replace(&format!("{{{{/{}}}}}", active), "");
+                        script = remove_block(&format!("{{{{#{}}}}}", inactive), &format!("{{{{/{}}}}}", inactive), script);
+                        script = process_optional_int("UPSCALE_DEVICE", resize.opencl_device.filter(|_| resize.opencl), script);
                         script = remove_block("{{#UPSCALE_EEDI3}}", "{{/UPSCALE_EEDI3}}", script);
                     }
                     UpscaleMethod::Eedi3Rpow2 => {
@@ -589,11 +869,19 @@ impl ScriptGenerator {
                 script = script.replace("{{TARGET_WIDTH}}", &width.to_string());
                 script = script.replace("{{TARGET_HEIGHT}}", &height.to_string());
 
-                // Handle maintain aspect ratio
-                if resize.maintain_aspect {
+                // Handle maintain aspect ratio (a display aspect sets the width instead)
+                if let Some((num, den)) = resize.target_display_aspect {
+                    script = remove_block("{{#MAINTAIN_ASPECT}}", "{{/MAINTAIN_ASPECT}}", script);
+                    script = script.replace("{{#DISPLAY_ASPECT}}", "");
+                    script = script.replace("{{/DISPLAY_ASPECT}}", "");
+                    script = script.replace("{{DISPLAY_ASPECT_NUM}}", &num.to_string());
+                    script = script.replace("{{DISPLAY_ASPECT_DEN}}", &den.to_string());
+                } else if resize.keeps_aspect() {
+                    script = remove_block("{{#DISPLAY_ASPECT}}", "{{/DISPLAY_ASPECT}}", script);
                     script = script.replace("{{#MAINTAIN_ASPECT}}", "");
                     script = script.replace("{{/MAINTAIN_ASPECT}}", "");
                 } else {
+                    script = remove_block("{{#DISPLAY_ASPECT}}", "{{/DISPLAY_ASPECT}}", script);
                     script = remove_block("{{#MAINTAIN_ASPECT}}", "{{/MAINTAIN_ASPECT}}", script);
                 }
 
@@ -635,12 +923,252 @@ impl ScriptGenerator {
             script = remove_block("{{#RESIZE}}", "{{/RESIZE}}", script);
         }
 
-        script
+        Ok(script)
     }
 }
 
-/// Process an optional integer parameter.
-fn process_optional_int(name: &str, value: Option<i32>, mut script: String) -> String {
+/// Reject parameter combinations the filters would fail on at runtime.
+pub fn validate_job(job: &VideoJob) -> Result<()> {
+    let pipeline = job.effective_pipeline();
+    if pipeline.deinterlace.enabled {
+        job.qtgmc_parameters.validate().map_err(|e| WorkerError::InvalidConfig.wrap(e))?;
+        if let Some(snippet) = &job.qtgmc_parameters.edi_ext_script {
+            sanitize_edi_ext_script(snippet).map_err(|e| WorkerError::InvalidConfig.wrap(e))?;
+        }
+    }
+    if pipeline.dedup.enabled {
+        pipeline.dedup.validate().map_err(|e| WorkerError::InvalidConfig.wrap(e))?;
+    }
+    // The source size isn't known yet; run_worker re-checks against the probe
+    pipeline.crop_resize.validate(None).map_err(|e| WorkerError::InvalidConfig.wrap(e))?;
+    job.validate_output_fps().map_err(|e| WorkerError::InvalidConfig.wrap(e))?;
+    job.encoding_settings.validate_output_chroma().map_err(|e| WorkerError::InvalidConfig.wrap(e))?;
+    job.encoding_settings.progress_mode.validate().map_err(|e| WorkerError::InvalidConfig.wrap(e))?;
+    if let Some(overlay) = &job.encoding_settings.overlay {
+        overlay.validate().map_err(|e| WorkerError::InvalidConfig.wrap(e))?;
+    }
+    Ok(())
+}
+
+/// Plugin namespaces bundled with VapourBox or called by the templates.
+pub const KNOWN_PLUGINS: &[&str] = &[
+    "bs", "cas", "ctmf", "dctf", "deblock", "dfttest", "eedi3m", "fmtc", "grain", "misc", "mv",
+    "neo_f3kdb", "nnedi3cl", "rgvs", "tcanny", "tdm", "warp", "znedi3",
+];
+
+/// Script reporting which of the `namespaces` VapourSynth loaded, as a
+/// `PLUGINS:` line of JSON (`{"mv": true, ...}`) on stderr.
+///
+/// It outputs a one-frame blank clip so `vspipe --info` can evaluate it.
+pub fn plugin_probe_script(namespaces: &[&str]) -> String {
+    let names = serde_json::to_string(namespaces).expect("string slices serialize");
+    format!(
+        "import json\n\
+         import sys\n\
+         import vapoursynth as vs\n\
+         core = vs.core\n\
+         print(\"PLUGINS:\" + json.dumps({{name: hasattr(core, name) for name in {}}}), file=sys.stderr)\n\
+         core.std.BlankClip(length=1).set_output()\n",
+        names
+    )
+}
+
+/// Script that only loads `input_path` with BestSource, for `vspipe --info`.
+///
+/// The index goes to `cache_dir` when set, so probing shares the run's locked
+/// index directory (see [`crate::index_cache`]) instead of BestSource's default.
+pub fn clip_probe_script(input_path: &str, cache_dir: Option<&Path>) -> String {
+    let cache_path = cache_dir
+        .map(|dir| format!(", cachepath=\"{}\"", python_string_contents(&platform::long_path(&dir.to_string_lossy()))))
+        .unwrap_or_default();
+    format!(
+        "import vapoursynth as vs\n\
+         vs.core.bs.VideoSource(source=\"{}\"{}).set_output()\n",
+        python_string_contents(&platform::long_path(input_path)),
+        cache_path
+    )
+}
+
+/// Runs of consecutive `keyframes` within source frames `start..=end`, as
+/// half-open `(first, end)` slices of the clip trimmed to start at `start`.
+///
+/// The first frame of the range is always kept, so the proxy starts where
+/// the export does even when that isn't a keyframe.
+fn keyframe_slices(keyframes: &[i32], start: i32, end: Option<i32>) -> Vec<(i32, i32)> {
+    let mut frames: Vec<i32> = keyframes.iter()
+        .filter(|&&k| k >= start && end.is_none_or(|end| k <= end))
+        .map(|&k| k - start)
+        .collect();
+    frames.push(0);
+    frames.sort_unstable();
+    frames.dedup();
+
+    let mut slices: Vec<(i32, i32)> = Vec::new();
+    for frame in frames {
+        match slices.last_mut() {
+            Some((_, end)) if *end == frame => *end += 1,
+            _ => slices.push((frame, frame + 1)),
+        }
+    }
+    slices
+}
+
+/// Escape `value` for use inside a double-quoted (non-raw) Python string literal.
+///
+/// Backslashes are doubled so the path reaches Python unchanged, including
+/// UNC (`\\server\share`) and extended-length (`\\?\`) prefixes.
+fn python_string_contents(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Optional QTGMC arguments, each omitted when it matches QTGMC's own default.
+fn qtgmc_params(params: &QTGMCParameters) -> ParamEmitter {
+    ParamEmitter::new()
+        .bool("TFF", params.tff)
+        .int_unless("INPUT_TYPE", params.input_type, 0)
+        .double("PROG_SAD_MASK", params.prog_sad_mask)
+        .int_unless("FPS_DIVISOR", params.fps_divisor, 1)
+        // Quality parameters
+        .int("TR0", params.tr0)
+        .int("TR1", params.tr1)
+        .int("TR2", params.tr2)
+        .int("REP0", params.rep0)
+        .int("REP1", params.rep1)
+        .int("REP2", params.rep2)
+        .bool_unless("REP_CHROMA", params.rep_chroma, true)
+        // Interpolation
+        .string("EDI_MODE", params.edi_mode.as_deref())
+        .int("NN_SIZE", params.nn_size)
+        .int("NN_NEURONS", params.nn_neurons)
+        .int_unless("EDI_QUAL", params.edi_qual, 1)
+        .int("EDI_MAX_D", params.edi_max_d)
+        .string_unless("CHROMA_EDI", &params.chroma_edi, "")
+        // Motion analysis
+        .int("BLOCK_SIZE", params.block_size)
+        .int("OVERLAP", params.overlap)
+        .int("SEARCH", params.search)
+        .int("SEARCH_PARAM", params.search_param)
+        .int("PEL_SEARCH", params.pel_search)
+        .bool("CHROMA_MOTION", params.chroma_motion)
+        .bool_unless("TRUE_MOTION", params.true_motion, false)
+        .int("LAMBDA", params.lambda)
+        .int("LSAD", params.lsad)
+        .int("P_NEW", params.p_new)
+        .int("P_LEVEL", params.p_level)
+        .bool_unless("GLOBAL_MOTION", params.global_motion, true)
+        .int("DCT", params.dct)
+        .int("SUB_PEL", params.sub_pel)
+        .int("SUB_PEL_INTERP", params.sub_pel_interp)
+        // Thresholds
+        .int_unless("TH_SAD1", params.th_sad1, 640)
+        .int_unless("TH_SAD2", params.th_sad2, 256)
+        .int_unless("TH_SCD1", params.th_scd1, 180)
+        .int_unless("TH_SCD2", params.th_scd2, 98)
+        // Sharpening
+        .double("SHARPNESS", params.sharpness)
+        .int("S_MODE", params.s_mode)
+        .int("SL_MODE", params.sl_mode)
+        .int("SL_RAD", params.sl_rad)
+        .int("S_OVS", params.s_ovs)
+        .double_unless("SV_THIN", params.sv_thin, 0.0)
+        .int("SBB", params.sbb)
+        .int("SRCH_CLIP_PP", params.srch_clip_pp)
+        // Noise processing
+        .int("NOISE_PROCESS", params.noise_process)
+        .double("EZ_DENOISE", params.ez_denoise)
+        .double("EZ_KEEP_GRAIN", params.ez_keep_grain)
+        .string_unless("NOISE_PRESET", &params.noise_preset, "Fast")
+        .string("DENOISER", params.denoiser.as_deref())
+        .int_unless("FFT_THREADS", params.fft_threads, 1)
+        .bool("DENOISE_MC", params.denoise_mc)
+        .int("NOISE_TR", params.noise_tr)
+        .double("SIGMA", params.sigma)
+        .bool_unless("CHROMA_NOISE", params.chroma_noise, false)
+        .double_unless("SHOW_NOISE", params.show_noise, 0.0)
+        .double("GRAIN_RESTORE", params.grain_restore)
+        .double("NOISE_RESTORE", params.noise_restore)
+        .string("NOISE_DEINT", params.noise_deint.as_deref())
+        .bool("STABILIZE_NOISE", params.stabilize_noise)
+        // Source matching
+        .int_unless("SOURCE_MATCH", params.source_match, 0)
+        .string("MATCH_PRESET", params.match_preset.as_deref())
+        .string("MATCH_EDI", params.match_edi.as_deref())
+        .string("MATCH_PRESET2", params.match_preset2.as_deref())
+        .string("MATCH_EDI2", params.match_edi2.as_deref())
+        .int_unless("MATCH_TR2", params.match_tr2, 1)
+        .double_unless("MATCH_ENHANCE", params.match_enhance, 0.5)
+        .int_unless("LOSSLESS", params.lossless, 0)
+        // Advanced
+        .bool_unless("BORDER", params.border, false)
+        .bool("PRECISE", params.precise)
+        .int_unless("FORCE_TR", params.force_tr, 0)
+        // GPU: the device index selects an OpenCL device; QTGMC ignores it on the CPU
+        .bool("OPENCL", Some(params.opencl))
+        .int("DEVICE", params.device.filter(|_| params.opencl))
+}
+
+/// Optional template parameters, applied together.
+///
+/// Each parameter fills a `{{#NAME}}...{{/NAME}}` block and its `{{NAME}}`
+/// placeholder, or removes the block when unset. The `*_unless` variants
+/// leave a parameter unset when it equals the filter's own default, so the
+/// generated call only names what the user changed.
+#[derive(Debug, Default)]
+struct ParamEmitter {
+    params: Vec<(&'static str, Option<String>)>,
+}
+
+impl ParamEmitter {
+    fn new() -> Self {
+        Self::default()
+    }
+
+    fn push(mut self, name: &'static str, value: Option<String>) -> Self {
+        self.params.push((name, value));
+        self
+    }
+
+    fn int(self, name: &'static str, value: Option<i32>) -> Self {
+        self.push(name, value.map(|v| v.to_string()))
+    }
+
+    fn double(self, name: &'static str, value: Option<f64>) -> Self {
+        self.push(name, value.map(format_double))
+    }
+
+    fn bool(self, name: &'static str, value: Option<bool>) -> Self {
+        self.push(name, value.map(|v| python_bool(v).to_string()))
+    }
+
+    fn string(self, name: &'static str, value: Option<&str>) -> Self {
+        self.push(name, value.map(str::to_string))
+    }
+
+    fn int_unless(self, name: &'static str, value: i32, default: i32) -> Self {
+        self.int(name, (value != default).then_some(value))
+    }
+
+    fn double_unless(self, name: &'static str, value: f64, default: f64) -> Self {
+        self.double(name, ((value - default).abs() > 0.0001).then_some(value))
+    }
+
+    fn bool_unless(self, name: &'static str, value: bool, default: bool) -> Self {
+        self.bool(name, (value != default).then_some(value))
+    }
+
+    fn string_unless(self, name: &'static str, value: &str, default: &str) -> Self {
+        self.string(name, (value != default).then_some(value))
+    }
+
+    /// Fill or remove every accumulated block in `script`.
+    fn apply(self, script: String) -> String {
+        self.params.into_iter()
+            .fold(script, |script, (name, value)| process_optional(name, value.as_deref(), script))
+    }
+}
+
+/// Process an optional parameter already formatted for Python.
+fn process_optional(name: &str, value: Option<&str>, mut script: String) -> String {
     let start_tag = format!("{{{{#{}}}}}", name);
     let end_tag = format!("{{{{/{}}}}}", name);
     let placeholder = format!("{{{{{}}}}}", name);
@@ -649,7 +1177,7 @@ fn process_optional_int(name: &str, value: Option<i32>, mut script: String) -> S
         // Include the block with substituted value
         script = script.replace(&start_tag, "");
         script = script.replace(&end_tag, "");
-        script = script.replace(&placeholder, &val.to_string());
+        script = script.replace(&placeholder, val);
     } else {
         // Remove the entire block
         script = remove_block(&start_tag, &end_tag, script);
@@ -657,58 +1185,144 @@ fn process_optional_int(name: &str, value: Option<i32>, mut script: String) -> S
     script
 }
 
-/// Process an optional double parameter.
-fn process_optional_double(name: &str, value: Option<f64>, mut script: String) -> String {
-    let start_tag = format!("{{{{#{}}}}}", name);
-    let end_tag = format!("{{{{/{}}}}}", name);
-    let placeholder = format!("{{{{{}}}}}", name);
-
-    if let Some(val) = value {
-        script = script.replace(&start_tag, "");
-        script = script.replace(&end_tag, "");
-        // Format with minimal precision
-        let formatted = if val.fract() == 0.0 {
-            format!("{:.1}", val)
-        } else {
-            format!("{:.4}", val).trim_end_matches('0').trim_end_matches('.').to_string()
-        };
-        script = script.replace(&placeholder, &formatted);
+/// Format a double with minimal precision (`2.0`, `0.35`).
+fn format_double(val: f64) -> String {
+    if val.fract() == 0.0 {
+        format!("{:.1}", val)
     } else {
-        script = remove_block(&start_tag, &end_tag, script);
+        format!("{:.4}", val).trim_end_matches('0').trim_end_matches('.').to_string()
     }
-    script
+}
+
+fn python_bool(val: bool) -> &'static str {
+    if val { "True" } else { "False" }
+}
+
+/// Process an optional integer parameter.
+fn process_optional_int(name: &str, value: Option<i32>, script: String) -> String {
+    process_optional(name, value.map(|v| v.to_string()).as_deref(), script)
+}
+
+/// Process an optional double parameter.
+fn process_optional_double(name: &str, value: Option<f64>, script: String) -> String {
+    process_optional(name, value.map(format_double).as_deref(), script)
 }
 
 /// Process an optional boolean parameter.
-fn process_optional_bool(name: &str, value: Option<bool>, mut script: String) -> String {
-    let start_tag = format!("{{{{#{}}}}}", name);
-    let end_tag = format!("{{{{/{}}}}}", name);
-    let placeholder = format!("{{{{{}}}}}", name);
+fn process_optional_bool(name: &str, value: Option<bool>, script: String) -> String {
+    process_optional(name, value.map(python_bool), script)
+}
 
-    if let Some(val) = value {
-        script = script.replace(&start_tag, "");
-        script = script.replace(&end_tag, "");
-        script = script.replace(&placeholder, if val { "True" } else { "False" });
-    } else {
-        script = remove_block(&start_tag, &end_tag, script);
+/// Placeholder and block names this module substitutes, in pipeline template
+/// order followed by the preview-only parameters. A placeholder added to a
+/// template must be listed here, or `--check-templates` reports it.
+const TEMPLATE_PLACEHOLDERS: &[&str] = &[
+    "VS_MAX_CACHE_MB", "INPUT_PATH", "SOURCE_THREADS", "SOURCE_SEEK_PREROLL", "SOURCE_CACHE_PATH",
+    "FRAME_TRIM", "START_FRAME", "END_FRAME", "KEYFRAMES_ONLY", "KEYFRAME_SLICES", "LINEAR_LIGHT",
+    "PRE_CROP", "CROP_LEFT", "CROP_RIGHT", "CROP_TOP", "CROP_BOTTOM", "DEDUP",
+    "INTERLACED_CHROMA_FIX", "INTERLACED_CHROMA_FIELD_BASED", "DEINTERLACE_RANGES",
+    "SELECTIVE_DEINTERLACE", "DEINTERLACE", "EDI_EXT", "EDI_EXT_SCRIPT", "PRESET", "TFF",
+    "INPUT_TYPE", "PROG_SAD_MASK", "FPS_DIVISOR", "TR0", "TR1", "TR2", "REP0", "REP1", "REP2",
+    "REP_CHROMA", "EDI_MODE", "NN_SIZE", "NN_NEURONS", "EDI_QUAL", "EDI_MAX_D", "CHROMA_EDI",
+    "BLOCK_SIZE", "OVERLAP", "SEARCH", "SEARCH_PARAM", "PEL_SEARCH", "CHROMA_MOTION", "TRUE_MOTION",
+    "LAMBDA", "LSAD", "P_NEW", "P_LEVEL", "GLOBAL_MOTION", "DCT", "SUB_PEL", "SUB_PEL_INTERP",
+    "TH_SAD1", "TH_SAD2", "TH_SCD1", "TH_SCD2", "SHARPNESS", "S_MODE", "SL_MODE", "SL_RAD", "S_OVS",
+    "SV_THIN", "SBB", "SRCH_CLIP_PP", "NOISE_PROCESS", "EZ_DENOISE", "EZ_KEEP_GRAIN",
+    "NOISE_PRESET", "DENOISER", "FFT_THREADS", "DENOISE_MC", "NOISE_TR", "SIGMA", "CHROMA_NOISE",
+    "SHOW_NOISE", "GRAIN_RESTORE", "NOISE_RESTORE", "NOISE_DEINT", "STABILIZE_NOISE",
+    "SOURCE_MATCH", "MATCH_PRESET", "MATCH_EDI", "MATCH_PRESET2", "MATCH_EDI2", "MATCH_TR2",
+    "MATCH_ENHANCE", "LOSSLESS", "BORDER", "PRECISE", "FORCE_TR", "OPENCL", "DEVICE",
+    "DEINTERLACE_NNEDI3_BOB", "NNEDI3_OPENCL", "NNEDI3_FIELD", "NNEDI3_DEVICE", "NNEDI3_CPU",
+    "DEDUP_DUP_THRESHOLD", "NOISE_REDUCTION", "NR_SMDEGRAIN", "NR_TR", "NR_TH_SAD", "NR_TH_SADC",
+    "NR_REFINE_MOTION", "NR_PREFILTER", "NR_TH_SCD1", "NR_TH_SCD2", "NR_CONTRASHARP", "NR_MCTD",
+    "NR_SIGMA", "NR_RADIUS", "DEHALO", "DEHALO_LINEARIZE", "DEHALO_DEHALO_ALPHA", "DEHALO_RX",
+    "DEHALO_RY", "DEHALO_DARKSTR", "DEHALO_BRIGHTSTR", "DEHALO_FINE_DEHALO", "DEHALO_LOW_THRESHOLD",
+    "DEHALO_HIGH_THRESHOLD", "DEHALO_YAHR", "DEHALO_YAHR_BLUR", "DEHALO_YAHR_DEPTH", "DEBLOCK",
+    "DEBLOCK_QED", "DEBLOCK_FALLBACK_QUANT", "DEBLOCK_QUANT1", "DEBLOCK_QUANT2", "DEBLOCK_AOFFSET1",
+    "DEBLOCK_AOFFSET2", "DEBLOCK_SIMPLE", "DEBAND", "DEBAND_RANGE", "DEBAND_Y", "DEBAND_CB",
+    "DEBAND_CR", "DEBAND_GRAINY", "DEBAND_GRAINC", "DEBAND_DYNAMIC_GRAIN", "DEBAND_OUTPUT_DEPTH",
+    "SHARPEN", "SHARPEN_LSFMOD", "SHARPEN_STRENGTH", "SHARPEN_OVERSHOOT", "SHARPEN_UNDERSHOOT",
+    "SHARPEN_SOFT_EDGE", "SHARPEN_CAS", "SHARPEN_CAS_OPENCL", "SHARPEN_CAS_DEVICE",
+    "SHARPEN_CAS_SHARPNESS", "CHROMA_FIXES", "CHROMA_FIX_BLEEDING", "CHROMA_CX", "CHROMA_CY",
+    "CHROMA_THR", "CHROMA_STRENGTH", "CHROMA_DECRAWL", "DECRAWL_YTHRESH", "DECRAWL_CTHRESH",
+    "DECRAWL_MAXDIFF", "CHROMA_VINVERSE", "VINVERSE_SSTR", "VINVERSE_AMNT", "COLOR_CORRECTION",
+    "COLOR_TWEAK", "COLOR_BRIGHTNESS", "COLOR_CONTRAST", "COLOR_SATURATION", "COLOR_HUE",
+    "COLOR_RANGE", "COLOR_START_HUE", "COLOR_END_HUE", "COLOR_MIN_SAT", "COLOR_MAX_SAT",
+    "COLOR_LEVELS", "LEVELS_INPUT_LOW", "LEVELS_INPUT_HIGH", "LEVELS_OUTPUT_LOW",
+    "LEVELS_OUTPUT_HIGH", "LEVELS_GAMMA", "COLOR_CHROMA_LIMIT", "COLOR_CORING", "GRAIN",
+    "GRAIN_VAR", "GRAIN_UVAR", "GRAIN_SIZE", "GRAIN_CONSTANT", "GRAIN_ADAPTIVE",
+    "GRAIN_LUMA_SCALING", "POST_CROP", "RESIZE", "RESIZE_LINEARIZE", "RESIZE_INTEGER_UPSCALE",
+    "UPSCALE_NNEDI3", "UPSCALE_NNEDI3_OPENCL", "UPSCALE_DEVICE", "UPSCALE_NNEDI3_CPU",
+    "UPSCALE_FACTOR", "UPSCALE_EEDI3", "RESIZE_STANDARD", "TARGET_WIDTH", "TARGET_HEIGHT",
+    "MAINTAIN_ASPECT", "DISPLAY_ASPECT", "DISPLAY_ASPECT_NUM", "DISPLAY_ASPECT_DEN",
+    "RESIZE_SPLINE36", "RESIZE_LANCZOS", "RESIZE_BICUBIC", "RESIZE_BILINEAR", "SHARPEN_POST_RESIZE",
+    "OUTPUT_FPS", "OUTPUT_FPS_NUM", "OUTPUT_FPS_DEN", "OUTPUT_CHROMA", "OUTPUT_CHROMA_NAME",
+    "OUTPUT_CHROMA_SSW", "OUTPUT_CHROMA_SSH", "MAX_OUTPUT_DEPTH", "VIDEO_PATH", "FPS_NUM",
+    "FPS_DEN", "FIELD_BASED", "PREVIEW_MIDDLE_FRAME",
+];
+
+/// [`TEMPLATE_PLACEHOLDERS`] as a set, for checking templates.
+fn known_placeholders() -> HashSet<String> {
+    TEMPLATE_PLACEHOLDERS.iter().map(|name| name.to_string()).collect()
+}
+
+/// Problems in one template: unbalanced block tags and unknown placeholders.
+///
+/// Tags inside the leading docstring (which documents the syntax) are skipped.
+fn check_template(name: &str, template: &str, known: &HashSet<String>) -> Vec<String> {
+    let body_start = template.strip_prefix("\"\"\"")
+        .and_then(|rest| rest.find("\"\"\"").map(|end| end + 6))
+        .unwrap_or(0);
+
+    let mut problems = Vec::new();
+    let mut open_blocks: Vec<(&str, usize)> = Vec::new();
+    for (index, line) in template.lines().enumerate() {
+        let line_start = line.as_ptr() as usize - template.as_ptr() as usize;
+        if line_start < body_start {
+            continue;
+        }
+        let line_no = index + 1;
+        let mut rest = line;
+        while let Some(start) = rest.find("{{") {
+            let Some(len) = rest[start..].find("}}") else { break };
+            let tag = &rest[start + 2..start + len];
+            rest = &rest[start + len + 2..];
+
+            // Unknown blocks are reported at their opening tag only
+            if !tag.starts_with('/') && !known.contains(tag.trim_start_matches('#')) {
+                problems.push(format!("{}:{}: unknown placeholder {{{{{}}}}}", name, line_no, tag));
+            }
+            if let Some(block) = tag.strip_prefix('#') {
+                open_blocks.push((block, line_no));
+            } else if let Some(block) = tag.strip_prefix('/') {
+                match open_blocks.pop() {
+                    Some((open, _)) if open == block => {}
+                    Some((open, open_line)) => {
+                        problems.push(format!(
+                            "{}:{}: {{{{/{}}}}} closes {{{{#{}}}}} opened on line {}",
+                            name, line_no, block, open, open_line
+                        ));
+                    }
+                    None => problems.push(format!("{}:{}: {{{{/{}}}}} has no opening tag", name, line_no, block)),
+                }
+            }
+        }
     }
-    script
+    for (block, line_no) in open_blocks {
+        problems.push(format!("{}:{}: {{{{#{}}}}} is never closed", name, line_no, block));
+    }
+    problems
 }
 
-/// Process an optional string parameter.
-fn process_optional_string(name: &str, value: Option<&str>, mut script: String) -> String {
-    let start_tag = format!("{{{{#{}}}}}", name);
-    let end_tag = format!("{{{{/{}}}}}", name);
-    let placeholder = format!("{{{{{}}}}}", name);
+/// Move a block (start tag to end tag, inclusive) to replace `marker`.
+fn move_block(start_tag: &str, end_tag: &str, marker: &str, script: String) -> String {
+    let Some(start_pos) = script.find(start_tag) else { return script };
+    let Some(end_offset) = script[start_pos..].find(end_tag) else { return script };
+    let end_pos = start_pos + end_offset + end_tag.len();
 
-    if let Some(val) = value {
-        script = script.replace(&start_tag, "");
-        script = script.replace(&end_tag, "");
-        script = script.replace(&placeholder, val);
-    } else {
-        script = remove_block(&start_tag, &end_tag, script);
-    }
-    script
+    let block = script[start_pos..end_pos].to_string();
+    let without = format!("{}{}", &script[..start_pos], &script[end_pos..]);
+    without.replacen(marker, &block, 1)
 }
 
 /// Remove a block from start tag to end tag (including the line).
@@ -734,6 +1348,127 @@ fn remove_block(start_tag: &str, end_tag: &str, mut script: String) -> String {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_plugin_probe_script() {
+        let script = plugin_probe_script(&["mv", "neo_f3kdb"]);
+        assert_eq!(
+            script,
+            "import json\n\
+             import sys\n\
+             import vapoursynth as vs\n\
+             core = vs.core\n\
+             print(\"PLUGINS:\" + json.dumps({name: hasattr(core, name) for name in [\"mv\",\"neo_f3kdb\"]}), file=sys.stderr)\n\
+             core.std.BlankClip(length=1).set_output()\n"
+        );
+        assert!(script.lines().all(|line| !line.starts_with(char::is_whitespace)), "Top-level Python must not be indented");
+    }
+
+    #[test]
+    fn test_clip_probe_script_uses_index_cache_dir() {
+        assert_eq!(
+            clip_probe_script("/videos/\"tape\".avi", None),
+            "import vapoursynth as vs\nvs.core.bs.VideoSource(source=\"/videos/\\\"tape\\\".avi\").set_output()\n"
+        );
+        let script = clip_probe_script("/videos/tape.avi", Some(Path::new("/cache/index/abc")));
+        assert!(script.contains("VideoSource(source=\"/videos/tape.avi\", cachepath=\"/cache/index/abc\")"));
+    }
+
+    #[test]
+    fn test_keyframe_slices() {
+        // Consecutive keyframes merge into one slice; the range start is always kept
+        assert_eq!(
+            keyframe_slices(&[0, 12, 13, 14, 24, 36], 0, None),
+            vec![(0, 1), (12, 15), (24, 25), (36, 37)]
+        );
+        // Trimmed to source frames 10..=30: shifted to the trimmed clip
+        assert_eq!(keyframe_slices(&[0, 12, 13, 14, 24, 36], 10, Some(30)), vec![(0, 1), (2, 5), (14, 15)]);
+        assert_eq!(keyframe_slices(&[], 5, None), vec![(0, 1)]);
+    }
+
+    #[test]
+    fn test_validate_template_reports_unbalanced_and_unknown_tags() {
+        let template = "\"\"\"\nBlocks use: {{#BLOCK_NAME}}...{{/BLOCK_NAME}}\n\"\"\"\n\
+            {{#DEHALO}}\n\
+            {{#DEBAND}}\n\
+            x = {{DEBAND_RANGE}}\n\
+            {{/DEHALO}}\n\
+            y = {{NOT_A_PARAMETER}}\n\
+            {{/SHARPEN}}\n\
+            {{#RESIZE}}\n";
+        let generator = ScriptGenerator {
+            template: template.to_string(),
+            preview_template: "{{#PRE_CROP}}\n{{CROP_LEFT}}\n{{/PRE_CROP}}\n".to_string(),
+            keep_temp: false,
+            index_cache_dir: None,
+        };
+        assert_eq!(generator.validate_template(), vec![
+            "pipeline_template.vpy:7: {{/DEHALO}} closes {{#DEBAND}} opened on line 5".to_string(),
+            "pipeline_template.vpy:8: unknown placeholder {{NOT_A_PARAMETER}}".to_string(),
+            "pipeline_template.vpy:9: {{/SHARPEN}} closes {{#DEHALO}} opened on line 4".to_string(),
+            "pipeline_template.vpy:10: {{#RESIZE}} is never closed".to_string(),
+        ]);
+    }
+
+    #[test]
+    fn test_known_placeholders_cover_substitutions() {
+        let known = known_placeholders();
+        for name in [
+            "INPUT_PATH", "PRE_CROP", "NNEDI3_OPENCL", "TR0", "VIDEO_PATH", "SHARPEN_POST_RESIZE",
+            "COLOR_RANGE", "COLOR_CHROMA_LIMIT", "DEDUP", "DEDUP_DUP_THRESHOLD", "PREVIEW_MIDDLE_FRAME",
+        ] {
+            assert!(known.contains(name), "{} should be known", name);
+        }
+        assert!(!known.contains("NOT_A_PARAMETER"));
+        assert_eq!(known.len(), TEMPLATE_PLACEHOLDERS.len(), "Placeholders are listed once");
+    }
+
+    #[test]
+    fn test_shipped_templates_pass_check() {
+        let generator = ScriptGenerator::new().unwrap();
+        assert_eq!(generator.validate_template(), Vec::<String>::new());
+
+        // Rendering leaves no placeholder behind
+        let job: VideoJob = serde_json::from_value(serde_json::json!({
+            "id": "00000000-0000-0000-0000-000000000001",
+            "inputPath": "input.avi",
+            "outputPath": "output.mkv",
+            "qtgmcParameters": {},
+            "encodingSettings": {}
+        }))
+        .unwrap();
+        // (the leading docstring documents the tag syntax)
+        let body = |script: String| script.splitn(3, "\"\"\"").last().unwrap().to_string();
+        assert!(!body(generator.generate_to_string(&job).unwrap()).contains("{{"));
+        let params = PreviewParams {
+            video_path: "clip.mkv".to_string(),
+            fps_num: 30000,
+            fps_den: 1001,
+            field_based: 2,
+            middle_frame: 5,
+        };
+        assert!(!body(generator.build_preview_script(&job, &params).unwrap()).contains("{{"));
+    }
+
+    #[test]
+    fn test_validate_job_checks_generated_edi_ext_script() {
+        let mut job: VideoJob = serde_json::from_value(serde_json::json!({
+            "id": "00000000-0000-0000-0000-000000000001",
+            "inputPath": "input.avi",
+            "outputPath": "output.mkv",
+            "qtgmcParameters": {},
+            "restorationPipeline": {},
+            "encodingSettings": {}
+        }))
+        .unwrap();
+        assert!(validate_job(&job).is_ok());
+
+        // The generator reads the QTGMC parameters' snippet, whatever the pipeline holds
+        job.qtgmc_parameters.edi_ext_script = Some("edi_ext_clip = clip\nedi_ext_clip.set_output()".to_string());
+        let err = validate_job(&job).unwrap_err();
+        assert!(format!("{:#}", err).contains("set_output"), "{:#}", err);
+        assert!(ScriptGenerator::new().unwrap().generate_to_string(&job).is_err());
+    }
+
     #[test]
     fn test_remove_block() {
         let input = "before\n{{#TEST}}content{{/TEST}}\nafter";
@@ -754,4 +1489,64 @@ mod tests {
         let result = process_optional_int("NUM", None, input.to_string());
         assert_eq!(result, "prefixsuffix");
     }
+
+    /// The QTGMC call block of the pipeline template.
+    fn qtgmc_block() -> String {
+        let template = include_str!("../templates/pipeline_template.vpy");
+        let start = template.find("{{#DEINTERLACE}}").unwrap();
+        let end = template.find("{{/DEINTERLACE}}").unwrap();
+        // The EdiExt snippet is substituted separately from the parameters
+        remove_block("{{#EDI_EXT}}", "{{/EDI_EXT}}", template[start..end].to_string())
+    }
+
+    #[test]
+    fn test_param_emitter_matches_process_optional() {
+        let script = "{{#A}}a={{A}},{{/A}}{{#B}}b={{B}},{{/B}}{{#C}}c={{C}},{{/C}}{{#D}}d={{D}},{{/D}}".to_string();
+        let expected = process_optional_int("A", Some(3), script.clone());
+        let expected = process_optional_double("B", Some(0.35), expected);
+        let expected = process_optional_bool("C", None, expected);
+        let expected = process_optional("D", Some("NNEDI3"), expected);
+
+        let emitted = ParamEmitter::new()
+            .int_unless("A", 3, 1)
+            .double("B", Some(0.35))
+            .bool_unless("C", true, true)
+            .string("D", Some("NNEDI3"))
+            .apply(script);
+        assert_eq!(emitted, expected);
+        assert_eq!(emitted, "a=3,b=0.35,d=NNEDI3,");
+    }
+
+    #[test]
+    fn test_qtgmc_params_render_deinterlace_block() {
+        let defaults = qtgmc_params(&QTGMCParameters::default()).apply(qtgmc_block());
+        assert_eq!(
+            defaults,
+            "{{#DEINTERLACE}}\nclip = haf.QTGMC(\n    clip,\n    Preset=\"{{PRESET}}\",\n\n    opencl=False,\n\n)\n",
+        );
+
+        let params = QTGMCParameters {
+            tff: Some(true),
+            fps_divisor: 2,
+            tr2: Some(3),
+            true_motion: true,
+            global_motion: false,
+            sharpness: Some(1.0),
+            match_enhance: 0.75,
+            noise_preset: "Slow".to_string(),
+            opencl: true,
+            device: Some(1),
+            ..QTGMCParameters::default()
+        };
+        let custom = qtgmc_params(&params).apply(qtgmc_block());
+        for line in [
+            "    TFF=True,\n", "    FPSDivisor=2,\n", "    TR2=3,\n", "    TrueMotion=True,\n",
+            "    GlobalMotion=False,\n", "    Sharpness=1.0,\n", "    MatchEnhance=0.75,\n",
+            "    NoisePreset=\"Slow\",\n", "    opencl=True,\n", "    device=1,\n",
+        ] {
+            assert!(custom.contains(line), "Missing {:?} in:\n{}", line, custom);
+        }
+        // Only the enclosing block tag and the preset placeholder remain
+        assert_eq!(custom.matches("{{").count(), 2, "Unresolved optional block in:\n{}", custom);
+    }
 }
@@ -0,0 +1,161 @@
+//! End-to-end tests that run the full vspipe | ffmpeg pipeline.
+//!
+//! These are skipped unless `VAPOURBOX_RUN_E2E=1` is set and vspipe, ffmpeg
+//! and ffprobe can be located. Run with:
+//! VAPOURBOX_RUN_E2E=1 cargo test --test e2e_test -- --nocapture
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use uuid::Uuid;
+
+use vapourbox_worker::dependency_locator::DependencyLocator;
+use vapourbox_worker::models::*;
+use vapourbox_worker::pipeline_executor::PipelineExecutor;
+use vapourbox_worker::progress_reporter::ProgressReporter;
+use vapourbox_worker::script_generator::ScriptGenerator;
+
+fn test_resources_dir() -> PathBuf {
+    let manifest_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+    PathBuf::from(manifest_dir).parent().unwrap().join("Tests")
+}
+
+/// ffprobe path if end-to-end tests should run, otherwise None (with the reason printed).
+fn e2e_ffprobe() -> Option<PathBuf> {
+    if std::env::var("VAPOURBOX_RUN_E2E").as_deref() != Ok("1") {
+        println!("Skipping: set VAPOURBOX_RUN_E2E=1 to run end-to-end tests");
+        return None;
+    }
+    let deps = match DependencyLocator::new() {
+        Ok(deps) => deps,
+        Err(e) => {
+            println!("Skipping: dependencies unavailable ({:#})", e);
+            return None;
+        }
+    };
+    if let Err(e) = deps.vspipe_path().and(deps.ffmpeg_path()) {
+        println!("Skipping: dependencies unavailable ({:#})", e);
+        return None;
+    }
+    match deps.ffprobe_path() {
+        Ok(path) => Some(path),
+        Err(e) => {
+            println!("Skipping: ffprobe unavailable ({:#})", e);
+            None
+        }
+    }
+}
+
+/// Decoded video frame count of `path`.
+fn count_frames(ffprobe: &Path, path: &Path) -> u64 {
+    let output = Command::new(ffprobe)
+        .args(["-v", "error", "-select_streams", "v:0", "-count_frames",
+               "-show_entries", "stream=nb_read_frames", "-of", "csv=p=0"])
+        .arg(path)
+        .output()
+        .expect("Failed to run ffprobe");
+    assert!(output.status.success(), "ffprobe failed on {:?}", path);
+    String::from_utf8_lossy(&output.stdout).trim().parse().expect("Unexpected ffprobe output")
+}
+
+fn create_e2e_job(name: &str, deinterlace: QTGMCParameters, crop_resize: CropResizeParameters) -> VideoJob {
+    let output_dir = test_resources_dir().join("TestOutput");
+    std::fs::create_dir_all(&output_dir).ok();
+
+    VideoJob {
+        id: Uuid::new_v4(),
+        input_path: test_resources_dir().join("TestResources").join("interlaced_test.avi").to_string_lossy().to_string(),
+        output_path: output_dir.join(format!("e2e_{}.mkv", name)).to_string_lossy().to_string(),
+        output_template: None,
+        qtgmc_parameters: deinterlace.clone(),
+        restoration_pipeline: Some(RestorationPipeline {
+            deinterlace,
+            crop_resize,
+            ..RestorationPipeline::default()
+        }),
+        encoding_settings: EncodingSettings {
+            codec: VideoCodec::FFV1,
+            container: ContainerFormat::Mkv,
+            ..EncodingSettings::default()
+        },
+        detected_field_order: Some(FieldOrder::TopFieldFirst),
+        total_frames: None,
+        input_frame_rate: None,
+        start_frame: None,
+        end_frame: None,
+        source_threads: None,
+        source_seek_preroll: None,
+        vs_max_cache_mb: None,
+        create_output_dirs: true,
+        measure_quality: false,
+        preview_range: PreviewRange::Auto,
+        warn_unnecessary_deinterlace: true,
+        check_disk_space: false,
+        keyframes_only: false,
+        keyframes: None,
+        on_complete_command: None,
+    }
+}
+
+/// Run `job` through the real pipeline and check the output frame count.
+fn run_e2e(ffprobe: &Path, job: &VideoJob, frame_multiplier: u64) {
+    let output = PathBuf::from(&job.output_path);
+    let _ = std::fs::remove_file(&output);
+
+    let script_path = ScriptGenerator::new()
+        .and_then(|g| g.generate(job))
+        .expect("Failed to generate script");
+    let mut executor = PipelineExecutor::new(ProgressReporter::disabled()).expect("Failed to create executor");
+    executor.execute(&script_path, job, || false).expect("Pipeline failed");
+
+    let size = std::fs::metadata(&output).map(|m| m.len()).unwrap_or(0);
+    assert!(size > 0, "Output {:?} should be non-empty", output);
+
+    let source_frames = count_frames(ffprobe, Path::new(&job.input_path));
+    assert_eq!(count_frames(ffprobe, &output), source_frames * frame_multiplier);
+}
+
+#[test]
+fn test_e2e_qtgmc_double_rate() {
+    let Some(ffprobe) = e2e_ffprobe() else { return };
+
+    let job = create_e2e_job(
+        "qtgmc_double_rate",
+        QTGMCParameters {
+            enabled: true,
+            preset: QTGMCPreset::Fast,
+            tff: Some(true),
+            fps_divisor: 1,
+            ..QTGMCParameters::default()
+        },
+        CropResizeParameters::default(),
+    );
+    run_e2e(&ffprobe, &job, 2);
+}
+
+#[test]
+fn test_e2e_qtgmc_single_rate_with_resize() {
+    let Some(ffprobe) = e2e_ffprobe() else { return };
+
+    let job = create_e2e_job(
+        "qtgmc_single_rate_resize",
+        QTGMCParameters {
+            enabled: true,
+            preset: QTGMCPreset::Fast,
+            tff: Some(true),
+            fps_divisor: 2,
+            ..QTGMCParameters::default()
+        },
+        CropResizeParameters {
+            enabled: true,
+            crop_enabled: true,
+            crop_left: 8,
+            crop_right: 8,
+            resize_enabled: true,
+            target_width: Some(640),
+            target_height: Some(480),
+            maintain_aspect: false,
+            ..CropResizeParameters::default()
+        },
+    );
+    run_e2e(&ffprobe, &job, 1);
+}
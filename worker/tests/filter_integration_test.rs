@@ -8,7 +8,8 @@ use uuid::Uuid;
 
 // Import the worker's models
 use vapourbox_worker::models::*;
-use vapourbox_worker::script_generator::ScriptGenerator;
+use vapourbox_worker::platform;
+use vapourbox_worker::script_generator::{PreviewParams, ScriptGenerator};
 
 fn get_test_input() -> PathBuf {
     let manifest_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
@@ -42,6 +43,7 @@ fn create_base_job(output_name: &str) -> VideoJob {
         id: Uuid::new_v4(),
         input_path: get_test_input().to_string_lossy().to_string(),
         output_path: get_output_path(output_name).to_string_lossy().to_string(),
+        output_template: None,
         qtgmc_parameters: QTGMCParameters::default(),
         restoration_pipeline: None,
         encoding_settings: EncodingSettings {
@@ -54,6 +56,17 @@ fn create_base_job(output_name: &str) -> VideoJob {
         input_frame_rate: None,
         start_frame: None,
         end_frame: None,
+        source_threads: None,
+        source_seek_preroll: None,
+        vs_max_cache_mb: None,
+        create_output_dirs: true,
+        measure_quality: false,
+        preview_range: PreviewRange::Auto,
+        warn_unnecessary_deinterlace: true,
+        check_disk_space: false,
+        keyframes_only: false,
+        keyframes: None,
+        on_complete_command: None,
     }
 }
 
@@ -516,7 +529,7 @@ fn test_18_codec_h264() {
     job.qtgmc_parameters.tff = Some(true);
     job.encoding_settings.codec = VideoCodec::H264;
     job.encoding_settings.container = ContainerFormat::Mp4;
-    job.encoding_settings.quality = 18;
+    job.encoding_settings.quality = Some(18);
 
     job.restoration_pipeline = Some(RestorationPipeline {
         deinterlace: job.qtgmc_parameters.clone(),
@@ -537,7 +550,7 @@ fn test_19_codec_h265() {
     job.qtgmc_parameters.tff = Some(true);
     job.encoding_settings.codec = VideoCodec::H265;
     job.encoding_settings.container = ContainerFormat::Mp4;
-    job.encoding_settings.quality = 20;
+    job.encoding_settings.quality = Some(20);
 
     job.restoration_pipeline = Some(RestorationPipeline {
         deinterlace: job.qtgmc_parameters.clone(),
@@ -564,6 +577,7 @@ fn test_20_combined_all_filters() {
     };
 
     job.restoration_pipeline = Some(RestorationPipeline {
+        dedup: DedupParameters::default(),
         deinterlace: job.qtgmc_parameters.clone(),
         noise_reduction: NoiseReductionParameters {
             enabled: true,
@@ -599,6 +613,7 @@ fn test_20_combined_all_filters() {
             vinverse_scl: 12,
             ..ChromaFixParameters::default()
         },
+        grain: GrainParameters::default(),
         crop_resize: CropResizeParameters {
             enabled: true,
             crop_enabled: true,
@@ -613,6 +628,7 @@ fn test_20_combined_all_filters() {
             maintain_aspect: true,
             ..CropResizeParameters::default()
         },
+        scene_sensitivity: None,
     });
 
     run_job(&job, "Combined - All Filters Active").unwrap();
@@ -637,6 +653,10 @@ fn test_21_sharpen_lsfmod() {
             undershoot: 2,
             soft_edge: 0,
             cas_sharpness: 0.5,
+            sharpen_after_resize: false,
+            opencl: false,
+            opencl_device: None,
+            bypass: false,
         },
         ..RestorationPipeline::default()
     });
@@ -663,6 +683,10 @@ fn test_22_sharpen_cas() {
             undershoot: 1,
             soft_edge: 0,
             cas_sharpness: 0.7,
+            sharpen_after_resize: false,
+            opencl: false,
+            opencl_device: None,
+            bypass: false,
         },
         ..RestorationPipeline::default()
     });
@@ -781,6 +805,7 @@ fn test_27_deband() {
         deinterlace: job.qtgmc_parameters.clone(),
         deband: DebandParameters {
             enabled: true,
+            bypass: false,
             range: 15,
             y: 64,
             cb: 64,
@@ -848,6 +873,10 @@ fn test_28_verify_sharpen_lsfmod_in_script() {
             undershoot: 2,
             soft_edge: 0,
             cas_sharpness: 0.5,
+            sharpen_after_resize: false,
+            opencl: false,
+            opencl_device: None,
+            bypass: false,
         },
         ..RestorationPipeline::default()
     });
@@ -879,6 +908,10 @@ fn test_29_verify_sharpen_cas_in_script() {
             undershoot: 1,
             soft_edge: 0,
             cas_sharpness: 0.7,
+            sharpen_after_resize: false,
+            opencl: false,
+            opencl_device: None,
+            bypass: false,
         },
         ..RestorationPipeline::default()
     });
@@ -960,6 +993,7 @@ fn test_32_verify_deband_in_script() {
         deinterlace: job.qtgmc_parameters.clone(),
         deband: DebandParameters {
             enabled: true,
+            bypass: false,
             range: 15,
             y: 64,
             cb: 64,
@@ -998,35 +1032,84 @@ fn build_ffmpeg_args(job: &VideoJob) -> Vec<String> {
     // Input 1: Original file for audio stream
     args.extend(["-i".to_string(), job.input_path.clone()]);
 
-    // Progress output
+    // Input 2: Overlay image
+    if let Some(overlay) = &settings.overlay {
+        args.extend(["-i".to_string(), overlay.path.clone()]);
+    }
+
+    // Progress output, with log messages tagged by level
+    args.extend(["-loglevel".to_string(), format!("level+{}", settings.ffmpeg_loglevel.as_str())]);
     args.extend(["-progress".to_string(), "pipe:2".to_string()]);
+    if let ProgressMode::EveryNFrames(_) = settings.progress_mode {
+        // ffmpeg writes progress every 0.5s by default; sample finely enough to see each boundary
+        args.extend(["-stats_period".to_string(), "0.1".to_string()]);
+    }
+
+    // Video filters: overlay graph (with the SAR tag) or just the SAR
+    if let Some(overlay) = &settings.overlay {
+        args.extend(["-filter_complex".to_string(), overlay.filtergraph(2, settings.output_sar)]);
+    } else if let Some((num, den)) = settings.output_sar {
+        args.extend(["-vf".to_string(), format!("setsar={}/{}", num, den)]);
+    }
 
-    // Map streams: video from input 0 (processed), audio from input 1 (original)
-    args.extend(["-map".to_string(), "0:v".to_string()]);
-    args.extend(["-map".to_string(), "1:a?".to_string()]);
+    // Map streams: video from input 0 (processed, or the overlay graph), audio from input 1 (original)
+    let video_map = if settings.overlay.is_some() { "[vout]" } else { "0:v" };
+    args.extend(["-map".to_string(), video_map.to_string()]);
+    if !job.keyframes_only {
+        args.extend(["-map".to_string(), settings.audio_map()]);
+    }
 
     // Video codec
     args.extend(["-c:v".to_string(), settings.codec.ffmpeg_codec().to_string()]);
 
-    // Quality settings (CRF for H.264/H.265)
-    if settings.codec.prores_profile().is_none() {
-        args.extend(["-crf".to_string(), settings.quality.to_string()]);
-        args.extend(["-preset".to_string(), settings.encoder_preset.clone()]);
+    // Quality settings (ProRes profile, CRF for H.264/H.265)
+    args.extend(settings.video_quality_args());
+
+    // Pixel format (keeps 4:2:2 chroma where the codec supports it)
+    if let Some(pix_fmt) = settings.pix_fmt() {
+        args.extend(["-pix_fmt".to_string(), pix_fmt]);
+    }
+
+    // Container frame rate tag
+    if let Some((num, den)) = settings.output_fps {
+        args.extend(["-r".to_string(), format!("{}/{}", num, den)]);
     }
 
     // Audio handling - this is the critical part for audio passthrough
-    if settings.audio_copy {
+    if job.keyframes_only {
+        // Keyframes-only proxy: no audio
+        args.push("-an".to_string());
+    } else if settings.audio_copy {
         // Copy audio stream unchanged from input
         args.extend(["-c:a".to_string(), "copy".to_string()]);
     } else {
         // Re-encode audio (not recommended for quality preservation)
         args.extend(["-c:a".to_string(), settings.audio_codec.clone()]);
         args.extend(["-b:a".to_string(), format!("{}k", settings.audio_bitrate)]);
+        if let Some(rate) = settings.audio_sample_rate {
+            args.extend(["-ar".to_string(), rate.to_string()]);
+        }
+        if let Some(channels) = settings.audio_channels {
+            args.extend(["-ac".to_string(), channels.to_string()]);
+        }
+    }
+
+    // Pipeline summary in the output metadata
+    if settings.embed_pipeline_metadata {
+        args.extend([
+            "-metadata".to_string(),
+            format!("comment=VapourBox: {}", job.effective_pipeline().describe()),
+        ]);
+    }
+
+    // Index at the front of MP4/MOV for progressive playback
+    if settings.faststart {
+        args.extend(["-movflags".to_string(), "+faststart".to_string()]);
     }
 
     // Output file
     args.push("-y".to_string());
-    args.push(job.output_path.clone());
+    args.push(platform::long_path(&job.output_path));
 
     args
 }
@@ -1197,3 +1280,1140 @@ fn test_36_verify_vspipe_outputs_video_only() {
     println!("  - FFmpeg copies audio from original input file");
     println!("  - Result: processed video + original unchanged audio");
 }
+
+// ============================================================================
+// Crop Stage Tests
+// ============================================================================
+
+fn create_crop_stage_job(output_name: &str, crop_stage: CropStage) -> VideoJob {
+    let mut job = create_base_job(output_name);
+    job.qtgmc_parameters.enabled = true;
+    job.qtgmc_parameters.preset = QTGMCPreset::Fast;
+    job.qtgmc_parameters.tff = Some(true);
+    job.restoration_pipeline = Some(RestorationPipeline {
+        deinterlace: job.qtgmc_parameters.clone(),
+        crop_resize: CropResizeParameters {
+            enabled: true,
+            crop_enabled: true,
+            crop_left: 8,
+            crop_right: 8,
+            crop_top: 4,
+            crop_bottom: 4,
+            crop_stage,
+            ..CropResizeParameters::default()
+        },
+        ..RestorationPipeline::default()
+    });
+    job
+}
+
+#[test]
+fn test_37_verify_pre_deinterlace_crop_before_qtgmc() {
+    // Test: Default crop stage crops the source before QTGMC sees it
+    create_output_dir();
+
+    let job = create_crop_stage_job("test_37_pre_crop", CropStage::PreDeinterlace);
+    let generator = ScriptGenerator::new().expect("Failed to create generator");
+    let script_path = generator.generate(&job).expect("Failed to generate script");
+    let script_content = std::fs::read_to_string(&script_path).unwrap_or_default();
+
+    let crop_pos = script_content.find("core.std.Crop(").expect("Script should crop");
+    let qtgmc_pos = script_content.find("haf.QTGMC(").expect("Script should deinterlace");
+    assert!(crop_pos < qtgmc_pos, "Pre-deinterlace crop must precede QTGMC");
+    assert_eq!(script_content.matches("core.std.Crop(").count(), 1, "Crop must be emitted once");
+
+    println!("✓ Pre-deinterlace crop runs before QTGMC");
+}
+
+#[test]
+fn test_38_verify_post_process_crop_after_qtgmc() {
+    // Test: Post-process crop runs after QTGMC so deinterlacer edge artifacts are removed
+    create_output_dir();
+
+    let job = create_crop_stage_job("test_38_post_crop", CropStage::PostProcess);
+    let generator = ScriptGenerator::new().expect("Failed to create generator");
+    let script_path = generator.generate(&job).expect("Failed to generate script");
+    let script_content = std::fs::read_to_string(&script_path).unwrap_or_default();
+
+    let crop_pos = script_content.find("core.std.Crop(").expect("Script should crop");
+    let qtgmc_pos = script_content.find("haf.QTGMC(").expect("Script should deinterlace");
+    assert!(crop_pos > qtgmc_pos, "Post-process crop must follow QTGMC");
+    assert_eq!(script_content.matches("core.std.Crop(").count(), 1, "Crop must be emitted once");
+
+    println!("✓ Post-process crop runs after QTGMC");
+}
+
+// ============================================================================
+// Source Filter Option Tests
+// ============================================================================
+
+#[test]
+fn test_39_verify_source_options_only_when_set() {
+    // Test: threads/seekpreroll are passed to BestSource only when explicitly set
+    create_output_dir();
+    let generator = ScriptGenerator::new().expect("Failed to create generator");
+
+    let job = create_base_job("test_39_source_defaults");
+    let script_path = generator.generate(&job).expect("Failed to generate script");
+    let script_content = std::fs::read_to_string(&script_path).unwrap_or_default();
+    assert!(!script_content.contains("threads="), "threads must be omitted when unset");
+    assert!(!script_content.contains("seekpreroll="), "seekpreroll must be omitted when unset");
+
+    let mut job = create_base_job("test_39_source_options");
+    job.source_threads = Some(4);
+    job.source_seek_preroll = Some(40);
+    let script_path = generator.generate(&job).expect("Failed to generate script");
+    let script_content = std::fs::read_to_string(&script_path).unwrap_or_default();
+    assert!(script_content.contains("threads=4,"), "threads should be passed to the source filter");
+    assert!(script_content.contains("seekpreroll=40,"), "seekpreroll should be passed to the source filter");
+
+    println!("✓ Source filter options emitted only when set");
+}
+
+// ============================================================================
+// A/B Preview Tests
+// ============================================================================
+
+#[test]
+fn test_40_verify_ab_preview_stacks_both_pipelines() {
+    // Test: A/B preview runs both pipelines in isolated functions and stacks the results
+    create_output_dir();
+
+    let mut job_a = create_base_job("test_40_ab_a");
+    job_a.restoration_pipeline = Some(RestorationPipeline {
+        noise_reduction: NoiseReductionParameters {
+            enabled: true,
+            method: NoiseReductionMethod::SmDegrain,
+            ..NoiseReductionParameters::default()
+        },
+        ..RestorationPipeline::default()
+    });
+
+    let mut job_b = create_base_job("test_40_ab_b");
+    job_b.restoration_pipeline = Some(RestorationPipeline {
+        color_correction: ColorCorrectionParameters {
+            enabled: true,
+            saturation: 1.3,
+            ..ColorCorrectionParameters::default()
+        },
+        ..RestorationPipeline::default()
+    });
+
+    let params = || PreviewParams {
+        video_path: "/tmp/preview_clip.mkv".to_string(),
+        fps_num: 29970,
+        fps_den: 1000,
+        field_based: 2,
+        middle_frame: 5,
+    };
+
+    let generator = ScriptGenerator::new().expect("Failed to create generator");
+    let script_path = generator
+        .generate_ab_preview(&job_a, &params(), &job_b, &params())
+        .expect("Failed to generate A/B preview script");
+    let script_content = std::fs::read_to_string(&script_path).unwrap_or_default();
+
+    let def_a = script_content.find("def pipeline_a():").expect("Pipeline A function missing");
+    let def_b = script_content.find("def pipeline_b():").expect("Pipeline B function missing");
+    assert!(def_a < def_b);
+
+    // Each pipeline's filters live in its own function body
+    let (body_a, body_b) = script_content[def_a..].split_at(def_b - def_a);
+    assert!(body_a.contains("SMDegrain("), "Pipeline A should denoise");
+    assert!(!body_b.contains("SMDegrain("), "Pipeline B should not denoise");
+    assert!(body_b.contains("adjust.Tweak("), "Pipeline B should color correct");
+    assert!(!body_a.contains("adjust.Tweak("), "Pipeline A should not color correct");
+
+    // Only the stacked result is output
+    assert!(script_content.contains("core.std.StackHorizontal([clip_a, clip_b])"));
+    assert_eq!(script_content.matches("set_output()").count(), 1, "Only the stacked clip should be output");
+    assert_eq!(script_content.matches("return clip").count(), 2);
+
+    // Both pipelines pick the window's middle frame rather than the clip midpoint
+    assert_eq!(script_content.matches("preview_frame = min(5 * preview_rate").count(), 2);
+    assert!(!script_content.contains("clip.num_frames // 2"));
+
+    println!("✓ A/B preview script stacks both pipelines");
+}
+
+// ============================================================================
+// Output Depth Negotiation Tests
+// ============================================================================
+
+#[test]
+fn test_41_verify_10bit_script_dithered_for_h264() {
+    // Test: A 10-bit deband output feeding H.264 is dithered to 8-bit at the script tail
+    create_output_dir();
+
+    let mut job = create_base_job("test_41_depth_h264");
+    job.encoding_settings.codec = VideoCodec::H264;
+    job.encoding_settings.container = ContainerFormat::Mp4;
+    job.restoration_pipeline = Some(RestorationPipeline {
+        deband: DebandParameters {
+            enabled: true,
+            output_depth: 10,
+            ..DebandParameters::default()
+        },
+        ..RestorationPipeline::default()
+    });
+
+    let generator = ScriptGenerator::new().expect("Failed to create generator");
+    let script_path = generator.generate(&job).expect("Failed to generate script");
+    let script_content = std::fs::read_to_string(&script_path).unwrap_or_default();
+
+    let deband_pos = script_content.find("output_depth=10").expect("Deband should output 10-bit");
+    let depth_pos = script_content.find("max_output_depth = 8").expect("H.264 should cap output at 8-bit");
+    let output_pos = script_content.rfind("clip.set_output()").unwrap();
+    assert!(deband_pos < depth_pos && depth_pos < output_pos, "Depth reduction must run after filtering, before output");
+    assert!(script_content.contains("dither_type=\"error_diffusion\""));
+
+    // FFV1 keeps the full depth
+    job.encoding_settings.codec = VideoCodec::FFV1;
+    let script_path = generator.generate(&job).expect("Failed to generate script");
+    let script_content = std::fs::read_to_string(&script_path).unwrap_or_default();
+    assert!(script_content.contains("max_output_depth = 16"));
+
+    println!("✓ Output depth negotiated for the target encoder");
+}
+
+// ============================================================================
+// NNEDI3 Bob Deinterlace Tests
+// ============================================================================
+
+fn generate_nnedi3_bob_script(output_name: &str, tff: bool) -> String {
+    let mut job = create_base_job(output_name);
+    job.qtgmc_parameters = QTGMCParameters {
+        enabled: true,
+        method: DeinterlaceMethod::Nnedi3Bob,
+        tff: Some(tff),
+        ..QTGMCParameters::default()
+    };
+    job.restoration_pipeline = Some(RestorationPipeline::from_legacy(&job.qtgmc_parameters));
+
+    let generator = ScriptGenerator::new().expect("Failed to create generator");
+    let script_path = generator.generate(&job).expect("Failed to generate script");
+    std::fs::read_to_string(&script_path).unwrap_or_default()
+}
+
+#[test]
+fn test_42_verify_nnedi3_bob_field_from_tff() {
+    // Test: NNEDI3 bob replaces QTGMC and derives the double-rate field parameter from TFF
+    create_output_dir();
+
+    let tff_script = generate_nnedi3_bob_script("test_42_nnedi3_bob_tff", true);
+    assert!(tff_script.contains("clip = core.znedi3.nnedi3(clip, field=3)"), "TFF should bob with field=3");
+    assert!(!tff_script.contains("haf.QTGMC("), "QTGMC must not run in NNEDI3 bob mode");
+    assert!(!tff_script.contains("NNEDI3CL"), "CPU path expected without OpenCL");
+
+    let bff_script = generate_nnedi3_bob_script("test_42_nnedi3_bob_bff", false);
+    assert!(bff_script.contains("clip = core.znedi3.nnedi3(clip, field=2)"), "BFF should bob with field=2");
+
+    println!("✓ NNEDI3 bob field derived from TFF");
+}
+
+// ============================================================================
+// Sharpen Placement Tests
+// ============================================================================
+
+#[test]
+fn test_43_verify_sharpen_after_resize() {
+    // Test: With sharpen_after_resize the sharpen call follows the resize call
+    create_output_dir();
+
+    let mut job = create_base_job("test_43_sharpen_after_resize");
+    job.restoration_pipeline = Some(RestorationPipeline {
+        sharpen: SharpenParameters {
+            enabled: true,
+            method: SharpenMethod::CAS,
+            sharpen_after_resize: true,
+            ..SharpenParameters::default()
+        },
+        crop_resize: CropResizeParameters {
+            enabled: true,
+            resize_enabled: true,
+            target_width: Some(1440),
+            target_height: Some(1080),
+            kernel: ResizeKernel::Spline36,
+            ..CropResizeParameters::default()
+        },
+        ..RestorationPipeline::default()
+    });
+
+    let generator = ScriptGenerator::new().expect("Failed to create generator");
+    let script_path = generator.generate(&job).expect("Failed to generate script");
+    let script_content = std::fs::read_to_string(&script_path).unwrap_or_default();
+
+    let resize_pos = script_content.find("core.resize.Spline36(clip, width=target_w").expect("Script should resize");
+    let sharpen_pos = script_content.find("core.cas.CAS(").expect("Script should sharpen");
+    assert!(sharpen_pos > resize_pos, "Sharpen must follow resize when sharpen_after_resize is set");
+    assert_eq!(script_content.matches("core.cas.CAS(").count(), 1, "Sharpen must be emitted once");
+    assert!(!script_content.contains("SHARPEN_POST_RESIZE"));
+
+    // Default placement keeps sharpen before resize
+    if let Some(pipeline) = job.restoration_pipeline.as_mut() {
+        pipeline.sharpen.sharpen_after_resize = false;
+    }
+    let script_path = generator.generate(&job).expect("Failed to generate script");
+    let script_content = std::fs::read_to_string(&script_path).unwrap_or_default();
+    let resize_pos = script_content.find("core.resize.Spline36(clip, width=target_w").unwrap();
+    let sharpen_pos = script_content.find("core.cas.CAS(").unwrap();
+    assert!(sharpen_pos < resize_pos, "Sharpen runs before resize by default");
+
+    println!("✓ Sharpen placement follows sharpen_after_resize");
+}
+
+// ============================================================================
+// Linear Light Tests
+// ============================================================================
+
+#[test]
+fn test_44_verify_linear_light_resize() {
+    // Test: linearize wraps the resize in linear light conversions
+    create_output_dir();
+
+    let mut job = create_base_job("test_44_linear_light_resize");
+    job.restoration_pipeline = Some(RestorationPipeline {
+        crop_resize: CropResizeParameters {
+            enabled: true,
+            resize_enabled: true,
+            target_width: Some(1440),
+            target_height: Some(1080),
+            kernel: ResizeKernel::Spline36,
+            linearize: true,
+            ..CropResizeParameters::default()
+        },
+        ..RestorationPipeline::default()
+    });
+
+    let generator = ScriptGenerator::new().expect("Failed to create generator");
+    let script_path = generator.generate(&job).expect("Failed to generate script");
+    let script_content = std::fs::read_to_string(&script_path).unwrap_or_default();
+
+    let to_linear_pos = script_content.find("clip = to_linear(clip)").expect("Should convert to linear");
+    let resize_pos = script_content.find("core.resize.Spline36(clip, width=target_w").expect("Script should resize");
+    let from_linear_pos = script_content.find("clip = from_linear(clip, linear_format)").expect("Should convert back");
+    assert!(to_linear_pos < resize_pos && resize_pos < from_linear_pos,
+        "Linear conversion must bracket the resize");
+    assert!(script_content.contains("def to_linear(c):"), "Helpers should be defined");
+    assert!(!script_content.contains("LINEARIZE"), "No unprocessed linearize tags");
+
+    // Without linearize there are no conversions or helpers
+    if let Some(pipeline) = job.restoration_pipeline.as_mut() {
+        pipeline.crop_resize.linearize = false;
+    }
+    let script_path = generator.generate(&job).expect("Failed to generate script");
+    let script_content = std::fs::read_to_string(&script_path).unwrap_or_default();
+    assert!(!script_content.contains("to_linear"));
+    assert!(!script_content.contains("from_linear"));
+
+    println!("✓ Linear light conversion brackets the resize");
+}
+
+// ============================================================================
+// Frame Cache Tests
+// ============================================================================
+
+#[test]
+fn test_45_verify_vs_max_cache_size() {
+    // Test: vs_max_cache_mb sets core.max_cache_size, defaulting to 1024 MB
+    create_output_dir();
+
+    let mut job = create_base_job("test_45_vs_max_cache_size");
+    let generator = ScriptGenerator::new().expect("Failed to create generator");
+
+    let script_path = generator.generate(&job).expect("Failed to generate script");
+    let script_content = std::fs::read_to_string(&script_path).unwrap_or_default();
+    assert!(script_content.contains("core.max_cache_size = 1024\n"), "Default cache size should be 1024 MB");
+
+    job.vs_max_cache_mb = Some(4096);
+    let script_path = generator.generate(&job).expect("Failed to generate script");
+    let script_content = std::fs::read_to_string(&script_path).unwrap_or_default();
+    assert!(script_content.contains("core.max_cache_size = 4096\n"), "Cache size should use the configured value");
+    assert!(!script_content.contains("VS_MAX_CACHE_MB"));
+
+    println!("✓ Frame cache size follows vs_max_cache_mb");
+}
+
+// ============================================================================
+// QTGMC Default Sentinel Tests
+// ============================================================================
+
+#[test]
+fn test_46_verify_explicit_zero_dct() {
+    // Test: Some(0) is passed to QTGMC, None leaves the QTGMC default
+    create_output_dir();
+
+    let mut job = create_base_job("test_46_explicit_zero_dct");
+    job.qtgmc_parameters = QTGMCParameters {
+        enabled: true,
+        dct: Some(0),
+        ..QTGMCParameters::default()
+    };
+    job.restoration_pipeline = Some(RestorationPipeline {
+        deinterlace: job.qtgmc_parameters.clone(),
+        ..RestorationPipeline::default()
+    });
+
+    let generator = ScriptGenerator::new().expect("Failed to create generator");
+    let script_path = generator.generate(&job).expect("Failed to generate script");
+    let script_content = std::fs::read_to_string(&script_path).unwrap_or_default();
+    assert!(script_content.contains("DCT=0,"), "Explicit DCT=0 should be emitted");
+
+    job.qtgmc_parameters.dct = None;
+    job.restoration_pipeline = Some(RestorationPipeline {
+        deinterlace: job.qtgmc_parameters.clone(),
+        ..RestorationPipeline::default()
+    });
+    let script_path = generator.generate(&job).expect("Failed to generate script");
+    let script_content = std::fs::read_to_string(&script_path).unwrap_or_default();
+    assert!(!script_content.contains("DCT="), "Unset DCT should be omitted");
+
+    println!("✓ DCT sentinel handling verified");
+}
+
+// ============================================================================
+// Interlaced Chroma Tests
+// ============================================================================
+
+#[test]
+fn test_47_verify_interlaced_chroma_fix_before_qtgmc() {
+    // Test: fix_interlaced_chroma upsamples chroma per field before QTGMC
+    create_output_dir();
+
+    let mut job = create_base_job("test_47_interlaced_chroma_fix");
+    job.qtgmc_parameters = QTGMCParameters {
+        enabled: true,
+        tff: Some(false),
+        fix_interlaced_chroma: true,
+        ..QTGMCParameters::default()
+    };
+    job.restoration_pipeline = Some(RestorationPipeline {
+        deinterlace: job.qtgmc_parameters.clone(),
+        ..RestorationPipeline::default()
+    });
+
+    let generator = ScriptGenerator::new().expect("Failed to create generator");
+    let script_path = generator.generate(&job).expect("Failed to generate script");
+    let script_content = std::fs::read_to_string(&script_path).unwrap_or_default();
+
+    let fix_pos = script_content.find("core.std.SetFieldBased(clip, 1)").expect("Chroma fix should mark BFF fields");
+    let qtgmc_pos = script_content.find("haf.QTGMC(").expect("Script should deinterlace");
+    assert!(fix_pos < qtgmc_pos, "Interlaced chroma fix must precede QTGMC");
+
+    job.qtgmc_parameters.fix_interlaced_chroma = false;
+    job.restoration_pipeline = Some(RestorationPipeline {
+        deinterlace: job.qtgmc_parameters.clone(),
+        ..RestorationPipeline::default()
+    });
+    let script_path = generator.generate(&job).expect("Failed to generate script");
+    let script_content = std::fs::read_to_string(&script_path).unwrap_or_default();
+    assert!(!script_content.contains("SetFieldBased"), "Chroma fix is off by default");
+
+    println!("✓ Interlaced chroma fix precedes QTGMC");
+}
+
+// ============================================================================
+// CAS OpenCL Tests
+// ============================================================================
+
+#[test]
+fn test_48_verify_cas_opencl() {
+    // Test: SharpenParameters.opencl requests the CAS OpenCL path when supported
+    create_output_dir();
+
+    let mut job = create_base_job("test_48_cas_opencl");
+    job.restoration_pipeline = Some(RestorationPipeline {
+        sharpen: SharpenParameters {
+            enabled: true,
+            method: SharpenMethod::CAS,
+            opencl: true,
+            ..SharpenParameters::default()
+        },
+        ..RestorationPipeline::default()
+    });
+
+    let generator = ScriptGenerator::new().expect("Failed to create generator");
+    let script_path = generator.generate(&job).expect("Failed to generate script");
+    let script_content = std::fs::read_to_string(&script_path).unwrap_or_default();
+
+    let opencl_pos = script_content.find("cas_args['opencl'] = True").expect("CAS should request OpenCL");
+    let cas_pos = script_content.find("core.cas.CAS(").expect("Script should sharpen");
+    assert!(opencl_pos < cas_pos, "OpenCL argument must be set before the CAS call");
+    assert!(script_content.contains("'opencl' in core.cas.CAS.signature"), "OpenCL must be feature-checked");
+    assert!(script_content.contains("**cas_args"));
+
+    if let Some(pipeline) = job.restoration_pipeline.as_mut() {
+        pipeline.sharpen.opencl = false;
+    }
+    let script_path = generator.generate(&job).expect("Failed to generate script");
+    let script_content = std::fs::read_to_string(&script_path).unwrap_or_default();
+    assert!(!script_content.contains("cas_args['opencl']"), "CAS stays on CPU by default");
+    assert!(!script_content.contains("SHARPEN_CAS_OPENCL"));
+
+    println!("✓ CAS OpenCL argument emitted when enabled");
+}
+
+// ============================================================================
+// Deband Output Depth Tests
+// ============================================================================
+
+#[test]
+fn test_49_verify_16bit_deband_converted_for_h264() {
+    // Test: The default 16-bit deband output is reduced to 8-bit for H.264, even when deband is the last filter
+    create_output_dir();
+
+    let mut job = create_base_job("test_49_deband_16bit_h264");
+    job.encoding_settings.codec = VideoCodec::H264;
+    job.encoding_settings.container = ContainerFormat::Mp4;
+    job.restoration_pipeline = Some(RestorationPipeline {
+        deband: DebandParameters {
+            enabled: true,
+            ..DebandParameters::default()
+        },
+        ..RestorationPipeline::default()
+    });
+
+    let generator = ScriptGenerator::new().expect("Failed to create generator");
+    let script_path = generator.generate(&job).expect("Failed to generate script");
+    let script_content = std::fs::read_to_string(&script_path).unwrap_or_default();
+
+    let deband_pos = script_content.find("output_depth=16").expect("Deband should output 16-bit by default");
+    let depth_pos = script_content.find("max_output_depth = 8").expect("H.264 should cap output at 8-bit");
+    let convert_pos = script_content.find("if clip.format.bits_per_sample > max_output_depth:").unwrap();
+    let output_pos = script_content.rfind("clip.set_output()").unwrap();
+    assert!(deband_pos < depth_pos && depth_pos < convert_pos && convert_pos < output_pos,
+        "16-bit deband output must be converted before the H.264 encode");
+
+    println!("✓ 16-bit deband output converted for H.264");
+}
+
+// ============================================================================
+// Bypass Tests
+// ============================================================================
+
+#[test]
+fn test_50_verify_bypassed_pass_not_generated() {
+    // Test: An enabled but bypassed pass is left out of the script
+    create_output_dir();
+
+    let mut job = create_base_job("test_50_bypass");
+    job.restoration_pipeline = Some(RestorationPipeline {
+        sharpen: SharpenParameters {
+            enabled: true,
+            bypass: true,
+            method: SharpenMethod::CAS,
+            ..SharpenParameters::default()
+        },
+        ..RestorationPipeline::default()
+    });
+
+    let generator = ScriptGenerator::new().expect("Failed to create generator");
+    let script_path = generator.generate(&job).expect("Failed to generate script");
+    let script_content = std::fs::read_to_string(&script_path).unwrap_or_default();
+    assert!(!script_content.contains("core.cas.CAS("), "Bypassed sharpen must not be generated");
+
+    // The resolved job keeps the pass enabled and configured
+    let resolved = job.resolved();
+    let sharpen = &resolved.restoration_pipeline.as_ref().unwrap().sharpen;
+    assert!(sharpen.enabled && sharpen.bypass);
+    assert_eq!(sharpen.method, SharpenMethod::CAS);
+
+    if let Some(pipeline) = job.restoration_pipeline.as_mut() {
+        pipeline.sharpen.bypass = false;
+    }
+    let script_path = generator.generate(&job).expect("Failed to generate script");
+    let script_content = std::fs::read_to_string(&script_path).unwrap_or_default();
+    assert!(script_content.contains("core.cas.CAS("), "Sharpen runs once un-bypassed");
+
+    println!("✓ Bypassed pass left out of the script");
+}
+
+// ============================================================================
+// Windows Path Tests
+// ============================================================================
+
+#[test]
+fn test_51_verify_unc_input_path_in_script() {
+    // Test: A UNC input path reaches the Python source call unchanged
+    create_output_dir();
+
+    let mut job = create_base_job("test_51_unc_path");
+    job.input_path = r"\\server\share\tapes\tape 01.avi".to_string();
+
+    let generator = ScriptGenerator::new().expect("Failed to create generator");
+    let script_path = generator.generate(&job).expect("Failed to generate script");
+    let script_content = std::fs::read_to_string(&script_path).unwrap_or_default();
+
+    // A non-raw literal with doubled backslashes evaluates to the original path
+    assert!(script_content.contains(r#"source="\\\\server\\share\\tapes\\tape 01.avi","#),
+        "UNC path must be escaped for a Python string literal");
+    assert!(!script_content.contains("source=r\""), "Source path must not be a raw string");
+
+    println!("✓ UNC input path escaped for Python");
+}
+
+// ============================================================================
+// QTGMC OpenCL Device Tests
+// ============================================================================
+
+#[test]
+fn test_52_verify_device_only_with_opencl() {
+    // Test: The QTGMC device index is passed only when OpenCL is enabled
+    create_output_dir();
+
+    let mut job = create_base_job("test_52_opencl_device");
+    job.qtgmc_parameters = QTGMCParameters {
+        enabled: true,
+        opencl: true,
+        device: Some(1),
+        ..QTGMCParameters::default()
+    };
+    job.restoration_pipeline = Some(RestorationPipeline {
+        deinterlace: job.qtgmc_parameters.clone(),
+        ..RestorationPipeline::default()
+    });
+
+    let generator = ScriptGenerator::new().expect("Failed to create generator");
+    let script_path = generator.generate(&job).expect("Failed to generate script");
+    let script_content = std::fs::read_to_string(&script_path).unwrap_or_default();
+    assert!(script_content.contains("opencl=True,"), "OpenCL should be enabled");
+    assert!(script_content.contains("device=1,"), "Device should be emitted with OpenCL");
+
+    job.qtgmc_parameters.opencl = false;
+    job.restoration_pipeline = Some(RestorationPipeline {
+        deinterlace: job.qtgmc_parameters.clone(),
+        ..RestorationPipeline::default()
+    });
+    let script_path = generator.generate(&job).expect("Failed to generate script");
+    let script_content = std::fs::read_to_string(&script_path).unwrap_or_default();
+    assert!(!script_content.contains("device="), "Device should be omitted without OpenCL");
+
+    println!("✓ QTGMC device emitted only with OpenCL");
+}
+
+// ============================================================================
+// Source Indexing Tests
+// ============================================================================
+
+#[test]
+fn test_53_verify_video_only_source_indexing() {
+    // Test: The source filter indexes only the video track (audio comes from ffmpeg)
+    create_output_dir();
+
+    let job = create_base_job("test_53_video_only_index");
+    let generator = ScriptGenerator::new().expect("Failed to create generator");
+    let script_path = generator.generate(&job).expect("Failed to generate script");
+    let script_content = std::fs::read_to_string(&script_path).unwrap_or_default();
+
+    let source_pos = script_content.find("core.bs.VideoSource(").expect("Script should load the source");
+    let call = &script_content[source_pos..];
+    let call = &call[..call.find(')').unwrap()];
+    // BestSource's default track (-1) is already the first video track
+    assert!(!call.contains("track="), "Source should keep the default video track: {}", call);
+    assert!(!script_content.contains("AudioSource"), "Audio must not be indexed");
+
+    println!("✓ Source indexes the video track only");
+}
+
+// ============================================================================
+// Mixed Content Tests
+// ============================================================================
+
+#[test]
+fn test_54_verify_selective_deinterlace_gate() {
+    // Test: Selective deinterlacing gates QTGMC on TDeintMod's combing detection
+    create_output_dir();
+
+    let mut job = create_base_job("test_54_selective_deinterlace");
+    job.qtgmc_parameters = QTGMCParameters {
+        enabled: true,
+        selective_deinterlace: true,
+        ..QTGMCParameters::default()
+    };
+    job.restoration_pipeline = Some(RestorationPipeline {
+        deinterlace: job.qtgmc_parameters.clone(),
+        ..RestorationPipeline::default()
+    });
+
+    let generator = ScriptGenerator::new().expect("Failed to create generator");
+    let script_path = generator.generate(&job).expect("Failed to generate script");
+    let script_content = std::fs::read_to_string(&script_path).unwrap_or_default();
+
+    let qtgmc_pos = script_content.find("haf.QTGMC(").expect("QTGMC should be applied");
+    let gate_pos = script_content.find("core.tdm.IsCombed(").expect("Combing detection should be applied");
+    assert!(gate_pos > qtgmc_pos, "Combing gate should follow QTGMC");
+    assert!(script_content.contains("core.std.FrameEval("), "Frames should be selected per combing flag");
+    assert!(script_content.contains("f.props['_Combed']"), "Selection should use the _Combed property");
+
+    job.qtgmc_parameters.selective_deinterlace = false;
+    job.restoration_pipeline = Some(RestorationPipeline {
+        deinterlace: job.qtgmc_parameters.clone(),
+        ..RestorationPipeline::default()
+    });
+    let script_path = generator.generate(&job).expect("Failed to generate script");
+    let script_content = std::fs::read_to_string(&script_path).unwrap_or_default();
+    assert!(!script_content.contains("IsCombed"), "Combing gate should be off by default");
+    assert!(!script_content.contains("FrameEval"), "Combing gate should be off by default");
+
+    println!("✓ Selective deinterlacing gated on combing detection");
+}
+
+#[test]
+fn test_55_verify_deinterlace_ranges_splice() {
+    // Test: Deinterlacing two frame ranges splices QTGMC output with the untouched source
+    create_output_dir();
+
+    let mut job = create_base_job("test_55_deinterlace_ranges");
+    job.qtgmc_parameters = QTGMCParameters {
+        enabled: true,
+        deinterlace_ranges: vec![(0, 99), (500, 599)],
+        ..QTGMCParameters::default()
+    };
+    job.restoration_pipeline = Some(RestorationPipeline {
+        deinterlace: job.qtgmc_parameters.clone(),
+        ..RestorationPipeline::default()
+    });
+
+    let generator = ScriptGenerator::new().expect("Failed to create generator");
+    let script_path = generator.generate(&job).expect("Failed to generate script");
+    let script_content = std::fs::read_to_string(&script_path).unwrap_or_default();
+
+    let untouched_pos = script_content.find("untouched = ").expect("Source should be kept for splicing");
+    let qtgmc_pos = script_content.find("haf.QTGMC(").expect("QTGMC should be applied");
+    let splice_pos = script_content.find("core.std.Splice(segments)").expect("Ranges should be spliced");
+    assert!(untouched_pos < qtgmc_pos && qtgmc_pos < splice_pos, "Source kept before QTGMC, spliced after");
+    assert!(script_content.contains("for first, last in [(0, 99), (500, 599)]:"), "Both ranges should be listed");
+
+    job.qtgmc_parameters.deinterlace_ranges.clear();
+    job.restoration_pipeline = Some(RestorationPipeline {
+        deinterlace: job.qtgmc_parameters.clone(),
+        ..RestorationPipeline::default()
+    });
+    let script_path = generator.generate(&job).expect("Failed to generate script");
+    let script_content = std::fs::read_to_string(&script_path).unwrap_or_default();
+    assert!(!script_content.contains("Splice"), "No splice without ranges");
+
+    println!("✓ Deinterlace ranges spliced with the untouched source");
+}
+
+#[test]
+fn test_56_verify_dedup_before_resize() {
+    // Test: Dedup drops duplicated frames after deinterlacing and before resize
+    create_output_dir();
+
+    let mut job = create_base_job("test_56_dedup");
+    job.restoration_pipeline = Some(RestorationPipeline {
+        deinterlace: job.qtgmc_parameters.clone(),
+        dedup: DedupParameters { enabled: true, dup_threshold: 0.25 },
+        crop_resize: CropResizeParameters {
+            enabled: true,
+            resize_enabled: true,
+            target_width: Some(640),
+            target_height: Some(480),
+            ..CropResizeParameters::default()
+        },
+        ..RestorationPipeline::default()
+    });
+
+    let generator = ScriptGenerator::new().expect("Failed to create generator");
+    let script_path = generator.generate(&job).expect("Failed to generate script");
+    let script_content = std::fs::read_to_string(&script_path).unwrap_or_default();
+
+    let dedup_pos = script_content.find("core.std.DeleteFrames(").expect("Dedup should delete frames");
+    let resize_pos = script_content.find("PASS 12: RESIZE").expect("Resize pass should be present");
+    assert!(dedup_pos < resize_pos, "Dedup should run before resize");
+    if let Some(qtgmc_pos) = script_content.find("haf.QTGMC(") {
+        assert!(qtgmc_pos < dedup_pos, "Dedup should run on deinterlaced frames");
+    }
+    assert!(!script_content.contains("VDecimate"), "Only duplicates are dropped, not one frame per cycle");
+    assert!(script_content.contains("[\"PlaneStatsDiff\"] * 100 <= 0.25\n"), "Duplicate threshold should be substituted");
+    let source_pos = script_content.find("dedup_source = clip\n").expect("Dedup should scan the source");
+    if let Some(qtgmc_pos) = script_content.find("haf.QTGMC(") {
+        assert!(source_pos < qtgmc_pos, "Duplicates are detected before deinterlacing");
+    }
+
+    println!("✓ Dedup placed before resize");
+}
+
+#[test]
+fn test_57_verify_standalone_script() {
+    // Test: The standalone script for vspreview loads the real input and sets the output
+    let job = create_base_job("test_57_standalone");
+
+    let generator = ScriptGenerator::new().expect("Failed to create generator");
+    let script = generator.generate_standalone(&job).expect("Failed to generate script");
+
+    assert!(script.starts_with("# VapourBox restoration pipeline\n"), "Should start with the pipeline header");
+    assert!(script.contains(&format!("# Input: {}\n", job.input_path)), "Header should name the input");
+    let escaped_input = job.input_path.replace('\\', "\\\\");
+    assert!(script.contains(&format!("source=\"{}\"", escaped_input)), "Should load the real input");
+    assert!(script.trim_end().ends_with("clip.set_output()"), "Should end with set_output()");
+    assert!(!script.contains("\n\n\n"), "Blank line runs should be collapsed");
+
+    println!("✓ Standalone script references the real input");
+}
+
+/// Parameter names of `def Tweak(...)` in the bundled adjust.py.
+fn bundled_tweak_params() -> Vec<String> {
+    let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("../deps/macos-arm64/python-packages/adjust.py");
+    let source = std::fs::read_to_string(&path).expect("Bundled adjust.py should exist");
+    let start = source.find("def Tweak(").expect("adjust.py should define Tweak") + "def Tweak(".len();
+    let end = start + source[start..].find(')').unwrap();
+    source[start..end]
+        .split(',')
+        .map(|p| p.split('=').next().unwrap().trim().to_string())
+        .collect()
+}
+
+/// Keyword arguments of the generated `adjust.Tweak(` call.
+fn tweak_call_kwargs(script: &str) -> Vec<String> {
+    let start = script.find("adjust.Tweak(").expect("Script should call adjust.Tweak");
+    let end = start + script[start..].find("\n)").expect("Tweak call should be closed");
+    script[start..end]
+        .lines()
+        .filter_map(|line| line.trim().split_once('=').map(|(name, _)| name.trim().to_string()))
+        .collect()
+}
+
+#[test]
+fn test_58_verify_selective_hue_tweak() {
+    // Test: Hue range limits mask Tweak's output instead of being passed as
+    // Tweak arguments the bundled adjust.py doesn't accept
+    let mut job = create_base_job("test_58_selective_hue");
+    job.restoration_pipeline = Some(RestorationPipeline {
+        deinterlace: job.qtgmc_parameters.clone(),
+        color_correction: ColorCorrectionParameters {
+            enabled: true,
+            brightness: 4.0,
+            saturation: 0.8,
+            hue: 5.0,
+            tweak_start_hue: Some(340.0),
+            tweak_end_hue: Some(20.0),
+            tweak_min_sat: Some(60.0),
+            ..ColorCorrectionParameters::default()
+        },
+        ..RestorationPipeline::default()
+    });
+
+    let accepted = bundled_tweak_params();
+    let generator = ScriptGenerator::new().expect("Failed to create generator");
+    let script_path = generator.generate(&job).expect("Failed to generate script");
+    let script_content = std::fs::read_to_string(&script_path).unwrap_or_default();
+    let kwargs = tweak_call_kwargs(&script_content);
+    assert!(!kwargs.is_empty(), "Tweak should receive the configured adjustments");
+    for kwarg in &kwargs {
+        assert!(accepted.contains(kwarg), "adjust.Tweak doesn't accept {}= (accepts {:?})", kwarg, accepted);
+    }
+    assert!(script_content.contains("core.std.MaskedMerge(color_source, clip, color_range_mask("), "Range should mask the tweak");
+    assert!(script_content.contains("start_hue=340.0,"), "Start hue should reach the mask");
+    assert!(script_content.contains("end_hue=20.0,"), "End hue should reach the mask");
+    assert!(script_content.contains("min_sat=60.0,"), "Minimum saturation should reach the mask");
+    assert!(!script_content.contains("\n    max_sat="), "Unset limits should be omitted");
+
+    if let Some(pipeline) = job.restoration_pipeline.as_mut() {
+        pipeline.color_correction = ColorCorrectionParameters {
+            enabled: true,
+            saturation: 0.8,
+            ..ColorCorrectionParameters::default()
+        };
+    }
+    let script_path = generator.generate(&job).expect("Failed to generate script");
+    let script_content = std::fs::read_to_string(&script_path).unwrap_or_default();
+    assert!(script_content.contains("sat=0.8,"), "Saturation should still be adjusted");
+    assert!(!script_content.contains("color_range_mask"), "Hue range is omitted by default");
+    for kwarg in tweak_call_kwargs(&script_content) {
+        assert!(accepted.contains(&kwarg), "adjust.Tweak doesn't accept {}=", kwarg);
+    }
+
+    println!("✓ Selective hue range masks the Tweak output");
+}
+
+#[test]
+fn test_59_verify_width_only_resize_without_maintain_aspect() {
+    // Test: A width-only resize computes the height from the source aspect
+    // instead of passing height=-1 to the resizer
+    let mut job = create_base_job("test_59_width_only_resize");
+    job.restoration_pipeline = Some(RestorationPipeline {
+        deinterlace: job.qtgmc_parameters.clone(),
+        crop_resize: CropResizeParameters {
+            enabled: true,
+            resize_enabled: true,
+            target_width: Some(960),
+            target_height: None,
+            maintain_aspect: false,
+            ..CropResizeParameters::default()
+        },
+        ..RestorationPipeline::default()
+    });
+
+    let generator = ScriptGenerator::new().expect("Failed to create generator");
+    let script_path = generator.generate(&job).expect("Failed to generate script");
+    let script_content = std::fs::read_to_string(&script_path).unwrap_or_default();
+    assert!(script_content.contains("target_w = 960\ntarget_h = -1\n"), "Width should be substituted");
+    assert!(
+        script_content.contains("target_h = int(target_w / aspect)"),
+        "Missing height should be computed from the source aspect"
+    );
+
+    println!("✓ Width-only resize keeps the source aspect");
+}
+
+#[test]
+fn test_60_verify_broadcast_safe_clamp() {
+    // Test: The broadcast-safe preset limits chroma magnitude and clamps to
+    // legal range without relying on Tweak options adjust.py lacks
+    let mut job = create_base_job("test_60_broadcast_safe");
+    let mut pipeline = RestorationPipeline {
+        deinterlace: job.qtgmc_parameters.clone(),
+        color_correction: ColorCorrectionParameters {
+            enabled: true,
+            preset: ColorCorrectionPreset::BroadcastSafe,
+            ..ColorCorrectionParameters::default()
+        },
+        ..RestorationPipeline::default()
+    };
+    pipeline.resolve_presets();
+    job.restoration_pipeline = Some(pipeline);
+
+    let generator = ScriptGenerator::new().expect("Failed to create generator");
+    let script_path = generator.generate(&job).expect("Failed to generate script");
+    let script_content = std::fs::read_to_string(&script_path).unwrap_or_default();
+    if script_content.contains("adjust.Tweak(") {
+        let accepted = bundled_tweak_params();
+        for kwarg in tweak_call_kwargs(&script_content) {
+            assert!(accepted.contains(&kwarg), "adjust.Tweak doesn't accept {}=", kwarg);
+        }
+    }
+    assert!(!script_content.contains("color_range_mask"), "No hue/saturation range is needed");
+    assert!(script_content.contains("chroma_limit = 100.0 * (1 << (clip.format.bits_per_sample - 8))"), "Chroma magnitude should be limited");
+    let limit = script_content.find("limited_u = core.std.Expr(").expect("Chroma limit should run");
+    let levels = script_content.find("clip = core.std.Levels(").expect("Levels should run");
+    let clamp = script_content.find("f\"x {16 << shift} max {235 << shift} min\"").expect("Luma should be clamped");
+    assert!(levels < limit && limit < clamp, "Limit and clamp run after the adjustments");
+    assert!(script_content.contains("{240 << shift} min"), "Chroma should be clamped to 240");
+
+    println!("✓ Broadcast-safe preset clamps to legal range");
+}
+
+#[test]
+fn test_61_verify_grain_pass() {
+    // Test: Film grain is added with the configured strength after the cleanup passes
+    let mut job = create_base_job("test_61_grain");
+    job.restoration_pipeline = Some(RestorationPipeline {
+        deinterlace: job.qtgmc_parameters.clone(),
+        noise_reduction: NoiseReductionParameters { enabled: true, ..NoiseReductionParameters::default() },
+        grain: GrainParameters {
+            enabled: true,
+            strength: 2.5,
+            size: 0.4,
+            dynamic: false,
+            adaptive: true,
+            ..GrainParameters::default()
+        },
+        crop_resize: CropResizeParameters {
+            enabled: true,
+            resize_enabled: true,
+            target_width: Some(1280),
+            target_height: Some(720),
+            ..CropResizeParameters::default()
+        },
+        ..RestorationPipeline::default()
+    });
+
+    let generator = ScriptGenerator::new().expect("Failed to create generator");
+    let script_path = generator.generate(&job).expect("Failed to generate script");
+    let script_content = std::fs::read_to_string(&script_path).unwrap_or_default();
+    let grain = script_content.find("core.grain.Add(").expect("Grain should be added");
+    assert!(script_content.contains("var=2.5,"), "Grain strength should be configured");
+    assert!(script_content.contains("hcorr=0.4,") && script_content.contains("vcorr=0.4,"), "Grain size should be configured");
+    assert!(script_content.contains("constant=True,"), "Static grain requested");
+    assert!(!script_content.contains("uvar="), "Chroma grain is off by default");
+    assert!(script_content.contains("exponent = f.props[\"PlaneStatsAverage\"] ** 2 * 10.0\n"), "Adaptive mask should use the luma scaling");
+    assert!(script_content.contains("core.std.MaskedMerge(clip, grained, grain_mask, first_plane=True)"), "Adaptive mask should be applied");
+    assert!(!script_content.contains("core.adg."), "adaptivegrain isn't bundled");
+
+    let denoise = script_content.find("haf.SMDegrain(").expect("Noise reduction should run");
+    let resize = script_content.find("PASS 12: RESIZE").expect("Resize pass should be present");
+    assert!(denoise < grain && grain < resize, "Grain runs after denoising and before resize");
+
+    println!("✓ Grain pass with configured strength");
+}
+
+#[test]
+fn test_62_verify_per_pass_opencl_devices() {
+    // Test: Each GPU pass targets its own OpenCL device
+    let mut job = create_base_job("test_62_opencl_devices");
+    job.qtgmc_parameters = QTGMCParameters {
+        enabled: true,
+        method: DeinterlaceMethod::Nnedi3Bob,
+        opencl: true,
+        device: Some(0),
+        ..QTGMCParameters::default()
+    };
+    job.restoration_pipeline = Some(RestorationPipeline {
+        deinterlace: job.qtgmc_parameters.clone(),
+        sharpen: SharpenParameters {
+            enabled: true,
+            method: SharpenMethod::CAS,
+            opencl: true,
+            opencl_device: Some(2),
+            ..SharpenParameters::default()
+        },
+        crop_resize: CropResizeParameters {
+            enabled: true,
+            use_integer_upscale: true,
+            opencl: true,
+            opencl_device: Some(1),
+            ..CropResizeParameters::default()
+        },
+        ..RestorationPipeline::default()
+    });
+
+    let generator = ScriptGenerator::new().expect("Failed to create generator");
+    let script_content = std::fs::read_to_string(generator.generate(&job).expect("Failed to generate script")).unwrap_or_default();
+    let bob = script_content.find("clip = core.nnedi3cl.NNEDI3CL(\n    clip,").expect("Bob should run on OpenCL");
+    let upscale = script_content.find("return core.nnedi3cl.NNEDI3CL(").expect("Upscale should run on OpenCL");
+    assert!(script_content[bob..upscale].contains("    device=0,\n"), "Deinterlace should use device 0");
+    assert!(script_content[upscale..].contains("        device=1,\n"), "Upscale should use device 1");
+    assert!(script_content.contains("cas_args['device'] = 2"), "CAS should use device 2");
+    assert!(!script_content.contains("core.znedi3"), "No pass should fall back to znedi3");
+
+    // Devices are dropped for passes running on the CPU
+    if let Some(pipeline) = job.restoration_pipeline.as_mut() {
+        pipeline.crop_resize.opencl = false;
+        pipeline.sharpen.opencl = false;
+    }
+    let script_content = std::fs::read_to_string(generator.generate(&job).expect("Failed to generate script")).unwrap_or_default();
+    assert!(script_content.contains("return core.znedi3.nnedi3(c, field=1, dh=True)"), "Upscale should run on the CPU");
+    assert!(!script_content.contains("device=1") && !script_content.contains("cas_args['device']"));
+    assert!(script_content.contains("device=0,"), "Deinterlace keeps its device");
+
+    println!("✓ Per-pass OpenCL devices");
+}
+
+#[test]
+fn test_63_verify_output_chroma_conversion() {
+    // Test: A 4:2:2 deliverable resamples the chroma before output and encodes 4:2:2
+    let mut job = create_base_job("test_63_output_chroma");
+    job.encoding_settings.codec = VideoCodec::H264;
+    job.encoding_settings.output_chroma = Some(ChromaSubsampling::Yuv422);
+
+    let generator = ScriptGenerator::new().expect("Failed to create generator");
+    let script_content = std::fs::read_to_string(generator.generate(&job).expect("Failed to generate script")).unwrap_or_default();
+    let chroma = script_content
+        .find("clip.format.replace(subsampling_w=1, subsampling_h=0)")
+        .expect("Chroma should be resampled to 4:2:2");
+    assert!(script_content.contains("clip = core.resize.Spline36(clip, format=chroma_format.id)"));
+    let depth = script_content.find("max_output_depth = 8").expect("Depth limit should follow");
+    assert!(chroma < depth, "Chroma is resampled before the final dither");
+    assert!(build_ffmpeg_args(&job).windows(2).any(|w| w == ["-pix_fmt", "yuv422p"]), "ffmpeg should encode 4:2:2");
+
+    job.encoding_settings.output_chroma = None;
+    let script_content = std::fs::read_to_string(generator.generate(&job).expect("Failed to generate script")).unwrap_or_default();
+    assert!(!script_content.contains("OUTPUT CHROMA"), "No conversion without an output chroma");
+
+    job.encoding_settings.codec = VideoCodec::ProRes422;
+    job.encoding_settings.output_chroma = Some(ChromaSubsampling::Yuv420);
+    assert!(generator.generate(&job).is_err(), "ProRes 422 can't deliver 4:2:0");
+
+    println!("✓ Output chroma 4:2:0 -> 4:2:2 conversion");
+}
+
+#[test]
+fn test_64_verify_edi_ext_script() {
+    // Test: An EdiExt snippet builds edi_ext_clip and QTGMC interpolates from it
+    let mut job = create_base_job("test_64_edi_ext");
+    job.qtgmc_parameters.edi_ext_script = Some(
+        "bobbed = core.nnedi3cl.NNEDI3CL(clip, field=3)\r\nedi_ext_clip = bobbed\r\n".to_string(),
+    );
+    job.restoration_pipeline = Some(RestorationPipeline {
+        deinterlace: job.qtgmc_parameters.clone(),
+        ..RestorationPipeline::default()
+    });
+
+    let generator = ScriptGenerator::new().expect("Failed to create generator");
+    let script_content = std::fs::read_to_string(generator.generate(&job).expect("Failed to generate script")).unwrap_or_default();
+    let snippet = script_content
+        .find("def make_edi_ext_clip(clip):\n    bobbed = core.nnedi3cl.NNEDI3CL(clip, field=3)\n    edi_ext_clip = bobbed\n    return edi_ext_clip\n")
+        .expect("Snippet should be wrapped in a function");
+    let qtgmc = script_content.find("clip = haf.QTGMC(").expect("QTGMC should run");
+    assert!(snippet < qtgmc, "EdiExt clip is built before QTGMC");
+    assert!(script_content[qtgmc..].contains("    EdiExt=edi_ext_clip,\n"), "QTGMC should use the external clip");
+    assert!(!script_content.contains('\r'));
+
+    // Unsafe snippets are rejected
+    job.qtgmc_parameters.edi_ext_script = Some("edi_ext_clip = clip\nedi_ext_clip.set_output()".to_string());
+    assert!(generator.generate(&job).is_err(), "Snippets must not set an output");
+
+    job.qtgmc_parameters.edi_ext_script = None;
+    job.restoration_pipeline = None;
+    let script_content = std::fs::read_to_string(generator.generate(&job).expect("Failed to generate script")).unwrap_or_default();
+    assert!(!script_content.contains("EdiExt") && !script_content.contains("edi_ext_clip"));
+
+    println!("✓ EdiExt external interpolation clip");
+}
+
+#[test]
+fn test_65_verify_prog_sad_mask() {
+    // Test: ProgSADMask is passed to QTGMC for progressive repair
+    let mut job = create_base_job("test_65_prog_sad_mask");
+    job.qtgmc_parameters = QTGMCParameters {
+        enabled: true,
+        input_type: 2,
+        prog_sad_mask: Some(10.0),
+        ..QTGMCParameters::default()
+    };
+    job.restoration_pipeline = Some(RestorationPipeline {
+        deinterlace: job.qtgmc_parameters.clone(),
+        ..RestorationPipeline::default()
+    });
+
+    let generator = ScriptGenerator::new().expect("Failed to create generator");
+    let script_content = std::fs::read_to_string(generator.generate(&job).expect("Failed to generate script")).unwrap_or_default();
+    let qtgmc = script_content.find("clip = haf.QTGMC(").expect("QTGMC should run");
+    let input_type = script_content[qtgmc..].find("    InputType=2,\n").expect("Progressive repair requested");
+    let mask = script_content[qtgmc..].find("    ProgSADMask=10.0,\n").expect("ProgSADMask should be passed");
+    assert!(input_type < mask, "ProgSADMask follows InputType");
+
+    job.qtgmc_parameters.prog_sad_mask = None;
+    job.restoration_pipeline = None;
+    let script_content = std::fs::read_to_string(generator.generate(&job).expect("Failed to generate script")).unwrap_or_default();
+    assert!(!script_content.contains("ProgSADMask="), "Unset mask keeps QTGMC's default");
+
+    println!("✓ ProgSADMask for progressive repair");
+}
+
+#[test]
+fn test_66_verify_deblock_qed_fallback() {
+    // Test: deblock_fallback stands in core.deblock.Deblock when havsfunc lacks Deblock_QED
+    let mut job = create_base_job("test_66_deblock_fallback");
+    job.restoration_pipeline = Some(RestorationPipeline {
+        deinterlace: job.qtgmc_parameters.clone(),
+        deblock: DeblockParameters {
+            enabled: true,
+            quant1: 30,
+            quant2: 40,
+            deblock_fallback: true,
+            ..DeblockParameters::default()
+        },
+        ..RestorationPipeline::default()
+    });
+
+    let generator = ScriptGenerator::new().expect("Failed to create generator");
+    let script_content = std::fs::read_to_string(generator.generate(&job).expect("Failed to generate script")).unwrap_or_default();
+    let check = script_content.find("if not hasattr(haf, 'Deblock_QED'):").expect("Deblock_QED should be checked for");
+    let qed = script_content.find("clip = haf.Deblock_QED(").expect("Deblock_QED should still be called");
+    assert!(check < qed);
+    assert!(script_content.contains("core.deblock.Deblock(clip, quant=35)"), "Fallback quant is the QED mean");
+    assert!(script_content.contains("print(\"DEBLOCK_FALLBACK:"), "Fallback should be reported");
+
+    if let Some(pipeline) = job.restoration_pipeline.as_mut() {
+        pipeline.deblock.deblock_fallback = false;
+    }
+    let script_content = std::fs::read_to_string(generator.generate(&job).expect("Failed to generate script")).unwrap_or_default();
+    assert!(!script_content.contains("hasattr(haf, 'Deblock_QED')"), "No fallback unless requested");
+
+    println!("✓ Deblock_QED fallback");
+}
+